@@ -0,0 +1,221 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{Method, StatusCode, Uri},
+    middleware::Next,
+    response::Response,
+};
+use http_body::{Frame, SizeHint};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+/// Query parameters whose values never show in logs, since they grant access to files
+pub const SENSITIVE_PARAMS: &[&str] = &[
+    "encryption_key",
+    "key",
+    "signature",
+    "owner_token",
+    "token",
+    "passphrase",
+];
+
+/// Which requests get a line in the access log
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccessLog {
+    /// No access log
+    #[default]
+    Off,
+    /// Only requests answered with a 4xx or 5xx status
+    Errors,
+    /// Every request
+    All,
+}
+
+impl FromStr for AccessLog {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(AccessLog::Off),
+            "errors" => Ok(AccessLog::Errors),
+            "all" => Ok(AccessLog::All),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Path and query of `uri`, with the values of sensitive parameters replaced
+///
+/// Parameters with percent-encoded names are redacted as well, since they may
+/// decode to a sensitive one.
+pub fn redact_uri(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.path().to_string();
+    };
+
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _))
+                if name.contains('%')
+                    || SENSITIVE_PARAMS.contains(&name.to_ascii_lowercase().as_str()) =>
+            {
+                format!("{}=REDACTED", name)
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", uri.path(), query)
+}
+
+/// Log method, redacted path, status, duration and bytes in and out of requests
+///
+/// Bodies are counted as they stream, so the line is written once the response
+/// has been sent in full (or the client went away), and its duration covers
+/// the whole transfer. It goes out in the request's span, with the ids the
+/// handlers recorded.
+pub async fn log_requests(
+    State(access_log): State<AccessLog>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if access_log == AccessLog::Off {
+        return next.run(request).await;
+    }
+
+    let bytes_in = Arc::new(AtomicU64::new(0));
+    let mut entry = Entry {
+        access_log,
+        method: request.method().clone(),
+        path: redact_uri(request.uri()),
+        started_at: Instant::now(),
+        status: StatusCode::OK,
+        bytes_in: bytes_in.clone(),
+        bytes_out: Arc::new(AtomicU64::new(0)),
+        span: tracing::Span::current(),
+    };
+
+    let request = request.map(|body| {
+        Body::new(CountedBody {
+            inner: body,
+            bytes: bytes_in,
+            _entry: None,
+        })
+    });
+    let response = next.run(request).await;
+
+    entry.status = response.status();
+    let bytes_out = entry.bytes_out.clone();
+    response.map(|body| {
+        Body::new(CountedBody {
+            inner: body,
+            bytes: bytes_out,
+            _entry: Some(entry),
+        })
+    })
+}
+
+// What is known of a request, logged when the response body is dropped
+struct Entry {
+    access_log: AccessLog,
+    method: Method,
+    path: String,
+    started_at: Instant,
+    status: StatusCode,
+    bytes_in: Arc<AtomicU64>,
+    bytes_out: Arc<AtomicU64>,
+    span: tracing::Span,
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        let is_error = self.status.is_client_error() || self.status.is_server_error();
+        if self.access_log == AccessLog::Errors && !is_error {
+            return;
+        }
+
+        let _span = self.span.enter();
+        tracing::info!(
+            method = %self.method,
+            path = %self.path,
+            status = self.status.as_u16(),
+            duration_ms = self.started_at.elapsed().as_millis() as u64,
+            bytes_in = self.bytes_in.load(Ordering::Relaxed),
+            bytes_out = self.bytes_out.load(Ordering::Relaxed),
+            "📜 Request served"
+        );
+    }
+}
+
+// Body that counts the bytes of its data frames, keeping the size hint of the
+// one it wraps so responses keep their Content-Length
+struct CountedBody {
+    inner: Body,
+    bytes: Arc<AtomicU64>,
+    // Held until the body is dropped, which writes the log line
+    _entry: Option<Entry>,
+}
+
+impl http_body::Body for CountedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                self.bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensitive_params_are_redacted() {
+        let uri: Uri = "/api/files/1/signed-download?expires=10&signature=abc&Encryption_Key=k"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            redact_uri(&uri),
+            "/api/files/1/signed-download?expires=10&signature=REDACTED&Encryption_Key=REDACTED"
+        );
+
+        let uri: Uri = "/api/upload?slug=notes&encryption%5Fkey=k".parse().unwrap();
+        assert_eq!(
+            redact_uri(&uri),
+            "/api/upload?slug=notes&encryption%5Fkey=REDACTED"
+        );
+
+        let uri: Uri = "/api/files/1/info".parse().unwrap();
+        assert_eq!(redact_uri(&uri), "/api/files/1/info");
+    }
+
+    #[test]
+    fn test_verbosity_is_parsed() {
+        assert_eq!("Errors".parse(), Ok(AccessLog::Errors));
+        assert_eq!("all".parse(), Ok(AccessLog::All));
+        assert_eq!("off".parse(), Ok(AccessLog::Off));
+        assert!("verbose".parse::<AccessLog>().is_err());
+    }
+}
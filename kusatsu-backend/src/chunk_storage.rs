@@ -1,34 +1,139 @@
+use crate::config::Config;
 use crate::error::{AppError, Result};
+use crate::storage::{create_dir_all, write_atomic, write_new};
+use crate::storage_health::write_error;
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use sha2::{Digest, Sha256};
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
 use uuid::Uuid;
 
-/// Manages temporary storage of file chunks during upload
+/// The contents of an upload, put back together from its chunks
+#[derive(Debug)]
+pub struct AssembledUpload {
+    pub data: Vec<u8>,
+    /// SHA-256 of `data`, computed while the chunks were read
+    pub sha256: [u8; 32],
+}
+
+/// Temporary storage of file chunks during upload
+///
+/// Chunks live on the local disk by default. A shared store lets any backend
+/// replica assemble an upload whose chunks went to other replicas.
+#[async_trait]
+pub trait ChunkStorage: Send + Sync {
+    /// Prepare the store (create directories, check connectivity)
+    async fn init(&self) -> Result<()>;
+
+    /// Store one chunk of an upload, replacing any previous copy
+    async fn store_chunk(
+        &self,
+        upload_id: Uuid,
+        chunk_number: i32,
+        chunk_data: &[u8],
+    ) -> Result<()>;
+
+    /// Store one chunk of an upload, unless a copy is already stored
+    ///
+    /// Returns whether this call stored it: of concurrent calls for the same
+    /// chunk, exactly one does.
+    async fn store_new_chunk(
+        &self,
+        upload_id: Uuid,
+        chunk_number: i32,
+        chunk_data: &[u8],
+    ) -> Result<bool>;
+
+    /// Check if a specific chunk exists
+    async fn chunk_exists(&self, upload_id: Uuid, chunk_number: i32) -> Result<bool>;
+
+    /// Get the stored size of a specific chunk
+    ///
+    /// Returns `AppError::FileNotFound` when the chunk isn't stored.
+    async fn get_chunk_size(&self, upload_id: Uuid, chunk_number: i32) -> Result<u64>;
+
+    /// Assemble all chunks into a single file and return the data with its hash
+    ///
+    /// Aborts as soon as the running total exceeds `max_total_size`, so
+    /// oversized chunks never get fully loaded into memory.
+    async fn assemble_chunks(
+        &self,
+        upload_id: Uuid,
+        total_chunks: i32,
+        max_total_size: u64,
+    ) -> Result<AssembledUpload>;
+
+    /// Delete all chunks for an upload
+    async fn cleanup_upload(&self, upload_id: Uuid) -> Result<()>;
+
+    /// Get the sorted numbers of the chunks stored for an upload
+    async fn get_uploaded_chunks(&self, upload_id: Uuid) -> Result<Vec<i32>>;
+
+    /// List the uploads that have chunks stored, whatever their age
+    async fn list_uploads(&self) -> Result<Vec<Uuid>>;
+
+    /// Remove chunks of abandoned uploads, returning how many uploads were removed
+    async fn cleanup_expired_sessions(&self) -> Result<u64>;
+}
+
+/// Open the chunk storage selected by the configuration
+pub async fn open_chunk_storage(config: &Config) -> Result<Arc<dyn ChunkStorage>> {
+    let chunk_storage: Arc<dyn ChunkStorage> = match &config.chunk_redis_url {
+        Some(url) => {
+            tracing::info!("🧩 Storing upload chunks in Redis");
+            Arc::new(
+                RedisChunkStorage::connect(url)
+                    .await?
+                    .with_retention(config.chunk_retention()),
+            )
+        }
+        None => Arc::new(
+            LocalChunkStorage::new(&config.storage_dir)
+                .with_strict_permissions(config.strict_file_permissions)
+                .with_retention(config.chunk_retention()),
+        ),
+    };
+    chunk_storage.init().await?;
+
+    Ok(chunk_storage)
+}
+
+// How long chunks of an abandoned upload are kept unless configured otherwise
+const DEFAULT_RETENTION: Duration = Duration::from_secs(2 * 3600);
+
+/// Keeps chunks on the local filesystem
 #[derive(Clone)]
-pub struct ChunkStorage {
+pub struct LocalChunkStorage {
     chunks_root: PathBuf,
+    strict_permissions: bool,
+    retention: Duration,
 }
 
-impl ChunkStorage {
+impl LocalChunkStorage {
     /// Create a new chunk storage instance
     pub fn new(storage_root: impl AsRef<Path>) -> Self {
         Self {
             chunks_root: storage_root.as_ref().join("chunks"),
+            strict_permissions: false,
+            retention: DEFAULT_RETENTION,
         }
     }
 
-    /// Initialize the chunk storage directory
-    pub async fn init(&self) -> Result<()> {
-        if !self.chunks_root.exists() {
-            fs::create_dir_all(&self.chunks_root).await.map_err(|e| {
-                AppError::ServerError(format!("Failed to create chunks directory: {}", e))
-            })?;
-            tracing::info!(
-                "📁 Created chunks directory: {}",
-                self.chunks_root.display()
-            );
-        }
-        Ok(())
+    /// Keep the chunks of an upload for `retention` after the latest one was stored,
+    /// see [`Config::chunk_retention`]
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Create chunks (0600) and directories (0700) accessible only by their owner, on Unix
+    pub fn with_strict_permissions(mut self, strict_permissions: bool) -> Self {
+        self.strict_permissions = strict_permissions;
+        self
     }
 
     /// Generate path for a specific chunk
@@ -41,9 +146,62 @@ impl ChunkStorage {
     fn get_upload_dir(&self, upload_id: Uuid) -> PathBuf {
         self.chunks_root.join(upload_id.to_string())
     }
+}
+
+// Read sized chunk files one after another into a buffer of their total size
+fn read_chunk_files(
+    upload_id: Uuid,
+    chunks: Vec<(PathBuf, u64)>,
+    total_size: usize,
+) -> Result<AssembledUpload> {
+    let mut data = vec![0u8; total_size];
+    let mut hasher = Sha256::new();
+    let mut offset = 0;
+
+    for (chunk_number, (chunk_path, chunk_size)) in chunks.into_iter().enumerate() {
+        let read_error = |e: std::io::Error| {
+            AppError::ServerError(format!("Failed to read chunk {}: {}", chunk_number, e))
+        };
+        let mut chunk_file = std::fs::File::open(&chunk_path).map_err(read_error)?;
+
+        // A chunk replaced since it was sized would land at the wrong offsets
+        if chunk_file.metadata().map_err(read_error)?.len() != chunk_size {
+            return Err(AppError::Conflict(format!(
+                "Chunk {} of upload {} changed during assembly",
+                chunk_number, upload_id
+            )));
+        }
+
+        let chunk_data = &mut data[offset..offset + chunk_size as usize];
+        chunk_file.read_exact(chunk_data).map_err(read_error)?;
+        hasher.update(&*chunk_data);
+        offset += chunk_size as usize;
+    }
+
+    Ok(AssembledUpload {
+        data,
+        sha256: hasher.finalize().into(),
+    })
+}
 
-    /// Store a chunk to disk
-    pub async fn store_chunk(
+#[async_trait]
+impl ChunkStorage for LocalChunkStorage {
+    async fn init(&self) -> Result<()> {
+        if !self.chunks_root.exists() {
+            create_dir_all(&self.chunks_root, self.strict_permissions)
+                .await
+                .map_err(|e| {
+                    AppError::ServerError(format!("Failed to create chunks directory: {}", e))
+                })?;
+            tracing::info!(
+                "📁 Created chunks directory: {}",
+                self.chunks_root.display()
+            );
+        }
+        Ok(())
+    }
+
+    async fn store_chunk(
         &self,
         upload_id: Uuid,
         chunk_number: i32,
@@ -53,15 +211,16 @@ impl ChunkStorage {
 
         // Create upload directory if it doesn't exist
         if let Some(parent) = chunk_path.parent() {
-            fs::create_dir_all(parent).await.map_err(|e| {
-                AppError::ServerError(format!("Failed to create upload directory: {}", e))
-            })?;
+            create_dir_all(parent, self.strict_permissions)
+                .await
+                .map_err(|e| write_error("Failed to create upload directory", e))?;
         }
 
-        // Write chunk data
-        fs::write(&chunk_path, chunk_data)
+        // Write through a temporary file so an interrupted write (e.g. a
+        // timed-out request) never leaves a valid-looking chunk behind
+        write_atomic(&chunk_path, &[chunk_data], self.strict_permissions)
             .await
-            .map_err(|e| AppError::ServerError(format!("Failed to write chunk: {}", e)))?;
+            .map_err(|e| write_error("Failed to write chunk", e))?;
 
         tracing::debug!(
             "💾 Stored chunk {}/{} ({} bytes)",
@@ -73,14 +232,42 @@ impl ChunkStorage {
         Ok(())
     }
 
-    /// Check if a specific chunk exists
-    pub async fn chunk_exists(&self, upload_id: Uuid, chunk_number: i32) -> bool {
+    async fn store_new_chunk(
+        &self,
+        upload_id: Uuid,
+        chunk_number: i32,
+        chunk_data: &[u8],
+    ) -> Result<bool> {
+        let chunk_path = self.get_chunk_path(upload_id, chunk_number);
+
+        if let Some(parent) = chunk_path.parent() {
+            create_dir_all(parent, self.strict_permissions)
+                .await
+                .map_err(|e| write_error("Failed to create upload directory", e))?;
+        }
+
+        let stored = write_new(&chunk_path, chunk_data, self.strict_permissions)
+            .await
+            .map_err(|e| write_error("Failed to write chunk", e))?;
+
+        if stored {
+            tracing::debug!(
+                "💾 Stored chunk {}/{} ({} bytes)",
+                upload_id,
+                chunk_number,
+                chunk_data.len()
+            );
+        }
+
+        Ok(stored)
+    }
+
+    async fn chunk_exists(&self, upload_id: Uuid, chunk_number: i32) -> Result<bool> {
         let chunk_path = self.get_chunk_path(upload_id, chunk_number);
-        chunk_path.exists()
+        Ok(chunk_path.exists())
     }
 
-    /// Get the size of a specific chunk
-    pub async fn get_chunk_size(&self, upload_id: Uuid, chunk_number: i32) -> Result<u64> {
+    async fn get_chunk_size(&self, upload_id: Uuid, chunk_number: i32) -> Result<u64> {
         let chunk_path = self.get_chunk_path(upload_id, chunk_number);
         let metadata = fs::metadata(&chunk_path)
             .await
@@ -91,9 +278,16 @@ impl ChunkStorage {
         Ok(metadata.len())
     }
 
-    /// Assemble all chunks into a single file and return the data
-    pub async fn assemble_chunks(&self, upload_id: Uuid, total_chunks: i32) -> Result<Vec<u8>> {
-        let mut assembled_data = Vec::new();
+    async fn assemble_chunks(
+        &self,
+        upload_id: Uuid,
+        total_chunks: i32,
+        max_total_size: u64,
+    ) -> Result<AssembledUpload> {
+        // Size every chunk first, so the output is allocated once and each
+        // chunk is read straight into its place
+        let mut chunks = Vec::with_capacity(total_chunks.max(0) as usize);
+        let mut total_bytes = 0u64;
 
         for chunk_number in 0..total_chunks {
             let chunk_path = self.get_chunk_path(upload_id, chunk_number);
@@ -105,25 +299,36 @@ impl ChunkStorage {
                 )));
             }
 
-            let chunk_data = fs::read(&chunk_path).await.map_err(|e| {
-                AppError::ServerError(format!("Failed to read chunk {}: {}", chunk_number, e))
-            })?;
-
-            assembled_data.extend_from_slice(&chunk_data);
+            let chunk_size = self.get_chunk_size(upload_id, chunk_number).await?;
+            total_bytes += chunk_size;
+            if total_bytes > max_total_size {
+                return Err(AppError::BadRequest(format!(
+                    "Assembled data for upload {} exceeds declared size of {} bytes",
+                    upload_id, max_total_size
+                )));
+            }
+            chunks.push((chunk_path, chunk_size));
         }
 
+        // Blocking reads go into the buffer directly, where tokio's would
+        // copy through buffers of their own
+        let assembled = tokio::task::spawn_blocking(move || {
+            read_chunk_files(upload_id, chunks, total_bytes as usize)
+        })
+        .await
+        .map_err(|e| AppError::ServerError(format!("Chunk assembly failed: {}", e)))??;
+
         tracing::info!(
             "🔧 Assembled {} chunks into {} bytes for upload {}",
             total_chunks,
-            assembled_data.len(),
+            assembled.data.len(),
             upload_id
         );
 
-        Ok(assembled_data)
+        Ok(assembled)
     }
 
-    /// Delete all chunks for an upload (cleanup)
-    pub async fn cleanup_upload(&self, upload_id: Uuid) -> Result<()> {
+    async fn cleanup_upload(&self, upload_id: Uuid) -> Result<()> {
         let upload_dir = self.get_upload_dir(upload_id);
 
         if upload_dir.exists() {
@@ -137,8 +342,7 @@ impl ChunkStorage {
         Ok(())
     }
 
-    /// Get list of uploaded chunks for an upload
-    pub async fn get_uploaded_chunks(&self, upload_id: Uuid) -> Result<Vec<i32>> {
+    async fn get_uploaded_chunks(&self, upload_id: Uuid) -> Result<Vec<i32>> {
         let upload_dir = self.get_upload_dir(upload_id);
 
         if !upload_dir.exists() {
@@ -169,8 +373,31 @@ impl ChunkStorage {
         Ok(chunks)
     }
 
-    /// Cleanup expired upload sessions
-    pub async fn cleanup_expired_sessions(&self) -> Result<u64> {
+    async fn list_uploads(&self) -> Result<Vec<Uuid>> {
+        if !self.chunks_root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut uploads = Vec::new();
+        let mut entries = fs::read_dir(&self.chunks_root).await.map_err(|e| {
+            AppError::ServerError(format!("Failed to read chunks directory: {}", e))
+        })?;
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| AppError::ServerError(format!("Failed to read directory entry: {}", e)))?
+        {
+            // Upload directories are named after their upload ID
+            if let Ok(upload_id) = entry.file_name().to_string_lossy().parse::<Uuid>() {
+                uploads.push(upload_id);
+            }
+        }
+
+        Ok(uploads)
+    }
+
+    async fn cleanup_expired_sessions(&self) -> Result<u64> {
         let mut cleanup_count = 0u64;
 
         if !self.chunks_root.exists() {
@@ -192,17 +419,19 @@ impl ChunkStorage {
                 .map_err(|e| AppError::ServerError(format!("Failed to get file type: {}", e)))?
                 .is_dir()
             {
-                // Check if directory is older than 2 hours (generous cleanup)
+                // Remove directories that got no chunk for longer than any upload
+                // session can last (kept-alive sessions included, as their
+                // chunks keep arriving)
                 if let Ok(metadata) = entry.metadata().await {
-                    if let Ok(created) = metadata.created() {
-                        let created_time = created
+                    if let Ok(modified) = metadata.modified() {
+                        let modified_time = modified
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default();
                         let now = std::time::SystemTime::now()
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default();
 
-                        if now.as_secs() - created_time.as_secs() > 2 * 3600
+                        if now.saturating_sub(modified_time) > self.retention
                             && fs::remove_dir_all(entry.path()).await.is_ok()
                         {
                             cleanup_count += 1;
@@ -220,15 +449,298 @@ impl ChunkStorage {
     }
 }
 
+fn redis_error(e: redis::RedisError) -> AppError {
+    AppError::ServerError(format!("Chunk store error: {}", e))
+}
+
+/// Keeps chunks in Redis, shared by all backend replicas
+///
+/// Each chunk is stored under `kusatsu:chunk:<upload_id>:<chunk_number>`,
+/// alongside a set of the chunk numbers stored for the upload.
+#[derive(Clone)]
+pub struct RedisChunkStorage {
+    connection: ConnectionManager,
+    retention: Duration,
+}
+
+impl RedisChunkStorage {
+    /// Connect to the Redis server at `url` (e.g. `redis://127.0.0.1:6379`)
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url)
+            .map_err(|e| AppError::ConfigError(format!("Invalid Redis URL: {}", e)))?;
+        let connection = ConnectionManager::new(client).await.map_err(redis_error)?;
+
+        Ok(Self {
+            connection,
+            retention: DEFAULT_RETENTION,
+        })
+    }
+
+    /// Expire chunks `retention` after the latest one of their upload was stored
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    fn chunk_key(upload_id: Uuid, chunk_number: i32) -> String {
+        format!("kusatsu:chunk:{}:{}", upload_id, chunk_number)
+    }
+
+    fn index_key(upload_id: Uuid) -> String {
+        format!("kusatsu:chunks:{}", upload_id)
+    }
+
+    fn index_key_pattern() -> &'static str {
+        "kusatsu:chunks:*"
+    }
+}
+
+#[async_trait]
+impl ChunkStorage for RedisChunkStorage {
+    async fn init(&self) -> Result<()> {
+        let mut connection = self.connection.clone();
+        redis::cmd("PING")
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn store_chunk(
+        &self,
+        upload_id: Uuid,
+        chunk_number: i32,
+        chunk_data: &[u8],
+    ) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let index_key = Self::index_key(upload_id);
+        // Chunks of abandoned uploads expire on their own; each stored chunk extends the deadline
+        let ttl_secs = self.retention.as_secs() as usize;
+
+        // A chunk only shows up in the index once its data is stored
+        redis::pipe()
+            .atomic()
+            .set_ex(
+                Self::chunk_key(upload_id, chunk_number),
+                chunk_data,
+                ttl_secs,
+            )
+            .ignore()
+            .sadd(&index_key, chunk_number)
+            .ignore()
+            .expire(&index_key, ttl_secs)
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .map_err(redis_error)?;
+
+        tracing::debug!(
+            "💾 Stored chunk {}/{} in Redis ({} bytes)",
+            upload_id,
+            chunk_number,
+            chunk_data.len()
+        );
+
+        Ok(())
+    }
+
+    async fn store_new_chunk(
+        &self,
+        upload_id: Uuid,
+        chunk_number: i32,
+        chunk_data: &[u8],
+    ) -> Result<bool> {
+        let mut connection = self.connection.clone();
+        let index_key = Self::index_key(upload_id);
+        let ttl_secs = self.retention.as_secs() as usize;
+
+        // SET NX leaves a stored copy alone; indexing the chunk again is harmless
+        let (stored,): (Option<String>,) = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(Self::chunk_key(upload_id, chunk_number))
+            .arg(chunk_data)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs)
+            .sadd(&index_key, chunk_number)
+            .ignore()
+            .expire(&index_key, ttl_secs)
+            .ignore()
+            .query_async(&mut connection)
+            .await
+            .map_err(redis_error)?;
+
+        if stored.is_some() {
+            tracing::debug!(
+                "💾 Stored chunk {}/{} in Redis ({} bytes)",
+                upload_id,
+                chunk_number,
+                chunk_data.len()
+            );
+        }
+
+        Ok(stored.is_some())
+    }
+
+    async fn chunk_exists(&self, upload_id: Uuid, chunk_number: i32) -> Result<bool> {
+        let mut connection = self.connection.clone();
+        connection
+            .exists(Self::chunk_key(upload_id, chunk_number))
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn get_chunk_size(&self, upload_id: Uuid, chunk_number: i32) -> Result<u64> {
+        let mut connection = self.connection.clone();
+        let chunk_key = Self::chunk_key(upload_id, chunk_number);
+        let size: u64 = connection.strlen(&chunk_key).await.map_err(redis_error)?;
+
+        // STRLEN reports missing keys as empty
+        if size == 0 && !self.chunk_exists(upload_id, chunk_number).await? {
+            return Err(AppError::FileNotFound);
+        }
+
+        Ok(size)
+    }
+
+    async fn assemble_chunks(
+        &self,
+        upload_id: Uuid,
+        total_chunks: i32,
+        max_total_size: u64,
+    ) -> Result<AssembledUpload> {
+        let mut connection = self.connection.clone();
+
+        // Size every chunk first, so the output is allocated once
+        let mut total_bytes = 0u64;
+
+        for chunk_number in 0..total_chunks {
+            let chunk_key = Self::chunk_key(upload_id, chunk_number);
+
+            // STRLEN is 0 for a missing key, and no stored chunk is empty
+            let chunk_size: u64 = connection.strlen(&chunk_key).await.map_err(redis_error)?;
+            if chunk_size == 0 {
+                return Err(AppError::BadRequest(format!(
+                    "Missing chunk {} for upload {}",
+                    chunk_number, upload_id
+                )));
+            }
+
+            total_bytes += chunk_size;
+            if total_bytes > max_total_size {
+                return Err(AppError::BadRequest(format!(
+                    "Assembled data for upload {} exceeds declared size of {} bytes",
+                    upload_id, max_total_size
+                )));
+            }
+        }
+
+        let mut assembled_data = Vec::with_capacity(total_bytes as usize);
+        let mut hasher = Sha256::new();
+
+        for chunk_number in 0..total_chunks {
+            let chunk_data: Option<Vec<u8>> = connection
+                .get(Self::chunk_key(upload_id, chunk_number))
+                .await
+                .map_err(redis_error)?;
+            let chunk_data = chunk_data.ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Missing chunk {} for upload {}",
+                    chunk_number, upload_id
+                ))
+            })?;
+
+            // Chunks replaced since they were sized could outgrow the allocation
+            if assembled_data.len() + chunk_data.len() > total_bytes as usize {
+                return Err(AppError::Conflict(format!(
+                    "Chunk {} of upload {} changed during assembly",
+                    chunk_number, upload_id
+                )));
+            }
+
+            hasher.update(&chunk_data);
+            assembled_data.extend_from_slice(&chunk_data);
+        }
+
+        tracing::info!(
+            "🔧 Assembled {} chunks from Redis into {} bytes for upload {}",
+            total_chunks,
+            assembled_data.len(),
+            upload_id
+        );
+
+        Ok(AssembledUpload {
+            data: assembled_data,
+            sha256: hasher.finalize().into(),
+        })
+    }
+
+    async fn cleanup_upload(&self, upload_id: Uuid) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let index_key = Self::index_key(upload_id);
+
+        let chunk_numbers: Vec<i32> = connection.smembers(&index_key).await.map_err(redis_error)?;
+        let mut keys: Vec<String> = chunk_numbers
+            .into_iter()
+            .map(|chunk_number| Self::chunk_key(upload_id, chunk_number))
+            .collect();
+        keys.push(index_key);
+
+        connection.del::<_, ()>(keys).await.map_err(redis_error)?;
+
+        tracing::debug!("🧹 Cleaned up Redis chunks for upload {}", upload_id);
+
+        Ok(())
+    }
+
+    async fn get_uploaded_chunks(&self, upload_id: Uuid) -> Result<Vec<i32>> {
+        let mut connection = self.connection.clone();
+        let mut chunks: Vec<i32> = connection
+            .smembers(Self::index_key(upload_id))
+            .await
+            .map_err(redis_error)?;
+
+        chunks.sort();
+        Ok(chunks)
+    }
+
+    async fn list_uploads(&self) -> Result<Vec<Uuid>> {
+        let mut connection = self.connection.clone();
+        let mut keys = connection
+            .scan_match::<_, String>(Self::index_key_pattern())
+            .await
+            .map_err(redis_error)?;
+
+        let mut uploads = Vec::new();
+        while let Some(key) = keys.next_item().await {
+            if let Some(upload_id) = key
+                .rsplit(':')
+                .next()
+                .and_then(|upload_id| upload_id.parse().ok())
+            {
+                uploads.push(upload_id);
+            }
+        }
+
+        Ok(uploads)
+    }
+
+    async fn cleanup_expired_sessions(&self) -> Result<u64> {
+        // Abandoned chunks expire through their TTL
+        Ok(0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::{atomic_temp_path, unique_temp_path};
     use tempfile::TempDir;
 
     #[tokio::test]
     async fn test_chunk_storage() {
         let temp_dir = TempDir::new().unwrap();
-        let chunk_storage = ChunkStorage::new(temp_dir.path());
+        let chunk_storage = LocalChunkStorage::new(temp_dir.path());
         chunk_storage.init().await.unwrap();
 
         let upload_id = Uuid::new_v4();
@@ -246,20 +758,130 @@ mod tests {
             .unwrap();
 
         // Check chunks exist
-        assert!(chunk_storage.chunk_exists(upload_id, 0).await);
-        assert!(chunk_storage.chunk_exists(upload_id, 1).await);
-        assert!(!chunk_storage.chunk_exists(upload_id, 2).await);
+        assert!(chunk_storage.chunk_exists(upload_id, 0).await.unwrap());
+        assert!(chunk_storage.chunk_exists(upload_id, 1).await.unwrap());
+        assert!(!chunk_storage.chunk_exists(upload_id, 2).await.unwrap());
 
         // Get uploaded chunks
         let uploaded_chunks = chunk_storage.get_uploaded_chunks(upload_id).await.unwrap();
         assert_eq!(uploaded_chunks, vec![0, 1]);
 
         // Assemble chunks
-        let assembled = chunk_storage.assemble_chunks(upload_id, 2).await.unwrap();
-        assert_eq!(assembled, b"Hello, World!");
+        let assembled = chunk_storage
+            .assemble_chunks(upload_id, 2, 13)
+            .await
+            .unwrap();
+        assert_eq!(assembled.data, b"Hello, World!");
+        assert_eq!(
+            assembled.sha256,
+            <[u8; 32]>::from(Sha256::digest(b"Hello, World!"))
+        );
 
         // Cleanup
         chunk_storage.cleanup_upload(upload_id).await.unwrap();
-        assert!(!chunk_storage.chunk_exists(upload_id, 0).await);
+        assert!(!chunk_storage.chunk_exists(upload_id, 0).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_new_chunk_is_stored_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_storage = Arc::new(LocalChunkStorage::new(temp_dir.path()));
+        chunk_storage.init().await.unwrap();
+
+        let upload_id = Uuid::new_v4();
+        let writes = (0..8u8).map(|copy| {
+            let chunk_storage = chunk_storage.clone();
+            tokio::spawn(async move {
+                chunk_storage
+                    .store_new_chunk(upload_id, 0, &[copy; 16])
+                    .await
+                    .unwrap()
+            })
+        });
+        let mut stored = 0;
+        for write in writes.collect::<Vec<_>>() {
+            stored += usize::from(write.await.unwrap());
+        }
+        assert_eq!(stored, 1);
+
+        // The first copy stays whole, and nothing else is left in the directory
+        assert_eq!(
+            chunk_storage.get_chunk_size(upload_id, 0).await.unwrap(),
+            16
+        );
+        assert!(!chunk_storage
+            .store_new_chunk(upload_id, 0, b"other")
+            .await
+            .unwrap());
+        assert_eq!(
+            std::fs::read_dir(chunk_storage.get_upload_dir(upload_id))
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_assemble_chunks_size_guard() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_storage = LocalChunkStorage::new(temp_dir.path());
+        chunk_storage.init().await.unwrap();
+
+        let upload_id = Uuid::new_v4();
+        chunk_storage
+            .store_chunk(upload_id, 0, b"Hello, ")
+            .await
+            .unwrap();
+        chunk_storage
+            .store_chunk(upload_id, 1, b"World!")
+            .await
+            .unwrap();
+
+        // Oversized: more data on disk than declared
+        let result = chunk_storage.assemble_chunks(upload_id, 2, 10).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+        // Undersized: the guard allows it, the caller checks the exact size
+        let assembled = chunk_storage
+            .assemble_chunks(upload_id, 2, 100)
+            .await
+            .unwrap();
+        assert_eq!(assembled.data.len(), 13);
+
+        // Missing chunk
+        let result = chunk_storage.assemble_chunks(upload_id, 3, 100).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_leftover_temp_chunk_is_ignored() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_storage = LocalChunkStorage::new(temp_dir.path());
+        chunk_storage.init().await.unwrap();
+
+        let upload_id = Uuid::new_v4();
+        chunk_storage
+            .store_chunk(upload_id, 0, b"Hello")
+            .await
+            .unwrap();
+
+        // Leftovers of interrupted writes, named as each writer names them
+        let leftovers = [
+            atomic_temp_path(&chunk_storage.get_chunk_path(upload_id, 1)),
+            unique_temp_path(&chunk_storage.get_chunk_path(upload_id, 2)),
+        ];
+        for leftover in &leftovers {
+            fs::write(leftover, b"Wor").await.unwrap();
+        }
+
+        for chunk_number in [1, 2] {
+            assert!(!chunk_storage
+                .chunk_exists(upload_id, chunk_number)
+                .await
+                .unwrap());
+        }
+        let uploaded_chunks = chunk_storage.get_uploaded_chunks(upload_id).await.unwrap();
+        assert_eq!(uploaded_chunks, vec![0]);
+        assert!(leftovers.iter().all(|leftover| leftover.exists()));
     }
 }
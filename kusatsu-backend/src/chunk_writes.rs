@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+/// Seconds a client is told to wait when its upload has too many chunks being written
+pub const CHUNK_WRITES_RETRY_AFTER_SECS: u64 = 1;
+
+/// Bound on the chunks of one upload session written at the same time
+///
+/// Each session gets its own semaphore, so a client sending every chunk of a
+/// huge file at once can't hold thousands of open files, whatever other uploads
+/// are doing. Writes over the limit are refused rather than queued, and a
+/// session's semaphore is dropped once none of its chunks are being written.
+#[derive(Clone)]
+pub struct ChunkWrites {
+    uploads: Arc<Mutex<HashMap<Uuid, Arc<Semaphore>>>>,
+    limit: usize,
+}
+
+/// Permission to write one chunk, released when dropped
+pub struct ChunkWritePermit {
+    permit: Option<OwnedSemaphorePermit>,
+    upload_id: Uuid,
+    writes: ChunkWrites,
+}
+
+impl ChunkWrites {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            uploads: Arc::default(),
+            limit: limit.max(1),
+        }
+    }
+
+    /// Start writing a chunk of `upload_id`, or None when it already has the
+    /// most writes in progress
+    pub fn try_acquire(&self, upload_id: Uuid) -> Option<ChunkWritePermit> {
+        let permit = self
+            .lock()
+            .entry(upload_id)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.limit)))
+            .clone()
+            .try_acquire_owned()
+            .ok()?;
+
+        Some(ChunkWritePermit {
+            permit: Some(permit),
+            upload_id,
+            writes: self.clone(),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Uuid, Arc<Semaphore>>> {
+        self.uploads.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for ChunkWritePermit {
+    fn drop(&mut self) {
+        // Released under the lock, so no write can start in between and find
+        // the semaphore gone
+        let mut uploads = self.writes.lock();
+        self.permit.take();
+        if uploads
+            .get(&self.upload_id)
+            .is_some_and(|semaphore| semaphore.available_permits() == self.writes.limit)
+        {
+            uploads.remove(&self.upload_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_are_limited_per_upload() {
+        let writes = ChunkWrites::new(2);
+        let upload_id = Uuid::new_v4();
+
+        let first = writes.try_acquire(upload_id).unwrap();
+        let second = writes.try_acquire(upload_id).unwrap();
+        assert!(writes.try_acquire(upload_id).is_none());
+
+        // Other uploads have their own limit
+        let other = writes.try_acquire(Uuid::new_v4()).unwrap();
+
+        drop(first);
+        let third = writes.try_acquire(upload_id).unwrap();
+
+        // Idle sessions are forgotten
+        drop((second, third, other));
+        assert!(writes.lock().is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::error::{AppError, Result};
+use flate2::read::GzDecoder;
+use kusatsu_types::compression_format;
+use std::io::Read;
+
+/// Check that an upload's declared compression format is one downloads can undo
+pub fn check_format(format: &str) -> Result<()> {
+    match format {
+        compression_format::GZIP => Ok(()),
+        _ => Err(AppError::BadRequest(format!(
+            "Unsupported compression format {:?}, expected {:?}",
+            format,
+            compression_format::GZIP
+        ))),
+    }
+}
+
+/// Whether `compressed_size` bytes may expand to `original_size`, at most
+/// `max_ratio` times their size
+pub fn within_ratio(compressed_size: u64, original_size: u64, max_ratio: u64) -> bool {
+    original_size <= compressed_size.saturating_mul(max_ratio)
+}
+
+/// Decompress a file's plaintext, which has to come out at exactly `original_size` bytes
+///
+/// The size was declared at upload time, so it also bounds the output of data
+/// crafted to expand far beyond it; sizes beyond `max_ratio` times the data
+/// aren't even attempted. The output grows as it is decompressed rather than
+/// being allocated upfront. Data that isn't what the uploader declared is an
+/// invalid file, not a server error.
+pub fn decompress(
+    format: &str,
+    data: &[u8],
+    original_size: u64,
+    max_ratio: u64,
+) -> Result<Vec<u8>> {
+    check_format(format)?;
+    if !within_ratio(data.len() as u64, original_size, max_ratio) {
+        return Err(AppError::InvalidFileFormat);
+    }
+
+    let mut plaintext = Vec::new();
+    GzDecoder::new(data)
+        .take(original_size + 1)
+        .read_to_end(&mut plaintext)
+        .map_err(|e| {
+            tracing::warn!("⚠️ Stored file failed to decompress: {}", e);
+            AppError::InvalidFileFormat
+        })?;
+
+    if plaintext.len() as u64 != original_size {
+        tracing::warn!(
+            "⚠️ Stored file decompressed to {} bytes instead of {}",
+            plaintext.len(),
+            original_size
+        );
+        return Err(AppError::InvalidFileFormat);
+    }
+
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    #[test]
+    fn test_decompress_checks_size() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[b'a'; 1000]).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(
+            decompress("gzip", &compressed, 1000, 1000).unwrap(),
+            [b'a'; 1000]
+        );
+        for (data, original_size) in [
+            (&compressed[..], 999),
+            (&compressed, 1001),
+            (b"not gzip", 8),
+        ] {
+            assert!(matches!(
+                decompress("gzip", data, original_size, 1000),
+                Err(AppError::InvalidFileFormat)
+            ));
+        }
+        assert!(check_format("zstd").is_err());
+    }
+
+    #[test]
+    fn test_decompress_refuses_large_ratios() {
+        // A small bomb declaring a huge size isn't decompressed at all
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&vec![0; 1 << 20]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(!within_ratio(compressed.len() as u64, 1 << 20, 100));
+
+        assert!(matches!(
+            decompress("gzip", &compressed, 1 << 20, 100),
+            Err(AppError::InvalidFileFormat)
+        ));
+        assert!(decompress("gzip", &compressed, 1 << 20, 10_000).is_ok());
+    }
+}
@@ -1,4 +1,7 @@
+use crate::access_log::AccessLog;
 use crate::error::{AppError, Result};
+use kusatsu_encrypt::{pow, Padding};
+use sea_orm::DbBackend;
 use std::env;
 
 #[derive(Clone, Debug)]
@@ -9,12 +12,75 @@ pub struct Config {
     pub base_url: String,
     pub api_url: String,
     pub max_file_size: usize,
+    pub max_expires_in_hours: i64,
+    pub default_expiry_hours: Option<i32>,
+    pub max_downloads_limit: i32,
     pub cleanup_interval_hours: u64,
+    pub sqlite_journal_mode: String,
+    pub sqlite_synchronous: String,
+    pub sqlite_busy_timeout_ms: u64,
+    pub sqlite_max_connections: u32,
+    pub db_max_connections: u32,
+    pub db_min_connections: u32,
+    pub db_connect_timeout_secs: u64,
+    pub db_acquire_timeout_secs: u64,
+    pub db_idle_timeout_secs: u64,
+    pub db_max_lifetime_secs: u64,
+    pub clamav_address: Option<String>,
+    pub clamav_fail_open: bool,
+    pub admin_token: Option<String>,
+    pub maintenance_retry_after_secs: u64,
+    pub static_dir: String,
+    pub serve_static: bool,
+    pub signing_secret: Option<String>,
+    pub request_timeout_secs: u64,
+    pub upload_timeout_secs: u64,
+    pub chunk_redis_url: Option<String>,
+    pub download_rate_limit: Option<u64>,
+    pub delete_on_limit_reached: bool,
+    pub api_docs: bool,
+    pub api_docs_assets: Option<String>,
+    pub strict_file_permissions: bool,
+    pub json_download_max_size: usize,
+    pub max_chunks: i32,
+    pub upload_session_ttl_hours: i64,
+    pub inline_threshold: usize,
+    pub max_concurrent_upload_size: usize,
+    pub max_compression_ratio: u64,
+    pub max_concurrent_chunks_per_upload: usize,
+    pub file_info_cache_ttl_secs: u64,
+    pub size_padding: Padding,
+    pub allowed_mime_types: Vec<String>,
+    pub server_side_encryption: bool,
+    pub denied_extensions: Vec<String>,
+    pub require_https: bool,
+    pub trust_forwarded_host: bool,
+    pub access_log: AccessLog,
+    pub require_upload_pow: bool,
+    pub upload_pow_difficulty: u8,
+}
+
+// Slowest upload rate sessions are sized for: a chunked upload gets the time it
+// would take at this rate on top of the configured TTL
+const SLOW_UPLOAD_BYTES_PER_SECOND: i64 = 1024 * 1024;
+
+// Chunks outlive the longest possible session by this much before they are
+// considered abandoned, so a session never loses chunks while it is still open
+const CHUNK_RETENTION_MARGIN: chrono::Duration = chrono::Duration::hours(1);
+
+// Comma-separated values of an environment variable, lowercased, without empty entries
+fn env_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| !value.is_empty())
+        .collect()
 }
 
 impl Config {
     pub fn from_env() -> Result<Self> {
-        Ok(Config {
+        let config = Config {
             database_url: env::var("KUSATSU_DATABASE_URL")
                 .unwrap_or_else(|_| "sqlite://kusatsu.db".to_string()),
 
@@ -37,12 +103,618 @@ impl Config {
                 * 1024
                 * 1024, // Convert MB to bytes
 
+            max_expires_in_hours: env::var("KUSATSU_MAX_EXPIRES_IN_HOURS")
+                .unwrap_or_else(|_| "8760".to_string()) // Default 1 year
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_MAX_EXPIRES_IN_HOURS".to_string())
+                })?,
+
+            // Applied to uploads that don't ask for an expiry; unset or 0 keeps them forever
+            default_expiry_hours: env::var("KUSATSU_DEFAULT_EXPIRY_HOURS")
+                .ok()
+                .filter(|hours| !hours.is_empty())
+                .map(|hours| {
+                    hours
+                        .parse::<i32>()
+                        .ok()
+                        .filter(|hours| *hours >= 0)
+                        .ok_or_else(|| {
+                            AppError::ConfigError(
+                                "Invalid KUSATSU_DEFAULT_EXPIRY_HOURS".to_string(),
+                            )
+                        })
+                })
+                .transpose()?
+                .filter(|hours| *hours > 0),
+
+            max_downloads_limit: env::var("KUSATSU_MAX_DOWNLOADS_LIMIT")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .ok()
+                .filter(|limit| *limit > 0)
+                .ok_or_else(|| {
+                    AppError::ConfigError("Invalid KUSATSU_MAX_DOWNLOADS_LIMIT".to_string())
+                })?,
+
             cleanup_interval_hours: env::var("KUSATSU_CLEANUP_INTERVAL_HOURS")
                 .unwrap_or_else(|_| "24".to_string())
                 .parse()
                 .map_err(|_| {
                     AppError::ConfigError("Invalid KUSATSU_CLEANUP_INTERVAL_HOURS".to_string())
                 })?,
-        })
+
+            sqlite_journal_mode: env::var("KUSATSU_SQLITE_JOURNAL_MODE")
+                .unwrap_or_else(|_| "WAL".to_string()),
+
+            sqlite_synchronous: env::var("KUSATSU_SQLITE_SYNCHRONOUS")
+                .unwrap_or_else(|_| "NORMAL".to_string()),
+
+            sqlite_busy_timeout_ms: env::var("KUSATSU_SQLITE_BUSY_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_SQLITE_BUSY_TIMEOUT_MS".to_string())
+                })?,
+
+            // SQLite serializes writes, so a large pool only adds lock contention
+            sqlite_max_connections: env::var("KUSATSU_SQLITE_MAX_CONNECTIONS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_SQLITE_MAX_CONNECTIONS".to_string())
+                })?,
+
+            // Pool bounds for server databases; SQLite takes its size from the setting above
+            db_max_connections: env::var("KUSATSU_DB_MAX_CONNECTIONS")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_DB_MAX_CONNECTIONS".to_string())
+                })?,
+
+            db_min_connections: env::var("KUSATSU_DB_MIN_CONNECTIONS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_DB_MIN_CONNECTIONS".to_string())
+                })?,
+
+            db_connect_timeout_secs: env::var("KUSATSU_DB_CONNECT_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_DB_CONNECT_TIMEOUT_SECS".to_string())
+                })?,
+
+            db_acquire_timeout_secs: env::var("KUSATSU_DB_ACQUIRE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_DB_ACQUIRE_TIMEOUT_SECS".to_string())
+                })?,
+
+            db_idle_timeout_secs: env::var("KUSATSU_DB_IDLE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "600".to_string()) // Default 10 minutes
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_DB_IDLE_TIMEOUT_SECS".to_string())
+                })?,
+
+            db_max_lifetime_secs: env::var("KUSATSU_DB_MAX_LIFETIME_SECS")
+                .unwrap_or_else(|_| "1800".to_string()) // Default 30 minutes
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_DB_MAX_LIFETIME_SECS".to_string())
+                })?,
+
+            clamav_address: env::var("KUSATSU_CLAMAV_ADDRESS")
+                .ok()
+                .filter(|address| !address.is_empty()),
+
+            clamav_fail_open: env::var("KUSATSU_CLAMAV_FAIL_OPEN")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_CLAMAV_FAIL_OPEN".to_string())
+                })?,
+
+            admin_token: env::var("KUSATSU_ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+
+            maintenance_retry_after_secs: env::var("KUSATSU_MAINTENANCE_RETRY_AFTER_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError(
+                        "Invalid KUSATSU_MAINTENANCE_RETRY_AFTER_SECS".to_string(),
+                    )
+                })?,
+
+            static_dir: env::var("KUSATSU_STATIC_DIR").unwrap_or_else(|_| "static".to_string()),
+
+            // Disable for API-only deployments where the frontend is hosted elsewhere
+            serve_static: env::var("KUSATSU_SERVE_STATIC")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .map_err(|_| AppError::ConfigError("Invalid KUSATSU_SERVE_STATIC".to_string()))?,
+
+            signing_secret: env::var("KUSATSU_SIGNING_SECRET")
+                .ok()
+                .filter(|secret| !secret.is_empty()),
+
+            request_timeout_secs: env::var("KUSATSU_REQUEST_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_REQUEST_TIMEOUT_SECS".to_string())
+                })?,
+
+            // Uploads and downloads move whole files, so they get a longer deadline
+            upload_timeout_secs: env::var("KUSATSU_UPLOAD_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "600".to_string()) // Default 10 minutes
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_UPLOAD_TIMEOUT_SECS".to_string())
+                })?,
+
+            // Share chunks between replicas through Redis instead of the local disk
+            chunk_redis_url: env::var("KUSATSU_CHUNK_REDIS_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+
+            // Caps each download separately, not the total across downloads
+            download_rate_limit: env::var("KUSATSU_DOWNLOAD_RATE_LIMIT_BYTES_PER_SEC")
+                .ok()
+                .filter(|limit| !limit.is_empty())
+                .map(|limit| {
+                    limit
+                        .parse()
+                        .ok()
+                        .filter(|limit| *limit > 0)
+                        .ok_or_else(|| {
+                            AppError::ConfigError(
+                                "Invalid KUSATSU_DOWNLOAD_RATE_LIMIT_BYTES_PER_SEC".to_string(),
+                            )
+                        })
+                })
+                .transpose()?,
+
+            // Delete files as soon as their last allowed download is counted, instead of at expiry
+            delete_on_limit_reached: env::var("KUSATSU_DELETE_ON_LIMIT_REACHED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_DELETE_ON_LIMIT_REACHED".to_string())
+                })?,
+
+            // Swagger UI at /api/docs; the OpenAPI JSON itself is always served
+            api_docs: env::var("KUSATSU_API_DOCS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| AppError::ConfigError("Invalid KUSATSU_API_DOCS".to_string()))?,
+
+            // Directory holding swagger-ui-dist's files, to serve them instead of using the CDN
+            api_docs_assets: env::var("KUSATSU_API_DOCS_ASSETS")
+                .ok()
+                .filter(|dir| !dir.is_empty()),
+
+            // Owner-only modes for stored files and directories, on Unix
+            strict_file_permissions: env::var("KUSATSU_STRICT_FILE_PERMISSIONS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_STRICT_FILE_PERMISSIONS".to_string())
+                })?,
+
+            // Larger files must use the streaming download instead of download-json
+            json_download_max_size: env::var("KUSATSU_JSON_DOWNLOAD_MAX_SIZE")
+                .unwrap_or_else(|_| "10".to_string()) // Default 10MB
+                .parse::<usize>()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_JSON_DOWNLOAD_MAX_SIZE".to_string())
+                })?
+                * 1024
+                * 1024, // Convert MB to bytes
+
+            // Upper bound on the chunks of one upload session, whatever its chunk size
+            max_chunks: env::var("KUSATSU_MAX_CHUNKS")
+                .unwrap_or_else(|_| "100000".to_string())
+                .parse()
+                .ok()
+                .filter(|max_chunks| *max_chunks > 0)
+                .ok_or_else(|| AppError::ConfigError("Invalid KUSATSU_MAX_CHUNKS".to_string()))?,
+
+            upload_session_ttl_hours: env::var("KUSATSU_UPLOAD_SESSION_TTL_HOURS")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .ok()
+                .filter(|hours| *hours > 0)
+                .ok_or_else(|| {
+                    AppError::ConfigError("Invalid KUSATSU_UPLOAD_SESSION_TTL_HOURS".to_string())
+                })?,
+
+            // Stored data smaller than this many bytes goes in the database; 0 disables it
+            inline_threshold: env::var("KUSATSU_INLINE_THRESHOLD")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_INLINE_THRESHOLD".to_string())
+                })?,
+
+            // How many times its compressed size a compressed upload may declare
+            max_compression_ratio: env::var("KUSATSU_MAX_COMPRESSION_RATIO")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse::<u64>()
+                .ok()
+                .filter(|ratio| *ratio > 0)
+                .ok_or_else(|| {
+                    AppError::ConfigError("Invalid KUSATSU_MAX_COMPRESSION_RATIO".to_string())
+                })?,
+
+            // Memory that uploads loading a whole file may hold at once
+            max_concurrent_upload_size: env::var("KUSATSU_MAX_CONCURRENT_UPLOAD_SIZE")
+                .unwrap_or_else(|_| "1024".to_string()) // Default 1GB
+                .parse::<usize>()
+                .ok()
+                .filter(|size| *size > 0)
+                .ok_or_else(|| {
+                    AppError::ConfigError("Invalid KUSATSU_MAX_CONCURRENT_UPLOAD_SIZE".to_string())
+                })?
+                * 1024
+                * 1024, // Convert MB to bytes
+
+            // Chunks of one upload session written at the same time
+            max_concurrent_chunks_per_upload: env::var("KUSATSU_MAX_CONCURRENT_CHUNKS_PER_UPLOAD")
+                .unwrap_or_else(|_| "16".to_string())
+                .parse::<usize>()
+                .ok()
+                .filter(|chunks| *chunks > 0)
+                .ok_or_else(|| {
+                    AppError::ConfigError(
+                        "Invalid KUSATSU_MAX_CONCURRENT_CHUNKS_PER_UPLOAD".to_string(),
+                    )
+                })?,
+
+            // How long file info lookups are served from memory; 0 disables the cache
+            file_info_cache_ttl_secs: env::var("KUSATSU_FILE_INFO_CACHE_TTL_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_FILE_INFO_CACHE_TTL_SECS".to_string())
+                })?,
+
+            // Rounds the sizes file info reports up: `power-of-two`, a bucket in bytes, or `none`
+            size_padding: match env::var("KUSATSU_SIZE_PADDING")
+                .unwrap_or_else(|_| "none".to_string())
+                .as_str()
+            {
+                "none" => Padding::None,
+                "power-of-two" => Padding::PowerOfTwo,
+                bucket => bucket
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|bucket| *bucket > 0)
+                    .map(Padding::Bucket)
+                    .ok_or_else(|| {
+                        AppError::ConfigError("Invalid KUSATSU_SIZE_PADDING".to_string())
+                    })?,
+            },
+
+            // When off, single uploads must be encrypted by the client
+            server_side_encryption: env::var("KUSATSU_SERVER_SIDE_ENCRYPTION")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_SERVER_SIDE_ENCRYPTION".to_string())
+                })?,
+
+            // Mime types uploads may declare, e.g. `image/*`; empty allows any
+            allowed_mime_types: env_list("KUSATSU_ALLOWED_MIME_TYPES"),
+
+            // Filename extensions uploads may not have, e.g. `exe`; empty allows any
+            denied_extensions: env_list("KUSATSU_DENIED_EXTENSIONS")
+                .into_iter()
+                .map(|extension| extension.trim_start_matches('.').to_string())
+                .collect(),
+
+            // Keys travel in some requests, so deployments behind a proxy can insist on TLS;
+            // the proxy must overwrite or append to X-Forwarded-Proto, whose last value counts
+            require_https: env::var("KUSATSU_REQUIRE_HTTPS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| AppError::ConfigError("Invalid KUSATSU_REQUIRE_HTTPS".to_string()))?,
+
+            // Build links from the host the client reached instead of the configured URLs
+            trust_forwarded_host: env::var("KUSATSU_TRUST_FORWARDED_HOST")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_TRUST_FORWARDED_HOST".to_string())
+                })?,
+
+            // Which requests get an access log line: off, errors or all
+            access_log: env::var("KUSATSU_ACCESS_LOG")
+                .unwrap_or_else(|_| "off".to_string())
+                .parse()
+                .map_err(|_| AppError::ConfigError("Invalid KUSATSU_ACCESS_LOG".to_string()))?,
+
+            // Uploads must include a solved challenge from /api/upload/challenge
+            require_upload_pow: env::var("KUSATSU_REQUIRE_UPLOAD_POW")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| {
+                    AppError::ConfigError("Invalid KUSATSU_REQUIRE_UPLOAD_POW".to_string())
+                })?,
+
+            // Leading zero bits a solution's hash must have; each one doubles the work
+            upload_pow_difficulty: env::var("KUSATSU_UPLOAD_POW_DIFFICULTY")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse::<u8>()
+                .ok()
+                .filter(|bits| (1..=pow::MAX_DIFFICULTY).contains(bits))
+                .ok_or_else(|| {
+                    AppError::ConfigError(format!(
+                        "KUSATSU_UPLOAD_POW_DIFFICULTY must be between 1 and {}",
+                        pow::MAX_DIFFICULTY
+                    ))
+                })?,
+        };
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that related settings are consistent with each other
+    pub fn validate(&self) -> Result<()> {
+        self.database_backend()?;
+
+        // A connection is always retired at max_lifetime, so an idle timeout
+        // beyond it would never apply
+        if self.db_max_lifetime_secs < self.db_idle_timeout_secs {
+            return Err(AppError::ConfigError(
+                "KUSATSU_DB_MAX_LIFETIME_SECS must be greater than or equal to KUSATSU_DB_IDLE_TIMEOUT_SECS"
+                    .to_string(),
+            ));
+        }
+
+        if self.db_max_connections == 0 || self.sqlite_max_connections == 0 {
+            return Err(AppError::ConfigError(
+                "KUSATSU_DB_MAX_CONNECTIONS and KUSATSU_SQLITE_MAX_CONNECTIONS must be at least 1"
+                    .to_string(),
+            ));
+        }
+
+        if self.db_min_connections > self.db_max_connections {
+            return Err(AppError::ConfigError(
+                "KUSATSU_DB_MIN_CONNECTIONS must not exceed KUSATSU_DB_MAX_CONNECTIONS".to_string(),
+            ));
+        }
+
+        if self
+            .default_expiry_hours
+            .is_some_and(|hours| i64::from(hours) > self.max_expires_in_hours)
+        {
+            return Err(AppError::ConfigError(
+                "KUSATSU_DEFAULT_EXPIRY_HOURS must not exceed KUSATSU_MAX_EXPIRES_IN_HOURS"
+                    .to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Database the URL points to, from its scheme
+    ///
+    /// Only SQLite and PostgreSQL drivers are built in; any other scheme is
+    /// rejected here rather than by a driver error at startup. The URL itself
+    /// isn't repeated in errors as it may hold a password.
+    pub fn database_backend(&self) -> Result<DbBackend> {
+        let scheme = self
+            .database_url
+            .split_once(':')
+            .map(|(scheme, _)| scheme.to_ascii_lowercase());
+
+        match scheme.as_deref() {
+            Some("sqlite") => Ok(DbBackend::Sqlite),
+            Some("postgres" | "postgresql") => Ok(DbBackend::Postgres),
+            Some("mysql" | "mariadb") => Err(AppError::ConfigError(
+                "KUSATSU_DATABASE_URL points to MySQL, which this build doesn't support; use sqlite:// or postgres://"
+                    .to_string(),
+            )),
+            Some(scheme) => Err(AppError::ConfigError(format!(
+                "Unsupported KUSATSU_DATABASE_URL scheme \"{}\"; use sqlite:// or postgres://",
+                scheme
+            ))),
+            None => Err(AppError::ConfigError(
+                "KUSATSU_DATABASE_URL has no scheme; use sqlite:// or postgres://".to_string(),
+            )),
+        }
+    }
+
+    /// How long a chunked upload of `file_size` bytes stays open
+    ///
+    /// The configured TTL, plus the time the upload would take at 1 MB/s, so
+    /// large uploads on slow connections don't expire midway.
+    pub fn upload_session_ttl(&self, file_size: i64) -> chrono::Duration {
+        chrono::Duration::hours(self.upload_session_ttl_hours)
+            + chrono::Duration::seconds(file_size.max(0) / SLOW_UPLOAD_BYTES_PER_SECOND)
+    }
+
+    /// Time without a new chunk after which an upload's chunks are abandoned
+    ///
+    /// Longer than any upload session can last, whatever the file size.
+    pub fn chunk_retention(&self) -> std::time::Duration {
+        (self.upload_session_ttl(self.max_file_size as i64) + CHUNK_RETENTION_MARGIN)
+            .to_std()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// The defaults of [`Config::from_env`], without reading the environment,
+    /// so tests don't depend on how the process running them was configured
+    pub(crate) fn for_tests() -> Self {
+        Config {
+            database_url: "sqlite://kusatsu.db".to_string(),
+            server_address: "127.0.0.1:3000".to_string(),
+            storage_dir: "./storage".to_string(),
+            base_url: "http://localhost:8080".to_string(),
+            api_url: "http://localhost:3000".to_string(),
+            max_file_size: 5000 * 1024 * 1024,
+            max_expires_in_hours: 8760,
+            default_expiry_hours: None,
+            max_downloads_limit: 1000,
+            cleanup_interval_hours: 24,
+            sqlite_journal_mode: "WAL".to_string(),
+            sqlite_synchronous: "NORMAL".to_string(),
+            sqlite_busy_timeout_ms: 5000,
+            sqlite_max_connections: 5,
+            db_max_connections: 100,
+            db_min_connections: 5,
+            db_connect_timeout_secs: 8,
+            db_acquire_timeout_secs: 8,
+            db_idle_timeout_secs: 600,
+            db_max_lifetime_secs: 1800,
+            clamav_address: None,
+            clamav_fail_open: false,
+            admin_token: None,
+            maintenance_retry_after_secs: 300,
+            static_dir: "static".to_string(),
+            serve_static: true,
+            signing_secret: None,
+            request_timeout_secs: 30,
+            upload_timeout_secs: 600,
+            chunk_redis_url: None,
+            download_rate_limit: None,
+            delete_on_limit_reached: false,
+            api_docs: false,
+            api_docs_assets: None,
+            strict_file_permissions: false,
+            json_download_max_size: 10 * 1024 * 1024,
+            max_chunks: 100_000,
+            upload_session_ttl_hours: 1,
+            inline_threshold: 0,
+            max_concurrent_upload_size: 1024 * 1024 * 1024,
+            max_compression_ratio: 100,
+            max_concurrent_chunks_per_upload: 16,
+            file_info_cache_ttl_secs: 0,
+            size_padding: Padding::None,
+            allowed_mime_types: Vec::new(),
+            server_side_encryption: true,
+            denied_extensions: Vec::new(),
+            require_https: false,
+            trust_forwarded_host: false,
+            access_log: AccessLog::Off,
+            require_upload_pow: false,
+            upload_pow_difficulty: 20,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_pool_lifetimes_are_valid() {
+        let config = Config::from_env().unwrap();
+        assert!(config.db_max_lifetime_secs >= config.db_idle_timeout_secs);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_test_config_has_the_defaults() {
+        // Only comparable when the environment doesn't change any setting
+        if env::vars().any(|(name, _)| name.starts_with("KUSATSU_")) {
+            return;
+        }
+        assert_eq!(
+            format!("{:?}", Config::for_tests()),
+            format!("{:?}", Config::from_env().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_max_lifetime_shorter_than_idle_timeout_is_rejected() {
+        let config = Config {
+            db_idle_timeout_secs: 600,
+            db_max_lifetime_secs: 8,
+            ..Config::for_tests()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_pool_bounds_are_validated() {
+        for (db_min_connections, db_max_connections, sqlite_max_connections) in
+            [(6, 5, 5), (0, 0, 5), (0, 5, 0)]
+        {
+            let config = Config {
+                db_min_connections,
+                db_max_connections,
+                sqlite_max_connections,
+                ..Config::for_tests()
+            };
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_default_expiry_beyond_maximum_is_rejected() {
+        let config = Config {
+            default_expiry_hours: Some(48),
+            max_expires_in_hours: 24,
+            ..Config::for_tests()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_database_url_schemes() {
+        let with_url = |database_url: &str| Config {
+            database_url: database_url.to_string(),
+            ..Config::for_tests()
+        };
+
+        for (database_url, backend) in [
+            ("sqlite://kusatsu.db", DbBackend::Sqlite),
+            ("sqlite::memory:", DbBackend::Sqlite),
+            ("postgres://kusatsu@localhost/kusatsu", DbBackend::Postgres),
+            (
+                "postgresql://kusatsu@localhost/kusatsu",
+                DbBackend::Postgres,
+            ),
+        ] {
+            assert_eq!(with_url(database_url).database_backend().unwrap(), backend);
+        }
+
+        for database_url in [
+            "sqlit://kusatsu.db",
+            "mysql://root@localhost/kusatsu",
+            "kusatsu.db",
+            "",
+        ] {
+            let config = with_url(database_url);
+            assert!(matches!(config.validate(), Err(AppError::ConfigError(_))));
+        }
+    }
+
+    #[test]
+    fn test_chunks_outlive_the_longest_session() {
+        let config = Config {
+            upload_session_ttl_hours: 2,
+            max_file_size: 10 * 1024 * 1024 * 1024,
+            ..Config::for_tests()
+        };
+        let longest_session = config
+            .upload_session_ttl(config.max_file_size as i64)
+            .to_std()
+            .unwrap();
+
+        assert!(longest_session > std::time::Duration::from_secs(2 * 3600));
+        assert!(config.chunk_retention() > longest_session);
     }
 }
@@ -0,0 +1,210 @@
+use crate::error::{AppError, Result};
+use crate::storage::StoredFileReader;
+use kusatsu_encrypt::{cipher, EncryptionKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use tokio::io::AsyncReadExt;
+
+/// First bytes of every stored file written in the container format
+pub const MAGIC: &[u8; 8] = b"KUSATSU\0";
+
+/// Version of the container layout, bumped when the header changes incompatibly
+pub const FORMAT_VERSION: u8 = 1;
+
+// Magic, version and the big-endian u32 length of the JSON header
+const PREAMBLE_LEN: usize = MAGIC.len() + 1 + 4;
+
+// Headers are a few hundred bytes; anything much larger is not one
+const MAX_HEADER_LEN: u32 = 64 * 1024;
+
+/// What a stored file needs to be recovered and checked without its database record
+///
+/// Stored files are laid out as [`MAGIC`], the [`FORMAT_VERSION`] byte, the
+/// length of the header as a big-endian u32, the header as JSON, then the
+/// ciphertext (the data as uploaded for unencrypted files). Files stored before
+/// the format existed hold the bare ciphertext, with the nonce only in the
+/// database.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContainerHeader {
+    /// Nonce the content was encrypted with; empty for unencrypted files
+    pub nonce: Vec<u8>,
+    /// SHA-256 of the key bytes (lowercase hex), only known when the server
+    /// encrypted the file; the CLI manifest records the same fingerprint
+    pub key_fingerprint: Option<String>,
+    /// Size of the content before encryption and compression
+    pub original_size: i64,
+    /// Size of the ciphertext following the header
+    pub ciphertext_size: u64,
+}
+
+impl ContainerHeader {
+    /// Header of data stored as uploaded, without encryption
+    pub fn plain(data: &[u8]) -> Self {
+        Self {
+            nonce: Vec::new(),
+            key_fingerprint: None,
+            original_size: data.len() as i64,
+            ciphertext_size: data.len() as u64,
+        }
+    }
+
+    /// Header of `ciphertext`, encrypted with `nonce`
+    pub fn encrypted(
+        ciphertext: &[u8],
+        nonce: Vec<u8>,
+        key_fingerprint: Option<String>,
+        original_size: i64,
+    ) -> Self {
+        Self {
+            nonce,
+            key_fingerprint,
+            original_size,
+            ciphertext_size: ciphertext.len() as u64,
+        }
+    }
+
+    /// Bytes stored ahead of the ciphertext: magic, version, header length and header
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let header = serde_json::to_vec(self)?;
+        let mut encoded = Vec::with_capacity(PREAMBLE_LEN + header.len());
+        encoded.extend_from_slice(MAGIC);
+        encoded.push(FORMAT_VERSION);
+        encoded.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        encoded.extend_from_slice(&header);
+        Ok(encoded)
+    }
+}
+
+/// Fingerprint of a file key, as recorded in [`ContainerHeader::key_fingerprint`]
+pub fn key_fingerprint(key: &EncryptionKey) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// Read the header of a stored file of `size` bytes from the start of `reader`
+///
+/// Returns the header, a reader positioned at the ciphertext and the ciphertext
+/// size. Files without the magic bytes predate the format and come back whole,
+/// with no header. A file with the magic bytes must have a supported version, a
+/// well-formed header and exactly the ciphertext size it declares.
+pub async fn read_header(
+    mut reader: StoredFileReader,
+    size: u64,
+) -> Result<(Option<ContainerHeader>, StoredFileReader, u64)> {
+    let mut preamble = Vec::with_capacity(PREAMBLE_LEN);
+    (&mut reader)
+        .take(PREAMBLE_LEN as u64)
+        .read_to_end(&mut preamble)
+        .await?;
+
+    if !preamble.starts_with(MAGIC) {
+        let reader = Box::new(AsyncReadExt::chain(Cursor::new(preamble), reader));
+        return Ok((None, reader, size));
+    }
+    if preamble.len() < PREAMBLE_LEN {
+        return Err(invalid_header("it is truncated"));
+    }
+
+    let version = preamble[MAGIC.len()];
+    if version != FORMAT_VERSION {
+        return Err(invalid_header(format!(
+            "version {} is not supported",
+            version
+        )));
+    }
+    let header_len = u32::from_be_bytes(preamble[MAGIC.len() + 1..].try_into().unwrap());
+    if header_len > MAX_HEADER_LEN {
+        return Err(invalid_header(format!("{} bytes is too long", header_len)));
+    }
+
+    let mut header = vec![0; header_len as usize];
+    reader
+        .read_exact(&mut header)
+        .await
+        .map_err(|_| invalid_header("it is truncated"))?;
+    let header: ContainerHeader = serde_json::from_slice(&header)
+        .map_err(|e| invalid_header(format!("it is malformed: {}", e)))?;
+
+    if !header.nonce.is_empty() && header.nonce.len() != cipher::NONCE_SIZE {
+        return Err(invalid_header(format!(
+            "its nonce has {} bytes",
+            header.nonce.len()
+        )));
+    }
+    let ciphertext_size = size.saturating_sub(PREAMBLE_LEN as u64 + header_len as u64);
+    if header.ciphertext_size != ciphertext_size {
+        return Err(invalid_header(format!(
+            "it declares {} bytes of ciphertext but {} follow",
+            header.ciphertext_size, ciphertext_size
+        )));
+    }
+
+    Ok((Some(header), reader, ciphertext_size))
+}
+
+fn invalid_header(reason: impl std::fmt::Display) -> AppError {
+    AppError::ServerError(format!("Invalid stored file header: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(stored: Vec<u8>) -> Result<(Option<ContainerHeader>, Vec<u8>)> {
+        let size = stored.len() as u64;
+        let (header, mut reader, ciphertext_size) =
+            read_header(Box::new(Cursor::new(stored)), size).await?;
+        let mut ciphertext = Vec::new();
+        reader.read_to_end(&mut ciphertext).await?;
+        assert_eq!(ciphertext.len() as u64, ciphertext_size);
+        Ok((header, ciphertext))
+    }
+
+    #[tokio::test]
+    async fn test_header_round_trip() {
+        let key = EncryptionKey::generate();
+        let header = ContainerHeader::encrypted(
+            b"ciphertext",
+            vec![7; cipher::NONCE_SIZE],
+            Some(key_fingerprint(&key)),
+            4,
+        );
+        let mut stored = header.encode().unwrap();
+        assert!(stored.starts_with(MAGIC));
+        stored.extend_from_slice(b"ciphertext");
+
+        let (read_back, ciphertext) = read(stored).await.unwrap();
+        assert_eq!(read_back, Some(header));
+        assert_eq!(ciphertext, b"ciphertext");
+    }
+
+    #[tokio::test]
+    async fn test_legacy_files_are_read_whole() {
+        for stored in [b"raw ciphertext".to_vec(), b"KUS".to_vec(), Vec::new()] {
+            let (header, ciphertext) = read(stored.clone()).await.unwrap();
+            assert_eq!(header, None);
+            assert_eq!(ciphertext, stored);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_headers_are_rejected() {
+        let header = ContainerHeader::plain(b"data");
+        let encoded = header.encode().unwrap();
+
+        // Ciphertext shorter than declared
+        let mut truncated = encoded.clone();
+        truncated.extend_from_slice(b"dat");
+        assert!(read(truncated).await.is_err());
+
+        // Unknown version
+        let mut future = encoded.clone();
+        future[MAGIC.len()] = FORMAT_VERSION + 1;
+        future.extend_from_slice(b"data");
+        assert!(read(future).await.is_err());
+
+        // Header cut short
+        assert!(read(encoded[..encoded.len() - 2].to_vec()).await.is_err());
+        assert!(read(MAGIC.to_vec()).await.is_err());
+    }
+}
@@ -0,0 +1,70 @@
+/// Build an `attachment` Content-Disposition value for a filename (RFC 6266)
+///
+/// `filename` carries an ASCII approximation for clients that only read that
+/// parameter, and `filename*` the exact name, UTF-8 and percent-encoded as
+/// RFC 5987 describes. Clients that understand `filename*` prefer it.
+pub fn attachment(filename: &str) -> String {
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        ascii_fallback(filename),
+        percent_encode(filename)
+    )
+}
+
+// Quoted-string form of the name, with non-ASCII characters replaced
+fn ascii_fallback(filename: &str) -> String {
+    let mut fallback = String::with_capacity(filename.len());
+    for c in filename.chars() {
+        match c {
+            '"' | '\\' => {
+                fallback.push('\\');
+                fallback.push(c);
+            }
+            ' '..='~' => fallback.push(c),
+            c if c.is_ascii_control() => fallback.push(' '),
+            _ => fallback.push('_'),
+        }
+    }
+    fallback
+}
+
+// Every byte outside RFC 5987's attr-char set is written as %XX
+fn percent_encode(filename: &str) -> String {
+    let mut encoded = String::with_capacity(filename.len());
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_encoding() {
+        assert_eq!(
+            attachment("report.pdf"),
+            "attachment; filename=\"report.pdf\"; filename*=UTF-8''report.pdf"
+        );
+        assert_eq!(
+            attachment("my \"notes\"\r\n.txt"),
+            "attachment; filename=\"my \\\"notes\\\"  .txt\"; filename*=UTF-8''my%20%22notes%22%0D%0A.txt"
+        );
+        assert_eq!(
+            attachment("温泉 🎉.txt"),
+            "attachment; filename=\"__ _.txt\"; filename*=UTF-8''%E6%B8%A9%E6%B3%89%20%F0%9F%8E%89.txt"
+        );
+        assert_eq!(
+            attachment("Привет.doc"),
+            "attachment; filename=\"______.doc\"; filename*=UTF-8''%D0%9F%D1%80%D0%B8%D0%B2%D0%B5%D1%82.doc"
+        );
+
+        // Always a valid header value, whatever the name
+        assert!(axum::http::HeaderValue::from_str(&attachment("日本語\u{7f}.txt")).is_ok());
+    }
+}
@@ -1,9 +1,52 @@
-use crate::error::Result;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::DatabasePoolStatus;
 use kusatsu_migration::{Migrator, MigratorTrait};
-use sea_orm::{ConnectOptions, Database, DatabaseConnection};
+use sea_orm::{
+    ConnectOptions, ConnectionTrait, Database, DatabaseConnection, DbBackend, DbErr, RuntimeErr,
+    SqlxSqliteConnector,
+};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteSynchronous};
+use sqlx::Sqlite;
+use std::str::FromStr;
 use std::time::Duration;
 use uuid::Uuid;
 
+// Number of expired rows processed per cleanup batch
+const CLEANUP_BATCH_SIZE: u64 = 500;
+
+/// Outcome of [`file_ops::check_storage_consistency`]
+#[derive(Debug, Default, serde::Serialize)]
+pub struct StorageCheckReport {
+    pub files_checked: u64,
+    pub records_checked: u64,
+    pub orphaned_files: u64,
+    pub orphaned_files_removed: u64,
+    /// Records whose file is missing from storage
+    pub missing_files: Vec<Uuid>,
+}
+
+/// Files an operator selects for deletion; every filter that is set must match
+#[derive(Debug, Default, Clone)]
+pub struct FileFilter {
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    pub mime_type: Option<String>,
+    /// Smallest original size, in bytes
+    pub min_size: Option<i64>,
+    pub file_ids: Option<Vec<Uuid>>,
+}
+
+impl FileFilter {
+    /// Whether no filter is set, which would select every file
+    pub fn is_empty(&self) -> bool {
+        self.created_before.is_none()
+            && self.mime_type.is_none()
+            && self.min_size.is_none()
+            && self.file_ids.is_none()
+    }
+}
+
 // Parameter structs for database operations
 #[derive(Debug)]
 pub struct CreateFileParams {
@@ -17,6 +60,19 @@ pub struct CreateFileParams {
     pub filename_nonce: Vec<u8>,
     pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
     pub max_downloads: Option<i32>,
+    /// Token handed to the uploader, stored as [`hash_owner_token`]
+    pub owner_token: String,
+    pub slug: Option<String>,
+    /// Uploader's detached signature of the plaintext
+    pub signature: Option<Vec<u8>>,
+    /// Stored data kept in the record instead of at `file_path`
+    pub inline_data: Option<Vec<u8>>,
+    /// Format the client compressed the plaintext with before encrypting it
+    pub compression: Option<String>,
+    /// Minutes the file stays available after its first download
+    pub burn_after_first_download_minutes: Option<i32>,
+    /// Hours without downloads after which the file expires
+    pub expire_after_inactivity_hours: Option<i32>,
 }
 
 #[derive(Debug)]
@@ -25,9 +81,50 @@ pub struct CreateUnencryptedFileParams {
     pub original_size: i64,
     pub mime_type: Option<String>,
     pub file_path: String,
-    pub filename: String,
+    /// Plain filename, or its ciphertext when `filename_nonce` is set
+    pub filename: Vec<u8>,
+    /// Nonce of a client-encrypted filename, empty for a plain one
+    pub filename_nonce: Vec<u8>,
     pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
     pub max_downloads: Option<i32>,
+    /// Token handed to the uploader, stored as [`hash_owner_token`]
+    pub owner_token: String,
+    pub slug: Option<String>,
+    /// Uploader's detached signature of the plaintext
+    pub signature: Option<Vec<u8>>,
+    /// SHA-256 of the stored contents, when the server saw them in full
+    pub content_sha256: Option<Vec<u8>>,
+    /// Stored data kept in the record instead of at `file_path`
+    pub inline_data: Option<Vec<u8>>,
+    /// Minutes the file stays available after its first download
+    pub burn_after_first_download_minutes: Option<i32>,
+    /// Hours without downloads after which the file expires
+    pub expire_after_inactivity_hours: Option<i32>,
+}
+
+#[cfg(test)]
+impl CreateUnencryptedFileParams {
+    /// A plain 5-byte `hello.txt` stored at `file_path`, owned with the token
+    /// `owner`, which never expires and has no download limit
+    pub(crate) fn for_tests(file_id: Uuid, file_path: String) -> Self {
+        Self {
+            file_id,
+            original_size: 5,
+            mime_type: None,
+            file_path,
+            filename: b"hello.txt".to_vec(),
+            filename_nonce: Vec::new(),
+            expires_at: None,
+            max_downloads: None,
+            owner_token: "owner".to_string(),
+            slug: None,
+            signature: None,
+            content_sha256: None,
+            inline_data: None,
+            burn_after_first_download_minutes: None,
+            expire_after_inactivity_hours: None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -40,23 +137,46 @@ pub struct CreateUploadSessionParams {
     pub chunk_size: i32,
     pub expires_in_hours: Option<i32>,
     pub max_downloads: Option<i32>,
+    pub slug: Option<String>,
+    /// Minutes the final file stays available after its first download
+    pub burn_after_first_download_minutes: Option<i32>,
+    /// Hours without downloads after which the final file expires
+    pub expire_after_inactivity_hours: Option<i32>,
+    /// When the session stops accepting chunks
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// How owner tokens are stored: their SHA-256, in lowercase hex
+///
+/// A record leaking through logs or a backup then can't be used to manage the file.
+pub fn hash_owner_token(owner_token: &str) -> String {
+    format!("{:x}", Sha256::digest(owner_token.as_bytes()))
 }
 
-pub async fn setup_database(database_url: &str) -> Result<DatabaseConnection> {
-    tracing::info!("🔗 Connecting to database: {}", database_url);
+pub async fn setup_database(config: &Config) -> Result<DatabaseConnection> {
+    let backend = config.database_backend()?;
+    tracing::info!(
+        "🔗 Connecting to {:?} database: {}",
+        backend,
+        config.database_url
+    );
 
     // Configure connection options
-    let mut opt = ConnectOptions::new(database_url.to_string());
-    opt.max_connections(100)
-        .min_connections(5)
-        .connect_timeout(Duration::from_secs(8))
-        .acquire_timeout(Duration::from_secs(8))
-        .idle_timeout(Duration::from_secs(8))
-        .max_lifetime(Duration::from_secs(8))
+    let mut opt = ConnectOptions::new(config.database_url.clone());
+    opt.max_connections(config.db_max_connections)
+        .min_connections(config.db_min_connections)
+        .connect_timeout(Duration::from_secs(config.db_connect_timeout_secs))
+        .acquire_timeout(Duration::from_secs(config.db_acquire_timeout_secs))
+        .idle_timeout(Duration::from_secs(config.db_idle_timeout_secs))
+        .max_lifetime(Duration::from_secs(config.db_max_lifetime_secs))
         .sqlx_logging(true);
 
     // Connect to database
-    let db = Database::connect(opt).await?;
+    let db = if backend == DbBackend::Sqlite {
+        connect_sqlite(opt, config).await?
+    } else {
+        Database::connect(opt).await?
+    };
 
     // Run migrations
     tracing::info!("🔄 Running database migrations...");
@@ -66,6 +186,95 @@ pub async fn setup_database(database_url: &str) -> Result<DatabaseConnection> {
     Ok(db)
 }
 
+// Connect to SQLite with WAL journaling and a busy timeout so concurrent
+// writers wait for the lock instead of failing with "database is locked"
+async fn connect_sqlite(mut opt: ConnectOptions, config: &Config) -> Result<DatabaseConnection> {
+    let journal_mode = SqliteJournalMode::from_str(&config.sqlite_journal_mode)
+        .map_err(|_| AppError::ConfigError("Invalid KUSATSU_SQLITE_JOURNAL_MODE".to_string()))?;
+    let synchronous = SqliteSynchronous::from_str(&config.sqlite_synchronous)
+        .map_err(|_| AppError::ConfigError("Invalid KUSATSU_SQLITE_SYNCHRONOUS".to_string()))?;
+
+    let sqlite_opts = SqliteConnectOptions::from_str(&config.database_url)
+        .map_err(|e| AppError::ConfigError(format!("Invalid SQLite database URL: {}", e)))?
+        .journal_mode(journal_mode)
+        .synchronous(synchronous)
+        .busy_timeout(Duration::from_millis(config.sqlite_busy_timeout_ms));
+    create_sqlite_directory(config, &sqlite_opts).await?;
+
+    // SQLite serializes writes, so cap the pool size. Outside WAL mode readers
+    // block the writer too, so more than one connection only adds lock contention
+    let max_connections = if journal_mode == SqliteJournalMode::Wal {
+        config.sqlite_max_connections
+    } else {
+        if config.sqlite_max_connections > 1 {
+            tracing::warn!(
+                "⚠️ SQLite journal mode {} doesn't allow concurrent readers, using a single connection",
+                config.sqlite_journal_mode
+            );
+        }
+        1
+    };
+    opt.max_connections(max_connections)
+        .min_connections(config.db_min_connections.min(max_connections));
+
+    let pool = opt
+        .pool_options::<Sqlite>()
+        .connect_with(sqlite_opts)
+        .await
+        .map_err(|e| DbErr::Conn(RuntimeErr::SqlxError(e)))?;
+
+    Ok(SqlxSqliteConnector::from_sqlx_sqlite_pool(pool))
+}
+
+// SQLite creates the database file but not its directory, which makes a
+// missing directory the usual first-run failure
+async fn create_sqlite_directory(config: &Config, opts: &SqliteConnectOptions) -> Result<()> {
+    if config.database_url.contains(":memory:") || config.database_url.contains("mode=memory") {
+        return Ok(());
+    }
+
+    let filename = opts.clone().get_filename();
+    let Some(directory) = filename
+        .parent()
+        .filter(|directory| !directory.as_os_str().is_empty())
+    else {
+        return Ok(());
+    };
+    if tokio::fs::try_exists(directory).await? {
+        return Ok(());
+    }
+
+    tokio::fs::create_dir_all(directory).await.map_err(|e| {
+        AppError::ConfigError(format!(
+            "Can't create the SQLite database directory {}: {}",
+            directory.display(),
+            e
+        ))
+    })?;
+    tracing::info!(
+        "📁 Created SQLite database directory {}",
+        directory.display()
+    );
+    Ok(())
+}
+
+/// Current use of the database connection pool
+pub fn pool_status(db: &DatabaseConnection) -> Option<DatabasePoolStatus> {
+    match db.get_database_backend() {
+        DbBackend::Sqlite => Some(status_of(db.get_sqlite_connection_pool())),
+        DbBackend::Postgres => Some(status_of(db.get_postgres_connection_pool())),
+        DbBackend::MySql => None,
+    }
+}
+
+fn status_of<DB: sqlx::Database>(pool: &sqlx::Pool<DB>) -> DatabasePoolStatus {
+    DatabasePoolStatus {
+        connections: pool.size(),
+        idle: pool.num_idle() as u32,
+        max_connections: pool.options().get_max_connections(),
+    }
+}
+
 // Helper functions for file operations
 pub mod file_ops {
     use super::*;
@@ -77,6 +286,9 @@ pub mod file_ops {
         db: &DatabaseConnection,
         params: CreateFileParams,
     ) -> Result<file::Model> {
+        if let Some(slug) = &params.slug {
+            release_expired_slug(db, slug).await?;
+        }
         let file_model = file::ActiveModel {
             file_id: Set(params.file_id),
             original_size: Set(params.original_size),
@@ -84,14 +296,22 @@ pub mod file_ops {
             mime_type: Set(params.mime_type),
             file_path: Set(params.file_path),
             nonce: Set(params.nonce),
+            is_encrypted: Set(true),
             encrypted_filename: Set(params.encrypted_filename),
             filename_nonce: Set(params.filename_nonce),
             expires_at: Set(params.expires_at),
             max_downloads: Set(params.max_downloads),
+            owner_token: Set(Some(hash_owner_token(&params.owner_token))),
+            slug: Set(params.slug),
+            signature: Set(params.signature),
+            inline_data: Set(params.inline_data),
+            compression: Set(params.compression),
+            burn_after_first_download_minutes: Set(params.burn_after_first_download_minutes),
+            expire_after_inactivity_hours: Set(params.expire_after_inactivity_hours),
             ..Default::default()
         };
 
-        let file = file_model.insert(db).await?;
+        let file = file_model.insert(db).await.map_err(insert_error)?;
         Ok(file)
     }
 
@@ -100,24 +320,57 @@ pub mod file_ops {
         db: &DatabaseConnection,
         params: CreateUnencryptedFileParams,
     ) -> Result<file::Model> {
+        if let Some(slug) = &params.slug {
+            release_expired_slug(db, slug).await?;
+        }
         let file_model = file::ActiveModel {
             file_id: Set(params.file_id),
             original_size: Set(params.original_size),
             encrypted_size: Set(params.original_size), // Same as original for unencrypted files
             mime_type: Set(params.mime_type),
             file_path: Set(params.file_path),
-            nonce: Set(Vec::new()), // Empty nonce indicates unencrypted file
-            encrypted_filename: Set(params.filename.as_bytes().to_vec()), // Store plain filename
-            filename_nonce: Set(Vec::new()), // Empty nonce for filename
+            nonce: Set(Vec::new()),
+            is_encrypted: Set(false),
+            encrypted_filename: Set(params.filename), // Plain unless the client encrypted it
+            filename_nonce: Set(params.filename_nonce), // Empty nonce for a plain filename
             expires_at: Set(params.expires_at),
             max_downloads: Set(params.max_downloads),
+            owner_token: Set(Some(hash_owner_token(&params.owner_token))),
+            slug: Set(params.slug),
+            signature: Set(params.signature),
+            content_sha256: Set(params.content_sha256),
+            inline_data: Set(params.inline_data),
+            burn_after_first_download_minutes: Set(params.burn_after_first_download_minutes),
+            expire_after_inactivity_hours: Set(params.expire_after_inactivity_hours),
             ..Default::default()
         };
 
-        let file = file_model.insert(db).await?;
+        let file = file_model.insert(db).await.map_err(insert_error)?;
         Ok(file)
     }
 
+    // An expired file still holding a slug gives it up to a new upload
+    async fn release_expired_slug(db: &DatabaseConnection, slug: &str) -> Result<()> {
+        File::update_many()
+            .col_expr(file::Column::Slug, Expr::value(Option::<String>::None))
+            .filter(file::Column::Slug.eq(slug))
+            .filter(file::Column::ExpiresAt.lte(chrono::Utc::now()))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    // Slugs are unique, so one taken since it was checked surfaces as a conflict
+    fn insert_error(err: DbErr) -> AppError {
+        match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => {
+                AppError::Conflict("Slug is already in use".to_string())
+            }
+            _ => err.into(),
+        }
+    }
+
     pub async fn get_file_by_id(
         db: &DatabaseConnection,
         file_id: Uuid,
@@ -130,56 +383,416 @@ pub mod file_ops {
         Ok(file)
     }
 
-    pub async fn increment_download_count(db: &DatabaseConnection, file_id: Uuid) -> Result<()> {
+    // Find the live file holding a slug; an expired one keeps it until another upload claims it
+    pub async fn get_file_by_slug(
+        db: &DatabaseConnection,
+        slug: &str,
+    ) -> Result<Option<file::Model>> {
         let file = File::find()
-            .filter(file::Column::FileId.eq(file_id))
+            .filter(file::Column::Slug.eq(slug))
+            .filter(
+                Condition::any()
+                    .add(file::Column::ExpiresAt.is_null())
+                    .add(file::Column::ExpiresAt.gt(chrono::Utc::now())),
+            )
             .one(db)
             .await?;
 
-        if let Some(file) = file {
-            let mut file: file::ActiveModel = file.into();
-            file.download_count = Set(file.download_count.unwrap() + 1);
-            file.update(db).await?;
+        Ok(file)
+    }
+
+    // Count a download only if the file is still within its expiry and download limit
+    //
+    // The check and the increment are a single UPDATE, so concurrent requests can't
+    // both take the last download. Returns whether the download was counted.
+    pub async fn try_consume_download(
+        db: &DatabaseConnection,
+        file_id: Uuid,
+        renewed_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<bool> {
+        let mut update = File::update_many().col_expr(
+            file::Column::DownloadCount,
+            Expr::col(file::Column::DownloadCount).add(1),
+        );
+        // Renewed in the same statement, so it only happens for a counted download
+        if let Some(expires_at) = renewed_expires_at {
+            update = update.col_expr(file::Column::ExpiresAt, Expr::value(expires_at));
         }
 
+        let result = update
+            .filter(file::Column::FileId.eq(file_id))
+            .filter(
+                Condition::any()
+                    .add(file::Column::ExpiresAt.is_null())
+                    .add(file::Column::ExpiresAt.gt(chrono::Utc::now())),
+            )
+            .filter(
+                Condition::any()
+                    .add(file::Column::MaxDownloads.is_null())
+                    .add(
+                        Expr::col(file::Column::DownloadCount)
+                            .lt(Expr::col(file::Column::MaxDownloads)),
+                    ),
+            )
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected == 1)
+    }
+
+    /// Note the first download of a file, starting its burn time if it has one
+    ///
+    /// The burn deadline becomes the file's expiry (unless it expires earlier
+    /// anyway), so every check of `expires_at`, like the download count and
+    /// cleanup, enforces it. Only the first call has any effect.
+    pub async fn record_first_download(db: &DatabaseConnection, file: &file::Model) -> Result<()> {
+        let now = chrono::Utc::now();
+        let mut update = File::update_many()
+            .col_expr(file::Column::FirstDownloadedAt, Expr::value(now))
+            .filter(file::Column::FileId.eq(file.file_id))
+            .filter(file::Column::FirstDownloadedAt.is_null());
+        if let Some(expires_at) = file.expiry_after_first_download(now) {
+            update = update.col_expr(file::Column::ExpiresAt, Expr::value(expires_at));
+        }
+        update.exec(db).await?;
+
         Ok(())
     }
 
+    /// Move the expiry of a file from `current_expires_at` to `expires_at`
+    ///
+    /// Downloads move the expiry too: a first one starts the burn time of a file
+    /// that has one, and others renew an inactivity period. The update only
+    /// applies if neither happened since `current_expires_at` was read, and is
+    /// a conflict otherwise, so a burn deadline is never replaced.
+    pub async fn update_expires_at(
+        db: &DatabaseConnection,
+        file_id: Uuid,
+        current_expires_at: chrono::DateTime<chrono::Utc>,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let result = File::update_many()
+            .col_expr(file::Column::ExpiresAt, Expr::value(expires_at))
+            .filter(file::Column::FileId.eq(file_id))
+            .filter(file::Column::ExpiresAt.eq(current_expires_at))
+            .filter(
+                Condition::any()
+                    .add(file::Column::BurnAfterFirstDownloadMinutes.is_null())
+                    .add(file::Column::FirstDownloadedAt.is_null()),
+            )
+            .exec(db)
+            .await?;
+
+        if result.rows_affected == 0 {
+            return Err(AppError::Conflict(
+                "File changed while its expiry was being extended, try again".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_wrapped_key(
+        db: &DatabaseConnection,
+        file_id: Uuid,
+        wrapped_key: Vec<u8>,
+        key_salt: Vec<u8>,
+    ) -> Result<file::Model> {
+        let file = File::find()
+            .filter(file::Column::FileId.eq(file_id))
+            .one(db)
+            .await?
+            .ok_or(crate::error::AppError::FileNotFound)?;
+
+        let mut file: file::ActiveModel = file.into();
+        file.wrapped_key = Set(Some(wrapped_key));
+        file.key_salt = Set(Some(key_salt));
+        let updated_file = file.update(db).await?;
+
+        Ok(updated_file)
+    }
+
+    pub async fn set_listing(
+        db: &DatabaseConnection,
+        file_id: Uuid,
+        listing: Vec<u8>,
+    ) -> Result<file::Model> {
+        let file = File::find()
+            .filter(file::Column::FileId.eq(file_id))
+            .one(db)
+            .await?
+            .ok_or(crate::error::AppError::FileNotFound)?;
+
+        let mut file: file::ActiveModel = file.into();
+        file.listing = Set(Some(listing));
+        let updated_file = file.update(db).await?;
+
+        Ok(updated_file)
+    }
+
+    // Give a file a new public ID; its stored data keeps its path
+    pub async fn rotate_file_id(
+        db: &DatabaseConnection,
+        file_id: Uuid,
+        new_file_id: Uuid,
+    ) -> Result<file::Model> {
+        let file = File::find()
+            .filter(file::Column::FileId.eq(file_id))
+            .one(db)
+            .await?
+            .ok_or(crate::error::AppError::FileNotFound)?;
+
+        let mut file: file::ActiveModel = file.into();
+        file.file_id = Set(new_file_id);
+        let updated_file = file.update(db).await?;
+
+        Ok(updated_file)
+    }
+
     pub async fn cleanup_expired_files(
         db: &DatabaseConnection,
-        storage: &crate::storage::FileStorage,
+        storage: &dyn crate::storage::FileStorage,
+    ) -> Result<u64> {
+        cleanup_expired_files_in_batches(db, storage, CLEANUP_BATCH_SIZE).await
+    }
+
+    pub(crate) async fn cleanup_expired_files_in_batches(
+        db: &DatabaseConnection,
+        storage: &dyn crate::storage::FileStorage,
+        batch_size: u64,
     ) -> Result<u64> {
         let now = chrono::Utc::now();
+        delete_files_in_batches(
+            db,
+            storage,
+            Condition::all().add(file::Column::ExpiresAt.lt(now)),
+            batch_size,
+        )
+        .await
+    }
 
-        // Get expired files first so we can delete them from storage
-        let expired_files = File::find()
-            .filter(file::Column::ExpiresAt.lt(now))
+    /// Number of files matching `filter`, and the ids of the first `sample_size` of them
+    pub async fn find_matching_files(
+        db: &DatabaseConnection,
+        filter: &FileFilter,
+        sample_size: u64,
+    ) -> Result<(u64, Vec<Uuid>)> {
+        let count = File::find()
+            .filter(filter_condition(filter))
+            .count(db)
+            .await?;
+        let sample = File::find()
+            .select_only()
+            .column(file::Column::FileId)
+            .filter(filter_condition(filter))
+            .order_by_asc(file::Column::Id)
+            .limit(sample_size)
+            .into_tuple()
             .all(db)
             .await?;
 
-        // Delete files from storage
-        for file in &expired_files {
-            if let Err(e) = storage.delete_file(&file.file_path).await {
-                tracing::warn!(
-                    "Failed to delete file from storage: {} - {}",
-                    file.file_path,
-                    e
-                );
+        Ok((count, sample))
+    }
+
+    /// Delete every file matching `filter`, from storage and the database
+    pub async fn delete_matching_files(
+        db: &DatabaseConnection,
+        storage: &dyn crate::storage::FileStorage,
+        filter: &FileFilter,
+    ) -> Result<u64> {
+        delete_files_in_batches(db, storage, filter_condition(filter), CLEANUP_BATCH_SIZE).await
+    }
+
+    fn filter_condition(filter: &FileFilter) -> Condition {
+        Condition::all()
+            .add_option(
+                filter
+                    .created_before
+                    .map(|created_before| file::Column::CreatedAt.lt(created_before)),
+            )
+            .add_option(
+                filter
+                    .mime_type
+                    .as_ref()
+                    .map(|mime_type| file::Column::MimeType.eq(mime_type.as_str())),
+            )
+            .add_option(
+                filter
+                    .min_size
+                    .map(|min_size| file::Column::OriginalSize.gte(min_size)),
+            )
+            .add_option(
+                filter
+                    .file_ids
+                    .clone()
+                    .map(|file_ids| file::Column::FileId.is_in(file_ids)),
+            )
+    }
+
+    // Delete matching files in bounded batches to keep memory use and lock time low
+    async fn delete_files_in_batches(
+        db: &DatabaseConnection,
+        storage: &dyn crate::storage::FileStorage,
+        condition: Condition,
+        batch_size: u64,
+    ) -> Result<u64> {
+        let mut total_deleted = 0;
+
+        loop {
+            let matching_files = File::find()
+                .filter(condition.clone())
+                .order_by_asc(file::Column::Id)
+                .limit(batch_size)
+                .all(db)
+                .await?;
+
+            if matching_files.is_empty() {
+                break;
+            }
+
+            // Delete files from storage; inline data goes with the record
+            for file in matching_files.iter().filter(|file| !file.is_inline()) {
+                if let Err(e) = storage.delete_file(&file.file_path).await {
+                    tracing::warn!(
+                        "Failed to delete file from storage: {} - {}",
+                        file.file_path,
+                        e
+                    );
+                }
             }
+
+            // Delete this batch from database
+            let ids: Vec<i32> = matching_files.iter().map(|file| file.id).collect();
+            let result = File::delete_many()
+                .filter(file::Column::Id.is_in(ids))
+                .exec(db)
+                .await?;
+
+            total_deleted += result.rows_affected;
+
+            // Stop if nothing could be deleted, or this was the last batch
+            if result.rows_affected == 0 || (matching_files.len() as u64) < batch_size {
+                break;
+            }
+
+            tokio::task::yield_now().await;
         }
 
-        // Delete from database
-        let result = File::delete_many()
-            .filter(file::Column::ExpiresAt.lt(now))
-            .exec(db)
-            .await?;
+        Ok(total_deleted)
+    }
+
+    // Cross-check stored files against file records
+    //
+    // Files on disk without a record are orphans (the record insert failed after the
+    // write); with `repair` they are deleted. Records whose file is gone are only
+    // reported. Files newer than `grace_period` are skipped, as their record may
+    // still be on its way.
+    pub async fn check_storage_consistency(
+        db: &DatabaseConnection,
+        storage: &dyn crate::storage::FileStorage,
+        repair: bool,
+        grace_period: Duration,
+    ) -> Result<StorageCheckReport> {
+        let mut report = StorageCheckReport::default();
+        let cutoff = std::time::SystemTime::now() - grace_period;
+
+        // Stored files, looked up in batches
+        let stored_files: Vec<_> = storage
+            .list_stored_files()
+            .await?
+            .into_iter()
+            .filter(|stored_file| stored_file.modified < cutoff)
+            .collect();
+
+        for batch in stored_files.chunks(CLEANUP_BATCH_SIZE as usize) {
+            report.files_checked += batch.len() as u64;
+
+            // A record keeps its stored file when its ID is rotated, so a file is
+            // known by either the ID in its name or its path
+            let file_ids: Vec<Uuid> = batch
+                .iter()
+                .map(|stored_file| stored_file.file_id)
+                .collect();
+            let file_paths: Vec<&str> = batch
+                .iter()
+                .map(|stored_file| stored_file.relative_path.as_str())
+                .collect();
+            let known: Vec<(Uuid, String)> = File::find()
+                .select_only()
+                .column(file::Column::FileId)
+                .column(file::Column::FilePath)
+                .filter(
+                    Condition::any()
+                        .add(file::Column::FileId.is_in(file_ids))
+                        .add(file::Column::FilePath.is_in(file_paths)),
+                )
+                .into_tuple()
+                .all(db)
+                .await?;
+            let known_ids: std::collections::HashSet<Uuid> =
+                known.iter().map(|(file_id, _)| *file_id).collect();
+            let known_paths: std::collections::HashSet<&str> = known
+                .iter()
+                .map(|(_, file_path)| file_path.as_str())
+                .collect();
+
+            for stored_file in batch.iter().filter(|f| {
+                !known_ids.contains(&f.file_id) && !known_paths.contains(f.relative_path.as_str())
+            }) {
+                report.orphaned_files += 1;
+                tracing::warn!("🔍 Orphaned file on disk: {}", stored_file.relative_path);
+
+                if repair {
+                    match storage.delete_file(&stored_file.relative_path).await {
+                        Ok(()) => report.orphaned_files_removed += 1,
+                        Err(e) => tracing::warn!(
+                            "Failed to delete orphaned file {}: {}",
+                            stored_file.relative_path,
+                            e
+                        ),
+                    }
+                }
+            }
+
+            tokio::task::yield_now().await;
+        }
+
+        // File records, paged by primary key
+        let mut last_id = 0;
+        loop {
+            let records = File::find()
+                .filter(file::Column::Id.gt(last_id))
+                .order_by_asc(file::Column::Id)
+                .limit(CLEANUP_BATCH_SIZE)
+                .all(db)
+                .await?;
 
-        Ok(result.rows_affected)
+            let Some(last) = records.last() else {
+                break;
+            };
+            last_id = last.id;
+            report.records_checked += records.len() as u64;
+
+            for record in records.iter().filter(|record| !record.is_inline()) {
+                if !storage.file_exists(&record.file_path).await {
+                    tracing::warn!(
+                        "🔍 File record {} points at missing file {}",
+                        record.file_id,
+                        record.file_path
+                    );
+                    report.missing_files.push(record.file_id);
+                }
+            }
+
+            tokio::task::yield_now().await;
+        }
+
+        Ok(report)
     }
 
     pub async fn delete_file_by_id(
         db: &DatabaseConnection,
-        storage: &crate::storage::FileStorage,
+        storage: &dyn crate::storage::FileStorage,
         file_id: Uuid,
     ) -> Result<bool> {
         let file = File::find()
@@ -188,13 +801,15 @@ pub mod file_ops {
             .await?;
 
         if let Some(file) = file {
-            // Delete from storage first
-            if let Err(e) = storage.delete_file(&file.file_path).await {
-                tracing::warn!(
-                    "Failed to delete file from storage: {} - {}",
-                    file.file_path,
-                    e
-                );
+            // Delete from storage first, unless the data is in the record
+            if !file.is_inline() {
+                if let Err(e) = storage.delete_file(&file.file_path).await {
+                    tracing::warn!(
+                        "Failed to delete file from storage: {} - {}",
+                        file.file_path,
+                        e
+                    );
+                }
             }
 
             // Delete from database
@@ -226,6 +841,10 @@ pub mod upload_session_ops {
             chunk_size: Set(params.chunk_size),
             expires_in_hours: Set(params.expires_in_hours),
             max_downloads: Set(params.max_downloads),
+            slug: Set(params.slug),
+            burn_after_first_download_minutes: Set(params.burn_after_first_download_minutes),
+            expire_after_inactivity_hours: Set(params.expire_after_inactivity_hours),
+            expires_at: Set(params.expires_at),
             ..Default::default()
         };
 
@@ -233,6 +852,17 @@ pub mod upload_session_ops {
         Ok(session)
     }
 
+    // Whether an unexpired upload session has reserved a slug
+    pub async fn slug_reserved(db: &DatabaseConnection, slug: &str) -> Result<bool> {
+        let count = UploadSession::find()
+            .filter(upload_session::Column::Slug.eq(slug))
+            .filter(upload_session::Column::ExpiresAt.gt(chrono::Utc::now()))
+            .count(db)
+            .await?;
+
+        Ok(count > 0)
+    }
+
     pub async fn get_upload_session_by_id(
         db: &DatabaseConnection,
         upload_id: Uuid,
@@ -245,23 +875,44 @@ pub mod upload_session_ops {
         Ok(session)
     }
 
-    pub async fn increment_uploaded_chunks(
+    // Raise the session's chunk count to what chunk storage holds; a count taken
+    // before a concurrent chunk landed never lowers it
+    pub async fn record_uploaded_chunks(
         db: &DatabaseConnection,
         upload_id: Uuid,
+        uploaded_chunks: i32,
     ) -> Result<upload_session::Model> {
-        let session = UploadSession::find()
+        UploadSession::update_many()
+            .col_expr(
+                upload_session::Column::UploadedChunks,
+                Expr::value(uploaded_chunks),
+            )
+            .filter(upload_session::Column::UploadId.eq(upload_id))
+            .filter(upload_session::Column::UploadedChunks.lt(uploaded_chunks))
+            .exec(db)
+            .await?;
+
+        UploadSession::find()
             .filter(upload_session::Column::UploadId.eq(upload_id))
             .one(db)
             .await?
             .ok_or_else(|| {
                 crate::error::AppError::BadRequest("Upload session not found".to_string())
-            })?;
+            })
+    }
 
-        let mut session: upload_session::ActiveModel = session.into();
-        session.uploaded_chunks = Set(session.uploaded_chunks.unwrap() + 1);
-        let updated_session = session.update(db).await?;
+    pub async fn extend_upload_session(
+        db: &DatabaseConnection,
+        upload_id: Uuid,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        UploadSession::update_many()
+            .col_expr(upload_session::Column::ExpiresAt, Expr::value(expires_at))
+            .filter(upload_session::Column::UploadId.eq(upload_id))
+            .exec(db)
+            .await?;
 
-        Ok(updated_session)
+        Ok(())
     }
 
     pub async fn delete_upload_session(db: &DatabaseConnection, upload_id: Uuid) -> Result<bool> {
@@ -275,33 +926,538 @@ pub mod upload_session_ops {
 
     pub async fn cleanup_expired_upload_sessions(
         db: &DatabaseConnection,
-        chunk_storage: &crate::chunk_storage::ChunkStorage,
+        chunk_storage: &dyn crate::chunk_storage::ChunkStorage,
     ) -> Result<u64> {
         let now = chrono::Utc::now();
+        let mut total_deleted = 0;
 
-        // Get expired sessions first so we can clean up their chunks
-        let expired_sessions = UploadSession::find()
-            .filter(upload_session::Column::ExpiresAt.lt(now))
-            .all(db)
-            .await?;
+        loop {
+            let expired_sessions = UploadSession::find()
+                .filter(upload_session::Column::ExpiresAt.lt(now))
+                .order_by_asc(upload_session::Column::Id)
+                .limit(CLEANUP_BATCH_SIZE)
+                .all(db)
+                .await?;
+
+            if expired_sessions.is_empty() {
+                break;
+            }
 
-        // Clean up chunks for expired sessions
-        for session in &expired_sessions {
-            if let Err(e) = chunk_storage.cleanup_upload(session.upload_id).await {
-                tracing::warn!(
-                    "Failed to cleanup chunks for upload {}: {}",
-                    session.upload_id,
-                    e
-                );
+            // Clean up chunks for expired sessions
+            for session in &expired_sessions {
+                if let Err(e) = chunk_storage.cleanup_upload(session.upload_id).await {
+                    tracing::warn!(
+                        "Failed to cleanup chunks for upload {}: {}",
+                        session.upload_id,
+                        e
+                    );
+                }
             }
+
+            // Delete this batch from database
+            let ids: Vec<i32> = expired_sessions.iter().map(|session| session.id).collect();
+            let result = UploadSession::delete_many()
+                .filter(upload_session::Column::Id.is_in(ids))
+                .exec(db)
+                .await?;
+
+            total_deleted += result.rows_affected;
+
+            // Stop if nothing could be deleted, or this was the last batch
+            if result.rows_affected == 0 || (expired_sessions.len() as u64) < CLEANUP_BATCH_SIZE {
+                break;
+            }
+
+            tokio::task::yield_now().await;
         }
 
-        // Delete from database
-        let result = UploadSession::delete_many()
-            .filter(upload_session::Column::ExpiresAt.lt(now))
-            .exec(db)
-            .await?;
+        Ok(total_deleted)
+    }
+
+    // Remove stored chunks of uploads that have no session left, however recent
+    //
+    // Chunks outlive their session when cleaning them up failed after the session
+    // was deleted; nothing else would ever remove them.
+    pub async fn cleanup_orphaned_chunks(
+        db: &DatabaseConnection,
+        chunk_storage: &dyn crate::chunk_storage::ChunkStorage,
+    ) -> Result<u64> {
+        let uploads = chunk_storage.list_uploads().await?;
+        let mut total_removed = 0;
+
+        for batch in uploads.chunks(CLEANUP_BATCH_SIZE as usize) {
+            let known: std::collections::HashSet<Uuid> = UploadSession::find()
+                .select_only()
+                .column(upload_session::Column::UploadId)
+                .filter(upload_session::Column::UploadId.is_in(batch.to_vec()))
+                .into_tuple()
+                .all(db)
+                .await?
+                .into_iter()
+                .collect();
+
+            for upload_id in batch.iter().filter(|upload_id| !known.contains(upload_id)) {
+                match chunk_storage.cleanup_upload(*upload_id).await {
+                    Ok(()) => {
+                        total_removed += 1;
+                        tracing::debug!("🧹 Removed orphaned chunks of upload {}", upload_id);
+                    }
+                    Err(e) => tracing::warn!(
+                        "Failed to cleanup orphaned chunks for upload {}: {}",
+                        upload_id,
+                        e
+                    ),
+                }
+            }
+
+            tokio::task::yield_now().await;
+        }
+
+        Ok(total_removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::ContainerHeader;
+    use crate::storage::{FileStorage, LocalFileStorage};
+    use sea_orm::{ConnectionTrait, Statement};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_missing_sqlite_directory_is_created() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_path = temp_dir.path().join("data").join("db").join("kusatsu.db");
+        let config = Config {
+            database_url: format!("sqlite://{}?mode=rwc", database_path.display()),
+            ..Config::for_tests()
+        };
+
+        setup_database(&config).await.unwrap();
+        assert!(database_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_database_url_is_a_config_error() {
+        let config = Config {
+            database_url: "mongodb://localhost/kusatsu".to_string(),
+            ..Config::for_tests()
+        };
+
+        let err = setup_database(&config).await.unwrap_err();
+        assert!(matches!(err, AppError::ConfigError(ref message) if message.contains("mongodb")));
+    }
+
+    #[tokio::test]
+    async fn test_existing_files_are_marked_encrypted_by_their_nonce() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_url = format!(
+            "sqlite://{}?mode=rwc",
+            temp_dir.path().join("kusatsu.db").display()
+        );
+
+        // Files stored before the column existed
+        let db = Database::connect(&database_url).await.unwrap();
+        let before_column = Migrator::migrations()
+            .iter()
+            .position(|migration| migration.name() == "m20231112_000001_add_is_encrypted_to_files")
+            .unwrap() as u32;
+        Migrator::up(&db, Some(before_column)).await.unwrap();
+        let encrypted_id = Uuid::new_v4();
+        let plain_id = Uuid::new_v4();
+        // UUIDs are stored as their 16 bytes
+        for (file_id, nonce) in [(encrypted_id, "X'00112233'"), (plain_id, "X''")] {
+            db.execute(Statement::from_string(
+                DbBackend::Sqlite,
+                format!(
+                    "INSERT INTO files (file_id, original_size, encrypted_size, file_path, \
+                     nonce, encrypted_filename, filename_nonce) \
+                     VALUES (X'{}', 1, 1, '', {}, X'', X'')",
+                    file_id.simple(),
+                    nonce
+                ),
+            ))
+            .await
+            .unwrap();
+        }
+        db.close().await.unwrap();
+
+        let config = Config {
+            database_url,
+            ..Config::for_tests()
+        };
+        let db = setup_database(&config).await.unwrap();
+        let is_encrypted = |file_id| {
+            let db = db.clone();
+            async move {
+                file_ops::get_file_by_id(&db, file_id)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .is_encrypted
+            }
+        };
+        assert!(is_encrypted(encrypted_id).await);
+        assert!(!is_encrypted(plain_id).await);
+    }
+
+    #[tokio::test]
+    async fn test_owner_tokens_are_stored_hashed() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_url = format!(
+            "sqlite://{}?mode=rwc",
+            temp_dir.path().join("kusatsu.db").display()
+        );
+
+        // A file whose token was stored as-is
+        let db = Database::connect(&database_url).await.unwrap();
+        let before_hashing = Migrator::migrations()
+            .iter()
+            .position(|migration| migration.name() == "m20231114_000001_hash_owner_tokens")
+            .unwrap() as u32;
+        Migrator::up(&db, Some(before_hashing)).await.unwrap();
+        let legacy_id = Uuid::new_v4();
+        db.execute(Statement::from_string(
+            DbBackend::Sqlite,
+            format!(
+                "INSERT INTO files (file_id, original_size, encrypted_size, file_path, \
+                 nonce, encrypted_filename, filename_nonce, owner_token) \
+                 VALUES (X'{}', 1, 1, '', X'', X'', X'', 'legacy-token')",
+                legacy_id.simple()
+            ),
+        ))
+        .await
+        .unwrap();
+        db.close().await.unwrap();
+
+        let config = Config {
+            database_url,
+            ..Config::for_tests()
+        };
+        let db = setup_database(&config).await.unwrap();
+        let legacy = file_ops::get_file_by_id(&db, legacy_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(legacy.owner_token, Some(hash_owner_token("legacy-token")));
+
+        let file = file_ops::create_unencrypted_file_record(
+            &db,
+            CreateUnencryptedFileParams {
+                original_size: 1,
+                filename: b"a.txt".to_vec(),
+                ..CreateUnencryptedFileParams::for_tests(Uuid::new_v4(), String::new())
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(file.owner_token, Some(hash_owner_token("owner")));
+    }
+
+    #[tokio::test]
+    async fn test_slugs_are_unique() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_url = format!(
+            "sqlite://{}?mode=rwc",
+            temp_dir.path().join("kusatsu.db").display()
+        );
+
+        // Two files that took the same slug before it was unique
+        let db = Database::connect(&database_url).await.unwrap();
+        let before_unique = Migrator::migrations()
+            .iter()
+            .position(|migration| migration.name() == "m20231115_000001_unique_file_slugs")
+            .unwrap() as u32;
+        Migrator::up(&db, Some(before_unique)).await.unwrap();
+        let (older_id, newer_id) = (Uuid::new_v4(), Uuid::new_v4());
+        for file_id in [older_id, newer_id] {
+            db.execute(Statement::from_string(
+                DbBackend::Sqlite,
+                format!(
+                    "INSERT INTO files (file_id, original_size, encrypted_size, file_path, \
+                     nonce, encrypted_filename, filename_nonce, slug) \
+                     VALUES (X'{}', 1, 1, '', X'', X'', X'', 'notes')",
+                    file_id.simple()
+                ),
+            ))
+            .await
+            .unwrap();
+        }
+        db.close().await.unwrap();
+
+        let config = Config {
+            database_url,
+            ..Config::for_tests()
+        };
+        let db = setup_database(&config).await.unwrap();
+        let slug_of = |file_id| {
+            let db = db.clone();
+            async move {
+                file_ops::get_file_by_id(&db, file_id)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .slug
+            }
+        };
+        assert_eq!(slug_of(older_id).await, None);
+        assert_eq!(slug_of(newer_id).await.as_deref(), Some("notes"));
+
+        let create = |slug: &str, expires_at| {
+            file_ops::create_unencrypted_file_record(
+                &db,
+                CreateUnencryptedFileParams {
+                    original_size: 1,
+                    filename: b"a.txt".to_vec(),
+                    expires_at,
+                    slug: Some(slug.to_string()),
+                    ..CreateUnencryptedFileParams::for_tests(Uuid::new_v4(), String::new())
+                },
+            )
+        };
+
+        // A slug taken since it was checked is a conflict
+        assert!(matches!(
+            create("notes", None).await,
+            Err(AppError::Conflict(_))
+        ));
+
+        // An expired file gives its slug up
+        let expired = create(
+            "draft",
+            Some(chrono::Utc::now() - chrono::Duration::hours(1)),
+        )
+        .await
+        .unwrap();
+        let file = create("draft", None).await.unwrap();
+        assert_eq!(slug_of(expired.file_id).await, None);
+        assert_eq!(file.slug.as_deref(), Some("draft"));
+    }
+
+    #[tokio::test]
+    async fn test_stale_expiry_updates_conflict() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            database_url: format!(
+                "sqlite://{}?mode=rwc",
+                temp_dir.path().join("kusatsu.db").display()
+            ),
+            ..Config::for_tests()
+        };
+        let db = setup_database(&config).await.unwrap();
+        let expires_at = chrono::Utc::now() + chrono::Duration::hours(1);
+        let later = expires_at + chrono::Duration::hours(1);
+
+        let mut files = Vec::new();
+        for burn_after_first_download_minutes in [None, Some(5)] {
+            let file = file_ops::create_unencrypted_file_record(
+                &db,
+                CreateUnencryptedFileParams {
+                    original_size: 1,
+                    filename: b"a.txt".to_vec(),
+                    expires_at: Some(expires_at),
+                    burn_after_first_download_minutes,
+                    ..CreateUnencryptedFileParams::for_tests(Uuid::new_v4(), String::new())
+                },
+            )
+            .await
+            .unwrap();
+            files.push(file);
+        }
+        let expiry_of = |file_id| {
+            let db = db.clone();
+            async move {
+                file_ops::get_file_by_id(&db, file_id)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .expires_at
+            }
+        };
+
+        // A first download starts the burn time of the second file in between
+        for file in &files {
+            file_ops::record_first_download(&db, file).await.unwrap();
+        }
+        let (plain, burning) = (&files[0], &files[1]);
+        let burns_at = expiry_of(burning.file_id).await;
+        assert!(burns_at < Some(expires_at));
+
+        assert!(matches!(
+            file_ops::update_expires_at(&db, burning.file_id, expires_at, later).await,
+            Err(AppError::Conflict(_))
+        ));
+        assert_eq!(expiry_of(burning.file_id).await, burns_at);
+
+        // Without a burn time, a downloaded file can still be extended, but
+        // only from the expiry it has
+        file_ops::update_expires_at(&db, plain.file_id, expires_at, later)
+            .await
+            .unwrap();
+        assert_eq!(expiry_of(plain.file_id).await, Some(later));
+        assert!(matches!(
+            file_ops::update_expires_at(&db, plain.file_id, expires_at, later).await,
+            Err(AppError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_pragmas() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            database_url: format!(
+                "sqlite://{}?mode=rwc",
+                temp_dir.path().join("kusatsu.db").display()
+            ),
+            ..Config::for_tests()
+        };
+
+        let db = setup_database(&config).await.unwrap();
+
+        let row = db
+            .query_one(Statement::from_string(
+                DbBackend::Sqlite,
+                "PRAGMA journal_mode",
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        let journal_mode: String = row.try_get_by_index(0).unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        let row = db
+            .query_one(Statement::from_string(
+                DbBackend::Sqlite,
+                "PRAGMA busy_timeout",
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        let busy_timeout: i32 = row.try_get_by_index(0).unwrap();
+        assert_eq!(busy_timeout as u64, config.sqlite_busy_timeout_ms);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_expired_files_in_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            database_url: format!(
+                "sqlite://{}?mode=rwc",
+                temp_dir.path().join("kusatsu.db").display()
+            ),
+            ..Config::for_tests()
+        };
+
+        let db = setup_database(&config).await.unwrap();
+        let storage = LocalFileStorage::new(temp_dir.path().join("files"));
+        storage.init().await.unwrap();
+
+        // 5 expired files and 1 that is still valid
+        for i in 0..6 {
+            let file_id = Uuid::new_v4();
+            let file_path = storage
+                .store_file(file_id, &ContainerHeader::plain(b"data"), b"data")
+                .await
+                .unwrap();
+            let expires_at = if i < 5 {
+                chrono::Utc::now() - chrono::Duration::hours(1)
+            } else {
+                chrono::Utc::now() + chrono::Duration::hours(1)
+            };
+
+            file_ops::create_unencrypted_file_record(
+                &db,
+                CreateUnencryptedFileParams {
+                    original_size: 4,
+                    filename: format!("file{}.txt", i).into_bytes(),
+                    expires_at: Some(expires_at),
+                    ..CreateUnencryptedFileParams::for_tests(file_id, file_path)
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let deleted = file_ops::cleanup_expired_files_in_batches(&db, &storage, 2)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 5);
+
+        let remaining = storage.get_stats().await.unwrap();
+        assert_eq!(remaining.total_files, 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_storage_consistency() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            database_url: format!(
+                "sqlite://{}?mode=rwc",
+                temp_dir.path().join("kusatsu.db").display()
+            ),
+            ..Config::for_tests()
+        };
+
+        let db = setup_database(&config).await.unwrap();
+        let storage = LocalFileStorage::new(temp_dir.path().join("files"));
+        storage.init().await.unwrap();
+
+        // One healthy file, one record without a file and one file without a record
+        let mut record_ids = Vec::new();
+        for stored in [true, false] {
+            let file_id = Uuid::new_v4();
+            let file_path = storage
+                .store_file(file_id, &ContainerHeader::plain(b"data"), b"data")
+                .await
+                .unwrap();
+            if !stored {
+                storage.delete_file(&file_path).await.unwrap();
+            }
+
+            file_ops::create_unencrypted_file_record(
+                &db,
+                CreateUnencryptedFileParams {
+                    original_size: 4,
+                    filename: b"file.txt".to_vec(),
+                    ..CreateUnencryptedFileParams::for_tests(file_id, file_path)
+                },
+            )
+            .await
+            .unwrap();
+            record_ids.push(file_id);
+        }
+        // A rotated file keeps its stored data, named after its old ID
+        file_ops::rotate_file_id(&db, record_ids[0], Uuid::new_v4())
+            .await
+            .unwrap();
+        let orphan_path = storage
+            .store_file(Uuid::new_v4(), &ContainerHeader::plain(b"data"), b"data")
+            .await
+            .unwrap();
+
+        // Recent files are left alone
+        let report =
+            file_ops::check_storage_consistency(&db, &storage, true, Duration::from_secs(3600))
+                .await
+                .unwrap();
+        assert_eq!(report.files_checked, 0);
+        assert!(storage.file_exists(&orphan_path).await);
+
+        let report = file_ops::check_storage_consistency(&db, &storage, false, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(report.files_checked, 2);
+        assert_eq!(report.records_checked, 2);
+        assert_eq!(report.orphaned_files, 1);
+        assert_eq!(report.orphaned_files_removed, 0);
+        assert_eq!(report.missing_files, vec![record_ids[1]]);
+        assert!(storage.file_exists(&orphan_path).await);
 
-        Ok(result.rows_affected)
+        let report = file_ops::check_storage_consistency(&db, &storage, true, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(report.orphaned_files_removed, 1);
+        assert!(!storage.file_exists(&orphan_path).await);
     }
 }
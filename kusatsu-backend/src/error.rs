@@ -1,5 +1,6 @@
+use crate::{error_code, FieldError};
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -25,6 +26,9 @@ pub enum AppError {
     #[error("File too large")]
     FileTooLarge,
 
+    #[error("File is over the {0} byte limit for JSON downloads, use POST /api/files/{{file_id}}/download instead")]
+    JsonDownloadTooLarge(usize),
+
     #[error("Invalid file format")]
     InvalidFileFormat,
 
@@ -43,19 +47,139 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Encryption key required for encrypted file")]
+    EncryptionKeyRequired,
+
+    #[error("Invalid encryption key")]
+    InvalidEncryptionKey,
+
+    #[error("Invalid owner token")]
+    Forbidden,
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Malware detected: {0}")]
+    MalwareDetected(String),
+
+    #[error("File type not allowed: {0}")]
+    FileTypeNotAllowed(String),
+
+    #[error("Invalid or tampered download signature")]
+    InvalidSignature,
+
+    #[error("Admin authentication required")]
+    Unauthorized,
+
+    #[error("Uploads are disabled for maintenance, retry after {0} seconds")]
+    MaintenanceMode(u64),
+
+    #[error("Server is busy with other uploads, retry after {0} seconds")]
+    ServerBusy(u64),
+
+    #[error("Too many chunks of this upload are being written, retry after {0} seconds")]
+    TooManyChunkWrites(u64),
+
+    #[error("Proof of work required: {0}")]
+    ProofOfWorkRequired(String),
+
+    #[error("Invalid request: {}", join_field_errors(.0))]
+    Validation(Vec<FieldError>),
+
+    #[error("HTTPS is required, retry the request over https://")]
+    HttpsRequired,
+
+    #[error("Storage is full, retry after {0} seconds")]
+    StorageFull(u64),
+
+    #[error("Storage is unavailable, retry after {0} seconds")]
+    StorageUnavailable(u64),
+
     #[error("Internal server error")]
     InternalServerError,
 }
 
+fn join_field_errors(fields: &[FieldError]) -> String {
+    fields
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl AppError {
+    /// Stable identifier sent as `code` in error responses
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::DatabaseError(_) => error_code::DATABASE_ERROR,
+            AppError::FileNotFound => error_code::FILE_NOT_FOUND,
+            AppError::FileExpired => error_code::FILE_EXPIRED,
+            AppError::DownloadLimitExceeded => error_code::DOWNLOAD_LIMIT_EXCEEDED,
+            AppError::FileTooLarge | AppError::JsonDownloadTooLarge(_) => {
+                error_code::FILE_TOO_LARGE
+            }
+            AppError::InvalidFileFormat => error_code::INVALID_FILE_FORMAT,
+            AppError::IoError(_) => error_code::IO_ERROR,
+            AppError::JsonError(_) => error_code::INVALID_JSON,
+            AppError::ConfigError(_) => error_code::CONFIGURATION_ERROR,
+            AppError::ServerError(_) => error_code::SERVER_ERROR,
+            AppError::BadRequest(_) => error_code::BAD_REQUEST,
+            AppError::EncryptionKeyRequired => error_code::ENCRYPTION_KEY_REQUIRED,
+            AppError::InvalidEncryptionKey => error_code::INVALID_ENCRYPTION_KEY,
+            AppError::Forbidden => error_code::FORBIDDEN,
+            AppError::Conflict(_) => error_code::CONFLICT,
+            AppError::MalwareDetected(_) => error_code::MALWARE_DETECTED,
+            AppError::FileTypeNotAllowed(_) => error_code::FILE_TYPE_NOT_ALLOWED,
+            AppError::InvalidSignature => error_code::INVALID_SIGNATURE,
+            AppError::Unauthorized => error_code::UNAUTHORIZED,
+            AppError::MaintenanceMode(_) => error_code::MAINTENANCE_MODE,
+            AppError::ServerBusy(_) => error_code::SERVER_BUSY,
+            AppError::TooManyChunkWrites(_) => error_code::TOO_MANY_CHUNK_WRITES,
+            AppError::ProofOfWorkRequired(_) => error_code::PROOF_OF_WORK_REQUIRED,
+            AppError::Validation(_) => error_code::VALIDATION_FAILED,
+            AppError::HttpsRequired => error_code::HTTPS_REQUIRED,
+            AppError::StorageFull(_) => error_code::STORAGE_FULL,
+            AppError::StorageUnavailable(_) => error_code::STORAGE_UNAVAILABLE,
+            AppError::InternalServerError => error_code::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error_message) = match &self {
             AppError::FileNotFound => (StatusCode::NOT_FOUND, "File not found"),
             AppError::FileExpired => (StatusCode::GONE, "File has expired"),
             AppError::DownloadLimitExceeded => (StatusCode::GONE, "Download limit exceeded"),
-            AppError::FileTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "File too large"),
+            AppError::FileTooLarge | AppError::JsonDownloadTooLarge(_) => {
+                (StatusCode::PAYLOAD_TOO_LARGE, "File too large")
+            }
             AppError::InvalidFileFormat => (StatusCode::BAD_REQUEST, "Invalid file format"),
             AppError::BadRequest(_) => (StatusCode::BAD_REQUEST, "Bad request"),
+            AppError::EncryptionKeyRequired => (StatusCode::BAD_REQUEST, "Encryption key required"),
+            AppError::InvalidEncryptionKey => (StatusCode::BAD_REQUEST, "Invalid encryption key"),
+            AppError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden"),
+            AppError::Conflict(_) => (StatusCode::CONFLICT, "Conflict"),
+            AppError::MalwareDetected(_) => (StatusCode::UNPROCESSABLE_ENTITY, "Malware detected"),
+            AppError::FileTypeNotAllowed(_) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, "File type not allowed")
+            }
+            AppError::InvalidSignature => (StatusCode::FORBIDDEN, "Invalid signature"),
+            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
+            AppError::MaintenanceMode(_) => (StatusCode::SERVICE_UNAVAILABLE, "Maintenance mode"),
+            AppError::ServerBusy(_) => (StatusCode::SERVICE_UNAVAILABLE, "Server busy"),
+            AppError::TooManyChunkWrites(_) => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            AppError::ProofOfWorkRequired(_) => (StatusCode::FORBIDDEN, "Proof of work required"),
+            AppError::Validation(_) => (StatusCode::UNPROCESSABLE_ENTITY, "Validation failed"),
+            AppError::HttpsRequired => (StatusCode::UPGRADE_REQUIRED, "HTTPS required"),
+            AppError::StorageFull(_) => {
+                tracing::error!("Storage error: {}", self);
+                (StatusCode::INSUFFICIENT_STORAGE, "Storage full")
+            }
+            AppError::StorageUnavailable(_) => {
+                tracing::error!("Storage error: {}", self);
+                (StatusCode::SERVICE_UNAVAILABLE, "Storage unavailable")
+            }
             AppError::ConfigError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Configuration error"),
             AppError::DatabaseError(_) => {
                 tracing::error!("Database error: {}", self);
@@ -76,10 +200,35 @@ impl IntoResponse for AppError {
             }
         };
 
-        let body = Json(json!({
+        let mut body = json!({
             "error": error_message,
+            "code": self.code(),
             "message": self.to_string()
-        }));
+        });
+        if let AppError::Validation(fields) = &self {
+            body["fields"] = json!(fields);
+        }
+        let body = Json(body);
+
+        // Tell clients when to try again
+        if let AppError::MaintenanceMode(retry_after_secs)
+        | AppError::ServerBusy(retry_after_secs)
+        | AppError::TooManyChunkWrites(retry_after_secs)
+        | AppError::StorageFull(retry_after_secs)
+        | AppError::StorageUnavailable(retry_after_secs) = self
+        {
+            return (
+                status,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                body,
+            )
+                .into_response();
+        }
+
+        // 426 responses name the protocol to switch to
+        if let AppError::HttpsRequired = self {
+            return (status, [(header::UPGRADE, "TLS/1.2, HTTP/1.1")], body).into_response();
+        }
 
         (status, body).into_response()
     }
@@ -0,0 +1,162 @@
+use kusatsu_entity::file;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// Past this many entries, expired ones are swept before adding another; if all
+// of them are still fresh, new records simply aren't cached
+const MAX_ENTRIES: usize = 10_000;
+
+/// File records recently looked up by the info endpoint, so popular links don't
+/// hit the database on every page load
+///
+/// Records are kept for a short TTL, and dropped as soon as a download, an
+/// expiry change or a deletion changes them. The filename stays encrypted in
+/// the cache and is decrypted again for every request, with the key it brings.
+/// Owner tokens, wrapped keys, inline contents and listings are left out, so
+/// cached records are only fit for building file info. A TTL of zero disables
+/// the cache.
+#[derive(Clone)]
+pub struct FileInfoCache {
+    entries: Arc<Mutex<HashMap<Uuid, (Instant, file::Model)>>>,
+    ttl: Duration,
+}
+
+impl FileInfoCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Arc::default(),
+            ttl,
+        }
+    }
+
+    /// Cached record of `file_id`, if it was stored within the TTL
+    pub fn get(&self, file_id: Uuid) -> Option<file::Model> {
+        self.get_at(file_id, Instant::now())
+    }
+
+    /// Cache the record of a file just read from the database
+    pub fn insert(&self, file: &file::Model) {
+        self.insert_at(file, Instant::now());
+    }
+
+    /// Forget `file_id`, after its record changed or was deleted
+    pub fn invalidate(&self, file_id: Uuid) {
+        self.lock().remove(&file_id);
+    }
+
+    /// Forget every file, after changes to records that aren't tracked one by one
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Uuid, (Instant, file::Model)>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn get_at(&self, file_id: Uuid, now: Instant) -> Option<file::Model> {
+        let mut entries = self.lock();
+        match entries.get(&file_id) {
+            Some((cached_at, file)) if now.duration_since(*cached_at) < self.ttl => {
+                Some(file.clone())
+            }
+            Some(_) => {
+                entries.remove(&file_id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert_at(&self, file: &file::Model, now: Instant) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        let mut entries = self.lock();
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, (cached_at, _)| now.duration_since(*cached_at) < self.ttl);
+            if entries.len() >= MAX_ENTRIES {
+                return;
+            }
+        }
+
+        let file = file::Model {
+            owner_token: None,
+            wrapped_key: None,
+            key_salt: None,
+            inline_data: None,
+            // Only whether there is one shows in file info
+            listing: file.listing.as_ref().map(|_| Vec::new()),
+            ..file.clone()
+        };
+        entries.insert(file.file_id, (now, file));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_file() -> file::Model {
+        file::Model {
+            id: 1,
+            file_id: Uuid::new_v4(),
+            original_size: 5,
+            encrypted_size: 21,
+            mime_type: None,
+            file_path: String::new(),
+            nonce: Vec::new(),
+            is_encrypted: false,
+            encrypted_filename: b"notes.txt".to_vec(),
+            filename_nonce: Vec::new(),
+            created_at: chrono::Utc::now(),
+            expires_at: None,
+            download_count: 0,
+            max_downloads: None,
+            owner_token: Some("owner".to_string()),
+            slug: None,
+            signature: None,
+            wrapped_key: None,
+            key_salt: None,
+            content_sha256: None,
+            inline_data: Some(b"hello".to_vec()),
+            compression: None,
+            burn_after_first_download_minutes: None,
+            expire_after_inactivity_hours: None,
+            first_downloaded_at: None,
+            listing: None,
+        }
+    }
+
+    #[test]
+    fn test_entries_expire_after_the_ttl() {
+        let cache = FileInfoCache::new(Duration::from_secs(5));
+        let file = test_file();
+        let now = Instant::now();
+
+        cache.insert_at(&file, now);
+        let cached = cache.get_at(file.file_id, now).unwrap();
+        assert_eq!(cached.original_size, file.original_size);
+        // Only what file info needs is kept
+        assert!(cached.owner_token.is_none() && cached.inline_data.is_none());
+
+        assert!(cache
+            .get_at(file.file_id, now + Duration::from_secs(5))
+            .is_none());
+
+        cache.insert_at(&file, now);
+        cache.invalidate(file.file_id);
+        assert!(cache.get_at(file.file_id, now).is_none());
+    }
+
+    #[test]
+    fn test_zero_ttl_disables_the_cache() {
+        let cache = FileInfoCache::new(Duration::ZERO);
+        let file = test_file();
+
+        cache.insert(&file);
+        assert!(cache.get(file.file_id).is_none());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+
+/// Check an upload's declared filename and mime type against the configured lists
+///
+/// Returns `AppError::FileTypeNotAllowed` when the filename ends with a denied
+/// extension, or when allowed mime types are configured and the declared type
+/// isn't one of them (or none was declared). The contents aren't inspected,
+/// so client-encrypted uploads are judged by the same metadata as others.
+pub fn check_upload(config: &Config, filename: &str, mime_type: Option<&str>) -> Result<()> {
+    let filename = filename.to_lowercase();
+    if let Some(extension) = config
+        .denied_extensions
+        .iter()
+        .find(|extension| has_extension(&filename, extension))
+    {
+        tracing::warn!("🚫 Rejected upload with denied extension .{}", extension);
+        return Err(AppError::FileTypeNotAllowed(format!(
+            "files ending in .{} are not accepted",
+            extension
+        )));
+    }
+
+    if config.allowed_mime_types.is_empty() {
+        return Ok(());
+    }
+
+    let mime_type = mime_type.map(essence).unwrap_or_default();
+    if !config
+        .allowed_mime_types
+        .iter()
+        .any(|allowed| mime_matches(allowed, &mime_type))
+    {
+        tracing::warn!("🚫 Rejected upload with mime type {:?}", mime_type);
+        return Err(AppError::FileTypeNotAllowed(if mime_type.is_empty() {
+            "a mime type is required".to_string()
+        } else {
+            format!("{} is not accepted", mime_type)
+        }));
+    }
+
+    Ok(())
+}
+
+// Extensions may span several dots, like `tar.gz`
+fn has_extension(filename: &str, extension: &str) -> bool {
+    filename
+        .strip_suffix(extension)
+        .is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+}
+
+// The type and subtype of a mime type, without parameters like `charset`
+fn essence(mime_type: &str) -> String {
+    mime_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+// `image/*` allows every image subtype
+fn mime_matches(allowed: &str, mime_type: &str) -> bool {
+    match allowed.strip_suffix("/*") {
+        Some(top_level) => mime_type
+            .strip_prefix(top_level)
+            .is_some_and(|rest| rest.starts_with('/')),
+        None => allowed == mime_type,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_and_mime_matching() {
+        assert!(has_extension("setup.exe", "exe"));
+        assert!(has_extension("archive.tar.gz", "tar.gz"));
+        assert!(!has_extension("exe", "exe"));
+        assert!(!has_extension("notexe", "exe"));
+
+        assert_eq!(essence("Text/Plain; charset=utf-8"), "text/plain");
+        assert!(mime_matches("image/*", "image/png"));
+        assert!(!mime_matches("image/*", "imagery/png"));
+        assert!(mime_matches("text/plain", "text/plain"));
+        assert!(!mime_matches("text/plain", "text/html"));
+    }
+}
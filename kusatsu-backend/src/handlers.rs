@@ -1,56 +1,562 @@
 use axum::{
-    body::Body,
+    body::{Body, Bytes},
     extract::{Form, Multipart, Path, Query, State},
-    http::{Response, StatusCode},
-    response::{IntoResponse, Json},
+    http::{header, HeaderMap, Response, StatusCode},
+    response::{Html, IntoResponse, Json},
 };
-use kusatsu_encrypt::{Encryption, EncryptionKey};
+use base64::{engine::general_purpose, Engine as _};
+use kusatsu_encrypt::{cipher, Encryption, EncryptionKey, Padding};
 use uuid::Uuid;
 
 use crate::{
-    database::{file_ops, upload_session_ops},
+    chunk_writes, compression,
+    container::{self, ContainerHeader},
+    content_disposition,
+    database::{self, file_ops, upload_session_ops, FileFilter, StorageCheckReport},
     error::{AppError, Result},
-    AppState, ChunkUploadResponse, CompleteUploadRequest, DownloadRequest, FileInfo,
-    StartUploadRequest, StartUploadResponse, UploadOptions, UploadResponse,
+    file_type, filename_placeholder, openapi,
+    public_url::PublicUrls,
+    recommended_chunk_size, scanner, signing,
+    storage::{self, TempFile},
+    storage_health::write_error,
+    throttle::ThrottledReader,
+    upload_memory, upload_pow, AppState, ChunkCheck, ChunkUploadResponse, CleanupResponse,
+    CompleteUploadRequest, DeleteFileRequest, DownloadRequest, ExtendExpiryRequest, FieldError,
+    FileInfo, FilePreviewResponse, HealthResponse, JsonDownloadResponse, ListingRequest,
+    ListingResponse, RotateFileRequest, RotateFileResponse, ServerConfigResponse,
+    SignDownloadRequest, SignDownloadResponse, StartUploadRequest, StartUploadResponse,
+    UploadChallengeResponse, UploadKeepaliveResponse, UploadMethod, UploadOptions,
+    UploadPlanResponse, UploadResponse, UploadStatusResponse, WrapKeyRequest, WrappedKeyResponse,
+    COMPRESSION_HEADER, KEY_PLACEHOLDER, MAX_CHUNK_SIZE, MAX_LISTING_SIZE, MAX_SINGLE_UPLOAD_SIZE,
+    SERVER_CONFIG_SCHEMA_VERSION, SIGNATURE_HEADER,
 };
+use kusatsu_entity::{file, upload_session};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::OwnedSemaphorePermit;
+use tokio_util::io::ReaderStream;
 
 #[derive(Deserialize)]
 pub struct DownloadFormData {
     pub encryption_key: String,
 }
 
+#[derive(Deserialize)]
+pub struct SignedDownloadQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct MaintenanceResponse {
+    pub maintenance: bool,
+}
+
+#[derive(Deserialize)]
+pub struct UploadStatusQuery {
+    #[serde(default)]
+    pub verify: bool,
+}
+
+#[derive(Deserialize)]
+pub struct StorageCheckQuery {
+    #[serde(default)]
+    pub repair: bool,
+}
+
+#[derive(Serialize)]
+pub struct StorageCheckResponse {
+    #[serde(flatten)]
+    pub report: StorageCheckReport,
+    pub repaired: bool,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+// Unknown fields are refused, so a misspelled filter can't widen a deletion
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BulkDeleteRequest {
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    pub mime_type: Option<String>,
+    pub min_size: Option<i64>,
+    pub file_ids: Option<Vec<Uuid>>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
 #[derive(Serialize)]
-pub struct CleanupResponse {
-    pub items_cleaned: u64,
-    pub cleanup_type: String,
+pub struct BulkDeleteResponse {
+    pub files_matched: u64,
+    pub files_deleted: u64,
+    // First matching files, to check a filter with a dry run
+    pub sample_file_ids: Vec<Uuid>,
+    pub dry_run: bool,
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
-// Default chunk size: 5MB
-const DEFAULT_CHUNK_SIZE: i32 = 5 * 1024 * 1024;
+// Most file ids one bulk delete may list
+const MAX_BULK_DELETE_IDS: usize = 1000;
+
+// Matching files listed in a bulk delete response
+const BULK_DELETE_SAMPLE_SIZE: u64 = 100;
+
+// Stored files younger than this may still be waiting for their database record
+const STORAGE_CHECK_GRACE_PERIOD: Duration = Duration::from_secs(3600);
+
+// Longest accepted slug
+const MAX_SLUG_LENGTH: usize = 64;
+
+// Shortest salt accepted for a passphrase-wrapped key (Argon2 requires 8 bytes)
+const MIN_KEY_SALT_LENGTH: usize = 8;
+
+// Largest stored file that can be fetched for an inline preview: 1MB
+const MAX_PREVIEW_SIZE: i64 = 1024 * 1024;
 
 // Health check endpoint
-pub async fn health_check() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "status": "healthy",
-        "service": "kusatsu-backend",
-        "timestamp": chrono::Utc::now()
-    }))
+pub async fn health_check(State(state): State<AppState>) -> Json<HealthResponse> {
+    // Downloads keep working when storage stops taking writes, so the service is
+    // degraded rather than down
+    let storage_error = state.storage_health.error_code();
+    let status = if storage_error.is_some() {
+        "degraded"
+    } else {
+        "healthy"
+    };
+
+    Json(HealthResponse {
+        status: status.to_string(),
+        service: "kusatsu-backend".to_string(),
+        maintenance: state.maintenance.load(Ordering::Relaxed),
+        timestamp: chrono::Utc::now(),
+        database_pool: database::pool_status(&state.db),
+        storage_error: storage_error.map(str::to_string),
+    })
+}
+
+// Limits and optional features of this server, so clients can adapt before uploading
+pub async fn get_server_config(State(state): State<AppState>) -> Json<ServerConfigResponse> {
+    let config = &state.config;
+    Json(ServerConfigResponse {
+        schema_version: SERVER_CONFIG_SCHEMA_VERSION,
+        max_file_size: config.max_file_size as i64,
+        single_upload_max: MAX_SINGLE_UPLOAD_SIZE,
+        default_chunk_size: recommended_chunk_size(0),
+        max_chunk_size: MAX_CHUNK_SIZE,
+        max_chunks: config.max_chunks,
+        client_encryption_supported: true,
+        server_side_encryption: config.server_side_encryption,
+        password_protection_supported: true,
+        signed_urls_supported: config.signing_secret.is_some(),
+        default_expiry_hours: config.default_expiry_hours,
+        max_expires_in_hours: config.max_expires_in_hours,
+        max_downloads_limit: config.max_downloads_limit,
+        upload_pow_difficulty: config
+            .require_upload_pow
+            .then_some(config.upload_pow_difficulty),
+    })
+}
+
+// OpenAPI description of the API
+pub async fn openapi_spec(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(openapi::spec(&state.config))
+}
+
+// Release of swagger-ui-dist loaded from the CDN, pinned so the page can't change underneath
+const SWAGGER_UI_VERSION: &str = "5.17.14";
+
+// Swagger UI for the OpenAPI description, from the assets in KUSATSU_API_DOCS_ASSETS
+// when set, otherwise from the CDN so the backend ships no assets
+pub async fn api_docs(State(state): State<AppState>) -> Html<String> {
+    let assets = match state.config.api_docs_assets {
+        Some(_) => "/api/docs/assets".to_string(),
+        None => format!("https://unpkg.com/swagger-ui-dist@{}", SWAGGER_UI_VERSION),
+    };
+    Html(API_DOCS_PAGE.replace("{assets}", &assets))
+}
+
+const API_DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Kusatsu API</title>
+  <link rel="stylesheet" href="{assets}/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="{assets}/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/api/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+// Hold memory for an upload that loads a whole file, until the permit is dropped
+fn reserve_upload_memory(state: &AppState, bytes: u64) -> Result<OwnedSemaphorePermit> {
+    state.upload_memory.try_reserve(bytes).ok_or_else(|| {
+        tracing::warn!(
+            "🚦 Upload memory budget exhausted, rejecting a {} byte upload",
+            bytes
+        );
+        AppError::ServerBusy(upload_memory::BUSY_RETRY_AFTER_SECS)
+    })
+}
+
+// Reject new uploads while maintenance mode is on
+fn ensure_accepting_uploads(state: &AppState) -> Result<()> {
+    if state.maintenance.load(Ordering::Relaxed) {
+        return Err(AppError::MaintenanceMode(
+            state.config.maintenance_retry_after_secs,
+        ));
+    }
+
+    Ok(())
+}
+
+// Redeem the solved challenge an upload must bring when proof of work is required
+fn check_upload_pow(state: &AppState, challenge: Option<&str>, nonce: Option<u64>) -> Result<()> {
+    if !state.config.require_upload_pow {
+        return Ok(());
+    }
+
+    let (Some(challenge), Some(nonce)) = (challenge, nonce) else {
+        return Err(AppError::ProofOfWorkRequired(
+            "send pow_challenge and pow_nonce, from POST /api/upload/challenge".to_string(),
+        ));
+    };
+    if !state
+        .upload_challenges
+        .redeem(challenge, nonce, state.config.upload_pow_difficulty)
+    {
+        tracing::warn!("🧮 Rejected an upload with an unknown, expired or unsolved challenge");
+        return Err(AppError::ProofOfWorkRequired(
+            "the challenge is unknown, expired, already used or not solved".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Check an owner token against the hash stored with the file, in constant time
+fn ensure_owner(file: &file::Model, owner_token: &str) -> Result<()> {
+    let provided = database::hash_owner_token(owner_token);
+    let owns_file = file
+        .owner_token
+        .as_ref()
+        .is_some_and(|stored| bool::from(stored.as_bytes().ct_eq(provided.as_bytes())));
+
+    if !owns_file {
+        return Err(AppError::Forbidden);
+    }
+    Ok(())
+}
+
+// Check the `Authorization: Bearer <token>` header against the configured admin token
+fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<()> {
+    let expected = state
+        .config
+        .admin_token
+        .as_deref()
+        .ok_or(AppError::Unauthorized)?;
+
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AppError::Unauthorized)?;
+
+    // Compared as digests of equal length, in constant time, so timing reveals nothing of the token
+    let matches = Sha256::digest(provided.as_bytes())
+        .as_slice()
+        .ct_eq(Sha256::digest(expected.as_bytes()).as_slice());
+    if !bool::from(matches) {
+        return Err(AppError::Unauthorized);
+    }
+
+    Ok(())
+}
+
+// Slugs are lowercase alphanumeric words separated by single hyphens
+fn slug_error(slug: &str) -> Option<String> {
+    let valid = !slug.is_empty()
+        && slug.len() <= MAX_SLUG_LENGTH
+        && slug
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && !slug.contains("--");
+
+    (!valid).then(|| {
+        format!(
+            "use 1-{} lowercase letters, digits and single hyphens",
+            MAX_SLUG_LENGTH
+        )
+    })
+}
+
+fn validate_slug(slug: &str) -> Result<()> {
+    match slug_error(slug) {
+        Some(message) => Err(AppError::BadRequest(format!(
+            "Invalid slug '{}': {}",
+            slug, message
+        ))),
+        None => Ok(()),
+    }
+}
+
+// Tag the request's span so every log line of the operation carries the upload id
+fn record_upload_id(upload_id: Uuid) {
+    tracing::Span::current().record("upload_id", tracing::field::display(upload_id));
+}
+
+// Tag the request's span so every log line of the operation carries the file id
+fn record_file_id(file_id: Uuid) {
+    tracing::Span::current().record("file_id", tracing::field::display(file_id));
+}
+
+// Reject a slug held by a live file or reserved by an in-progress chunked upload
+async fn ensure_slug_available(state: &AppState, slug: &str) -> Result<()> {
+    validate_slug(slug)?;
+
+    if file_ops::get_file_by_slug(&state.db, slug).await?.is_some()
+        || upload_session_ops::slug_reserved(&state.db, slug).await?
+    {
+        return Err(AppError::Conflict(format!(
+            "Slug '{}' is already in use",
+            slug
+        )));
+    }
+
+    Ok(())
+}
+
+// A download limit must be positive and within the configured maximum
+fn max_downloads_error(state: &AppState, max_downloads: Option<i32>) -> Option<String> {
+    max_downloads
+        .filter(|max_downloads| {
+            *max_downloads <= 0 || *max_downloads > state.config.max_downloads_limit
+        })
+        .map(|_| format!("must be between 1 and {}", state.config.max_downloads_limit))
+}
+
+fn validate_max_downloads(state: &AppState, max_downloads: Option<i32>) -> Result<()> {
+    match max_downloads_error(state, max_downloads) {
+        Some(message) => Err(AppError::BadRequest(format!(
+            "Invalid max_downloads, {}",
+            message
+        ))),
+        None => Ok(()),
+    }
+}
+
+// Uploads are held to the same longest lifetime that extending an expiry is
+fn expires_in_hours_error(state: &AppState, hours: Option<i32>) -> Option<String> {
+    hours
+        .filter(|hours| *hours <= 0 || i64::from(*hours) > state.config.max_expires_in_hours)
+        .map(|_| {
+            format!(
+                "must be between 1 and {} hours",
+                state.config.max_expires_in_hours
+            )
+        })
+}
+
+fn validate_expires_in_hours(state: &AppState, hours: Option<i32>) -> Result<()> {
+    match expires_in_hours_error(state, hours) {
+        Some(message) => Err(AppError::BadRequest(format!(
+            "Invalid expires_in_hours, {}",
+            message
+        ))),
+        None => Ok(()),
+    }
+}
+
+// A file can't outlive the longest allowed expiry after its first download either
+fn burn_minutes_error(state: &AppState, minutes: Option<i32>) -> Option<String> {
+    let max_minutes = state.config.max_expires_in_hours * 60;
+    minutes
+        .filter(|minutes| *minutes <= 0 || i64::from(*minutes) > max_minutes)
+        .map(|_| format!("must be between 1 and {} minutes", max_minutes))
+}
+
+fn validate_burn_minutes(state: &AppState, minutes: Option<i32>) -> Result<()> {
+    match burn_minutes_error(state, minutes) {
+        Some(message) => Err(AppError::BadRequest(format!(
+            "Invalid burn_after_first_download_minutes, {}",
+            message
+        ))),
+        None => Ok(()),
+    }
+}
+
+// Downloads renew an inactivity period, so it stands in for a fixed expiry and is
+// bounded like one
+fn inactivity_hours_error(
+    state: &AppState,
+    hours: Option<i32>,
+    expires_in_hours: Option<i32>,
+) -> Option<String> {
+    let hours = hours?;
+    if hours <= 0 || i64::from(hours) > state.config.max_expires_in_hours {
+        Some(format!(
+            "must be between 1 and {} hours",
+            state.config.max_expires_in_hours
+        ))
+    } else if expires_in_hours.is_some() {
+        Some("can't be combined with expires_in_hours".to_string())
+    } else {
+        None
+    }
+}
+
+fn validate_inactivity_hours(
+    state: &AppState,
+    hours: Option<i32>,
+    expires_in_hours: Option<i32>,
+) -> Result<()> {
+    match inactivity_hours_error(state, hours, expires_in_hours) {
+        Some(message) => Err(AppError::BadRequest(format!(
+            "Invalid expire_after_inactivity_hours, {}",
+            message
+        ))),
+        None => Ok(()),
+    }
+}
+
+// Requested lifetime of an upload in hours, or the configured default when none was
+// requested; a file that expires after inactivity starts with its inactivity period
+fn effective_expires_in_hours(
+    state: &AppState,
+    requested: Option<i32>,
+    inactivity_hours: Option<i32>,
+) -> Option<i32> {
+    inactivity_hours
+        .or(requested)
+        .or(state.config.default_expiry_hours)
+}
+
+fn expires_at_from_hours(hours: Option<i32>) -> Option<chrono::DateTime<chrono::Utc>> {
+    hours.map(|hours| chrono::Utc::now() + chrono::Duration::hours(hours as i64))
+}
+
+// Base API URL of a file, by slug when it has one
+fn file_api_url(urls: &PublicUrls, file_id: Uuid, slug: Option<&str>) -> String {
+    match slug {
+        Some(slug) => format!("{}/api/files/by-slug/{}", urls.api_url, slug),
+        None => format!("{}/api/files/{}", urls.api_url, file_id),
+    }
+}
+
+// Store the data of a new file, returning its storage path and any data kept inline
+//
+// Data under the inline threshold goes in the file record instead, with an empty
+// path, so tiny shares don't each cost a file and its directories on disk.
+async fn store_file_data(
+    state: &AppState,
+    file_id: Uuid,
+    header: &ContainerHeader,
+    data: &[u8],
+) -> Result<(String, Option<Vec<u8>>)> {
+    if data.len() < state.config.inline_threshold {
+        return Ok((String::new(), Some(data.to_vec())));
+    }
+
+    let stored = state.storage.store_file(file_id, header, data).await;
+    state.storage_health.record(&stored);
+    Ok((stored?, None))
+}
+
+// Remove the stored data of a file whose record couldn't be created
+async fn discard_file_data(state: &AppState, file_path: &str) {
+    if file_path.is_empty() {
+        return;
+    }
+    if let Err(e) = state.storage.delete_file(file_path).await {
+        tracing::warn!("⚠️ Failed to remove data of an unrecorded file: {}", e);
+    }
+}
+
+// Read the whole stored data of a file, from its record when kept inline
+async fn read_file_data(state: &AppState, file: &file::Model) -> Result<Vec<u8>> {
+    let (mut reader, size) = open_file_data(state, file).await?;
+    let mut data = Vec::with_capacity(size as usize);
+    reader.read_to_end(&mut data).await?;
+    Ok(data)
+}
+
+// Open the stored data of a file for streaming, with its size in bytes
+async fn open_file_data(
+    state: &AppState,
+    file: &file::Model,
+) -> Result<(storage::StoredFileReader, u64)> {
+    if let Some(data) = &file.inline_data {
+        return Ok((
+            Box::new(std::io::Cursor::new(data.clone())),
+            data.len() as u64,
+        ));
+    }
+
+    // Files stored before the container format hold the bare ciphertext, at the
+    // size the record gives; a container is always larger by its header. The
+    // record decides, since an unencrypted legacy file may start like one.
+    let (reader, size) = state.storage.open_file(&file.file_path).await?;
+    if size == file.encrypted_size as u64 {
+        return Ok((reader, size));
+    }
+
+    let (_header, reader, size) = container::read_header(reader, size).await?;
+    Ok((reader, size))
 }
 
 // File upload endpoint - receives plaintext file data and encrypts server-side
 pub async fn upload_file(
     State(state): State<AppState>,
     Query(options): Query<UploadOptions>,
+    headers: HeaderMap,
     mut multipart: Multipart,
 ) -> Result<Json<UploadResponse>> {
-    let mut file_data: Option<Vec<u8>> = None;
+    ensure_accepting_uploads(&state)?;
+    validate_expires_in_hours(&state, options.expires_in_hours)?;
+    validate_max_downloads(&state, options.max_downloads)?;
+    validate_burn_minutes(&state, options.burn_after_first_download_minutes)?;
+    validate_inactivity_hours(
+        &state,
+        options.expire_after_inactivity_hours,
+        options.expires_in_hours,
+    )?;
+
+    if let Some(slug) = &options.slug {
+        ensure_slug_available(&state, slug).await?;
+    }
+
+    // Before reading the body, so unsolved uploads cost the server nothing
+    check_upload_pow(&state, options.pow_challenge.as_deref(), options.pow_nonce)?;
+
+    let mut file_data: Option<TempFile> = None;
     let mut filename: Option<String> = None;
     let mut mime_type: Option<String> = None;
+    let mut signature: Option<Vec<u8>> = None;
+    let mut declared_size: Option<usize> = None;
+    let mut client_encrypted = false;
+    let mut nonce: Option<String> = None;
+    let mut encrypted_filename: Option<String> = None;
+    let mut filename_nonce: Option<String> = None;
+    let mut compressed_with: Option<String> = None;
+    let mut declared_original_size: Option<u64> = None;
+    let mut received_size = 0usize;
 
     // Process multipart form data
-    while let Some(field) = multipart
+    while let Some(mut field) = multipart
         .next_field()
         .await
         .map_err(|_| AppError::BadRequest("Invalid multipart data".to_string()))?
@@ -59,16 +565,37 @@ pub async fn upload_file(
 
         match name.as_str() {
             "file" | "file_data" => {
-                let data = field
-                    .bytes()
-                    .await
-                    .map_err(|_| AppError::BadRequest("Failed to read file data".to_string()))?;
+                // Spool to a temp file instead of buffering the whole upload in memory
+                let temp = storage::create_temp_file(
+                    &state.config.storage_dir,
+                    state.config.strict_file_permissions,
+                )
+                .await;
+                state.storage_health.record(&temp);
+                let (temp_file, mut writer) = temp?;
+                let mut size = 0usize;
 
-                if data.len() > state.config.max_file_size {
-                    return Err(AppError::FileTooLarge);
+                while let Some(chunk) = field
+                    .chunk()
+                    .await
+                    .map_err(|_| AppError::BadRequest("Failed to read file data".to_string()))?
+                {
+                    size += chunk.len();
+                    if size > state.config.max_file_size {
+                        return Err(AppError::FileTooLarge);
+                    }
+                    writer
+                        .write_all(&chunk)
+                        .await
+                        .map_err(|e| write_error("Failed to spool upload", e))?;
                 }
+                writer
+                    .flush()
+                    .await
+                    .map_err(|e| write_error("Failed to spool upload", e))?;
 
-                file_data = Some(data.to_vec());
+                file_data = Some(temp_file);
+                received_size = size;
             }
             "filename" => {
                 let data = field
@@ -88,6 +615,57 @@ pub async fn upload_file(
                     mime_type = Some(data);
                 }
             }
+            "signature" => {
+                let data = field
+                    .text()
+                    .await
+                    .map_err(|_| AppError::BadRequest("Failed to read signature".to_string()))?;
+                signature = parse_signature(Some(&data))?;
+            }
+            "file_size" => {
+                let data = field
+                    .text()
+                    .await
+                    .map_err(|_| AppError::BadRequest("Failed to read file size".to_string()))?;
+                declared_size = Some(data.trim().parse().map_err(|_| {
+                    AppError::BadRequest("file_size must be a non-negative integer".to_string())
+                })?);
+            }
+            "encrypted" => {
+                let data = field.text().await.map_err(|_| {
+                    AppError::BadRequest("Failed to read encrypted flag".to_string())
+                })?;
+                client_encrypted = data.trim().parse().map_err(|_| {
+                    AppError::BadRequest("encrypted must be true or false".to_string())
+                })?;
+            }
+            // `filename_ciphertext` is another name for `encrypted_filename`
+            "nonce" | "encrypted_filename" | "filename_ciphertext" | "filename_nonce" => {
+                let data = field
+                    .text()
+                    .await
+                    .map_err(|_| AppError::BadRequest(format!("Failed to read {}", name)))?;
+                let value = Some(data).filter(|data| !data.is_empty());
+                match name.as_str() {
+                    "nonce" => nonce = value,
+                    "filename_nonce" => filename_nonce = value,
+                    _ => encrypted_filename = value,
+                }
+            }
+            "compression" => {
+                let data = field.text().await.map_err(|_| {
+                    AppError::BadRequest("Failed to read compression format".to_string())
+                })?;
+                compressed_with = Some(data.trim().to_string()).filter(|data| !data.is_empty());
+            }
+            "original_size" => {
+                let data = field.text().await.map_err(|_| {
+                    AppError::BadRequest("Failed to read original size".to_string())
+                })?;
+                declared_original_size = Some(data.trim().parse().map_err(|_| {
+                    AppError::BadRequest("original_size must be a non-negative integer".to_string())
+                })?);
+            }
             _ => {
                 // Skip unknown fields
                 let _ = field.bytes().await;
@@ -96,83 +674,318 @@ pub async fn upload_file(
     }
 
     // Validate required fields
-    let file_data =
+    let temp_file =
         file_data.ok_or_else(|| AppError::BadRequest("Missing file data".to_string()))?;
-    let filename = filename.ok_or_else(|| AppError::BadRequest("Missing filename".to_string()))?;
 
-    let original_size = file_data.len() as i64;
+    // Without server-side encryption, only content the client encrypted is accepted
+    if !client_encrypted && !state.config.server_side_encryption {
+        return Err(AppError::BadRequest(
+            "Server-side encryption is disabled, send content encrypted by the client with encrypted=true and its nonce"
+                .to_string(),
+        ));
+    }
 
-    // Generate encryption key and encrypt the file server-side
-    let encryption_key = EncryptionKey::generate();
+    // Content is either the client's ciphertext or plaintext for the server to
+    // encrypt; fields of one mode sent in the other would be silently dropped
+    if nonce.is_some() && !client_encrypted {
+        return Err(AppError::BadRequest(
+            "nonce is only accepted with encrypted=true".to_string(),
+        ));
+    }
+    if filename.is_some() && encrypted_filename.is_some() {
+        return Err(AppError::BadRequest(
+            "Send either filename or encrypted_filename, not both".to_string(),
+        ));
+    }
 
-    // Encrypt file content
-    let encrypted_file_data = Encryption::encrypt(&file_data, &encryption_key)
-        .map_err(|e| AppError::ServerError(format!("Failed to encrypt file: {}", e)))?;
+    let encrypted_filename = match (encrypted_filename, filename_nonce) {
+        (Some(encrypted_filename), Some(filename_nonce)) if client_encrypted => Some(
+            decode_encrypted_filename(&encrypted_filename, &filename_nonce)?,
+        ),
+        (Some(_), Some(_)) => {
+            return Err(AppError::BadRequest(
+                "encrypted_filename is only accepted with encrypted=true".to_string(),
+            ))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(AppError::BadRequest(
+                "encrypted_filename and filename_nonce must be sent together".to_string(),
+            ))
+        }
+    };
+    if filename.is_none() && encrypted_filename.is_none() {
+        return Err(AppError::BadRequest("Missing filename".to_string()));
+    }
 
-    // Encrypt filename
-    let encrypted_filename_data = Encryption::encrypt(filename.as_bytes(), &encryption_key)
-        .map_err(|e| AppError::ServerError(format!("Failed to encrypt filename: {}", e)))?;
+    // The client compresses before it encrypts, so compressed content is always
+    // client-encrypted. Its size before compression is declared, since the server
+    // can't see it and downloads check the decompressed data against it.
+    let original_size = match (&compressed_with, declared_original_size) {
+        (Some(_), _) if !client_encrypted => {
+            return Err(AppError::BadRequest(
+                "compression is only accepted with encrypted=true".to_string(),
+            ))
+        }
+        (Some(format), Some(original_size)) => {
+            compression::check_format(format)?;
+            if original_size > state.config.max_file_size as u64 {
+                return Err(AppError::FileTooLarge);
+            }
+            // Downloads decompress in memory, so small data can't claim a huge size
+            if !compression::within_ratio(
+                received_size as u64,
+                original_size,
+                state.config.max_compression_ratio,
+            ) {
+                return Err(AppError::BadRequest(format!(
+                    "original_size may be at most {} times the size of the compressed data",
+                    state.config.max_compression_ratio
+                )));
+            }
+            Some(original_size as i64)
+        }
+        (Some(_), None) => {
+            return Err(AppError::BadRequest(
+                "original_size is required with compression".to_string(),
+            ))
+        }
+        (None, Some(_)) => {
+            return Err(AppError::BadRequest(
+                "original_size is only accepted with compression".to_string(),
+            ))
+        }
+        (None, None) => None,
+    };
+
+    // An encrypted filename has no extension to check, only the mime type is
+    file_type::check_upload(
+        &state.config,
+        filename.as_deref().unwrap_or_default(),
+        mime_type.as_deref(),
+    )?;
+
+    // A truncated body would otherwise be stored as a complete file
+    if let Some(declared_size) = declared_size {
+        if received_size != declared_size {
+            return Err(AppError::BadRequest(format!(
+                "Received {} bytes of file data but file_size is {}",
+                received_size, declared_size
+            )));
+        }
+    }
+
+    // Encryption works on whole buffers, so read the spooled data back once the
+    // plaintext and its ciphertext fit in the memory budget. Content encrypted by
+    // the client is stored as received, without a second copy.
+    let copies = if client_encrypted { 1 } else { 2 };
+    let _memory = reserve_upload_memory(&state, copies * received_size as u64)?;
+    let file_data = tokio::fs::read(temp_file.path()).await?;
+    drop(temp_file);
+
+    let sealed = if client_encrypted {
+        let nonce = nonce.ok_or_else(|| {
+            AppError::BadRequest("Missing nonce for encrypted content".to_string())
+        })?;
+        let filename = encrypted_filename
+            .unwrap_or_else(|| (filename.unwrap_or_default().into_bytes(), Vec::new()));
+        let mut sealed = seal_client_encrypted(file_data, &nonce, filename)?;
+        if let Some(original_size) = original_size {
+            sealed.original_size = original_size;
+        }
+        sealed
+    } else {
+        // Scan the plaintext before it is encrypted and stored
+        scanner::check_upload(&state.config, &file_data).await?;
+        seal_on_server(&file_data, &filename.unwrap_or_default())?
+    };
 
     // Calculate expiration time
-    let expires_at = options
-        .expires_in_hours
-        .map(|hours| chrono::Utc::now() + chrono::Duration::hours(hours as i64));
+    let expires_at = expires_at_from_hours(effective_expires_in_hours(
+        &state,
+        options.expires_in_hours,
+        options.expire_after_inactivity_hours,
+    ));
 
-    // Generate file ID
+    // Generate file ID and owner token
     let file_id = Uuid::new_v4();
-    let encrypted_size = encrypted_file_data.ciphertext.len() as i64;
-
-    // Store encrypted file to disk
-    let file_path = state
-        .storage
-        .store_file(file_id, &encrypted_file_data.ciphertext)
-        .await?;
+    record_file_id(file_id);
+    let owner_token = Encryption::generate_key_string();
+    let encrypted_size = sealed.ciphertext.len() as i64;
+
+    // Store encrypted file to disk, or inline when it is small enough
+    let header = ContainerHeader::encrypted(
+        &sealed.ciphertext,
+        sealed.nonce.clone(),
+        sealed
+            .encryption_key
+            .as_ref()
+            .map(container::key_fingerprint),
+        sealed.original_size,
+    );
+    let (file_path, inline_data) =
+        store_file_data(&state, file_id, &header, &sealed.ciphertext).await?;
 
-    // Store file metadata in database
-    let _file_record = file_ops::create_file_record(
+    // Store file metadata in database; the slug may have been taken meanwhile
+    let created = file_ops::create_file_record(
         &state.db,
         crate::database::CreateFileParams {
             file_id,
-            original_size,
+            original_size: sealed.original_size,
             encrypted_size,
             mime_type,
-            file_path,
-            nonce: encrypted_file_data.nonce,
-            encrypted_filename: encrypted_filename_data.ciphertext,
-            filename_nonce: encrypted_filename_data.nonce,
+            file_path: file_path.clone(),
+            nonce: sealed.nonce,
+            encrypted_filename: sealed.encrypted_filename,
+            filename_nonce: sealed.filename_nonce,
             expires_at,
             max_downloads: options.max_downloads,
+            owner_token: owner_token.clone(),
+            slug: options.slug.clone(),
+            signature,
+            inline_data,
+            compression: compressed_with,
+            burn_after_first_download_minutes: options.burn_after_first_download_minutes,
+            expire_after_inactivity_hours: options.expire_after_inactivity_hours,
         },
     )
-    .await?;
+    .await;
+    if let Err(e) = created {
+        discard_file_data(&state, &file_path).await;
+        return Err(e);
+    }
 
     // Encode encryption key for return to client
-    let encoded_key = encryption_key.to_base64();
-
-    // Generate download URL
-    let download_url = format!(
-        "{}/download/{}#{}",
-        state.config.base_url, file_id, encoded_key
-    );
-
-    // Generate curl command
-    let curl_command = format!(
-        "curl -X POST -JLO --fail -d 'encryption_key={}' {}/api/files/{}/download",
-        encoded_key, state.config.api_url, file_id
-    );
-
-    tracing::info!(
-        "📁 File uploaded and encrypted server-side: {} ({} bytes -> {} bytes encrypted)",
-        file_id,
-        original_size,
-        encrypted_size
-    );
+    let encoded_key = sealed.encryption_key.map(|key| key.to_base64());
+    let urls = PublicUrls::for_request(&state.config, &headers);
+    let file_url = file_api_url(&urls, file_id, options.slug.as_deref());
+
+    let (download_url, curl_command) = match &encoded_key {
+        Some(encoded_key) => {
+            tracing::info!(
+                "📁 File uploaded and encrypted server-side: {} ({} bytes -> {} bytes encrypted)",
+                file_id,
+                sealed.original_size,
+                encrypted_size
+            );
+            (
+                format!("{}/download/{}#{}", urls.base_url, file_id, encoded_key),
+                format!(
+                    "curl -X POST -JLO --fail -d 'encryption_key={}' {}/download",
+                    encoded_key, file_url
+                ),
+            )
+        }
+        // Only the client has the key, so it adds it to the link and fills in the placeholder
+        None => {
+            tracing::info!(
+                "📁 File uploaded, encrypted by the client: {} ({} bytes encrypted)",
+                file_id,
+                encrypted_size
+            );
+            (
+                format!("{}/download/{}", urls.base_url, file_id),
+                format!(
+                    "curl -X POST -JLO --fail -d 'encryption_key={}' {}/download",
+                    KEY_PLACEHOLDER, file_url
+                ),
+            )
+        }
+    };
 
     Ok(Json(UploadResponse {
         file_id,
         download_url,
-        encryption_key: Some(encoded_key),
+        encryption_key: encoded_key,
         curl_command,
+        owner_token: Some(owner_token),
+        slug: options.slug,
+        expires_at,
+        content_sha256: None,
+    }))
+}
+
+// Data and filename of a single upload, in the form they are stored
+struct SealedUpload {
+    ciphertext: Vec<u8>,
+    nonce: Vec<u8>,
+    encrypted_filename: Vec<u8>,
+    filename_nonce: Vec<u8>,
+    original_size: i64,
+    // Only known when the server did the encryption
+    encryption_key: Option<EncryptionKey>,
+}
+
+// Encrypt an upload's contents and filename under a new key
+fn seal_on_server(file_data: &[u8], filename: &str) -> Result<SealedUpload> {
+    let encryption_key = EncryptionKey::generate();
+
+    let encrypted_file_data = Encryption::encrypt(file_data, &encryption_key)
+        .map_err(|e| AppError::ServerError(format!("Failed to encrypt file: {}", e)))?;
+
+    let encrypted_filename_data = Encryption::encrypt(filename.as_bytes(), &encryption_key)
+        .map_err(|e| AppError::ServerError(format!("Failed to encrypt filename: {}", e)))?;
+
+    Ok(SealedUpload {
+        ciphertext: encrypted_file_data.ciphertext,
+        nonce: encrypted_file_data.nonce,
+        encrypted_filename: encrypted_filename_data.ciphertext,
+        filename_nonce: encrypted_filename_data.nonce,
+        original_size: file_data.len() as i64,
+        encryption_key: Some(encryption_key),
+    })
+}
+
+// Take contents the client encrypted as they are; the filename is either encrypted
+// too or kept in plain form with an empty nonce
+fn seal_client_encrypted(
+    ciphertext: Vec<u8>,
+    nonce: &str,
+    (encrypted_filename, filename_nonce): (Vec<u8>, Vec<u8>),
+) -> Result<SealedUpload> {
+    let nonce = general_purpose::STANDARD
+        .decode(nonce)
+        .ok()
+        .filter(|nonce| nonce.len() == cipher::NONCE_SIZE)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Invalid nonce, expected {} base64-encoded bytes",
+                cipher::NONCE_SIZE
+            ))
+        })?;
+
+    // Every ciphertext ends with its authentication tag
+    if ciphertext.len() < cipher::TAG_SIZE {
+        return Err(AppError::BadRequest(
+            "Encrypted content is shorter than its authentication tag".to_string(),
+        ));
+    }
+
+    Ok(SealedUpload {
+        original_size: (ciphertext.len() - cipher::TAG_SIZE) as i64,
+        ciphertext,
+        nonce,
+        encrypted_filename,
+        filename_nonce,
+        encryption_key: None,
+    })
+}
+
+// Upload challenge endpoint - proof of work to do before an upload, on servers that require it
+pub async fn issue_upload_challenge(
+    State(state): State<AppState>,
+) -> Result<Json<UploadChallengeResponse>> {
+    if !state.config.require_upload_pow {
+        return Err(AppError::BadRequest(
+            "Uploads to this server don't require proof of work".to_string(),
+        ));
+    }
+
+    Ok(Json(UploadChallengeResponse {
+        challenge: state.upload_challenges.issue(),
+        difficulty: state.config.upload_pow_difficulty,
+        expires_at: chrono::Utc::now()
+            + chrono::Duration::from_std(upload_pow::CHALLENGE_TTL).unwrap_or_default(),
     }))
 }
 
@@ -181,39 +994,57 @@ pub async fn start_chunked_upload(
     State(state): State<AppState>,
     Json(request): Json<StartUploadRequest>,
 ) -> Result<Json<StartUploadResponse>> {
-    // Validate file size
-    if request.file_size > state.config.max_file_size as i64 {
-        return Err(AppError::FileTooLarge);
-    }
+    ensure_accepting_uploads(&state)?;
 
-    if request.file_size <= 0 {
-        return Err(AppError::BadRequest("Invalid file size".to_string()));
-    }
+    let (chunk_size, total_chunks) = validate_upload_request(&state, &request)?;
+    file_type::check_upload(
+        &state.config,
+        &request.filename,
+        request.mime_type.as_deref(),
+    )?;
 
-    // Determine chunk size
-    let chunk_size = request.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
-    if chunk_size <= 0 || chunk_size > 50 * 1024 * 1024 {
-        return Err(AppError::BadRequest("Invalid chunk size".to_string()));
+    if let Some(slug) = &request.slug {
+        ensure_slug_available(&state, slug).await?;
     }
 
-    // Calculate total chunks
-    let total_chunks = ((request.file_size as f64) / (chunk_size as f64)).ceil() as i32;
+    // Chunks and completion belong to the session, so it is only needed here
+    check_upload_pow(&state, request.pow_challenge.as_deref(), request.pow_nonce)?;
 
     // Generate upload ID
     let upload_id = Uuid::new_v4();
+    record_upload_id(upload_id);
+
+    // The file's expiry is counted from completion, so the session keeps the hours
+    let expires_in_hours = effective_expires_in_hours(
+        &state,
+        request.expires_in_hours,
+        request.expire_after_inactivity_hours,
+    );
+
+    // A filename that will arrive encrypted isn't kept, even if the client sent all of it
+    let filename = if request.filename_encrypted {
+        filename_placeholder(&request.filename)
+    } else {
+        request.filename
+    };
 
     // Create upload session in database
     let _session = upload_session_ops::create_upload_session(
         &state.db,
         crate::database::CreateUploadSessionParams {
             upload_id,
-            filename: request.filename,
+            filename,
             mime_type: request.mime_type,
             total_size: request.file_size,
             total_chunks,
             chunk_size,
-            expires_in_hours: request.expires_in_hours,
+            expires_in_hours,
             max_downloads: request.max_downloads,
+            slug: request.slug,
+            burn_after_first_download_minutes: request.burn_after_first_download_minutes,
+            expire_after_inactivity_hours: request.expire_after_inactivity_hours,
+            // Larger files get longer to arrive
+            expires_at: chrono::Utc::now() + state.config.upload_session_ttl(request.file_size),
         },
     )
     .await?;
@@ -230,59 +1061,147 @@ pub async fn start_chunked_upload(
         upload_id,
         chunk_size,
         total_chunks,
+        expires_in_hours,
     }))
 }
 
-// Upload a chunk
-pub async fn upload_chunk(
+// Describe how an upload would be handled, without creating a session or storing data
+pub async fn plan_upload(
     State(state): State<AppState>,
-    Path((upload_id, chunk_number)): Path<(Uuid, i32)>,
-    mut multipart: Multipart,
-) -> Result<Json<ChunkUploadResponse>> {
-    // Get upload session
-    let session = upload_session_ops::get_upload_session_by_id(&state.db, upload_id)
-        .await?
-        .ok_or_else(|| AppError::BadRequest("Upload session not found".to_string()))?;
+    Json(request): Json<StartUploadRequest>,
+) -> Result<Json<UploadPlanResponse>> {
+    ensure_accepting_uploads(&state)?;
+
+    let (chunk_size, total_chunks) = validate_upload_request(&state, &request)?;
+    file_type::check_upload(
+        &state.config,
+        &request.filename,
+        request.mime_type.as_deref(),
+    )?;
+
+    if let Some(slug) = &request.slug {
+        ensure_slug_available(&state, slug).await?;
+    }
 
-    // Check if session has expired
-    if session.is_expired() {
-        return Err(AppError::BadRequest(
-            "Upload session has expired".to_string(),
+    let method = if request.file_size <= MAX_SINGLE_UPLOAD_SIZE {
+        UploadMethod::Single
+    } else {
+        UploadMethod::Chunked
+    };
+
+    let (chunk_size, total_chunks) = match method {
+        UploadMethod::Single => (None, None),
+        UploadMethod::Chunked => (Some(chunk_size), Some(total_chunks)),
+    };
+
+    let expires_at = expires_at_from_hours(effective_expires_in_hours(
+        &state,
+        request.expires_in_hours,
+        request.expire_after_inactivity_hours,
+    ));
+
+    Ok(Json(UploadPlanResponse {
+        method,
+        file_size: request.file_size,
+        chunk_size,
+        total_chunks,
+        expires_at,
+        max_downloads: request.max_downloads,
+        max_file_size: state.config.max_file_size as i64,
+    }))
+}
+
+// Check an upload request against the server limits, returning its chunk size and chunk count
+//
+// Every invalid field is reported at once, so clients can point at each of them.
+fn validate_upload_request(state: &AppState, request: &StartUploadRequest) -> Result<(i32, i32)> {
+    let mut errors = Vec::new();
+
+    if request.filename.trim().is_empty() {
+        errors.push(FieldError::new("filename", "must not be empty"));
+    }
+
+    if request.file_size <= 0 {
+        errors.push(FieldError::new("file_size", "must be greater than 0"));
+    }
+
+    // Determine chunk size, scaled with the file size unless the client picked one
+    let chunk_size = request
+        .chunk_size
+        .unwrap_or_else(|| recommended_chunk_size(request.file_size));
+    if chunk_size <= 0 || chunk_size > MAX_CHUNK_SIZE {
+        errors.push(FieldError::new(
+            "chunk_size",
+            format!("must be between 1 and {} bytes", MAX_CHUNK_SIZE),
         ));
     }
 
-    // Check if upload is already complete
-    if session.is_complete() {
-        return Err(AppError::BadRequest(
-            "Upload is already complete".to_string(),
+    if let Some(message) = expires_in_hours_error(state, request.expires_in_hours) {
+        errors.push(FieldError::new("expires_in_hours", message));
+    }
+
+    if let Some(message) = max_downloads_error(state, request.max_downloads) {
+        errors.push(FieldError::new("max_downloads", message));
+    }
+
+    if let Some(message) = burn_minutes_error(state, request.burn_after_first_download_minutes) {
+        errors.push(FieldError::new(
+            "burn_after_first_download_minutes",
+            message,
         ));
     }
 
-    // Validate chunk number
-    if chunk_number < 0 || chunk_number >= session.total_chunks {
-        return Err(AppError::BadRequest("Invalid chunk number".to_string()));
+    if let Some(message) = inactivity_hours_error(
+        state,
+        request.expire_after_inactivity_hours,
+        request.expires_in_hours,
+    ) {
+        errors.push(FieldError::new("expire_after_inactivity_hours", message));
     }
 
-    // Check if this chunk was already uploaded
-    if state
-        .chunk_storage
-        .chunk_exists(upload_id, chunk_number)
-        .await
-    {
-        tracing::warn!(
-            "Chunk {} for upload {} already exists, skipping",
-            chunk_number,
-            upload_id
-        );
+    if let Some(message) = request.slug.as_deref().and_then(slug_error) {
+        errors.push(FieldError::new("slug", message));
+    }
 
-        return Ok(Json(ChunkUploadResponse {
-            chunk_number,
-            uploaded_chunks: session.uploaded_chunks,
-            total_chunks: session.total_chunks,
-            progress: session.progress(),
-        }));
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    // Over quota keeps its own error, the same as for direct uploads
+    if request.file_size > state.config.max_file_size as i64 {
+        return Err(AppError::FileTooLarge);
+    }
+
+    // Calculate total chunks, bounded so a tiny chunk size can't create a
+    // session with millions of chunk files and counters to match
+    let chunk_size_bytes = i64::from(chunk_size);
+    let total_chunks = (request.file_size + chunk_size_bytes - 1) / chunk_size_bytes;
+    let max_chunks = i64::from(state.config.max_chunks);
+    if total_chunks > max_chunks {
+        return Err(AppError::BadRequest(format!(
+            "{} chunks of {} bytes is more than the limit of {} chunks, use a chunk_size of at least {} bytes",
+            total_chunks,
+            chunk_size,
+            max_chunks,
+            (request.file_size + max_chunks - 1) / max_chunks
+        )));
     }
 
+    Ok((chunk_size, total_chunks as i32))
+}
+
+// Upload a chunk
+pub async fn upload_chunk(
+    State(state): State<AppState>,
+    Path((upload_id, chunk_number)): Path<(Uuid, i32)>,
+    mut multipart: Multipart,
+) -> Result<Json<ChunkUploadResponse>> {
+    let (session, replaces_damaged) = match chunk_target(&state, upload_id, chunk_number).await? {
+        ChunkTarget::New(session) => (session, false),
+        ChunkTarget::Damaged(session) => (session, true),
+        ChunkTarget::Duplicate(response) => return Ok(Json(response)),
+    };
+
     // Extract chunk data from multipart
     let mut chunk_data: Option<Vec<u8>> = None;
 
@@ -307,15 +1226,141 @@ pub async fn upload_chunk(
     let chunk_data =
         chunk_data.ok_or_else(|| AppError::BadRequest("Missing chunk data".to_string()))?;
 
-    // Validate chunk size (last chunk can be smaller)
-    let expected_size = if chunk_number == session.total_chunks - 1 {
-        // Last chunk: calculate remaining bytes
-        let remaining = session.total_size - (chunk_number as i64 * session.chunk_size as i64);
-        remaining as usize
-    } else {
-        session.chunk_size as usize
+    store_chunk(
+        &state,
+        &session,
+        chunk_number,
+        &chunk_data,
+        replaces_damaged,
+    )
+    .await
+    .map(Json)
+}
+
+// Upload a chunk sent as the raw request body, skipping multipart parsing
+pub async fn upload_chunk_raw(
+    State(state): State<AppState>,
+    Path((upload_id, chunk_number)): Path<(Uuid, i32)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ChunkUploadResponse>> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream");
+    if !content_type.starts_with("application/octet-stream") {
+        return Err(AppError::BadRequest(
+            "Expected Content-Type: application/octet-stream".to_string(),
+        ));
+    }
+
+    let (session, replaces_damaged) = match chunk_target(&state, upload_id, chunk_number).await? {
+        ChunkTarget::New(session) => (session, false),
+        ChunkTarget::Damaged(session) => (session, true),
+        ChunkTarget::Duplicate(response) => return Ok(Json(response)),
     };
 
+    store_chunk(&state, &session, chunk_number, &body, replaces_damaged)
+        .await
+        .map(Json)
+}
+
+// Where an incoming chunk goes, once its session and number are checked
+enum ChunkTarget {
+    New(upload_session::Model),
+    // Stored, but not at the size expected for its position; already counted
+    Damaged(upload_session::Model),
+    // Already stored, e.g. a retry after a lost response
+    Duplicate(ChunkUploadResponse),
+}
+
+async fn chunk_target(state: &AppState, upload_id: Uuid, chunk_number: i32) -> Result<ChunkTarget> {
+    record_upload_id(upload_id);
+    ensure_accepting_uploads(state)?;
+
+    // Get upload session
+    let session = upload_session_ops::get_upload_session_by_id(&state.db, upload_id)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Upload session not found".to_string()))?;
+
+    // Check if session has expired
+    if session.is_expired() {
+        return Err(AppError::BadRequest(
+            "Upload session has expired".to_string(),
+        ));
+    }
+
+    // Validate chunk number
+    if chunk_number < 0 || chunk_number >= session.total_chunks {
+        return Err(AppError::BadRequest("Invalid chunk number".to_string()));
+    }
+
+    // Check if this chunk was already uploaded, and whether it's intact
+    match state
+        .chunk_storage
+        .get_chunk_size(upload_id, chunk_number)
+        .await
+    {
+        Ok(size) if size == session.chunk_len(chunk_number) as u64 => {
+            tracing::warn!(
+                "Chunk {} for upload {} already exists, skipping",
+                chunk_number,
+                upload_id
+            );
+
+            return Ok(ChunkTarget::Duplicate(ChunkUploadResponse {
+                chunk_number,
+                uploaded_chunks: session.uploaded_chunks,
+                total_chunks: session.total_chunks,
+                progress: session.progress(),
+            }));
+        }
+        // Replaced even once every chunk is counted, since the upload can't complete without it
+        Ok(size) => {
+            tracing::warn!(
+                "⚠️ Chunk {} for upload {} has {} bytes stored, replacing it",
+                chunk_number,
+                upload_id,
+                size
+            );
+            return Ok(ChunkTarget::Damaged(session));
+        }
+        Err(AppError::FileNotFound) => {}
+        Err(e) => return Err(e),
+    }
+
+    // Check if upload is already complete
+    if session.is_complete() {
+        return Err(AppError::BadRequest(
+            "Upload is already complete".to_string(),
+        ));
+    }
+
+    Ok(ChunkTarget::New(session))
+}
+
+async fn store_chunk(
+    state: &AppState,
+    session: &upload_session::Model,
+    chunk_number: i32,
+    chunk_data: &[u8],
+    replaces_damaged: bool,
+) -> Result<ChunkUploadResponse> {
+    let upload_id = session.upload_id;
+
+    // Held until the chunk is stored, bounding the files one upload has open
+    let _permit = state.chunk_writes.try_acquire(upload_id).ok_or_else(|| {
+        tracing::warn!(
+            "🚦 Too many chunks of upload {} being written, rejecting chunk {}",
+            upload_id,
+            chunk_number
+        );
+        AppError::TooManyChunkWrites(chunk_writes::CHUNK_WRITES_RETRY_AFTER_SECS)
+    })?;
+
+    // Validate chunk size (last chunk can be smaller)
+    let expected_size = session.chunk_len(chunk_number) as usize;
+
     if chunk_data.len() != expected_size {
         return Err(AppError::BadRequest(format!(
             "Invalid chunk size: expected {}, got {}",
@@ -324,15 +1369,40 @@ pub async fn upload_chunk(
         )));
     }
 
-    // Store chunk
-    state
-        .chunk_storage
-        .store_chunk(upload_id, chunk_number, &chunk_data)
-        .await?;
+    // A new chunk is only written if no concurrent request stored it first
+    let stored = if replaces_damaged {
+        state
+            .chunk_storage
+            .store_chunk(upload_id, chunk_number, chunk_data)
+            .await
+            .map(|()| true)
+    } else {
+        state
+            .chunk_storage
+            .store_new_chunk(upload_id, chunk_number, chunk_data)
+            .await
+    };
+    state.storage_health.record(&stored);
+    if !stored? {
+        tracing::warn!(
+            "Chunk {} for upload {} was stored by a concurrent request, keeping that copy",
+            chunk_number,
+            upload_id
+        );
+    }
 
-    // Update session (increment uploaded chunks)
-    let updated_session =
-        upload_session_ops::increment_uploaded_chunks(&state.db, upload_id).await?;
+    // Counted from storage rather than incremented, so a chunk stored twice
+    // never counts twice
+    let uploaded_chunks = state.chunk_storage.get_uploaded_chunks(upload_id).await?;
+    let updated_session = upload_session_ops::record_uploaded_chunks(
+        &state.db,
+        upload_id,
+        uploaded_chunks.len() as i32,
+    )
+    .await?;
+    state
+        .upload_rates
+        .record(upload_id, chunk_data.len() as u64);
 
     tracing::debug!(
         "📦 Uploaded chunk {}/{} for upload {} ({}/{} chunks complete)",
@@ -343,19 +1413,63 @@ pub async fn upload_chunk(
         updated_session.total_chunks
     );
 
-    Ok(Json(ChunkUploadResponse {
+    Ok(ChunkUploadResponse {
         chunk_number,
         uploaded_chunks: updated_session.uploaded_chunks,
         total_chunks: updated_session.total_chunks,
         progress: updated_session.progress(),
+    })
+}
+
+// Keep a slow upload's session open: its deadline moves to the configured TTL from
+// now, plus the time the missing chunks take at a slow rate. Never shortens it.
+pub async fn keepalive_upload(
+    State(state): State<AppState>,
+    Path(upload_id): Path<Uuid>,
+) -> Result<Json<UploadKeepaliveResponse>> {
+    record_upload_id(upload_id);
+
+    let session = upload_session_ops::get_upload_session_by_id(&state.db, upload_id)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Upload session not found".to_string()))?;
+
+    // An expired session may already have lost its chunks
+    if session.is_expired() {
+        return Err(AppError::BadRequest(
+            "Upload session has expired".to_string(),
+        ));
+    }
+
+    if session.is_complete() {
+        return Err(AppError::BadRequest(
+            "Upload is already complete".to_string(),
+        ));
+    }
+
+    let remaining_bytes =
+        session.total_size - i64::from(session.uploaded_chunks) * i64::from(session.chunk_size);
+    let expires_at = session
+        .expires_at
+        .max(chrono::Utc::now() + state.config.upload_session_ttl(remaining_bytes));
+    upload_session_ops::extend_upload_session(&state.db, upload_id, expires_at).await?;
+
+    tracing::debug!("⏳ Upload {} kept alive until {}", upload_id, expires_at);
+
+    Ok(Json(UploadKeepaliveResponse {
+        upload_id,
+        expires_at,
     }))
 }
 
 // Complete chunked upload
 pub async fn complete_chunked_upload(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<CompleteUploadRequest>,
 ) -> Result<Json<UploadResponse>> {
+    record_upload_id(request.upload_id);
+    ensure_accepting_uploads(&state)?;
+
     // Get upload session
     let session = upload_session_ops::get_upload_session_by_id(&state.db, request.upload_id)
         .await?
@@ -368,121 +1482,336 @@ pub async fn complete_chunked_upload(
         ));
     }
 
-    // Check if all chunks have been uploaded
-    if !session.is_complete() {
+    let (filename, filename_nonce) = completion_filename(&request, &session.filename)?;
+    let is_filename_encrypted = !filename_nonce.is_empty();
+    let signature = parse_signature(request.signature.as_deref())?;
+
+    // Check if all chunks have been uploaded, going by the chunks on disk rather than the counter
+    let uploaded_chunks = state
+        .chunk_storage
+        .get_uploaded_chunks(request.upload_id)
+        .await?
+        .into_iter()
+        .filter(|chunk_number| (0..session.total_chunks).contains(chunk_number))
+        .count() as i32;
+    if uploaded_chunks < session.total_chunks {
         return Err(AppError::BadRequest(format!(
             "Upload incomplete: {}/{} chunks uploaded",
-            session.uploaded_chunks, session.total_chunks
+            uploaded_chunks, session.total_chunks
         )));
     }
 
-    // Assemble chunks into complete file
-    let assembled_data = state
+    // Re-validate against the current limit in case the config changed since start
+    if session.total_size > state.config.max_file_size as i64 {
+        discard_upload(&state, request.upload_id).await;
+        return Err(AppError::FileTooLarge);
+    }
+
+    // The session reserved its slug, but never hand out one a live file holds
+    if let Some(slug) = &session.slug {
+        if file_ops::get_file_by_slug(&state.db, slug).await?.is_some() {
+            discard_upload(&state, request.upload_id).await;
+            return Err(AppError::Conflict(format!(
+                "Slug '{}' is already in use",
+                slug
+            )));
+        }
+    }
+
+    // The assembled file is held in memory; when the budget is used up the
+    // session is kept, so the client can complete it later
+    let _memory = reserve_upload_memory(&state, session.total_size as u64)?;
+
+    // Assemble chunks into complete file, never reading past the declared size
+    let assembled = match state
         .chunk_storage
-        .assemble_chunks(request.upload_id, session.total_chunks)
-        .await?;
+        .assemble_chunks(
+            request.upload_id,
+            session.total_chunks,
+            session.total_size as u64,
+        )
+        .await
+    {
+        Ok(assembled) => assembled,
+        Err(e) => {
+            discard_upload(&state, request.upload_id).await;
+            return Err(e);
+        }
+    };
+    let assembled_data = assembled.data;
 
     // Verify assembled file size matches expected size
     if assembled_data.len() != session.total_size as usize {
-        return Err(AppError::ServerError(format!(
+        discard_upload(&state, request.upload_id).await;
+        return Err(AppError::BadRequest(format!(
             "Assembled file size mismatch: expected {}, got {}",
             session.total_size,
             assembled_data.len()
         )));
     }
 
-    // Calculate expiration time
-    let expires_at = session
-        .expires_in_hours
-        .map(|hours| chrono::Utc::now() + chrono::Duration::hours(hours as i64));
+    // Scan the assembled file before it is stored
+    if let Err(e) = scanner::check_upload(&state.config, &assembled_data).await {
+        discard_upload(&state, request.upload_id).await;
+        return Err(e);
+    }
+
+    // Calculate expiration time (the session already holds any default expiry)
+    let expires_at = expires_at_from_hours(session.expires_in_hours);
 
-    // Generate file ID
+    // Generate file ID and owner token
     let file_id = Uuid::new_v4();
+    record_file_id(file_id);
+    let owner_token = Encryption::generate_key_string();
 
-    // Store unencrypted file to disk (chunked uploads are not encrypted)
-    let file_path = state.storage.store_file(file_id, &assembled_data).await?;
+    // Store unencrypted file (chunked uploads are not encrypted), inline when small enough
+    let (file_path, inline_data) = store_file_data(
+        &state,
+        file_id,
+        &ContainerHeader::plain(&assembled_data),
+        &assembled_data,
+    )
+    .await?;
 
-    // Store file metadata in database (unencrypted)
-    let _file_record = file_ops::create_unencrypted_file_record(
+    // Store file metadata in database (unencrypted); the slug may have been taken meanwhile
+    let created = file_ops::create_unencrypted_file_record(
         &state.db,
         crate::database::CreateUnencryptedFileParams {
             file_id,
             original_size: session.total_size,
             mime_type: session.mime_type,
-            file_path,
-            filename: session.filename.clone(),
+            file_path: file_path.clone(),
+            filename,
+            filename_nonce,
             expires_at,
             max_downloads: session.max_downloads,
+            owner_token: owner_token.clone(),
+            slug: session.slug.clone(),
+            signature,
+            content_sha256: Some(assembled.sha256.to_vec()),
+            inline_data,
+            burn_after_first_download_minutes: session.burn_after_first_download_minutes,
+            expire_after_inactivity_hours: session.expire_after_inactivity_hours,
         },
     )
-    .await?;
-
-    // Clean up chunks and upload session
-    if let Err(e) = state.chunk_storage.cleanup_upload(request.upload_id).await {
-        tracing::warn!(
-            "Failed to cleanup chunks for upload {}: {}",
-            request.upload_id,
-            e
-        );
+    .await;
+    if let Err(e) = created {
+        discard_file_data(&state, &file_path).await;
+        // The upload can't complete with a slug another file holds
+        if matches!(e, AppError::Conflict(_)) {
+            discard_upload(&state, request.upload_id).await;
+        }
+        return Err(e);
     }
 
-    if let Err(e) = upload_session_ops::delete_upload_session(&state.db, request.upload_id).await {
-        tracing::warn!(
-            "Failed to delete upload session {}: {}",
-            request.upload_id,
-            e
-        );
-    }
+    // Clean up chunks and upload session
+    discard_upload(&state, request.upload_id).await;
 
     // Generate download URL (no encryption key needed for chunked uploads)
-    let download_url = format!("{}/download/{}", state.config.base_url, file_id);
+    let urls = PublicUrls::for_request(&state.config, &headers);
+    let download_url = format!("{}/download/{}", urls.base_url, file_id);
 
     tracing::info!(
-        "✅ Completed chunked upload: {} -> {} ({} bytes unencrypted)",
+        "✅ Completed chunked upload: {} -> {} ({} bytes unencrypted{})",
         request.upload_id,
         file_id,
-        session.total_size
+        session.total_size,
+        if is_filename_encrypted {
+            ", encrypted filename"
+        } else {
+            ""
+        }
     );
 
-    let curl_command = format!(
-        "curl -X POST -JLO --fail -d \"encryption_key=\" {}/api/files/{}/download",
-        state.config.api_url, file_id
-    );
+    // Only the client knows the key of an encrypted filename, so it fills in the placeholder
+    let curl_command = if is_filename_encrypted {
+        format!(
+            "curl -X POST -JLO --fail -d 'encryption_key={}' {}/download",
+            KEY_PLACEHOLDER,
+            file_api_url(&urls, file_id, session.slug.as_deref())
+        )
+    } else {
+        format!(
+            "curl -X POST -JLO --fail -d \"encryption_key=\" {}/download",
+            file_api_url(&urls, file_id, session.slug.as_deref())
+        )
+    };
 
     Ok(Json(UploadResponse {
         file_id,
         download_url,
         encryption_key: None,
         curl_command,
+        owner_token: Some(owner_token),
+        slug: session.slug,
+        expires_at,
+        content_sha256: Some(hex_digest(&assembled.sha256)),
     }))
 }
 
-// Get upload status
+// The filename to store for a completed chunked upload, with its nonce
+//
+// Clients can encrypt the name with the file's key so it isn't kept in plain form;
+// otherwise the name given when the upload started is stored as-is.
+fn completion_filename(
+    request: &CompleteUploadRequest,
+    session_filename: &str,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    match (&request.encrypted_filename, &request.filename_nonce) {
+        (Some(encrypted_filename), Some(filename_nonce)) => {
+            decode_encrypted_filename(encrypted_filename, filename_nonce)
+        }
+        (None, None) => Ok((session_filename.as_bytes().to_vec(), Vec::new())),
+        _ => Err(AppError::BadRequest(
+            "encrypted_filename and filename_nonce must be sent together".to_string(),
+        )),
+    }
+}
+
+// Decode a filename the client encrypted, with its nonce, from base64
+fn decode_encrypted_filename(
+    encrypted_filename: &str,
+    filename_nonce: &str,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let decode = |value: &str| {
+        general_purpose::STANDARD
+            .decode(value)
+            .map_err(|_| AppError::BadRequest("Invalid encrypted filename encoding".to_string()))
+    };
+    let encrypted_filename = decode(encrypted_filename)?;
+    let filename_nonce = decode(filename_nonce)?;
+
+    if encrypted_filename.is_empty() || filename_nonce.len() != cipher::NONCE_SIZE {
+        return Err(AppError::BadRequest(
+            "Invalid encrypted filename".to_string(),
+        ));
+    }
+
+    Ok((encrypted_filename, filename_nonce))
+}
+
+// Decode an uploader's detached signature; the server can't check it without the
+// uploader's public key, so only its shape is validated
+fn parse_signature(signature: Option<&str>) -> Result<Option<Vec<u8>>> {
+    let Some(signature) = signature.filter(|signature| !signature.is_empty()) else {
+        return Ok(None);
+    };
+
+    kusatsu_encrypt::signing::signature_from_base64(signature)
+        .map(|signature| Some(signature.to_bytes().to_vec()))
+        .map_err(|_| {
+            AppError::BadRequest(format!(
+                "Invalid signature, expected {} base64-encoded bytes",
+                kusatsu_encrypt::signing::SIGNATURE_LENGTH
+            ))
+        })
+}
+
+// Remove the chunks and session of an upload
+async fn discard_upload(state: &AppState, upload_id: Uuid) {
+    state.upload_rates.remove(upload_id);
+
+    if let Err(e) = state.chunk_storage.cleanup_upload(upload_id).await {
+        tracing::warn!("Failed to cleanup chunks for upload {}: {}", upload_id, e);
+    }
+
+    if let Err(e) = upload_session_ops::delete_upload_session(&state.db, upload_id).await {
+        tracing::warn!("Failed to delete upload session {}: {}", upload_id, e);
+    }
+}
+
+// Get upload status; with `verify`, also check the stored size of every received chunk
 pub async fn get_upload_status(
     State(state): State<AppState>,
     Path(upload_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>> {
+    Query(query): Query<UploadStatusQuery>,
+) -> Result<Json<UploadStatusResponse>> {
+    record_upload_id(upload_id);
+
     // Get upload session
     let session = upload_session_ops::get_upload_session_by_id(&state.db, upload_id)
         .await?
         .ok_or_else(|| AppError::BadRequest("Upload session not found".to_string()))?;
 
-    // Get list of uploaded chunks
+    // Get list of uploaded chunks, which the progress is counted from
     let uploaded_chunk_numbers = state.chunk_storage.get_uploaded_chunks(upload_id).await?;
+    let session = upload_session::Model {
+        uploaded_chunks: uploaded_chunk_numbers.len() as i32,
+        ..session
+    };
+
+    // Throughput over the latest chunks, and the time the missing ones would take at it
+    let received_bytes: i64 = uploaded_chunk_numbers
+        .iter()
+        .map(|chunk_number| session.chunk_len(*chunk_number))
+        .sum();
+    let remaining_bytes = (session.total_size - received_bytes).max(0) as u64;
+    let estimate = state.upload_rates.estimate(upload_id, remaining_bytes);
+
+    let verified_chunks = if query.verify {
+        Some(verify_chunks(&state, &session, &uploaded_chunk_numbers).await?)
+    } else {
+        None
+    };
+
+    Ok(Json(UploadStatusResponse {
+        upload_id,
+        bytes_per_second: estimate.map(|estimate| estimate.bytes_per_second),
+        eta_seconds: estimate.map(|estimate| estimate.eta_seconds),
+        progress: session.progress(),
+        is_complete: session.is_complete(),
+        is_expired: session.is_expired(),
+        filename: session.filename,
+        total_size: session.total_size,
+        total_chunks: session.total_chunks,
+        chunk_size: session.chunk_size,
+        uploaded_chunks: session.uploaded_chunks,
+        uploaded_chunk_numbers,
+        created_at: session.created_at,
+        expires_at: session.expires_at,
+        verified_chunks,
+    }))
+}
+
+// Compare each listed chunk's stored size with the size expected at its position,
+// so a resuming client only sends the damaged ones again
+async fn verify_chunks(
+    state: &AppState,
+    session: &upload_session::Model,
+    chunk_numbers: &[i32],
+) -> Result<Vec<ChunkCheck>> {
+    let mut checks = Vec::with_capacity(chunk_numbers.len());
+    for &chunk_number in chunk_numbers {
+        let size = match state
+            .chunk_storage
+            .get_chunk_size(session.upload_id, chunk_number)
+            .await
+        {
+            Ok(size) => size,
+            // Removed since it was listed
+            Err(AppError::FileNotFound) => continue,
+            Err(e) => return Err(e),
+        };
+        let expected_size = session.chunk_len(chunk_number) as u64;
+        if size != expected_size {
+            tracing::warn!(
+                "⚠️ Chunk {} for upload {} has {} bytes stored, expected {}",
+                chunk_number,
+                session.upload_id,
+                size,
+                expected_size
+            );
+        }
+
+        checks.push(ChunkCheck {
+            chunk_number,
+            size,
+            expected_size,
+            is_valid: size == expected_size,
+        });
+    }
 
-    Ok(Json(serde_json::json!({
-        "upload_id": upload_id,
-        "filename": session.filename,
-        "total_size": session.total_size,
-        "total_chunks": session.total_chunks,
-        "uploaded_chunks": session.uploaded_chunks,
-        "uploaded_chunk_numbers": uploaded_chunk_numbers,
-        "progress": session.progress(),
-        "is_complete": session.is_complete(),
-        "is_expired": session.is_expired(),
-        "created_at": session.created_at,
-        "expires_at": session.expires_at
-    })))
+    Ok(checks)
 }
 
 // Form-based file download endpoint - accepts form data with encryption key and streams file download
@@ -496,80 +1825,69 @@ pub async fn download_file_form(
         .await?
         .ok_or(AppError::FileNotFound)?;
 
-    // Check if file is accessible
-    if !file.is_accessible() {
-        if file.is_expired() {
-            return Err(AppError::FileExpired);
-        } else if file.is_download_limit_reached() {
-            return Err(AppError::DownloadLimitExceeded);
-        }
-    }
-
-    // Check if file is encrypted (nonce is empty for unencrypted files)
-    let is_encrypted = !file.nonce.is_empty();
-
-    let (file_data, original_filename) = if is_encrypted {
-        // Handle encrypted file (direct upload)
-        let encryption_key = EncryptionKey::from_base64(&form_data.encryption_key)
-            .map_err(|_| AppError::BadRequest("Invalid encryption key".to_string()))?;
+    serve_download(&state, file, &form_data.encryption_key).await
+}
 
-        // Read encrypted file from disk
-        let encrypted_file_data_bytes = state.storage.retrieve_file(&file.file_path).await?;
+// JSON download endpoint - returns the decrypted file and its metadata in one body,
+// for scripts that would rather not parse Content-Disposition. Only for small files,
+// since the whole file is held in memory and grows by a third when base64-encoded.
+pub async fn download_file_json(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    Json(download_request): Json<DownloadRequest>,
+) -> Result<Json<JsonDownloadResponse>> {
+    record_file_id(file_id);
 
-        // Reconstruct encrypted data structures
-        let encrypted_file_data = kusatsu_encrypt::EncryptedData {
-            ciphertext: encrypted_file_data_bytes,
-            nonce: file.nonce.clone(),
-        };
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
 
-        let encrypted_filename_data = kusatsu_encrypt::EncryptedData {
-            ciphertext: file.encrypted_filename.clone(),
-            nonce: file.filename_nonce.clone(),
-        };
+    ensure_downloadable(&file)?;
+    if file.original_size > state.config.json_download_max_size as i64 {
+        return Err(AppError::JsonDownloadTooLarge(
+            state.config.json_download_max_size,
+        ));
+    }
 
-        // Decrypt the file content
-        let decrypted_data =
-            Encryption::decrypt(&encrypted_file_data, &encryption_key).map_err(|_| {
-                AppError::BadRequest("Failed to decrypt file - invalid encryption key".to_string())
-            })?;
-
-        // Decrypt the filename
-        let decrypted_filename_bytes =
-            Encryption::decrypt(&encrypted_filename_data, &encryption_key).map_err(|_| {
-                AppError::BadRequest(
-                    "Failed to decrypt filename - invalid encryption key".to_string(),
-                )
-            })?;
+    let (file_data, filename) = read_download(
+        &state,
+        &file,
+        download_request.encryption_key.as_deref().unwrap_or(""),
+    )
+    .await?;
 
-        let filename = String::from_utf8(decrypted_filename_bytes)
-            .map_err(|_| AppError::ServerError("Invalid filename encoding".to_string()))?;
+    // Count the download, checked again atomically in case another request took the last one
+    consume_download(&state, &file).await?;
 
-        (decrypted_data, filename)
-    } else {
-        // Handle unencrypted file (chunked upload)
-        if !form_data.encryption_key.is_empty() {
-            return Err(AppError::BadRequest(
-                "This file is unencrypted and does not require an encryption key".to_string(),
-            ));
-        }
+    tracing::info!(
+        "📥 File downloaded as JSON: {} ({} bytes, download #{})",
+        file_id,
+        file_data.len(),
+        file.download_count + 1
+    );
 
-        // Read unencrypted file from disk
-        let file_data = state.storage.retrieve_file(&file.file_path).await?;
+    Ok(Json(JsonDownloadResponse {
+        filename,
+        mime_type: file.mime_type,
+        size: file_data.len() as i64,
+        data_base64: general_purpose::STANDARD.encode(&file_data),
+    }))
+}
 
-        // Get plain filename (stored as bytes in encrypted_filename field)
-        let filename = String::from_utf8(file.encrypted_filename.clone())
-            .map_err(|_| AppError::ServerError("Invalid filename encoding".to_string()))?;
+// Decrypt (if needed) and stream a file, counting it as a download
+async fn serve_download(
+    state: &AppState,
+    file: file::Model,
+    encryption_key: &str,
+) -> Result<Response<Body>> {
+    let file_id = file.file_id;
+    record_file_id(file_id);
 
-        (file_data, filename)
-    };
+    let is_encrypted = file.is_encrypted;
+    let (file_data, original_filename) = read_download(state, &file, encryption_key).await?;
 
-    // Increment download count
-    file_ops::increment_download_count(&state.db, file_id).await?;
-
-    // Sanitize filename for Content-Disposition header
-    let sanitized_filename = original_filename
-        .replace('\"', "\\\"")
-        .replace(['\n', '\r'], " ");
+    // Count the download, checked again atomically in case another request took the last one
+    consume_download(state, &file).await?;
 
     // Build streaming response with proper headers for direct download
     let response = Response::builder()
@@ -582,7 +1900,7 @@ pub async fn download_file_form(
         )
         .header(
             "Content-Disposition",
-            format!("attachment; filename=\"{}\"", sanitized_filename),
+            content_disposition::attachment(&original_filename),
         )
         .header("Content-Length", file_data.len().to_string())
         .header("X-File-ID", file_id.to_string())
@@ -590,8 +1908,21 @@ pub async fn download_file_form(
         .header("Cache-Control", "no-cache, no-store, must-revalidate")
         .header("Pragma", "no-cache")
         .header("Expires", "0")
-        .header("Access-Control-Expose-Headers", "Content-Disposition")
-        .body(Body::from(file_data))
+        .header(
+            "Access-Control-Expose-Headers",
+            "Content-Disposition, X-Signature",
+        );
+
+    let response = match &file.signature {
+        Some(signature) => response.header(
+            SIGNATURE_HEADER,
+            general_purpose::STANDARD.encode(signature),
+        ),
+        None => response,
+    };
+
+    let response = response
+        .body(download_body(state, std::io::Cursor::new(file_data)))
         .map_err(|e| AppError::ServerError(format!("Failed to build streaming response: {}", e)))?;
 
     tracing::info!(
@@ -609,70 +1940,907 @@ pub async fn download_file_form(
     Ok(response)
 }
 
+// Check that a file can be downloaded with the given key, then read and decrypt it
+// along with its filename. The download isn't counted yet.
+async fn read_download(
+    state: &AppState,
+    file: &file::Model,
+    encryption_key: &str,
+) -> Result<(Vec<u8>, String)> {
+    ensure_downloadable(file)?;
+
+    let is_encrypted = file.is_encrypted;
+
+    let encryption_key = if requires_encryption_key(file) {
+        if encryption_key.is_empty() {
+            return Err(AppError::EncryptionKeyRequired);
+        }
+        Some(
+            EncryptionKey::from_base64(encryption_key)
+                .map_err(|_| AppError::InvalidEncryptionKey)?,
+        )
+    } else {
+        if !encryption_key.is_empty() {
+            return Err(AppError::BadRequest(
+                "This file is unencrypted and does not require an encryption key".to_string(),
+            ));
+        }
+        None
+    };
+
+    // Read the stored file
+    let stored_data = read_file_data(state, file).await?;
+
+    let file_data = match &encryption_key {
+        // Handle encrypted file (direct upload)
+        Some(encryption_key) if is_encrypted => {
+            let encrypted_file_data = kusatsu_encrypt::EncryptedData {
+                ciphertext: stored_data,
+                nonce: file.nonce.clone(),
+                key_size: encryption_key.size(),
+            };
+
+            Encryption::decrypt(&encrypted_file_data, encryption_key)
+                .map_err(|_| AppError::InvalidEncryptionKey)?
+        }
+        // Handle unencrypted file (chunked upload)
+        _ => stored_data,
+    };
+
+    // The client compressed the contents before encrypting them
+    // The decompressed contents count against the memory budget while they are built
+    let file_data = match &file.compression {
+        Some(format) => {
+            let _reservation = reserve_upload_memory(state, file.original_size as u64)?;
+            compression::decompress(
+                format,
+                &file_data,
+                file.original_size as u64,
+                state.config.max_compression_ratio,
+            )?
+        }
+        None => file_data,
+    };
+
+    let original_filename = decrypt_filename(file, encryption_key.as_ref())?;
+
+    Ok((file_data, original_filename))
+}
+
+// Refuse files that expired or reached their download limit
+fn ensure_downloadable(file: &file::Model) -> Result<()> {
+    if !file.is_accessible() {
+        if file.is_expired() {
+            return Err(AppError::FileExpired);
+        } else if file.is_download_limit_reached() {
+            return Err(AppError::DownloadLimitExceeded);
+        }
+    }
+
+    Ok(())
+}
+
+// Count a download if the file still allows one
+//
+// With KUSATSU_DELETE_ON_LIMIT_REACHED, the file is deleted once its last allowed
+// download is counted. That happens in a background task, so the download in
+// progress still completes (it already holds the data or an open file handle).
+async fn consume_download(state: &AppState, file: &file::Model) -> Result<()> {
+    // Counted or refused, the cached record is out of date either way
+    state.file_info_cache.invalidate(file.file_id);
+
+    // A file that expires after inactivity gets its full period back
+    let renewed_expires_at = file.expiry_after_download(
+        chrono::Utc::now(),
+        chrono::Duration::hours(state.config.max_expires_in_hours),
+    );
+    if !file_ops::try_consume_download(&state.db, file.file_id, renewed_expires_at).await? {
+        return Err(if file.is_expired() {
+            AppError::FileExpired
+        } else {
+            AppError::DownloadLimitExceeded
+        });
+    }
+
+    if file.first_downloaded_at.is_none() {
+        // The burn deadline is weighed against the renewed expiry, not the old one
+        let file = file::Model {
+            expires_at: renewed_expires_at.or(file.expires_at),
+            ..file.clone()
+        };
+        file_ops::record_first_download(&state.db, &file).await?;
+    }
+
+    if state.config.delete_on_limit_reached && file.max_downloads.is_some() {
+        let state = state.clone();
+        let file_id = file.file_id;
+        tokio::spawn(async move {
+            if let Err(e) = delete_if_limit_reached(&state, file_id).await {
+                tracing::warn!("Failed to delete fully downloaded file {}: {}", file_id, e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// Re-read the count, since concurrent downloads may have been counted in between
+async fn delete_if_limit_reached(state: &AppState, file_id: Uuid) -> Result<()> {
+    let Some(file) = file_ops::get_file_by_id(&state.db, file_id).await? else {
+        return Ok(());
+    };
+
+    if file.is_download_limit_reached()
+        && file_ops::delete_file_by_id(&state.db, state.storage.as_ref(), file_id).await?
+    {
+        state.file_info_cache.invalidate(file_id);
+        tracing::info!(
+            "🗑️ Deleted file {} after its last allowed download",
+            file_id
+        );
+    }
+
+    Ok(())
+}
+
+// Body of a download, paced to the configured rate limit if any
+fn download_body<R>(state: &AppState, reader: R) -> Body
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    match state.config.download_rate_limit {
+        Some(bytes_per_sec) => Body::from_stream(ReaderStream::new(ThrottledReader::new(
+            reader,
+            bytes_per_sec,
+        ))),
+        None => Body::from_stream(ReaderStream::new(reader)),
+    }
+}
+
+// Raw download endpoint - streams the stored bytes so the client can decrypt them
+//
+// The key never reaches the server in this flow: for encrypted files the nonces
+// and the encrypted filename are returned base64-encoded in response headers.
+pub async fn download_raw(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+) -> Result<Response<Body>> {
+    record_file_id(file_id);
+
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    if file.is_expired() {
+        return Err(AppError::FileExpired);
+    } else if file.is_download_limit_reached() {
+        return Err(AppError::DownloadLimitExceeded);
+    }
+
+    // Open the file before counting the download, so a missing blob doesn't use one up
+    let (stored_file, size) = open_file_data(&state, &file).await?;
+
+    // Checked again atomically, in case another request took the last download
+    consume_download(&state, &file).await?;
+
+    let is_encrypted = file.is_encrypted;
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/octet-stream")
+        .header("Content-Length", size.to_string())
+        .header("X-File-ID", file_id.to_string())
+        .header("X-Original-Size", file.original_size.to_string())
+        .header("X-Encrypted", is_encrypted.to_string())
+        .header("Cache-Control", "no-cache, no-store, must-revalidate")
+        .header(
+            "Access-Control-Expose-Headers",
+            "Content-Disposition, X-File-ID, X-Original-Size, X-Encrypted, X-Mime-Type, \
+             X-Content-Nonce, X-Filename-Ciphertext, X-Filename-Nonce, X-Signature, \
+             X-Compression",
+        );
+
+    if let Some(signature) = &file.signature {
+        response = response.header(
+            SIGNATURE_HEADER,
+            general_purpose::STANDARD.encode(signature),
+        );
+    }
+
+    if let Some(mime_type) = &file.mime_type {
+        response = response.header("X-Mime-Type", mime_type);
+    }
+
+    // Decompressing is up to the client too, after it decrypts
+    if let Some(format) = &file.compression {
+        response = response.header(COMPRESSION_HEADER, format);
+    }
+
+    if is_encrypted {
+        response = response.header(
+            "X-Content-Nonce",
+            general_purpose::STANDARD.encode(&file.nonce),
+        );
+    }
+
+    // Chunked uploads can have an encrypted filename even though their contents are plain
+    if !file.filename_nonce.is_empty() {
+        response = response
+            .header(
+                "X-Filename-Ciphertext",
+                general_purpose::STANDARD.encode(&file.encrypted_filename),
+            )
+            .header(
+                "X-Filename-Nonce",
+                general_purpose::STANDARD.encode(&file.filename_nonce),
+            );
+    } else {
+        // Get plain filename (stored as bytes in encrypted_filename field)
+        let filename = String::from_utf8(file.encrypted_filename.clone())
+            .map_err(|_| AppError::ServerError("Invalid filename encoding".to_string()))?;
+        response = response.header(
+            "Content-Disposition",
+            content_disposition::attachment(&filename),
+        );
+    }
+
+    let response = response
+        .body(download_body(&state, stored_file))
+        .map_err(|e| AppError::ServerError(format!("Failed to build streaming response: {}", e)))?;
+
+    tracing::info!(
+        "📥 Raw file download {}: {} (download #{})",
+        if is_encrypted {
+            "for client-side decryption"
+        } else {
+            "(unencrypted)"
+        },
+        file_id,
+        file.download_count + 1
+    );
+
+    Ok(response)
+}
+
+// Find the file currently published under a slug
+async fn resolve_slug(state: &AppState, slug: &str) -> Result<Uuid> {
+    file_ops::get_file_by_slug(&state.db, slug)
+        .await?
+        .map(|file| file.file_id)
+        .ok_or(AppError::FileNotFound)
+}
+
+// Slug-addressed variants of the file endpoints
+pub async fn get_file_info_by_slug(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    request: Json<DownloadRequest>,
+) -> Result<Json<FileInfo>> {
+    let file_id = resolve_slug(&state, &slug).await?;
+    get_file_info(State(state), Path(file_id), request).await
+}
+
+pub async fn download_file_form_by_slug(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    form_data: Form<DownloadFormData>,
+) -> Result<impl IntoResponse> {
+    let file_id = resolve_slug(&state, &slug).await?;
+    download_file_form(State(state), Path(file_id), form_data).await
+}
+
+pub async fn download_raw_by_slug(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Response<Body>> {
+    let file_id = resolve_slug(&state, &slug).await?;
+    download_raw(State(state), Path(file_id)).await
+}
+
+pub async fn get_file_preview_by_slug(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Json<FilePreviewResponse>> {
+    let file_id = resolve_slug(&state, &slug).await?;
+    get_file_preview(State(state), Path(file_id)).await
+}
+
+pub async fn file_exists_by_slug(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Response<Body>> {
+    let file_id = resolve_slug(&state, &slug).await?;
+    file_exists(State(state), Path(file_id)).await
+}
+
+// Existence check (HEAD) - only reveals whether the file exists and is encrypted,
+// never its name or size, and doesn't count as a download
+pub async fn file_exists(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+) -> Result<Response<Body>> {
+    record_file_id(file_id);
+
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    let status = if file.is_accessible() {
+        StatusCode::OK
+    } else {
+        StatusCode::GONE
+    };
+
+    Response::builder()
+        .status(status)
+        .header("X-File-Exists", "true")
+        .header("X-File-Encrypted", file.is_encrypted.to_string())
+        .header("Cache-Control", "no-cache, no-store, must-revalidate")
+        .header(
+            "Access-Control-Expose-Headers",
+            "X-File-Exists, X-File-Encrypted",
+        )
+        .body(Body::empty())
+        .map_err(|e| AppError::ServerError(format!("Failed to build response: {}", e)))
+}
+
 // Get file info endpoint - returns decrypted filename
 pub async fn get_file_info(
     State(state): State<AppState>,
     Path(file_id): Path<Uuid>,
     Json(download_request): Json<DownloadRequest>,
 ) -> Result<Json<FileInfo>> {
+    record_file_id(file_id);
     tracing::info!("Getting file info for file: {}", file_id);
 
+    // Popular links are served from the cache; the filename is still decrypted
+    // for each request, as only the key it brings can do that
+    let file = match state.file_info_cache.get(file_id) {
+        Some(file) => file,
+        None => {
+            let file = file_ops::get_file_by_id(&state.db, file_id)
+                .await?
+                .ok_or(AppError::FileNotFound)?;
+            state.file_info_cache.insert(&file);
+            file
+        }
+    };
+
+    tracing::info!("File found: {}", file.file_id);
+
+    // Links that stopped working say why, with a code clients can branch on
+    ensure_downloadable(&file)?;
+
+    build_file_info(
+        file,
+        download_request.encryption_key.as_deref(),
+        state.config.size_padding,
+    )
+    .map(Json)
+}
+
+// File preview endpoint - returns the stored bytes of small files so the client can
+// decrypt and render them inline; does not count as a download
+pub async fn get_file_preview(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+) -> Result<Json<FilePreviewResponse>> {
+    record_file_id(file_id);
+
     // Get file from database
     let file = file_ops::get_file_by_id(&state.db, file_id)
         .await?
         .ok_or(AppError::FileNotFound)?;
 
-    tracing::info!("File found: {:?}", file);
+    // Check if file is accessible
+    if file.is_expired() {
+        return Err(AppError::FileExpired);
+    } else if file.is_download_limit_reached() {
+        return Err(AppError::DownloadLimitExceeded);
+    }
+
+    // A preview of a small file is the whole file, so it would bypass the download limit
+    if file.max_downloads.is_some() {
+        return Err(AppError::BadRequest(
+            "Preview is not available for files with a download limit".to_string(),
+        ));
+    }
+
+    // Nor would it start the burn timer, so such a file could be read forever
+    if file.burn_after_first_download_minutes.is_some() {
+        return Err(AppError::BadRequest(
+            "Preview is not available for files that burn after their first download".to_string(),
+        ));
+    }
 
-    // Check if file is encrypted (nonce is empty for unencrypted files)
-    let is_encrypted = !file.nonce.is_empty();
+    if file.encrypted_size > MAX_PREVIEW_SIZE {
+        return Err(AppError::BadRequest(
+            "File is too large to preview".to_string(),
+        ));
+    }
 
-    tracing::info!("File is encrypted: {}", is_encrypted);
+    // Previews are decrypted but not decompressed by the client
+    if file.compression.is_some() {
+        return Err(AppError::BadRequest(
+            "Preview is not available for compressed files".to_string(),
+        ));
+    }
 
-    let decrypted_filename = if is_encrypted {
-        tracing::info!("Decrypting filename for encrypted file {}", file_id);
-        // Handle encrypted file (direct upload)
-        let encryption_key_str = download_request.encryption_key.as_ref().ok_or_else(|| {
-            AppError::BadRequest("Encryption key required for encrypted file".to_string())
+    let data = read_file_data(&state, &file).await?;
+
+    Ok(Json(FilePreviewResponse {
+        data: general_purpose::STANDARD.encode(data),
+        nonce: general_purpose::STANDARD.encode(&file.nonce),
+        is_encrypted: file.is_encrypted,
+    }))
+}
+
+// Extend file expiry endpoint - requires the owner token issued at upload time
+pub async fn extend_file_expiry(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    Json(request): Json<ExtendExpiryRequest>,
+) -> Result<Json<FileInfo>> {
+    record_file_id(file_id);
+
+    // Get file from database
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    // Check ownership
+    ensure_owner(&file, &request.owner_token)?;
+
+    // A file that has already lapsed cannot be brought back
+    if file.is_expired() {
+        return Err(AppError::FileExpired);
+    }
+
+    // Nor can one whose burn time is running
+    if file.burns_at().is_some() {
+        return Err(AppError::BadRequest(
+            "File burns after its first download, which already happened, so its expiry cannot be extended"
+                .to_string(),
+        ));
+    }
+
+    let now = chrono::Utc::now();
+
+    // Resolve the requested expiry
+    let new_expires_at = match (request.expires_in_hours, request.expires_at) {
+        (Some(hours), None) if hours > 0 => now + chrono::Duration::hours(hours as i64),
+        (None, Some(expires_at)) => expires_at,
+        (Some(_), None) => {
+            return Err(AppError::BadRequest("Invalid expires_in_hours".to_string()));
+        }
+        _ => {
+            return Err(AppError::BadRequest(
+                "Provide exactly one of expires_in_hours or expires_at".to_string(),
+            ));
+        }
+    };
+
+    // Only allow pushing the expiry further out
+    let current_expires_at = file.expires_at.ok_or_else(|| {
+        AppError::BadRequest("File has no expiry and cannot be extended".to_string())
+    })?;
+
+    if new_expires_at <= current_expires_at {
+        return Err(AppError::BadRequest(
+            "New expiry must be later than the current expiry".to_string(),
+        ));
+    }
+
+    // Enforce the maximum lifetime policy
+    if new_expires_at > now + chrono::Duration::hours(state.config.max_expires_in_hours) {
+        return Err(AppError::BadRequest(format!(
+            "Expiry cannot be more than {} hours from now",
+            state.config.max_expires_in_hours
+        )));
+    }
+
+    // Built first, so a missing or wrong key leaves the expiry as it was
+    let file_info = build_file_info(
+        file::Model {
+            expires_at: Some(new_expires_at),
+            ..file.clone()
+        },
+        request.encryption_key.as_deref(),
+        state.config.size_padding,
+    )?;
+
+    file_ops::update_expires_at(&state.db, file_id, current_expires_at, new_expires_at).await?;
+    state.file_info_cache.invalidate(file_id);
+
+    tracing::info!(
+        "⏳ Extended expiry of file {}: {} -> {}",
+        file_id,
+        current_expires_at,
+        new_expires_at
+    );
+
+    Ok(Json(file_info))
+}
+
+// Give a file a new ID (owner only), for a link that was shared too widely. The
+// stored data and its encryption are untouched, so the key stays valid; links
+// and signed URLs with the old ID stop working, while a slug keeps pointing at
+// the file.
+pub async fn rotate_file_id(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<RotateFileRequest>,
+) -> Result<Json<RotateFileResponse>> {
+    record_file_id(file_id);
+
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    ensure_owner(&file, &request.owner_token)?;
+
+    if file.is_expired() {
+        return Err(AppError::FileExpired);
+    }
+
+    let new_file_id = Uuid::new_v4();
+    file_ops::rotate_file_id(&state.db, file_id, new_file_id).await?;
+    state.file_info_cache.invalidate(file_id);
+
+    tracing::info!("🔄 Rotated file ID: {} -> {}", file_id, new_file_id);
+
+    Ok(Json(RotateFileResponse {
+        file_id: new_file_id,
+        download_url: format!(
+            "{}/download/{}",
+            PublicUrls::for_request(&state.config, &headers).base_url,
+            new_file_id
+        ),
+    }))
+}
+
+// Delete a file and its stored data (owner only)
+pub async fn delete_file(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    Json(request): Json<DeleteFileRequest>,
+) -> Result<StatusCode> {
+    record_file_id(file_id);
+
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    ensure_owner(&file, &request.owner_token)?;
+
+    // Expired files can be removed too, ahead of the cleanup job
+    file_ops::delete_file_by_id(&state.db, state.storage.as_ref(), file_id).await?;
+    state.file_info_cache.invalidate(file_id);
+
+    tracing::info!("🗑️ Deleted file {} at its owner's request", file_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Store the file key wrapped with a passphrase (owner only); the server never sees
+// the passphrase or the key, only the wrapped form
+pub async fn set_wrapped_key(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    Json(request): Json<WrapKeyRequest>,
+) -> Result<Json<WrappedKeyResponse>> {
+    record_file_id(file_id);
+
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    ensure_owner(&file, &request.owner_token)?;
+
+    if file.is_expired() {
+        return Err(AppError::FileExpired);
+    }
+
+    // Without encrypted content or filename there is no key to protect
+    if !file.is_encrypted && file.filename_nonce.is_empty() {
+        return Err(AppError::BadRequest(
+            "File is not encrypted, there is no key to wrap".to_string(),
+        ));
+    }
+
+    let wrapped_key = general_purpose::STANDARD
+        .decode(&request.wrapped_key)
+        .ok()
+        .filter(|wrapped_key| kusatsu_encrypt::WrappedKey::from_bytes(wrapped_key).is_ok())
+        .ok_or_else(|| AppError::BadRequest("Invalid wrapped_key encoding".to_string()))?;
+    let key_salt = general_purpose::STANDARD
+        .decode(&request.key_salt)
+        .ok()
+        .filter(|key_salt| key_salt.len() >= MIN_KEY_SALT_LENGTH)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "key_salt must be at least {} base64-encoded bytes",
+                MIN_KEY_SALT_LENGTH
+            ))
         })?;
 
-        let encryption_key = EncryptionKey::from_url_encoded(encryption_key_str)
-            .map_err(|_| AppError::BadRequest("Invalid encryption key".to_string()))?;
+    file_ops::set_wrapped_key(&state.db, file_id, wrapped_key, key_salt).await?;
+
+    tracing::info!("🔒 Stored wrapped key for file {}", file_id);
+
+    Ok(Json(WrappedKeyResponse {
+        wrapped_key: request.wrapped_key,
+        key_salt: request.key_salt,
+    }))
+}
+
+// Passphrase-wrapped key of a file, for links shared without the key; 404 when the
+// key isn't wrapped. Does not count as a download.
+pub async fn get_wrapped_key(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+) -> Result<Json<WrappedKeyResponse>> {
+    record_file_id(file_id);
+
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    if file.is_expired() {
+        return Err(AppError::FileExpired);
+    } else if file.is_download_limit_reached() {
+        return Err(AppError::DownloadLimitExceeded);
+    }
+
+    match (&file.wrapped_key, &file.key_salt) {
+        (Some(wrapped_key), Some(key_salt)) => Ok(Json(WrappedKeyResponse {
+            wrapped_key: general_purpose::STANDARD.encode(wrapped_key),
+            key_salt: general_purpose::STANDARD.encode(key_salt),
+        })),
+        _ => Err(AppError::FileNotFound),
+    }
+}
+
+// Attach the encrypted listing of a bundle's contents (owner only); like the
+// filename, it is encrypted with the file key and the server can't read it
+pub async fn set_listing(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    Json(request): Json<ListingRequest>,
+) -> Result<Json<ListingResponse>> {
+    record_file_id(file_id);
+
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    ensure_owner(&file, &request.owner_token)?;
+
+    if file.is_expired() {
+        return Err(AppError::FileExpired);
+    }
+
+    // Without a file key, a listing could only be stored in the clear
+    if !file.is_encrypted && file.filename_nonce.is_empty() {
+        return Err(AppError::BadRequest(
+            "File is not encrypted, there is no key to encrypt a listing with".to_string(),
+        ));
+    }
+
+    let nonce = general_purpose::STANDARD
+        .decode(&request.nonce)
+        .ok()
+        .filter(|nonce| nonce.len() == cipher::NONCE_SIZE)
+        .ok_or_else(|| AppError::BadRequest("Invalid nonce encoding".to_string()))?;
+    let ciphertext = general_purpose::STANDARD
+        .decode(&request.ciphertext)
+        .map_err(|_| AppError::BadRequest("Invalid ciphertext encoding".to_string()))?;
+    if ciphertext.len() > MAX_LISTING_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "Listing is too large, the limit is {} bytes",
+            MAX_LISTING_SIZE
+        )));
+    }
+
+    let listing = [nonce, ciphertext].concat();
+    file_ops::set_listing(&state.db, file_id, listing).await?;
+    state.file_info_cache.invalidate(file_id);
+
+    tracing::info!("📋 Stored bundle listing for file {}", file_id);
+
+    Ok(Json(ListingResponse {
+        ciphertext: request.ciphertext,
+        nonce: request.nonce,
+    }))
+}
+
+// Encrypted listing of a bundle's contents, to show before downloading it; 404 when
+// none was attached. Does not count as a download.
+pub async fn get_listing(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+) -> Result<Json<ListingResponse>> {
+    record_file_id(file_id);
+
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    if file.is_expired() {
+        return Err(AppError::FileExpired);
+    } else if file.is_download_limit_reached() {
+        return Err(AppError::DownloadLimitExceeded);
+    }
+
+    let listing = file.listing.ok_or(AppError::FileNotFound)?;
+    let (nonce, ciphertext) = listing.split_at(cipher::NONCE_SIZE.min(listing.len()));
+
+    Ok(Json(ListingResponse {
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        nonce: general_purpose::STANDARD.encode(nonce),
+    }))
+}
+
+// Create a time-limited signed download URL (owner only)
+pub async fn sign_download(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<SignDownloadRequest>,
+) -> Result<Json<SignDownloadResponse>> {
+    record_file_id(file_id);
+
+    let secret =
+        state.config.signing_secret.as_deref().ok_or_else(|| {
+            AppError::BadRequest("Signed download URLs are not enabled".to_string())
+        })?;
+
+    // Get file from database
+    let file = file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)?;
+
+    // Check ownership
+    ensure_owner(&file, &request.owner_token)?;
+
+    if file.is_expired() {
+        return Err(AppError::FileExpired);
+    }
+
+    // Enforce the same upper bound as file expiry
+    if request.expires_in_seconds <= 0
+        || request.expires_in_seconds > state.config.max_expires_in_hours * 3600
+    {
+        return Err(AppError::BadRequest(
+            "Invalid expires_in_seconds".to_string(),
+        ));
+    }
 
-        // Decrypt the filename
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(request.expires_in_seconds);
+    let expires = expires_at.timestamp();
+    let signature = signing::sign(secret, file_id, expires);
+
+    let url = format!(
+        "{}/api/files/{}/signed-download?expires={}&signature={}",
+        PublicUrls::for_request(&state.config, &headers).api_url,
+        file_id,
+        expires,
+        signature
+    );
+
+    tracing::info!(
+        "🔏 Signed download URL for {} until {}",
+        file_id,
+        expires_at
+    );
+
+    Ok(Json(SignDownloadResponse { url, expires_at }))
+}
+
+// Download through a signed URL (unencrypted files, e.g. for embedding)
+pub async fn signed_download(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<SignedDownloadQuery>,
+) -> Result<impl IntoResponse> {
+    let file = verify_signed_download(&state, file_id, &query).await?;
+    serve_download(&state, file, "").await
+}
+
+// Download through a signed URL, with the encryption key posted separately
+pub async fn signed_download_form(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<SignedDownloadQuery>,
+    Form(form_data): Form<DownloadFormData>,
+) -> Result<impl IntoResponse> {
+    let file = verify_signed_download(&state, file_id, &query).await?;
+    serve_download(&state, file, &form_data.encryption_key).await
+}
+
+async fn verify_signed_download(
+    state: &AppState,
+    file_id: Uuid,
+    query: &SignedDownloadQuery,
+) -> Result<file::Model> {
+    let secret = state
+        .config
+        .signing_secret
+        .as_deref()
+        .ok_or(AppError::FileNotFound)?;
+
+    signing::verify(secret, file_id, query.expires, &query.signature)?;
+
+    file_ops::get_file_by_id(&state.db, file_id)
+        .await?
+        .ok_or(AppError::FileNotFound)
+}
+
+// Build file info for a file record, decrypting the filename when needed
+// Files with encrypted contents or a client-encrypted filename need the key
+fn requires_encryption_key(file: &file::Model) -> bool {
+    file.is_encrypted || !file.filename_nonce.is_empty()
+}
+
+// Recover the original filename, decrypting it if it was stored encrypted
+fn decrypt_filename(file: &file::Model, encryption_key: Option<&EncryptionKey>) -> Result<String> {
+    // Plain filenames are stored as bytes in the encrypted_filename field with an empty nonce
+    let filename_bytes = if file.filename_nonce.is_empty() {
+        file.encrypted_filename.clone()
+    } else {
+        let encryption_key = encryption_key.ok_or(AppError::EncryptionKeyRequired)?;
         let encrypted_filename_data = kusatsu_encrypt::EncryptedData {
-            ciphertext: file.encrypted_filename,
-            nonce: file.filename_nonce,
+            ciphertext: file.encrypted_filename.clone(),
+            nonce: file.filename_nonce.clone(),
+            key_size: encryption_key.size(),
         };
 
-        let decrypted_filename_bytes =
-            Encryption::decrypt(&encrypted_filename_data, &encryption_key).map_err(|_| {
-                AppError::BadRequest(
-                    "Failed to decrypt filename - invalid encryption key".to_string(),
-                )
-            })?;
+        Encryption::decrypt(&encrypted_filename_data, encryption_key)
+            .map_err(|_| AppError::InvalidEncryptionKey)?
+    };
 
-        String::from_utf8(decrypted_filename_bytes)
-            .map_err(|_| AppError::ServerError("Invalid filename encoding".to_string()))?
-    } else {
-        tracing::info!("Decrypting filename for unencrypted file {}", file_id);
+    String::from_utf8(filename_bytes)
+        .map_err(|_| AppError::ServerError("Invalid filename encoding".to_string()))
+}
 
-        // Handle unencrypted file (chunked upload)
-        if download_request.encryption_key.is_some() {
+fn build_file_info(
+    file: file::Model,
+    encryption_key: Option<&str>,
+    size_padding: Padding,
+) -> Result<FileInfo> {
+    let is_encrypted = file.is_encrypted;
+    let sizes_padded = size_padding != Padding::None;
+    let size = |size: i64| {
+        if sizes_padded {
+            padded_size(size, size_padding)
+        } else {
+            size
+        }
+    };
+
+    let encryption_key = if requires_encryption_key(&file) {
+        let encryption_key_str = encryption_key.ok_or(AppError::EncryptionKeyRequired)?;
+
+        Some(
+            EncryptionKey::from_url_encoded(encryption_key_str)
+                .map_err(|_| AppError::InvalidEncryptionKey)?,
+        )
+    } else {
+        if encryption_key.is_some() {
             return Err(AppError::BadRequest(
                 "This file is unencrypted and does not require an encryption key".to_string(),
             ));
         }
-
-        // Get plain filename (stored as bytes in encrypted_filename field)
-        String::from_utf8(file.encrypted_filename)
-            .map_err(|_| AppError::ServerError("Invalid filename encoding".to_string()))?
+        None
     };
 
-    Ok(Json(FileInfo {
+    let decrypted_filename = decrypt_filename(&file, encryption_key.as_ref())?;
+
+    Ok(FileInfo {
         file_id: file.file_id,
-        original_size: file.original_size,
-        encrypted_size: file.encrypted_size,
+        original_size: size(file.original_size),
+        encrypted_size: size(file.encrypted_size),
         mime_type: file.mime_type,
         created_at: file.created_at,
         expires_at: file.expires_at,
@@ -680,14 +2848,41 @@ pub async fn get_file_info(
         max_downloads: file.max_downloads,
         filename: decrypted_filename,
         is_encrypted,
-    }))
+        signature: file
+            .signature
+            .as_ref()
+            .map(|signature| general_purpose::STANDARD.encode(signature)),
+        content_sha256: file.content_sha256.as_deref().map(hex_digest),
+        compression: file.compression,
+        burn_after_first_download_minutes: file.burn_after_first_download_minutes,
+        first_downloaded_at: file.first_downloaded_at,
+        expire_after_inactivity_hours: file.expire_after_inactivity_hours,
+        has_listing: file.listing.is_some(),
+        sizes_padded,
+    })
+}
+
+// A size rounded up as the padding would pad it, so it can be reported without
+// telling the exact size; sizes too large to pad are reported as the maximum
+fn padded_size(size: i64, padding: Padding) -> i64 {
+    usize::try_from(size)
+        .ok()
+        .and_then(|size| padding.padded_len(size).ok())
+        .and_then(|size| i64::try_from(size).ok())
+        .unwrap_or(i64::MAX)
+}
+
+// Lowercase hex form of a digest, as `sha256sum` prints it
+fn hex_digest(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
 }
 
 // Cleanup expired files endpoint
 pub async fn cleanup_expired_files(State(state): State<AppState>) -> Result<Json<CleanupResponse>> {
     tracing::info!("🧹 Starting cleanup of expired files");
 
-    let cleaned_count = file_ops::cleanup_expired_files(&state.db, &state.storage).await?;
+    let cleaned_count = file_ops::cleanup_expired_files(&state.db, state.storage.as_ref()).await?;
+    state.file_info_cache.clear();
 
     tracing::info!("✅ Cleaned up {} expired files", cleaned_count);
 
@@ -698,15 +2893,138 @@ pub async fn cleanup_expired_files(State(state): State<AppState>) -> Result<Json
     }))
 }
 
+// Toggle maintenance mode (admin only)
+pub async fn set_maintenance_mode(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<MaintenanceRequest>,
+) -> Result<Json<MaintenanceResponse>> {
+    require_admin(&state, &headers)?;
+
+    state.maintenance.store(request.enabled, Ordering::Relaxed);
+
+    if request.enabled {
+        tracing::info!("🚧 Maintenance mode enabled, uploads are disabled");
+    } else {
+        tracing::info!("✅ Maintenance mode disabled, uploads are enabled");
+    }
+
+    Ok(Json(MaintenanceResponse {
+        maintenance: request.enabled,
+    }))
+}
+
+// Cross-check stored files against the database (admin only)
+pub async fn check_storage(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<StorageCheckQuery>,
+) -> Result<Json<StorageCheckResponse>> {
+    require_admin(&state, &headers)?;
+
+    tracing::info!(
+        "🔍 Checking storage consistency{}",
+        if query.repair { " (repairing)" } else { "" }
+    );
+
+    let report = file_ops::check_storage_consistency(
+        &state.db,
+        state.storage.as_ref(),
+        query.repair,
+        STORAGE_CHECK_GRACE_PERIOD,
+    )
+    .await?;
+
+    tracing::info!(
+        "✅ Storage check complete: {} orphaned files ({} removed), {} records with missing files",
+        report.orphaned_files,
+        report.orphaned_files_removed,
+        report.missing_files.len()
+    );
+
+    Ok(Json(StorageCheckResponse {
+        report,
+        repaired: query.repair,
+        timestamp: chrono::Utc::now(),
+    }))
+}
+
+// Delete every file matching the filters, or only report them with dry_run (admin only)
+pub async fn bulk_delete_files(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<BulkDeleteRequest>,
+) -> Result<Json<BulkDeleteResponse>> {
+    require_admin(&state, &headers)?;
+
+    let filter = FileFilter {
+        created_before: request.created_before,
+        mime_type: request.mime_type,
+        min_size: request.min_size,
+        file_ids: request.file_ids,
+    };
+
+    // Deleting everything takes an explicit filter, such as a future created_before
+    if filter.is_empty() {
+        return Err(AppError::BadRequest(
+            "At least one filter is required".to_string(),
+        ));
+    }
+    if filter
+        .file_ids
+        .as_ref()
+        .is_some_and(|file_ids| file_ids.len() > MAX_BULK_DELETE_IDS)
+    {
+        return Err(AppError::BadRequest(format!(
+            "At most {} file ids can be deleted at once",
+            MAX_BULK_DELETE_IDS
+        )));
+    }
+
+    tracing::info!(
+        "🗑️ Bulk delete requested{}: {:?}",
+        if request.dry_run { " (dry run)" } else { "" },
+        filter
+    );
+
+    let (files_matched, sample_file_ids) =
+        file_ops::find_matching_files(&state.db, &filter, BULK_DELETE_SAMPLE_SIZE).await?;
+
+    let files_deleted = if request.dry_run {
+        0
+    } else {
+        let deleted =
+            file_ops::delete_matching_files(&state.db, state.storage.as_ref(), &filter).await?;
+        state.file_info_cache.clear();
+        deleted
+    };
+
+    tracing::info!(
+        "✅ Bulk delete complete: {} files matched, {} deleted",
+        files_matched,
+        files_deleted
+    );
+
+    Ok(Json(BulkDeleteResponse {
+        files_matched,
+        files_deleted,
+        sample_file_ids,
+        dry_run: request.dry_run,
+        timestamp: chrono::Utc::now(),
+    }))
+}
+
 // Cleanup expired upload sessions endpoint
 pub async fn cleanup_expired_upload_sessions(
     State(state): State<AppState>,
 ) -> Result<Json<CleanupResponse>> {
     tracing::info!("🧹 Starting cleanup of expired upload sessions");
 
-    let cleaned_count =
-        upload_session_ops::cleanup_expired_upload_sessions(&state.db, &state.chunk_storage)
-            .await?;
+    let cleaned_count = upload_session_ops::cleanup_expired_upload_sessions(
+        &state.db,
+        state.chunk_storage.as_ref(),
+    )
+    .await?;
 
     tracing::info!("✅ Cleaned up {} expired upload sessions", cleaned_count);
 
@@ -716,3 +3034,24 @@ pub async fn cleanup_expired_upload_sessions(
         timestamp: chrono::Utc::now(),
     }))
 }
+
+// Remove chunks left behind by upload sessions that no longer exist
+pub async fn cleanup_orphaned_chunks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<CleanupResponse>> {
+    require_admin(&state, &headers)?;
+    tracing::info!("🧹 Starting cleanup of orphaned upload chunks");
+
+    let cleaned_count =
+        upload_session_ops::cleanup_orphaned_chunks(&state.db, state.chunk_storage.as_ref())
+            .await?;
+
+    tracing::info!("✅ Cleaned up chunks of {} orphaned uploads", cleaned_count);
+
+    Ok(Json(CleanupResponse {
+        items_cleaned: cleaned_count,
+        cleanup_type: "orphaned_chunks".to_string(),
+        timestamp: chrono::Utc::now(),
+    }))
+}
@@ -0,0 +1,42 @@
+use crate::error::{AppError, Result};
+use axum::{extract::Request, middleware::Next, response::Response};
+
+/// Header a TLS-terminating proxy sets to the scheme the client used
+pub const FORWARDED_PROTO_HEADER: &str = "x-forwarded-proto";
+
+// API paths that stay reachable over plain HTTP, since nothing secret goes through them
+const PLAIN_HTTP_PATHS: &[&str] = &["/api/config", "/api/openapi.json", "/api/docs"];
+
+/// Reject API requests that didn't reach the proxy over HTTPS
+///
+/// The backend doesn't terminate TLS itself, so it relies on the proxy in
+/// front of it to set `X-Forwarded-Proto`, overwriting any value sent by
+/// clients or appending its own. Only the last value counts, since earlier
+/// ones may come from the client. Requests without the header count as plain
+/// HTTP. The frontend, health check and API description are left alone.
+pub async fn require_https(request: Request, next: Next) -> Result<Response> {
+    let path = request.uri().path();
+    if !path.starts_with("/api/") || PLAIN_HTTP_PATHS.contains(&path) {
+        return Ok(next.run(request).await);
+    }
+
+    // Proxies that append rather than overwrite leave a client's value first
+    let forwarded_proto = request
+        .headers()
+        .get(FORWARDED_PROTO_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .map(str::trim);
+
+    if !forwarded_proto.is_some_and(|proto| proto.eq_ignore_ascii_case("https")) {
+        tracing::warn!(
+            "🔓 Rejected {} {} sent over {}",
+            request.method(),
+            path,
+            forwarded_proto.unwrap_or("a direct connection")
+        );
+        return Err(AppError::HttpsRequired);
+    }
+
+    Ok(next.run(request).await)
+}
@@ -1,39 +1,87 @@
 use axum::{
+    body::Body,
     extract::DefaultBodyLimit,
-    routing::{get, post},
+    http::{HeaderName, Request},
+    routing::{get, head, post, put},
     Router,
 };
 use sea_orm::DatabaseConnection;
+use std::sync::{atomic::AtomicBool, Arc};
+use std::time::Duration;
 use tower::ServiceBuilder;
 use tower_http::{
     cors::{Any, CorsLayer},
-    services::ServeDir,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
+    services::{ServeDir, ServeFile},
+    timeout::TimeoutLayer,
     trace::TraceLayer,
 };
 
 // Re-export shared types from kusatsu-types
 pub use kusatsu_types::*;
 
+pub mod access_log;
 pub mod chunk_storage;
+pub mod chunk_writes;
+pub mod compression;
 pub mod config;
+pub mod container;
+pub mod content_disposition;
 pub mod database;
 pub mod error;
+pub mod file_info_cache;
+pub mod file_type;
 pub mod handlers;
+pub mod https;
+#[cfg(any(test, feature = "test-util"))]
+pub mod memory_storage;
+pub mod openapi;
+pub mod public_url;
+pub mod scanner;
+pub mod signing;
 pub mod storage;
+pub mod storage_health;
+pub mod throttle;
+pub mod upload_memory;
+pub mod upload_pow;
+pub mod upload_rate;
 
-use chunk_storage::ChunkStorage;
+use chunk_storage::{open_chunk_storage, ChunkStorage};
+use chunk_writes::ChunkWrites;
 use config::Config;
 use database::setup_database;
 use error::{AppError, Result};
-use storage::FileStorage;
+use file_info_cache::FileInfoCache;
+use storage::{FileStorage, LocalFileStorage};
+use storage_health::StorageHealth;
+use upload_memory::UploadMemory;
+use upload_pow::UploadChallenges;
+use upload_rate::UploadRates;
+
+// Room for multipart boundaries and the small text fields around the file data
+const MULTIPART_OVERHEAD: usize = 64 * 1024;
 
 // Application state shared across all handlers
 #[derive(Clone)]
 pub struct AppState {
     pub db: DatabaseConnection,
     pub config: Config,
-    pub storage: FileStorage,
-    pub chunk_storage: ChunkStorage,
+    pub storage: Arc<dyn FileStorage>,
+    pub chunk_storage: Arc<dyn ChunkStorage>,
+    // When set, new uploads are rejected while downloads keep working
+    pub maintenance: Arc<AtomicBool>,
+    // Recent chunk arrivals, for the throughput and ETA in upload status
+    pub upload_rates: UploadRates,
+    // Chunks being written, bounded per upload session
+    pub chunk_writes: ChunkWrites,
+    // Memory budget of uploads that load a whole file at once
+    pub upload_memory: UploadMemory,
+    // File records recently served by the info endpoint
+    pub file_info_cache: FileInfoCache,
+    // Whether storage takes writes, for the health check
+    pub storage_health: StorageHealth,
+    // Proof-of-work challenges handed out to uploaders
+    pub upload_challenges: UploadChallenges,
 }
 
 // All API types are now defined in kusatsu-types and re-exported above
@@ -46,15 +94,17 @@ pub async fn run_server() -> Result<()> {
     let config = Config::from_env()?;
 
     // Setup database
-    let db = setup_database(&config.database_url).await?;
+    let db = setup_database(&config).await?;
 
     // Setup file storage
-    let storage = FileStorage::new(&config.storage_dir);
+    let storage = Arc::new(
+        LocalFileStorage::new(&config.storage_dir)
+            .with_strict_permissions(config.strict_file_permissions),
+    );
     storage.init().await?;
 
-    // Setup chunk storage
-    let chunk_storage = ChunkStorage::new(&config.storage_dir);
-    chunk_storage.init().await?;
+    // Setup chunk storage (local disk, or Redis when shared between replicas)
+    let chunk_storage = open_chunk_storage(&config).await?;
 
     // Extract config values before moving state
     let server_address = config.server_address.clone();
@@ -63,11 +113,22 @@ pub async fn run_server() -> Result<()> {
     // Create application state
     let state = AppState {
         db,
+        upload_memory: UploadMemory::new(config.max_concurrent_upload_size as u64),
+        chunk_writes: ChunkWrites::new(config.max_concurrent_chunks_per_upload),
+        file_info_cache: FileInfoCache::new(Duration::from_secs(config.file_info_cache_ttl_secs)),
         config,
         storage,
         chunk_storage,
+        maintenance: Arc::new(AtomicBool::new(false)),
+        upload_rates: UploadRates::new(),
+        storage_health: StorageHealth::new(),
+        upload_challenges: UploadChallenges::new(),
     };
 
+    // Keep checking that storage takes writes, so /health notices a full disk
+    // even while no uploads come in
+    storage_health::spawn_probe(state.storage.clone(), state.storage_health.clone());
+
     // Build the application router
     let app = create_app(state);
 
@@ -89,32 +150,72 @@ pub async fn run_server() -> Result<()> {
     Ok(())
 }
 
+// Span for one request, tagged with its request id; handlers fill in the
+// upload and file ids once they know them
+fn request_span(request: &Request<Body>) -> tracing::Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %access_log::redact_uri(request.uri()),
+        request_id = %request_id,
+        upload_id = tracing::field::Empty,
+        file_id = tracing::field::Empty,
+    )
+}
+
 fn create_app(state: AppState) -> Router {
-    Router::new()
+    // Routes that move whole files or chunks get the longer deadline
+    let transfer_routes = Router::new()
         // File operations (legacy single upload)
-        .route("/api/upload", post(handlers::upload_file))
+        .route(
+            "/api/upload",
+            post(handlers::upload_file).layer(DefaultBodyLimit::max(
+                state.config.max_file_size + MULTIPART_OVERHEAD,
+            )),
+        )
         .route(
             "/api/files/:file_id/download",
             post(handlers::download_file_form),
         )
-        .route("/api/files/:file_id/info", post(handlers::get_file_info))
-        // Chunked upload operations
         .route(
-            "/api/upload/start",
-            post(handlers::start_chunked_upload).layer(DefaultBodyLimit::max(1024 * 1024)),
-        ) // 1MB for JSON requests
+            "/api/files/:file_id/download-json",
+            post(handlers::download_file_json),
+        )
+        .route("/api/files/:file_id/raw", get(handlers::download_raw))
+        .route(
+            "/api/files/by-slug/:slug/download",
+            post(handlers::download_file_form_by_slug),
+        )
+        .route(
+            "/api/files/by-slug/:slug/raw",
+            get(handlers::download_raw_by_slug),
+        )
+        .route(
+            "/api/files/:file_id/signed-download",
+            get(handlers::signed_download).post(handlers::signed_download_form),
+        )
+        // Chunked upload operations
         .route(
             "/api/upload/chunk/:upload_id/:chunk_number",
-            post(handlers::upload_chunk).layer(DefaultBodyLimit::max(20 * 1024 * 1024)),
-        ) // 20MB for chunk uploads
+            post(handlers::upload_chunk)
+                .layer(DefaultBodyLimit::max(
+                    MAX_CHUNK_SIZE as usize + MULTIPART_OVERHEAD,
+                ))
+                .merge(
+                    put(handlers::upload_chunk_raw)
+                        .layer(DefaultBodyLimit::max(MAX_CHUNK_SIZE as usize)),
+                ),
+        )
         .route(
             "/api/upload/complete",
             post(handlers::complete_chunked_upload),
         )
-        .route(
-            "/api/upload/status/:upload_id",
-            get(handlers::get_upload_status),
-        )
         // Cleanup operations
         .route(
             "/api/admin/cleanup/files",
@@ -124,20 +225,4989 @@ fn create_app(state: AppState) -> Router {
             "/api/admin/cleanup/upload-sessions",
             post(handlers::cleanup_expired_upload_sessions),
         )
+        .route(
+            "/api/admin/cleanup/chunks",
+            post(handlers::cleanup_orphaned_chunks),
+        )
+        .route("/api/admin/fsck", post(handlers::check_storage))
+        .route("/api/admin/files/delete", post(handlers::bulk_delete_files))
+        .layer(TimeoutLayer::new(Duration::from_secs(
+            state.config.upload_timeout_secs,
+        )));
+
+    let router = Router::new()
+        // Existence checks, never counted as downloads
+        .route("/api/files/:file_id", head(handlers::file_exists))
+        .route(
+            "/api/files/by-slug/:slug",
+            head(handlers::file_exists_by_slug),
+        )
+        .route("/api/files/:file_id/info", post(handlers::get_file_info))
+        .route(
+            "/api/files/by-slug/:slug/info",
+            post(handlers::get_file_info_by_slug),
+        )
+        .route(
+            "/api/files/by-slug/:slug/preview",
+            get(handlers::get_file_preview_by_slug),
+        )
+        .route(
+            "/api/files/:file_id/preview",
+            get(handlers::get_file_preview),
+        )
+        .route(
+            "/api/files/:file_id/extend",
+            post(handlers::extend_file_expiry),
+        )
+        .route(
+            "/api/files/:file_id/wrapped-key",
+            get(handlers::get_wrapped_key).post(handlers::set_wrapped_key),
+        )
+        .route(
+            "/api/files/:file_id/listing",
+            get(handlers::get_listing).post(handlers::set_listing),
+        )
+        .route("/api/files/:file_id/sign", post(handlers::sign_download))
+        .route("/api/files/:file_id/rotate", post(handlers::rotate_file_id))
+        .route("/api/files/:file_id/delete", post(handlers::delete_file))
+        .route(
+            "/api/upload/start",
+            post(handlers::start_chunked_upload).layer(DefaultBodyLimit::max(1024 * 1024)),
+        ) // 1MB for JSON requests
+        .route(
+            "/api/upload/challenge",
+            post(handlers::issue_upload_challenge),
+        )
+        .route(
+            "/api/upload/plan",
+            post(handlers::plan_upload).layer(DefaultBodyLimit::max(1024 * 1024)),
+        )
+        .route(
+            "/api/upload/status/:upload_id",
+            get(handlers::get_upload_status),
+        )
+        .route(
+            "/api/upload/:upload_id/keepalive",
+            post(handlers::keepalive_upload),
+        )
+        .route(
+            "/api/admin/maintenance",
+            post(handlers::set_maintenance_mode),
+        )
         // Health check
         .route("/health", get(handlers::health_check))
-        // Static file serving for frontend
-        .nest_service("/", ServeDir::new("static"))
+        // Limits and features for clients
+        .route("/api/config", get(handlers::get_server_config))
+        // API description
+        .route("/api/openapi.json", get(handlers::openapi_spec))
+        // Slow clients get 408 Request Timeout instead of holding a connection
+        .layer(TimeoutLayer::new(Duration::from_secs(
+            state.config.request_timeout_secs,
+        )))
+        .merge(transfer_routes);
+
+    let router = match (&state.config.api_docs, &state.config.api_docs_assets) {
+        (true, Some(assets)) => router
+            .route("/api/docs", get(handlers::api_docs))
+            .nest_service("/api/docs/assets", ServeDir::new(assets)),
+        (true, None) => router.route("/api/docs", get(handlers::api_docs)),
+        (false, _) => router,
+    };
+
+    // Static file serving for frontend, falling back to index.html so
+    // client-side routes like /download/:id survive a hard refresh
+    let router = if state.config.serve_static {
+        let static_dir = std::path::Path::new(&state.config.static_dir);
+        router.nest_service(
+            "/",
+            ServeDir::new(static_dir).fallback(ServeFile::new(static_dir.join("index.html"))),
+        )
+    } else {
+        router
+    };
+
+    // Behind a TLS-terminating proxy, refuse API requests the client sent over plain HTTP
+    let router = if state.config.require_https {
+        router.layer(axum::middleware::from_fn(https::require_https))
+    } else {
+        router
+    };
+
+    // Clients can send their own id to join their logs with ours, otherwise one is generated
+    let request_id_header = HeaderName::from_static(REQUEST_ID_HEADER);
+
+    router
         // Add middleware
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
+                .layer(SetRequestIdLayer::new(
+                    request_id_header.clone(),
+                    MakeRequestUuid,
+                ))
+                .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+                .layer(TraceLayer::new_for_http().make_span_with(request_span))
+                .layer(axum::middleware::from_fn_with_state(
+                    state.config.access_log,
+                    access_log::log_requests,
+                ))
                 .layer(
                     CorsLayer::new()
                         .allow_origin(Any)
                         .allow_methods(Any)
-                        .allow_headers(Any),
+                        .allow_headers(Any)
+                        .expose_headers([request_id_header]),
                 ),
         )
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_storage::LocalChunkStorage;
+    use crate::container::{self, ContainerHeader};
+    use crate::database::{file_ops, upload_session_ops, CreateUnencryptedFileParams};
+    use crate::memory_storage::InMemoryStorage;
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    // Config backed by a throwaway SQLite database and storage directory
+    fn test_config(temp_dir: &TempDir) -> Config {
+        Config {
+            database_url: format!(
+                "sqlite://{}?mode=rwc",
+                temp_dir.path().join("kusatsu.db").display()
+            ),
+            storage_dir: temp_dir.path().join("storage").display().to_string(),
+            ..Config::for_tests()
+        }
+    }
+
+    async fn test_state(config: Config) -> AppState {
+        let db = setup_database(&config).await.unwrap();
+        // Files stay in memory; the database and chunks still use the temp directory
+        let storage = Arc::new(InMemoryStorage::new());
+        let chunk_storage = Arc::new(LocalChunkStorage::new(&config.storage_dir));
+        chunk_storage.init().await.unwrap();
+
+        AppState {
+            db,
+            upload_memory: UploadMemory::new(config.max_concurrent_upload_size as u64),
+            chunk_writes: ChunkWrites::new(config.max_concurrent_chunks_per_upload),
+            file_info_cache: FileInfoCache::new(Duration::from_secs(
+                config.file_info_cache_ttl_secs,
+            )),
+            config,
+            storage,
+            chunk_storage,
+            maintenance: Arc::new(AtomicBool::new(false)),
+            upload_rates: UploadRates::new(),
+            storage_health: StorageHealth::new(),
+            upload_challenges: UploadChallenges::new(),
+        }
+    }
+
+    // Serve the app on a random local port and return its base URL
+    async fn spawn_app(state: AppState) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, create_app(state)).await.unwrap();
+        });
+        base
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode_blocks_uploads_but_not_downloads() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            admin_token: Some("admin-secret".to_string()),
+            ..test_config(&temp_dir)
+        })
+        .await;
+
+        // An existing unencrypted file that should stay downloadable
+        let file_id = Uuid::new_v4();
+        let file_path = state
+            .storage
+            .store_file(file_id, &ContainerHeader::plain(b"hello"), b"hello")
+            .await
+            .unwrap();
+        file_ops::create_unencrypted_file_record(
+            &state.db,
+            CreateUnencryptedFileParams::for_tests(file_id, file_path),
+        )
+        .await
+        .unwrap();
+
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+        // The toggle requires the admin token
+        let response = client
+            .post(format!("{}/api/admin/maintenance", base))
+            .json(&serde_json::json!({ "enabled": true }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let response = client
+            .post(format!("{}/api/admin/maintenance", base))
+            .bearer_auth("admin-secret")
+            .json(&serde_json::json!({ "enabled": true }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        // Uploads are rejected with Retry-After
+        let response = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({ "filename": "a.txt", "file_size": 5 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 503);
+        assert!(response.headers().contains_key("retry-after"));
+
+        // Downloads keep working
+        let response = client
+            .post(format!("{}/api/files/{}/download", base, file_id))
+            .form(&[("encryption_key", "")])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+
+        // Health reports the maintenance state
+        let health: HealthResponse = client
+            .get(format!("{}/health", base))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(health.maintenance);
+
+        // And how busy the database pool is
+        let database_pool = health.database_pool.unwrap();
+        assert!(database_pool.idle <= database_pool.connections);
+        assert!(database_pool.connections <= database_pool.max_connections);
+    }
+
+    #[tokio::test]
+    async fn test_client_routes_fall_back_to_spa_shell() {
+        let temp_dir = TempDir::new().unwrap();
+        let static_dir = temp_dir.path().join("static");
+        std::fs::create_dir_all(&static_dir).unwrap();
+        std::fs::write(static_dir.join("index.html"), "<html>kusatsu</html>").unwrap();
+
+        let base = spawn_app(
+            test_state(Config {
+                static_dir: static_dir.display().to_string(),
+                ..test_config(&temp_dir)
+            })
+            .await,
+        )
+        .await;
+
+        let response = reqwest::get(format!("{}/download/{}", base, Uuid::new_v4()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "<html>kusatsu</html>");
+    }
+
+    #[tokio::test]
+    async fn test_static_serving_can_be_disabled() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(
+            test_state(Config {
+                serve_static: false,
+                ..test_config(&temp_dir)
+            })
+            .await,
+        )
+        .await;
+
+        let response = reqwest::get(format!("{}/download/{}", base, Uuid::new_v4()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let response = reqwest::get(format!("{}/health", base)).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_signed_download_url() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            signing_secret: Some("signing-secret".to_string()),
+            ..test_config(&temp_dir)
+        })
+        .await;
+
+        let file_id = Uuid::new_v4();
+        let file_path = state
+            .storage
+            .store_file(file_id, &ContainerHeader::plain(b"hello"), b"hello")
+            .await
+            .unwrap();
+        file_ops::create_unencrypted_file_record(
+            &state.db,
+            CreateUnencryptedFileParams::for_tests(file_id, file_path),
+        )
+        .await
+        .unwrap();
+
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        // Only the owner can sign
+        let response = client
+            .post(format!("{}/api/files/{}/sign", base, file_id))
+            .json(&SignDownloadRequest {
+                owner_token: "not-the-owner".to_string(),
+                expires_in_seconds: 60,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+
+        let signed: SignDownloadResponse = client
+            .post(format!("{}/api/files/{}/sign", base, file_id))
+            .json(&SignDownloadRequest {
+                owner_token: "owner".to_string(),
+                expires_in_seconds: 60,
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        // The URL points at the configured API URL; replay it against the test server
+        let path = &signed.url[signed.url.find("/api/").unwrap()..];
+        let response = client
+            .get(format!("{}{}", base, path))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+
+        // A tampered expiry invalidates the signature
+        let tampered = path.replace("expires=", "expires=1");
+        let response = client
+            .get(format!("{}{}", base, tampered))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+    }
+
+    #[tokio::test]
+    async fn test_upload_plan_has_no_side_effects() {
+        use sea_orm::{EntityTrait, PaginatorTrait};
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_file_size: 100 * 1024 * 1024,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let db = state.db.clone();
+
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let plan: UploadPlanResponse = client
+            .post(format!("{}/api/upload/plan", base))
+            .json(&serde_json::json!({
+                "filename": "big.bin",
+                "file_size": 12 * 1024 * 1024,
+                "expires_in_hours": 2,
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(plan.method, UploadMethod::Chunked);
+        assert_eq!(plan.total_chunks, Some(3));
+        assert!(plan.expires_at.is_some());
+
+        let plan: UploadPlanResponse = client
+            .post(format!("{}/api/upload/plan", base))
+            .json(&serde_json::json!({ "filename": "small.txt", "file_size": 10 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(plan.method, UploadMethod::Single);
+        assert_eq!(plan.total_chunks, None);
+
+        // Over quota is rejected just like a real upload
+        let response = client
+            .post(format!("{}/api/upload/plan", base))
+            .json(&serde_json::json!({ "filename": "huge.bin", "file_size": 200 * 1024 * 1024 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 413);
+
+        // No upload session was created
+        let sessions = kusatsu_entity::upload_session::Entity::find()
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(sessions, 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_is_throttled() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            download_rate_limit: Some(20_000),
+            ..test_config(&temp_dir)
+        })
+        .await;
+
+        let data = vec![42u8; 30_000];
+        let file_id = Uuid::new_v4();
+        let file_path = state
+            .storage
+            .store_file(file_id, &ContainerHeader::plain(&data), &data)
+            .await
+            .unwrap();
+        file_ops::create_unencrypted_file_record(
+            &state.db,
+            CreateUnencryptedFileParams {
+                original_size: data.len() as i64,
+                filename: b"data.bin".to_vec(),
+                ..CreateUnencryptedFileParams::for_tests(file_id, file_path)
+            },
+        )
+        .await
+        .unwrap();
+
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        // 30 kB at 20 kB/s can't finish before the last slice is paid for
+        let started = std::time::Instant::now();
+        let response = client
+            .get(format!("{}/api/files/{}/raw", base, file_id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().len(), data.len());
+        assert!(started.elapsed() >= std::time::Duration::from_millis(1400));
+    }
+
+    #[tokio::test]
+    async fn test_file_is_deleted_after_last_download() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            delete_on_limit_reached: true,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let db = state.db.clone();
+        let storage = state.storage.clone();
+
+        let file_id = Uuid::new_v4();
+        let file_path = state
+            .storage
+            .store_file(file_id, &ContainerHeader::plain(b"hello"), b"hello")
+            .await
+            .unwrap();
+        file_ops::create_unencrypted_file_record(
+            &state.db,
+            CreateUnencryptedFileParams {
+                max_downloads: Some(2),
+                ..CreateUnencryptedFileParams::for_tests(file_id, file_path.clone())
+            },
+        )
+        .await
+        .unwrap();
+
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+        let download = || async {
+            client
+                .get(format!("{}/api/files/{}/raw", base, file_id))
+                .send()
+                .await
+                .unwrap()
+        };
+
+        let response = download().await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+        assert!(storage.file_exists(&file_path).await);
+
+        // The last download is still served in full
+        let response = download().await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+
+        // Deletion happens in the background, right after
+        let mut deleted = false;
+        for _ in 0..50 {
+            if file_ops::get_file_by_id(&db, file_id)
+                .await
+                .unwrap()
+                .is_none()
+            {
+                deleted = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(deleted);
+        assert!(!storage.file_exists(&file_path).await);
+        assert_eq!(download().await.status(), 404);
+    }
+
+    // Log output of the test's thread, for checking what a request logged
+    #[derive(Clone, Default)]
+    struct LogCapture(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogCapture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl LogCapture {
+        // Lines logged so far that contain `needle`, waiting briefly for them
+        // since the access log is written once the response body is dropped
+        async fn lines_with(&self, needle: &str) -> Vec<String> {
+            for _ in 0..50 {
+                let logs = String::from_utf8_lossy(&self.0.lock().unwrap()).to_string();
+                let lines: Vec<String> = logs
+                    .lines()
+                    .filter(|line| line.contains(needle))
+                    .map(str::to_string)
+                    .collect();
+                if !lines.is_empty() {
+                    return lines;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            Vec::new()
+        }
+    }
+
+    // The test runtime runs the server on the test's thread, so its logs are captured
+    #[tokio::test]
+    async fn test_access_log_records_sizes_and_redacts_keys() {
+        let capture = LogCapture::default();
+        let writer = capture.clone();
+        let _subscriber = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .finish(),
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(
+            test_state(Config {
+                access_log: access_log::AccessLog::All,
+                ..test_config(&temp_dir)
+            })
+            .await,
+        )
+        .await;
+        let client = reqwest::Client::new();
+
+        let form = reqwest::multipart::Form::new()
+            .part(
+                "file_data",
+                reqwest::multipart::Part::bytes(vec![7u8; 4096]),
+            )
+            .text("filename", "sizes.bin");
+        let response = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        // Counting the body keeps its length known
+        assert!(response.headers().contains_key("content-length"));
+        let body = response.bytes().await.unwrap();
+
+        let lines = capture.lines_with("path=/api/upload").await;
+        assert_eq!(lines.len(), 1, "{:?}", lines);
+        assert!(lines[0].contains("method=POST"));
+        assert!(lines[0].contains("status=200"));
+        assert!(lines[0].contains("duration_ms="));
+        assert!(lines[0].contains(&format!("bytes_out={}", body.len())));
+        let bytes_in: u64 = lines[0]
+            .split("bytes_in=")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|bytes_in| bytes_in.parse().ok())
+            .unwrap();
+        assert!(bytes_in > 4096, "{}", lines[0]);
+
+        // A key sent in the query string stays out of the logs, request span included
+        let response = client
+            .get(format!(
+                "{}/api/files/{}/signed-download?expires=1&signature=top-secret-signature",
+                base,
+                Uuid::new_v4()
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_client_error());
+        drop(response.bytes().await);
+
+        let lines = capture.lines_with("signed-download").await;
+        assert!(!lines.is_empty());
+        assert!(lines
+            .iter()
+            .any(|line| line.contains("path=/api/files/") && line.contains("signature=REDACTED")));
+        let logs = String::from_utf8_lossy(&capture.0.lock().unwrap()).to_string();
+        assert!(!logs.contains("top-secret-signature"));
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_echoed() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        // A client-provided id is kept so its logs can be joined with ours
+        let response = client
+            .get(format!("{}/health", base))
+            .header(REQUEST_ID_HEADER, "cli-upload-42")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "cli-upload-42");
+
+        // Otherwise one is generated, also for errors
+        let response = client
+            .get(format!("{}/api/files/{}/raw", base, Uuid::new_v4()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        let request_id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(Uuid::parse_str(request_id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_upload_expiry_is_capped() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_expires_in_hours: 24,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let upload = |hours: i32| {
+            let form = reqwest::multipart::Form::new()
+                .text("filename", "a.txt")
+                .part("file", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+            client
+                .post(format!("{}/api/upload?expires_in_hours={}", base, hours))
+                .multipart(form)
+                .send()
+        };
+        let start = |hours: i32| {
+            client
+                .post(format!("{}/api/upload/start", base))
+                .json(&serde_json::json!({
+                    "filename": "a.txt",
+                    "file_size": 5,
+                    "expires_in_hours": hours,
+                }))
+                .send()
+        };
+
+        for hours in [-1, 0, 25] {
+            assert_eq!(upload(hours).await.unwrap().status(), 400, "{}", hours);
+            assert_eq!(start(hours).await.unwrap().status(), 422, "{}", hours);
+        }
+        assert_eq!(upload(24).await.unwrap().status(), 200);
+        assert_eq!(start(24).await.unwrap().status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_start_upload_reports_invalid_fields() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let start = |body: serde_json::Value| {
+            client
+                .post(format!("{}/api/upload/start", base))
+                .json(&body)
+                .send()
+        };
+
+        let cases = [
+            (
+                serde_json::json!({ "filename": "", "file_size": 5 }),
+                "filename",
+            ),
+            (
+                serde_json::json!({ "filename": "a.txt", "file_size": 0 }),
+                "file_size",
+            ),
+            (
+                serde_json::json!({ "filename": "a.txt", "file_size": 5, "chunk_size": 0 }),
+                "chunk_size",
+            ),
+            (
+                serde_json::json!({ "filename": "a.txt", "file_size": 5, "chunk_size": MAX_CHUNK_SIZE + 1 }),
+                "chunk_size",
+            ),
+            (
+                serde_json::json!({ "filename": "a.txt", "file_size": 5, "expires_in_hours": -1 }),
+                "expires_in_hours",
+            ),
+            (
+                serde_json::json!({ "filename": "a.txt", "file_size": 5, "expires_in_hours": 100_000 }),
+                "expires_in_hours",
+            ),
+            (
+                serde_json::json!({ "filename": "a.txt", "file_size": 5, "slug": "Not A Slug" }),
+                "slug",
+            ),
+        ];
+
+        for (body, field) in cases {
+            let response = start(body).await.unwrap();
+            assert_eq!(response.status(), 422, "{}", field);
+            let error: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(error.code, error_code::VALIDATION_FAILED);
+            assert_eq!(error.fields.len(), 1, "{}", field);
+            assert_eq!(error.fields[0].field, field);
+        }
+
+        // All problems are reported together
+        let response = start(serde_json::json!({
+            "filename": "a.txt",
+            "file_size": -5,
+            "chunk_size": -1,
+            "max_downloads": 0,
+        }))
+        .await
+        .unwrap();
+        let error: ErrorResponse = response.json().await.unwrap();
+        let fields: Vec<_> = error.fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(fields, ["file_size", "chunk_size", "max_downloads"]);
+        assert!(error
+            .message
+            .contains("max_downloads: must be between 1 and"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_max_downloads_is_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_downloads_limit: 10,
+            ..test_config(&temp_dir)
+        })
+        .await;
+
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        for max_downloads in [0, -1, 11] {
+            let form = reqwest::multipart::Form::new()
+                .part(
+                    "file_data",
+                    reqwest::multipart::Part::bytes(b"hello".to_vec()),
+                )
+                .text("filename", "hello.txt");
+            let response = client
+                .post(format!(
+                    "{}/api/upload?max_downloads={}",
+                    base, max_downloads
+                ))
+                .multipart(form)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(
+                response.status(),
+                400,
+                "direct upload with {}",
+                max_downloads
+            );
+
+            let response = client
+                .post(format!("{}/api/upload/start", base))
+                .json(&serde_json::json!({
+                    "filename": "hello.txt",
+                    "file_size": 5,
+                    "max_downloads": max_downloads,
+                }))
+                .send()
+                .await
+                .unwrap();
+            // Chunked uploads report it as a field error
+            assert_eq!(
+                response.status(),
+                422,
+                "chunked upload with {}",
+                max_downloads
+            );
+        }
+
+        // The limit itself is accepted
+        let response = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({
+                "filename": "hello.txt",
+                "file_size": 5,
+                "max_downloads": 10,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_times_out() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            request_timeout_secs: 1,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let base = spawn_app(state).await;
+
+        // Announce a body and never send it
+        let mut stream = tokio::net::TcpStream::connect(base.trim_start_matches("http://"))
+            .await
+            .unwrap();
+        stream
+            .write_all(
+                b"POST /api/upload/start HTTP/1.1\r\nHost: localhost\r\n\
+                  Content-Type: application/json\r\nContent-Length: 100\r\n\r\n{",
+            )
+            .await
+            .unwrap();
+
+        let mut response = vec![0u8; 64];
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        let response = String::from_utf8_lossy(&response[..read]);
+        assert!(response.starts_with("HTTP/1.1 408"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_raw_download_for_client_side_decryption() {
+        use base64::{engine::general_purpose, Engine as _};
+        use kusatsu_encrypt::{EncryptedData, Encryption, EncryptionKey};
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+
+        let key = EncryptionKey::generate();
+        let content = Encryption::encrypt(b"secret content", &key).unwrap();
+        let filename = Encryption::encrypt(b"secret.txt", &key).unwrap();
+
+        let file_id = Uuid::new_v4();
+        let file_path = state
+            .storage
+            .store_file(
+                file_id,
+                &ContainerHeader::encrypted(&content.ciphertext, content.nonce.clone(), None, 14),
+                &content.ciphertext,
+            )
+            .await
+            .unwrap();
+        file_ops::create_file_record(
+            &state.db,
+            crate::database::CreateFileParams {
+                file_id,
+                original_size: 14,
+                encrypted_size: content.ciphertext.len() as i64,
+                mime_type: Some("text/plain".to_string()),
+                file_path,
+                nonce: content.nonce,
+                encrypted_filename: filename.ciphertext,
+                filename_nonce: filename.nonce,
+                expires_at: None,
+                max_downloads: Some(1),
+                burn_after_first_download_minutes: None,
+                expire_after_inactivity_hours: None,
+                owner_token: "owner".to_string(),
+                slug: None,
+                signature: None,
+                inline_data: None,
+                compression: None,
+            },
+        )
+        .await
+        .unwrap();
+        let db = state.db.clone();
+
+        let base = spawn_app(state).await;
+        let url = format!("{}/api/files/{}/raw", base, file_id);
+
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["x-encrypted"], "true");
+
+        let header = |name: &str| {
+            general_purpose::STANDARD
+                .decode(response.headers()[name].to_str().unwrap())
+                .unwrap()
+        };
+        let nonce = header("x-content-nonce");
+        let filename_ciphertext = header("x-filename-ciphertext");
+        let filename_nonce = header("x-filename-nonce");
+        let ciphertext = response.bytes().await.unwrap().to_vec();
+
+        // Decrypt on the "client" with the key the server never saw
+        let decrypted = Encryption::decrypt(
+            &EncryptedData {
+                ciphertext,
+                nonce,
+                key_size: key.size(),
+            },
+            &key,
+        )
+        .unwrap();
+        assert_eq!(decrypted, b"secret content");
+        let decrypted_filename = Encryption::decrypt(
+            &EncryptedData {
+                ciphertext: filename_ciphertext,
+                nonce: filename_nonce,
+                key_size: key.size(),
+            },
+            &key,
+        )
+        .unwrap();
+        assert_eq!(decrypted_filename, b"secret.txt");
+
+        // The download was counted exactly once and the limit is enforced
+        let file = file_ops::get_file_by_id(&db, file_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(file.download_count, 1);
+        let response = reqwest::get(&url).await.unwrap();
+        assert!(!response.status().is_success());
+    }
+
+    #[tokio::test]
+    async fn test_upload_with_slug() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let upload = |slug: &str| {
+            let form = reqwest::multipart::Form::new()
+                .part(
+                    "file_data",
+                    reqwest::multipart::Part::bytes(b"hello".to_vec()),
+                )
+                .text("filename", "hello.txt");
+            client
+                .post(format!("{}/api/upload?slug={}", base, slug))
+                .multipart(form)
+                .send()
+        };
+
+        let response = upload("release-notes").await.unwrap();
+        assert_eq!(response.status(), 200);
+        let uploaded: UploadResponse = response.json().await.unwrap();
+        assert_eq!(uploaded.slug.as_deref(), Some("release-notes"));
+        assert!(uploaded
+            .curl_command
+            .contains("/api/files/by-slug/release-notes/download"));
+
+        // Existence checks reveal only existence and encryption
+        for url in [
+            format!("{}/api/files/{}", base, uploaded.file_id),
+            format!("{}/api/files/by-slug/release-notes", base),
+        ] {
+            let response = client.head(url).send().await.unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()["x-file-exists"], "true");
+            assert_eq!(response.headers()["x-file-encrypted"], "true");
+            assert!(response.headers().get("x-original-size").is_none());
+        }
+        let response = client
+            .head(format!("{}/api/files/{}", base, Uuid::new_v4()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        // Taken slugs and invalid charsets are rejected
+        assert_eq!(upload("release-notes").await.unwrap().status(), 409);
+        assert_eq!(upload("Release_Notes").await.unwrap().status(), 400);
+
+        // The slug resolves to the file, alongside the UUID
+        let key = uploaded.encryption_key.unwrap();
+        for url in [
+            format!("{}/api/files/by-slug/release-notes/download", base),
+            format!("{}/api/files/{}/download", base, uploaded.file_id),
+        ] {
+            let response = client
+                .post(url)
+                .form(&[("encryption_key", key.as_str())])
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+        }
+
+        let response = client
+            .post(format!("{}/api/files/by-slug/unknown/download", base))
+            .form(&[("encryption_key", "")])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        let error: ErrorResponse = response.json().await.unwrap();
+        assert_eq!(error.code, error_code::FILE_NOT_FOUND);
+
+        // Missing and wrong keys are told apart by their error code
+        for (key, code) in [
+            ("", error_code::ENCRYPTION_KEY_REQUIRED),
+            ("not-a-key", error_code::INVALID_ENCRYPTION_KEY),
+        ] {
+            let response = client
+                .post(format!("{}/api/files/by-slug/release-notes/download", base))
+                .form(&[("encryption_key", key)])
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 400);
+            let error: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(error.code, code);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slug_taken_during_chunked_upload_conflicts() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let db = state.db.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let started: StartUploadResponse = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({ "filename": "a.txt", "file_size": 5, "slug": "notes" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let form = reqwest::multipart::Form::new()
+            .part("chunk", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+        let response = client
+            .post(format!("{}/api/upload/chunk/{}/0", base, started.upload_id))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        // Another upload that checked the slug at the same time got there first
+        file_ops::create_unencrypted_file_record(
+            &db,
+            CreateUnencryptedFileParams {
+                filename: b"b.txt".to_vec(),
+                slug: Some("notes".to_string()),
+                inline_data: Some(b"hello".to_vec()),
+                ..CreateUnencryptedFileParams::for_tests(Uuid::new_v4(), String::new())
+            },
+        )
+        .await
+        .unwrap();
+
+        let response = client
+            .post(format!("{}/api/upload/complete", base))
+            .json(&CompleteUploadRequest {
+                upload_id: started.upload_id,
+                encrypted_filename: None,
+                filename_nonce: None,
+                signature: None,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 409);
+        let error: ErrorResponse = response.json().await.unwrap();
+        assert_eq!(error.code, error_code::CONFLICT);
+
+        // The upload can't complete anymore, so it is discarded
+        assert!(
+            upload_session_ops::get_upload_session_by_id(&db, started.upload_id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_upload_is_rejected_while_streaming() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_file_size: 1024,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let upload = |size: usize| {
+            let form = reqwest::multipart::Form::new()
+                .text("filename", "data.bin")
+                .part(
+                    "file_data",
+                    reqwest::multipart::Part::bytes(vec![0u8; size]),
+                );
+            client
+                .post(format!("{}/api/upload", base))
+                .multipart(form)
+                .send()
+        };
+
+        assert_eq!(upload(1024).await.unwrap().status(), 200);
+        assert_eq!(upload(1025).await.unwrap().status(), 413);
+
+        // Bodies past the route limit are refused before reaching the handler
+        assert_eq!(upload(1024 * 1024).await.unwrap().status(), 413);
+
+        // No spooled data is left behind
+        let mut temp_files = tokio::fs::read_dir(temp_dir.path().join("storage").join("tmp"))
+            .await
+            .unwrap();
+        assert!(temp_files.next_entry().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_chunk_uploads_are_all_counted() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let db = state.db.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let data: Vec<u8> = (0..64).collect();
+        let started: StartUploadResponse = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({
+                "filename": "data.bin",
+                "file_size": data.len(),
+                "chunk_size": 4,
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(started.total_chunks, 16);
+
+        let uploads = data.chunks(4).enumerate().map(|(chunk_number, chunk)| {
+            let form = reqwest::multipart::Form::new()
+                .part("chunk", reqwest::multipart::Part::bytes(chunk.to_vec()));
+            let request = client
+                .post(format!(
+                    "{}/api/upload/chunk/{}/{}",
+                    base, started.upload_id, chunk_number
+                ))
+                .multipart(form);
+            tokio::spawn(async move { request.send().await.unwrap().status() })
+        });
+        for upload in uploads.collect::<Vec<_>>() {
+            assert_eq!(upload.await.unwrap(), 200);
+        }
+
+        let session = upload_session_ops::get_upload_session_by_id(&db, started.upload_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.uploaded_chunks, 16);
+
+        let status: UploadStatusResponse = client
+            .get(format!("{}/api/upload/status/{}", base, started.upload_id))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status.uploaded_chunks, 16);
+        assert_eq!(status.uploaded_chunk_numbers, (0..16).collect::<Vec<_>>());
+        assert!(status.is_complete);
+
+        let response = client
+            .post(format!("{}/api/upload/complete", base))
+            .json(&CompleteUploadRequest {
+                upload_id: started.upload_id,
+                encrypted_filename: None,
+                filename_nonce: None,
+                signature: None,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_copies_of_a_chunk_count_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let db = state.db.clone();
+        let storage_dir = state.config.storage_dir.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let data: Vec<u8> = (0..8).collect();
+        let started: StartUploadResponse = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({
+                "filename": "data.bin",
+                "file_size": data.len(),
+                "chunk_size": 4,
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        // Retries of one chunk racing each other past the existence check
+        let chunk_url = |chunk_number: usize| {
+            format!(
+                "{}/api/upload/chunk/{}/{}",
+                base, started.upload_id, chunk_number
+            )
+        };
+        let uploads = (0..16).map(|_| {
+            let request = client
+                .put(chunk_url(0))
+                .header("content-type", "application/octet-stream")
+                .body(data[..4].to_vec());
+            tokio::spawn(async move { request.send().await.unwrap().status() })
+        });
+        for upload in uploads.collect::<Vec<_>>() {
+            assert_eq!(upload.await.unwrap(), 200);
+        }
+
+        let session = upload_session_ops::get_upload_session_by_id(&db, started.upload_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.uploaded_chunks, 1);
+        assert!(!session.is_complete());
+
+        let status: UploadStatusResponse = client
+            .get(format!("{}/api/upload/status/{}", base, started.upload_id))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status.uploaded_chunks, 1);
+        assert_eq!(status.uploaded_chunk_numbers, vec![0]);
+
+        // No copy left a temporary file behind
+        let chunk_dir = std::path::Path::new(&storage_dir)
+            .join("chunks")
+            .join(started.upload_id.to_string());
+        assert_eq!(std::fs::read_dir(&chunk_dir).unwrap().count(), 1);
+
+        let response = client
+            .put(chunk_url(1))
+            .header("content-type", "application/octet-stream")
+            .body(data[4..].to_vec())
+            .send()
+            .await
+            .unwrap();
+        let uploaded: ChunkUploadResponse = response.json().await.unwrap();
+        assert_eq!(uploaded.uploaded_chunks, 2);
+
+        let response = client
+            .post(format!("{}/api/upload/complete", base))
+            .json(&CompleteUploadRequest {
+                upload_id: started.upload_id,
+                encrypted_filename: None,
+                filename_nonce: None,
+                signature: None,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_chunk_writes_are_limited_per_upload() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_concurrent_chunks_per_upload: 2,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let chunk_writes = state.chunk_writes.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let data: Vec<u8> = (0..32).collect();
+        let start = |filename: &'static str| {
+            client
+                .post(format!("{}/api/upload/start", base))
+                .json(&serde_json::json!({
+                    "filename": filename,
+                    "file_size": data.len(),
+                    "chunk_size": 4,
+                }))
+                .send()
+        };
+        let started: StartUploadResponse = start("data.bin").await.unwrap().json().await.unwrap();
+        let other: StartUploadResponse = start("other.bin").await.unwrap().json().await.unwrap();
+        let put_chunk = |upload_id: Uuid, chunk_number: usize| {
+            let request = client
+                .put(format!(
+                    "{}/api/upload/chunk/{}/{}",
+                    base, upload_id, chunk_number
+                ))
+                .header("Content-Type", "application/octet-stream")
+                .body(data[chunk_number * 4..(chunk_number + 1) * 4].to_vec());
+            tokio::spawn(async move { request.send().await.unwrap() })
+        };
+
+        // While the upload has as many chunks being written as allowed, a burst
+        // of further chunks is turned away
+        let writes_in_progress = [
+            chunk_writes.try_acquire(started.upload_id).unwrap(),
+            chunk_writes.try_acquire(started.upload_id).unwrap(),
+        ];
+        let burst: Vec<_> = (0..8)
+            .map(|chunk_number| put_chunk(started.upload_id, chunk_number))
+            .collect();
+        for response in burst {
+            let response = response.await.unwrap();
+            assert_eq!(response.status(), 429);
+            assert_eq!(response.headers()["retry-after"], "1");
+            let body: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(body.code, error_code::TOO_MANY_CHUNK_WRITES);
+        }
+
+        // Other uploads are not held back
+        let response = put_chunk(other.upload_id, 0).await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        drop(writes_in_progress);
+        for chunk_number in 0..8 {
+            let response = put_chunk(started.upload_id, chunk_number).await.unwrap();
+            assert_eq!(response.status(), 200);
+        }
+        let status: UploadStatusResponse = client
+            .get(format!("{}/api/upload/status/{}", base, started.upload_id))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(status.is_complete);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_with_encrypted_filename() {
+        use base64::{engine::general_purpose, Engine as _};
+        use kusatsu_encrypt::{Encryption, EncryptionKey};
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let start = || async {
+            let started: StartUploadResponse = client
+                .post(format!("{}/api/upload/start", base))
+                .json(&serde_json::json!({ "filename": "upload", "file_size": 5 }))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            let form = reqwest::multipart::Form::new()
+                .part("chunk", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+            client
+                .post(format!("{}/api/upload/chunk/{}/0", base, started.upload_id))
+                .multipart(form)
+                .send()
+                .await
+                .unwrap();
+            started.upload_id
+        };
+
+        let key = EncryptionKey::generate();
+        let filename = Encryption::encrypt(b"secret-plans.txt", &key).unwrap();
+
+        // Ciphertext without its nonce is rejected
+        let response = client
+            .post(format!("{}/api/upload/complete", base))
+            .json(&CompleteUploadRequest {
+                upload_id: start().await,
+                encrypted_filename: Some(general_purpose::STANDARD.encode(&filename.ciphertext)),
+                filename_nonce: None,
+                signature: None,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload/complete", base))
+            .json(&CompleteUploadRequest {
+                upload_id: start().await,
+                encrypted_filename: Some(general_purpose::STANDARD.encode(&filename.ciphertext)),
+                filename_nonce: Some(general_purpose::STANDARD.encode(&filename.nonce)),
+                signature: None,
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(uploaded.curl_command.contains(KEY_PLACEHOLDER));
+
+        // The name can't be read without the key
+        let response = client
+            .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+            .json(&DownloadRequest {
+                encryption_key: None,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let body: ErrorResponse = response.json().await.unwrap();
+        assert_eq!(body.code, error_code::ENCRYPTION_KEY_REQUIRED);
+
+        let info: FileInfo = client
+            .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+            .json(&DownloadRequest {
+                encryption_key: Some(key.to_base64()),
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info.filename, "secret-plans.txt");
+        assert!(!info.is_encrypted);
+
+        let response = client
+            .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+            .form(&[("encryption_key", key.to_base64())])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["content-disposition"],
+            "attachment; filename=\"secret-plans.txt\"; filename*=UTF-8''secret-plans.txt"
+        );
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_stored_files_carry_a_header_and_legacy_ones_still_download() {
+        use kusatsu_encrypt::{EncryptedData, Encryption, EncryptionKey};
+        use tokio::io::AsyncReadExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let storage_dir = temp_dir.path().join("files");
+        let storage = Arc::new(LocalFileStorage::new(&storage_dir));
+        storage.init().await.unwrap();
+        let state = AppState {
+            storage: storage.clone(),
+            ..test_state(config).await
+        };
+        let db = state.db.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        // New uploads can be recovered from the stored file alone
+        let form = reqwest::multipart::Form::new()
+            .text("filename", "notes.txt")
+            .part(
+                "file_data",
+                reqwest::multipart::Part::bytes(b"hello".to_vec()),
+            );
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let key = EncryptionKey::from_url_encoded(&uploaded.encryption_key.unwrap()).unwrap();
+        let record = file_ops::get_file_by_id(&db, uploaded.file_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (header, mut reader, size) = storage.read_container(&record.file_path).await.unwrap();
+        let header = header.unwrap();
+        assert_eq!(header.nonce, record.nonce);
+        assert_eq!(
+            header.key_fingerprint,
+            Some(container::key_fingerprint(&key))
+        );
+        assert_eq!(
+            (header.original_size, size),
+            (5, record.encrypted_size as u64)
+        );
+        let mut ciphertext = Vec::new();
+        reader.read_to_end(&mut ciphertext).await.unwrap();
+        let decrypted = Encryption::decrypt(
+            &EncryptedData {
+                ciphertext,
+                nonce: header.nonce,
+                key_size: key.size(),
+            },
+            &key,
+        )
+        .unwrap();
+        assert_eq!(decrypted, b"hello");
+
+        // Files stored before the format hold the bare ciphertext
+        let content = Encryption::encrypt(b"legacy content", &key).unwrap();
+        let filename = Encryption::encrypt(b"legacy.txt", &key).unwrap();
+        let file_id = Uuid::new_v4();
+        let file_path = storage::relative_file_path(file_id);
+        std::fs::create_dir_all(storage_dir.join(&file_path).parent().unwrap()).unwrap();
+        std::fs::write(storage_dir.join(&file_path), &content.ciphertext).unwrap();
+        file_ops::create_file_record(
+            &db,
+            crate::database::CreateFileParams {
+                file_id,
+                original_size: 14,
+                encrypted_size: content.ciphertext.len() as i64,
+                mime_type: None,
+                file_path: file_path.to_string_lossy().to_string(),
+                nonce: content.nonce,
+                encrypted_filename: filename.ciphertext,
+                filename_nonce: filename.nonce,
+                expires_at: None,
+                max_downloads: None,
+                burn_after_first_download_minutes: None,
+                expire_after_inactivity_hours: None,
+                owner_token: "owner".to_string(),
+                slug: None,
+                signature: None,
+                inline_data: None,
+                compression: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        for (file_id, content) in [
+            (uploaded.file_id, &b"hello"[..]),
+            (file_id, b"legacy content"),
+        ] {
+            let response = client
+                .post(format!("{}/api/files/{}/download", base, file_id))
+                .form(&[("encryption_key", key.to_base64())])
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.bytes().await.unwrap().as_ref(), content);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openapi_spec_and_docs() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(test_config(&temp_dir)).await).await;
+
+        let spec: serde_json::Value = reqwest::get(format!("{}/api/openapi.json", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(spec["openapi"], "3.0.3");
+        assert!(spec["paths"]["/api/upload/start"]["post"].is_object());
+        assert!(spec["paths"]["/api/files/by-slug/{slug}/raw"]["get"].is_object());
+        assert!(spec["components"]["schemas"]["ErrorResponse"].is_object());
+
+        // Every referenced schema is defined
+        let text = spec.to_string();
+        for reference in text.split("#/components/schemas/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(
+                spec["components"]["schemas"][name].is_object(),
+                "missing schema {}",
+                name
+            );
+        }
+
+        // The UI is opt-in
+        let response = reqwest::get(format!("{}/api/docs", base)).await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(
+            test_state(Config {
+                api_docs: true,
+                ..test_config(&temp_dir)
+            })
+            .await,
+        )
+        .await;
+        let response = reqwest::get(format!("{}/api/docs", base)).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let page = response.text().await.unwrap();
+        assert!(page.contains("/api/openapi.json"));
+        // Loaded from an exact release, not whatever the CDN serves for a major version
+        assert!(page.contains("https://unpkg.com/swagger-ui-dist@5.17.14/swagger-ui-bundle.js"));
+
+        // Or from assets the server was given
+        let temp_dir = TempDir::new().unwrap();
+        let assets = temp_dir.path().join("swagger-ui");
+        std::fs::create_dir(&assets).unwrap();
+        std::fs::write(assets.join("swagger-ui-bundle.js"), "// bundle").unwrap();
+        let base = spawn_app(
+            test_state(Config {
+                api_docs: true,
+                api_docs_assets: Some(assets.to_string_lossy().into_owned()),
+                ..test_config(&temp_dir)
+            })
+            .await,
+        )
+        .await;
+        let page = reqwest::get(format!("{}/api/docs", base))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(page.contains(r#"<script src="/api/docs/assets/swagger-ui-bundle.js">"#));
+        assert!(!page.contains("unpkg.com"));
+        let bundle = reqwest::get(format!("{}/api/docs/assets/swagger-ui-bundle.js", base))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(bundle, "// bundle");
+    }
+
+    #[tokio::test]
+    async fn test_default_expiry_applies_when_omitted() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(
+            test_state(Config {
+                default_expiry_hours: Some(24),
+                ..test_config(&temp_dir)
+            })
+            .await,
+        )
+        .await;
+        let client = reqwest::Client::new();
+
+        let upload = |query: &'static str| {
+            let form = reqwest::multipart::Form::new()
+                .part(
+                    "file_data",
+                    reqwest::multipart::Part::bytes(b"hello".to_vec()),
+                )
+                .text("filename", "hello.txt");
+            client
+                .post(format!("{}/api/upload{}", base, query))
+                .multipart(form)
+                .send()
+        };
+
+        // Without an expiry the default applies, and is reported back
+        let response: UploadResponse = upload("").await.unwrap().json().await.unwrap();
+        let lifetime = response.expires_at.unwrap() - chrono::Utc::now();
+        assert!((23..=24).contains(&lifetime.num_hours()));
+
+        // An explicit expiry still wins
+        let response: UploadResponse = upload("?expires_in_hours=2")
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let lifetime = response.expires_at.unwrap() - chrono::Utc::now();
+        assert!((1..=2).contains(&lifetime.num_hours()));
+
+        let response: StartUploadResponse = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({ "filename": "big.bin", "file_size": 5 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response.expires_in_hours, Some(24));
+    }
+
+    #[tokio::test]
+    async fn test_upload_with_signature() {
+        use kusatsu_encrypt::signing;
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let signing_key = signing::generate_signing_key();
+        let signature = signing::signature_to_base64(&signing::sign(b"hello", &signing_key));
+
+        let upload = |signature: String| {
+            let form = reqwest::multipart::Form::new()
+                .part(
+                    "file_data",
+                    reqwest::multipart::Part::bytes(b"hello".to_vec()),
+                )
+                .text("filename", "hello.txt")
+                .text("signature", signature);
+            client
+                .post(format!("{}/api/upload", base))
+                .multipart(form)
+                .send()
+        };
+
+        // Signatures that aren't 64 base64-encoded bytes are rejected
+        assert_eq!(upload("c2hvcnQ=".to_string()).await.unwrap().status(), 400);
+
+        let response = upload(signature.clone()).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let uploaded: UploadResponse = response.json().await.unwrap();
+        let key = uploaded.encryption_key.unwrap();
+
+        let info: FileInfo = client
+            .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+            .json(&DownloadRequest {
+                encryption_key: Some(key.clone()),
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info.signature.as_deref(), Some(signature.as_str()));
+
+        // The download carries the signature, which verifies the decrypted content
+        let response = client
+            .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+            .form(&[("encryption_key", key.as_str())])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let header = response.headers()["x-signature"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(header, signature);
+        let data = response.bytes().await.unwrap();
+        let signature = signing::signature_from_base64(&header).unwrap();
+        assert!(signing::verify(&data, &signature, &signing_key.verifying_key()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_upload_with_declared_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let upload = |file_size: &str| {
+            let form = reqwest::multipart::Form::new()
+                .text("file_size", file_size.to_string())
+                .part(
+                    "file_data",
+                    reqwest::multipart::Part::bytes(b"hello".to_vec()),
+                )
+                .text("filename", "hello.txt");
+            client
+                .post(format!("{}/api/upload", base))
+                .multipart(form)
+                .send()
+        };
+
+        // A body shorter or longer than declared is rejected
+        for file_size in ["4", "6", "-1", "five"] {
+            let response = upload(file_size).await.unwrap();
+            assert_eq!(response.status(), 400);
+            let error: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(error.code, error_code::BAD_REQUEST);
+        }
+
+        assert_eq!(upload("5").await.unwrap().status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_passphrase_wrapped_key() {
+        use base64::{engine::general_purpose, Engine as _};
+        use kusatsu_encrypt::{key_wrap, EncryptionKey, WrappedKey};
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let form = reqwest::multipart::Form::new()
+            .part(
+                "file_data",
+                reqwest::multipart::Part::bytes(b"hello".to_vec()),
+            )
+            .text("filename", "hello.txt");
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let wrapped_key_url = format!("{}/api/files/{}/wrapped-key", base, uploaded.file_id);
+
+        // Nothing is wrapped yet
+        let response = client.get(&wrapped_key_url).send().await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        let file_key = EncryptionKey::from_base64(&uploaded.encryption_key.unwrap()).unwrap();
+        let salt = key_wrap::generate_salt();
+        let kek = EncryptionKey::from_passphrase("hunter2", &salt).unwrap();
+        let request = WrapKeyRequest {
+            owner_token: "not-the-owner".to_string(),
+            wrapped_key: general_purpose::STANDARD.encode(file_key.wrap(&kek).unwrap().to_bytes()),
+            key_salt: general_purpose::STANDARD.encode(salt),
+        };
+
+        let response = client
+            .post(&wrapped_key_url)
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+
+        let response = client
+            .post(&wrapped_key_url)
+            .json(&WrapKeyRequest {
+                owner_token: uploaded.owner_token.unwrap(),
+                ..request
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        // A recipient with the passphrase recovers the key and downloads the file
+        let stored: WrappedKeyResponse = client
+            .get(&wrapped_key_url)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let salt = general_purpose::STANDARD.decode(&stored.key_salt).unwrap();
+        let wrapped = WrappedKey::from_bytes(
+            &general_purpose::STANDARD
+                .decode(&stored.wrapped_key)
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(EncryptionKey::unwrap(
+            &wrapped,
+            &EncryptionKey::from_passphrase("wrong", &salt).unwrap()
+        )
+        .is_err());
+        let key = EncryptionKey::unwrap(
+            &wrapped,
+            &EncryptionKey::from_passphrase("hunter2", &salt).unwrap(),
+        )
+        .unwrap();
+
+        let response = client
+            .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+            .form(&[("encryption_key", key.to_base64())])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_bundle_listing() {
+        use base64::{engine::general_purpose, Engine as _};
+        use kusatsu_encrypt::{EncryptedData, Encryption, EncryptionKey};
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let form = reqwest::multipart::Form::new()
+            .part(
+                "file_data",
+                reqwest::multipart::Part::bytes(b"not really a tar".to_vec()),
+            )
+            .text("filename", "bundle.tar");
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let encryption_key = uploaded.encryption_key.unwrap();
+        let listing_url = format!("{}/api/files/{}/listing", base, uploaded.file_id);
+        let info = || async {
+            client
+                .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+                .json(&DownloadRequest {
+                    encryption_key: Some(encryption_key.clone()),
+                })
+                .send()
+                .await
+                .unwrap()
+                .json::<FileInfo>()
+                .await
+                .unwrap()
+        };
+
+        // Plain uploads have none
+        assert!(!info().await.has_listing);
+        let response = client.get(&listing_url).send().await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        let listing = BundleListing {
+            entries: vec![
+                BundleEntry {
+                    name: "photos/onsen.jpg".to_string(),
+                    size: 123_456,
+                },
+                BundleEntry {
+                    name: "notes.txt".to_string(),
+                    size: 42,
+                },
+            ],
+        };
+        let key = EncryptionKey::from_base64(&encryption_key).unwrap();
+        let encrypted = Encryption::encrypt(&serde_json::to_vec(&listing).unwrap(), &key).unwrap();
+        let request = ListingRequest {
+            owner_token: "not-the-owner".to_string(),
+            ciphertext: general_purpose::STANDARD.encode(&encrypted.ciphertext),
+            nonce: general_purpose::STANDARD.encode(&encrypted.nonce),
+        };
+
+        let response = client
+            .post(&listing_url)
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+
+        let owner_token = uploaded.owner_token.unwrap();
+        let response = client
+            .post(&listing_url)
+            .json(&ListingRequest {
+                owner_token: owner_token.clone(),
+                ciphertext: general_purpose::STANDARD.encode(vec![0u8; MAX_LISTING_SIZE + 1]),
+                ..request.clone()
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let response = client
+            .post(&listing_url)
+            .json(&ListingRequest {
+                owner_token,
+                ..request
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        // Recipients with the key read it without downloading the bundle
+        assert!(info().await.has_listing);
+        let stored: ListingResponse = client
+            .get(&listing_url)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let decrypted = Encryption::decrypt(
+            &EncryptedData {
+                ciphertext: general_purpose::STANDARD
+                    .decode(&stored.ciphertext)
+                    .unwrap(),
+                nonce: general_purpose::STANDARD.decode(&stored.nonce).unwrap(),
+                key_size: key.size(),
+            },
+            &key,
+        )
+        .unwrap();
+        let decrypted: BundleListing = serde_json::from_slice(&decrypted).unwrap();
+        assert_eq!(decrypted, listing);
+        assert_eq!(info().await.download_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_json() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            json_download_max_size: 8,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let upload = |data: &'static [u8]| {
+            let form = reqwest::multipart::Form::new()
+                .part("file_data", reqwest::multipart::Part::bytes(data.to_vec()))
+                .text("filename", "hello.txt");
+            let request = client
+                .post(format!("{}/api/upload", base))
+                .multipart(form)
+                .send();
+            async move {
+                request
+                    .await
+                    .unwrap()
+                    .json::<UploadResponse>()
+                    .await
+                    .unwrap()
+            }
+        };
+        let download_json = |file_id: uuid::Uuid, encryption_key: Option<String>| {
+            client
+                .post(format!("{}/api/files/{}/download-json", base, file_id))
+                .json(&DownloadRequest { encryption_key })
+                .send()
+        };
+
+        let uploaded = upload(b"hello").await;
+
+        // The key is still required
+        let response = download_json(uploaded.file_id, None).await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        let response = download_json(uploaded.file_id, uploaded.encryption_key.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let downloaded: JsonDownloadResponse = response.json().await.unwrap();
+        assert_eq!(downloaded.filename, "hello.txt");
+        assert_eq!(downloaded.size, 5);
+        assert_eq!(
+            general_purpose::STANDARD
+                .decode(&downloaded.data_base64)
+                .unwrap(),
+            b"hello"
+        );
+
+        // Counted once, like a streaming download
+        let info: FileInfo = client
+            .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+            .json(&DownloadRequest {
+                encryption_key: uploaded.encryption_key,
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info.download_count, 1);
+
+        // Files over the cap are pointed to the streaming endpoint
+        let large = upload(b"hello, world").await;
+        let response = download_json(large.file_id, large.encryption_key.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 413);
+        let error: ErrorResponse = response.json().await.unwrap();
+        assert_eq!(error.code, error_code::FILE_TOO_LARGE);
+        assert!(error.message.contains("/download"));
+    }
+
+    #[tokio::test]
+    async fn test_max_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_chunks: 4,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        for endpoint in ["start", "plan"] {
+            let request = |chunk_size: i32| {
+                client
+                    .post(format!("{}/api/upload/{}", base, endpoint))
+                    .json(&serde_json::json!({
+                        "filename": "a.txt",
+                        "file_size": 20,
+                        "chunk_size": chunk_size,
+                    }))
+                    .send()
+            };
+
+            // Exactly at the limit
+            assert_eq!(request(5).await.unwrap().status(), 200, "{}", endpoint);
+
+            // One chunk over, with the smallest chunk size that fits suggested
+            let response = request(4).await.unwrap();
+            assert_eq!(response.status(), 400, "{}", endpoint);
+            let error: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(error.code, error_code::BAD_REQUEST);
+            assert!(
+                error.message.contains("at least 5 bytes"),
+                "{}",
+                error.message
+            );
+
+            // Far over the limit
+            let response = request(1).await.unwrap();
+            assert_eq!(response.status(), 400, "{}", endpoint);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotate_file_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let form = reqwest::multipart::Form::new()
+            .part(
+                "file_data",
+                reqwest::multipart::Part::bytes(b"hello".to_vec()),
+            )
+            .text("filename", "hello.txt");
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let encryption_key = uploaded.encryption_key.unwrap();
+        let rotate = |owner_token: String| {
+            client
+                .post(format!("{}/api/files/{}/rotate", base, uploaded.file_id))
+                .json(&RotateFileRequest { owner_token })
+                .send()
+        };
+        let download = |file_id: uuid::Uuid| {
+            client
+                .post(format!("{}/api/files/{}/download", base, file_id))
+                .form(&[("encryption_key", encryption_key.as_str())])
+                .send()
+        };
+
+        let response = rotate("not-the-owner".to_string()).await.unwrap();
+        assert_eq!(response.status(), 403);
+
+        let response = rotate(uploaded.owner_token.unwrap()).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let rotated: RotateFileResponse = response.json().await.unwrap();
+        assert_ne!(rotated.file_id, uploaded.file_id);
+        assert!(rotated
+            .download_url
+            .ends_with(&format!("/download/{}", rotated.file_id)));
+
+        // The old ID is gone, the new one serves the same data with the same key
+        let response = download(uploaded.file_id).await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        let response = download(rotated.file_id).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_server_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            max_chunks: 10,
+            default_expiry_hours: Some(24),
+            signing_secret: None,
+            ..test_config(&temp_dir)
+        };
+        let max_file_size = config.max_file_size as i64;
+        let base = spawn_app(test_state(config).await).await;
+
+        let server_config: ServerConfigResponse = reqwest::get(format!("{}/api/config", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(server_config.schema_version, SERVER_CONFIG_SCHEMA_VERSION);
+        assert_eq!(server_config.max_file_size, max_file_size);
+        assert_eq!(server_config.single_upload_max, MAX_SINGLE_UPLOAD_SIZE);
+        assert_eq!(server_config.max_chunk_size, MAX_CHUNK_SIZE);
+        assert_eq!(server_config.max_chunks, 10);
+        assert_eq!(server_config.default_expiry_hours, Some(24));
+        assert!(!server_config.signed_urls_supported);
+        assert!(server_config.password_protection_supported);
+        assert_eq!(server_config.upload_pow_difficulty, None);
+
+        // No challenges when uploads don't need them
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/upload/challenge", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_raw_chunk_upload() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(test_config(&temp_dir)).await).await;
+        let client = reqwest::Client::new();
+
+        let started: StartUploadResponse = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({ "filename": "data.bin", "file_size": 8, "chunk_size": 4 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let put_chunk = |chunk_number: i32, content_type: &'static str, body: &'static [u8]| {
+            client
+                .put(format!(
+                    "{}/api/upload/chunk/{}/{}",
+                    base, started.upload_id, chunk_number
+                ))
+                .header("Content-Type", content_type)
+                .body(body)
+                .send()
+        };
+
+        // Same size checks as multipart chunks
+        let response = put_chunk(0, "application/octet-stream", b"abc")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let response = put_chunk(0, "text/plain", b"abcd").await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        let uploaded: ChunkUploadResponse = put_chunk(0, "application/octet-stream", b"abcd")
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(uploaded.uploaded_chunks, 1);
+
+        // Retries of a stored chunk aren't counted twice
+        let retried: ChunkUploadResponse = put_chunk(0, "application/octet-stream", b"abcd")
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(retried.uploaded_chunks, 1);
+
+        // Both endpoints can feed one session
+        let form = reqwest::multipart::Form::new()
+            .part("chunk", reqwest::multipart::Part::bytes(b"efgh".to_vec()));
+        let response = client
+            .post(format!("{}/api/upload/chunk/{}/1", base, started.upload_id))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload/complete", base))
+            .json(&CompleteUploadRequest {
+                upload_id: started.upload_id,
+                encrypted_filename: None,
+                filename_nonce: None,
+                signature: None,
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let downloaded = client
+            .get(format!("{}/api/files/{}/raw", base, uploaded.file_id))
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(downloaded.as_ref(), b"abcdefgh");
+    }
+
+    #[tokio::test]
+    async fn test_file_info_of_unavailable_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+
+        let create = |expires_at, max_downloads| {
+            let state = state.clone();
+            async move {
+                let file_id = Uuid::new_v4();
+                let file_path = state
+                    .storage
+                    .store_file(file_id, &ContainerHeader::plain(b"hello"), b"hello")
+                    .await
+                    .unwrap();
+                file_ops::create_unencrypted_file_record(
+                    &state.db,
+                    CreateUnencryptedFileParams {
+                        expires_at,
+                        max_downloads,
+                        ..CreateUnencryptedFileParams::for_tests(file_id, file_path)
+                    },
+                )
+                .await
+                .unwrap();
+                file_id
+            }
+        };
+        let expired = create(Some(chrono::Utc::now() - chrono::Duration::hours(1)), None).await;
+        let used_up = create(None, Some(1)).await;
+        assert!(file_ops::try_consume_download(&state.db, used_up, None)
+            .await
+            .unwrap());
+
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+        for (file_id, code) in [
+            (expired, error_code::FILE_EXPIRED),
+            (used_up, error_code::DOWNLOAD_LIMIT_EXCEEDED),
+        ] {
+            let response = client
+                .post(format!("{}/api/files/{}/info", base, file_id))
+                .json(&DownloadRequest {
+                    encryption_key: None,
+                })
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 410);
+            let error: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(error.code, code);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_session_ttl() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            upload_session_ttl_hours: 3,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let db = state.db.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let session_ttl = |file_size: i64| {
+            let client = client.clone();
+            let base = base.clone();
+            let db = db.clone();
+            async move {
+                let started: StartUploadResponse = client
+                    .post(format!("{}/api/upload/start", base))
+                    .json(&serde_json::json!({ "filename": "a.bin", "file_size": file_size }))
+                    .send()
+                    .await
+                    .unwrap()
+                    .json()
+                    .await
+                    .unwrap();
+                let session = upload_session_ops::get_upload_session_by_id(&db, started.upload_id)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                (session.expires_at - session.created_at).num_seconds()
+            }
+        };
+
+        // Small uploads get the configured TTL
+        let ttl = session_ttl(10).await;
+        assert!((3 * 3600 - 5..3 * 3600 + 5).contains(&ttl), "{}", ttl);
+
+        // Large ones get the time they'd take at 1 MB/s on top
+        let ttl = session_ttl(600 * 1024 * 1024).await;
+        assert!((3 * 3600 + 595..3 * 3600 + 605).contains(&ttl), "{}", ttl);
+    }
+
+    #[tokio::test]
+    async fn test_upload_keepalive() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let db = state.db.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let started: StartUploadResponse = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({ "filename": "a.bin", "file_size": 8, "chunk_size": 4 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let keepalive = || {
+            client
+                .post(format!(
+                    "{}/api/upload/{}/keepalive",
+                    base, started.upload_id
+                ))
+                .send()
+        };
+
+        // A session about to expire gets the configured TTL again
+        let soon = chrono::Utc::now() + chrono::Duration::minutes(5);
+        upload_session_ops::extend_upload_session(&db, started.upload_id, soon)
+            .await
+            .unwrap();
+        let kept: UploadKeepaliveResponse = keepalive().await.unwrap().json().await.unwrap();
+        assert!(kept.expires_at > chrono::Utc::now() + chrono::Duration::minutes(55));
+        let session = upload_session_ops::get_upload_session_by_id(&db, started.upload_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.expires_at, kept.expires_at);
+
+        // Fully uploaded sessions only wait for completion
+        for (chunk_number, chunk) in [b"abcd", b"efgh"].into_iter().enumerate() {
+            client
+                .put(format!(
+                    "{}/api/upload/chunk/{}/{}",
+                    base, started.upload_id, chunk_number
+                ))
+                .body(chunk.to_vec())
+                .send()
+                .await
+                .unwrap();
+        }
+        assert_eq!(keepalive().await.unwrap().status(), 400);
+
+        // Expired sessions can't be revived
+        let past = chrono::Utc::now() - chrono::Duration::minutes(1);
+        upload_session_ops::extend_upload_session(&db, started.upload_id, past)
+            .await
+            .unwrap();
+        assert_eq!(keepalive().await.unwrap().status(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_content_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(test_config(&temp_dir)).await).await;
+        let client = reqwest::Client::new();
+
+        let started: StartUploadResponse = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({ "filename": "abc.txt", "file_size": 3, "chunk_size": 2 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        for (chunk_number, chunk) in [&b"ab"[..], &b"c"[..]].into_iter().enumerate() {
+            let response = client
+                .put(format!(
+                    "{}/api/upload/chunk/{}/{}",
+                    base, started.upload_id, chunk_number
+                ))
+                .body(chunk)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+        }
+
+        // SHA-256 of "abc", as sha256sum prints it
+        let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload/complete", base))
+            .json(&CompleteUploadRequest {
+                upload_id: started.upload_id,
+                encrypted_filename: None,
+                filename_nonce: None,
+                signature: None,
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(uploaded.content_sha256.as_deref(), Some(expected));
+
+        // The hash is kept with the file for downloaders to check against
+        let info: FileInfo = client
+            .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+            .json(&DownloadRequest {
+                encryption_key: None,
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info.content_sha256.as_deref(), Some(expected));
+    }
+
+    #[tokio::test]
+    async fn test_inline_storage_threshold() {
+        async fn upload(client: &reqwest::Client, base: &str, data: &'static [u8]) -> Uuid {
+            let started: StartUploadResponse = client
+                .post(format!("{}/api/upload/start", base))
+                .json(&serde_json::json!({
+                    "filename": "small.txt",
+                    "file_size": data.len(),
+                    "chunk_size": 16
+                }))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            client
+                .put(format!("{}/api/upload/chunk/{}/0", base, started.upload_id))
+                .body(data)
+                .send()
+                .await
+                .unwrap();
+            let uploaded: UploadResponse = client
+                .post(format!("{}/api/upload/complete", base))
+                .json(&CompleteUploadRequest {
+                    upload_id: started.upload_id,
+                    encrypted_filename: None,
+                    filename_nonce: None,
+                    signature: None,
+                })
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            uploaded.file_id
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            inline_threshold: 16,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let db = state.db.clone();
+        let storage = state.storage.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        // Just under the threshold: kept in the record, nothing on disk
+        let inline_id = upload(&client, &base, b"fifteen bytes!!").await;
+        let inline_file = file_ops::get_file_by_id(&db, inline_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(inline_file.is_inline());
+        assert!(inline_file.file_path.is_empty());
+        assert!(storage.list_stored_files().await.unwrap().is_empty());
+
+        // At the threshold: stored on disk as usual
+        let stored_id = upload(&client, &base, b"sixteen bytes!!!").await;
+        let stored_file = file_ops::get_file_by_id(&db, stored_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!stored_file.is_inline());
+        assert_eq!(storage.list_stored_files().await.unwrap().len(), 1);
+
+        // Both download the same way
+        for (file_id, expected) in [
+            (inline_id, &b"fifteen bytes!!"[..]),
+            (stored_id, &b"sixteen bytes!!!"[..]),
+        ] {
+            let downloaded = client
+                .get(format!("{}/api/files/{}/raw", base, file_id))
+                .send()
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+            assert_eq!(downloaded.as_ref(), expected);
+        }
+
+        // Inline records don't count as missing from storage
+        let report = file_ops::check_storage_consistency(
+            &db,
+            storage.as_ref(),
+            false,
+            std::time::Duration::ZERO,
+        )
+        .await
+        .unwrap();
+        assert!(report.missing_files.is_empty());
+
+        // Deleting an inline file leaves the stored one alone
+        assert!(
+            file_ops::delete_file_by_id(&db, storage.as_ref(), inline_id)
+                .await
+                .unwrap()
+        );
+        assert!(file_ops::get_file_by_id(&db, inline_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(storage.list_stored_files().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_orphaned_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            admin_token: Some("admin-secret".to_string()),
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let chunk_storage = state.chunk_storage.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        // A live upload keeps its chunks
+        let started: StartUploadResponse = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({ "filename": "data.bin", "file_size": 8, "chunk_size": 4 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let response = client
+            .put(format!("{}/api/upload/chunk/{}/0", base, started.upload_id))
+            .body(&b"abcd"[..])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        // Fresh chunks whose session is gone are removed anyway
+        let orphan_id = Uuid::new_v4();
+        chunk_storage
+            .store_chunk(orphan_id, 0, b"lost")
+            .await
+            .unwrap();
+
+        // Only admins may sweep storage; a prefix or extension of the token isn't it
+        for request in [
+            client.post(format!("{}/api/admin/cleanup/chunks", base)),
+            client
+                .post(format!("{}/api/admin/cleanup/chunks", base))
+                .bearer_auth("wrong"),
+            client
+                .post(format!("{}/api/admin/cleanup/chunks", base))
+                .bearer_auth("admin-secre"),
+            client
+                .post(format!("{}/api/admin/cleanup/chunks", base))
+                .bearer_auth("admin-secret2"),
+        ] {
+            assert_eq!(request.send().await.unwrap().status(), 401);
+        }
+        assert!(chunk_storage.chunk_exists(orphan_id, 0).await.unwrap());
+
+        let cleanup: CleanupResponse = client
+            .post(format!("{}/api/admin/cleanup/chunks", base))
+            .bearer_auth("admin-secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(cleanup.items_cleaned, 1);
+        assert!(!chunk_storage.chunk_exists(orphan_id, 0).await.unwrap());
+        assert!(chunk_storage
+            .chunk_exists(started.upload_id, 0)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_upload_memory_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_concurrent_upload_size: 1024 * 1024,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let upload_memory = state.upload_memory.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        // 100 KB uploads hold 200 KB each (plaintext and ciphertext)
+        let upload = || {
+            let form = reqwest::multipart::Form::new()
+                .text("filename", "data.bin")
+                .part(
+                    "file_data",
+                    reqwest::multipart::Part::bytes(vec![0u8; 100 * 1024]),
+                );
+            client
+                .post(format!("{}/api/upload", base))
+                .multipart(form)
+                .send()
+        };
+
+        // Four uploads in progress take 800 KB of the 1 MB budget, so a fifth fits
+        let in_flight: Vec<_> = (0..4)
+            .map(|_| upload_memory.try_reserve(200 * 1024).unwrap())
+            .collect();
+        assert_eq!(upload().await.unwrap().status(), 200);
+
+        // With a fifth one in progress, the next upload is turned away
+        let fifth = upload_memory.try_reserve(200 * 1024).unwrap();
+        let response = upload().await.unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(response.headers()["retry-after"], "5");
+        let body: ErrorResponse = response.json().await.unwrap();
+        assert_eq!(body.code, error_code::SERVER_BUSY);
+
+        // Capacity comes back as uploads finish
+        drop(fifth);
+        drop(in_flight);
+        assert_eq!(upload().await.unwrap().status(), 200);
+    }
+
+    // Upload a small file with the given name and mime type, returning the response
+    async fn upload_typed(base: &str, filename: &str, mime_type: &str) -> reqwest::Response {
+        let form = reqwest::multipart::Form::new()
+            .text("filename", filename.to_string())
+            .text("mime_type", mime_type.to_string())
+            .part(
+                "file_data",
+                reqwest::multipart::Part::bytes(b"data".to_vec()),
+            );
+        reqwest::Client::new()
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // Start a chunked upload with the given name and mime type, returning the response
+    async fn start_typed(base: &str, filename: &str, mime_type: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({
+                "filename": filename,
+                "file_size": 1024,
+                "mime_type": mime_type,
+            }))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    fn file_type_config(temp_dir: &TempDir) -> Config {
+        Config {
+            allowed_mime_types: vec!["image/*".to_string(), "application/pdf".to_string()],
+            denied_extensions: vec!["exe".to_string(), "tar.gz".to_string()],
+            ..test_config(temp_dir)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_type_allowed() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(file_type_config(&temp_dir)).await).await;
+
+        let response = upload_typed(&base, "photo.PNG", "image/png").await;
+        assert_eq!(response.status(), 200);
+        let response = start_typed(&base, "report.pdf", "application/pdf; charset=binary").await;
+        assert_eq!(response.status(), 200);
+
+        // Empty lists allow anything
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(test_config(&temp_dir)).await).await;
+        let response = upload_typed(&base, "setup.exe", "application/x-msdownload").await;
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_file_type_denied_by_extension() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(file_type_config(&temp_dir)).await).await;
+
+        // An allowed mime type doesn't make a denied extension acceptable
+        for response in [
+            upload_typed(&base, "setup.EXE", "image/png").await,
+            start_typed(&base, "backup.tar.gz", "image/png").await,
+        ] {
+            assert_eq!(response.status(), 415);
+            let body: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(body.code, error_code::FILE_TYPE_NOT_ALLOWED);
+        }
+
+        // Only whole extensions match
+        let response = upload_typed(&base, "notexe", "image/png").await;
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_filename_is_not_kept_when_starting() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(file_type_config(&temp_dir)).await).await;
+        let client = reqwest::Client::new();
+        let start = |filename: &str| {
+            client
+                .post(format!("{}/api/upload/start", base))
+                .json(&serde_json::json!({
+                    "filename": filename,
+                    "file_size": 1024,
+                    "mime_type": "image/png",
+                    "filename_encrypted": true,
+                }))
+                .send()
+        };
+
+        // Only the extensions are kept, and still checked
+        assert_eq!(
+            filename_placeholder("tax return.2024.tar.gz"),
+            "file.tar.gz"
+        );
+        assert_eq!(filename_placeholder("README"), "file");
+        let response = start(&filename_placeholder("backup.tar.gz")).await.unwrap();
+        assert_eq!(response.status(), 415);
+
+        // Even a client sending the whole name doesn't get it stored
+        let started: StartUploadResponse = start("holiday photo.png")
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let status: UploadStatusResponse = client
+            .get(format!("{}/api/upload/status/{}", base, started.upload_id))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status.filename, "file.png");
+    }
+
+    #[tokio::test]
+    async fn test_file_type_denied_by_mime() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(file_type_config(&temp_dir)).await).await;
+
+        for response in [
+            upload_typed(&base, "page.html", "text/html").await,
+            start_typed(&base, "page.html", "text/html").await,
+            // Without a declared type there is nothing to allow
+            upload_typed(&base, "photo.png", "").await,
+        ] {
+            assert_eq!(response.status(), 415);
+            let body: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(body.code, error_code::FILE_TYPE_NOT_ALLOWED);
+        }
+
+        // Plans are held to the same lists
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/upload/plan", base))
+            .json(&serde_json::json!({
+                "filename": "page.html",
+                "file_size": 1024,
+                "mime_type": "text/html",
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 415);
+    }
+
+    #[tokio::test]
+    async fn test_client_encrypted_upload_without_server_side_encryption() {
+        use base64::{engine::general_purpose, Engine as _};
+        use kusatsu_encrypt::{Encryption, EncryptionKey};
+
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(
+            test_state(Config {
+                server_side_encryption: false,
+                ..test_config(&temp_dir)
+            })
+            .await,
+        )
+        .await;
+        let client = reqwest::Client::new();
+
+        let server_config: ServerConfigResponse = client
+            .get(format!("{}/api/config", base))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(!server_config.server_side_encryption);
+
+        // Plaintext is turned away
+        let form = reqwest::multipart::Form::new()
+            .text("filename", "plain.txt")
+            .part("file", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+        let response = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        // Content and filename encrypted by the client are stored as sent
+        let key = EncryptionKey::generate();
+        let contents = Encryption::encrypt(b"top secret", &key).unwrap();
+        let filename = Encryption::encrypt(b"secret.txt", &key).unwrap();
+        let form = reqwest::multipart::Form::new()
+            .text("encrypted", "true")
+            .text("nonce", general_purpose::STANDARD.encode(&contents.nonce))
+            .text(
+                "encrypted_filename",
+                general_purpose::STANDARD.encode(&filename.ciphertext),
+            )
+            .text(
+                "filename_nonce",
+                general_purpose::STANDARD.encode(&filename.nonce),
+            )
+            .part("file", reqwest::multipart::Part::bytes(contents.ciphertext));
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        // The server never had the key
+        assert!(uploaded.encryption_key.is_none());
+        assert!(!uploaded.download_url.contains('#'));
+        assert!(uploaded.curl_command.contains(KEY_PLACEHOLDER));
+
+        let info: FileInfo = client
+            .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+            .json(&DownloadRequest {
+                encryption_key: Some(key.to_base64()),
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info.original_size, 10);
+        assert_eq!(info.filename, "secret.txt");
+
+        let response = client
+            .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+            .form(&[("encryption_key", key.to_base64())])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["content-disposition"],
+            "attachment; filename=\"secret.txt\"; filename*=UTF-8''secret.txt"
+        );
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"top secret");
+
+        // A nonce of the wrong size is rejected
+        let form = reqwest::multipart::Form::new()
+            .text("encrypted", "true")
+            .text("filename", "data.bin")
+            .text("nonce", general_purpose::STANDARD.encode([0u8; 8]))
+            .part("file", reqwest::multipart::Part::bytes(vec![0u8; 32]));
+        let response = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_owner_can_delete_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let storage = state.storage.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let form = reqwest::multipart::Form::new()
+            .text("filename", "bench.bin")
+            .part("file", reqwest::multipart::Part::bytes(vec![1u8; 1024]));
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let delete_url = format!("{}/api/files/{}/delete", base, uploaded.file_id);
+
+        let response = client
+            .post(&delete_url)
+            .json(&DeleteFileRequest {
+                owner_token: "wrong".to_string(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+
+        let response = client
+            .post(&delete_url)
+            .json(&DeleteFileRequest {
+                owner_token: uploaded.owner_token.clone().unwrap(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+
+        // The record and the stored data are both gone
+        let response = client
+            .head(format!("{}/api/files/{}", base, uploaded.file_id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        assert!(storage.list_stored_files().await.unwrap().is_empty());
+
+        let response = client
+            .post(&delete_url)
+            .json(&DeleteFileRequest {
+                owner_token: uploaded.owner_token.unwrap(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_admin_bulk_delete_with_filters() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            admin_token: Some("admin-secret".to_string()),
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let storage = state.storage.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let mut file_ids = Vec::new();
+        for (filename, mime_type) in [
+            ("photo.png", "image/png"),
+            ("setup.exe", "application/x-msdownload"),
+            ("update.exe", "application/x-msdownload"),
+        ] {
+            let uploaded: UploadResponse = upload_typed(&base, filename, mime_type)
+                .await
+                .json()
+                .await
+                .unwrap();
+            file_ids.push(uploaded.file_id);
+        }
+        let delete_url = format!("{}/api/admin/files/delete", base);
+        let bulk_delete = |body: serde_json::Value| {
+            client
+                .post(&delete_url)
+                .bearer_auth("admin-secret")
+                .json(&body)
+                .send()
+        };
+
+        let response = client
+            .post(&delete_url)
+            .json(&serde_json::json!({ "mime_type": "application/x-msdownload" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        // Without a filter, or with a misspelled one, nothing is selected
+        let response = bulk_delete(serde_json::json!({ "dry_run": true }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let response = bulk_delete(serde_json::json!({ "mimetype": "image/png" }))
+            .await
+            .unwrap();
+        assert!(response.status().is_client_error());
+
+        // A dry run only reports the matches
+        let report: serde_json::Value = bulk_delete(serde_json::json!({
+            "mime_type": "application/x-msdownload",
+            "dry_run": true,
+        }))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+        assert_eq!(report["files_matched"], 2);
+        assert_eq!(report["files_deleted"], 0);
+        assert_eq!(
+            report["sample_file_ids"],
+            serde_json::json!([file_ids[1], file_ids[2]])
+        );
+        assert_eq!(storage.list_stored_files().await.unwrap().len(), 3);
+
+        // Every set filter must match
+        let report: serde_json::Value = bulk_delete(serde_json::json!({
+            "mime_type": "application/x-msdownload",
+            "file_ids": [file_ids[0], file_ids[1]],
+            "created_before": chrono::Utc::now() + chrono::Duration::minutes(1),
+        }))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+        assert_eq!(report["files_deleted"], 1);
+
+        let mut statuses = Vec::new();
+        for file_id in &file_ids {
+            let response = client
+                .head(format!("{}/api/files/{}", base, file_id))
+                .send()
+                .await
+                .unwrap();
+            statuses.push(response.status().as_u16());
+        }
+        assert_eq!(statuses, [200, 404, 200]);
+        assert_eq!(storage.list_stored_files().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_upload_is_decompressed_on_download() {
+        use base64::{engine::general_purpose, Engine as _};
+        use flate2::{write::GzEncoder, Compression};
+        use kusatsu_encrypt::{Encryption, EncryptionKey};
+        use std::io::Write;
+
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(test_config(&temp_dir)).await).await;
+        let client = reqwest::Client::new();
+
+        let plaintext = b"hello kusatsu ".repeat(100);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&plaintext).unwrap();
+        let key = EncryptionKey::generate();
+        let contents = Encryption::encrypt(&encoder.finish().unwrap(), &key).unwrap();
+
+        let form = |compression: &str, original_size: Option<usize>, encrypted: bool| {
+            let form = reqwest::multipart::Form::new()
+                .text("encrypted", encrypted.to_string())
+                .text("filename", "notes.txt")
+                .text("nonce", general_purpose::STANDARD.encode(&contents.nonce))
+                .text("compression", compression.to_string())
+                .part(
+                    "file",
+                    reqwest::multipart::Part::bytes(contents.ciphertext.clone()),
+                );
+            match original_size {
+                Some(size) => form.text("original_size", size.to_string()),
+                None => form,
+            }
+        };
+        let upload = |form| {
+            client
+                .post(format!("{}/api/upload", base))
+                .multipart(form)
+                .send()
+        };
+
+        // Compression needs client encryption, a known format and the original size
+        for form in [
+            form("gzip", Some(plaintext.len()), false),
+            form("zstd", Some(plaintext.len()), true),
+            form("gzip", None, true),
+            // Far more than the data could hold at the allowed compression ratio
+            form("gzip", Some(contents.ciphertext.len() * 100 + 1), true),
+        ] {
+            assert_eq!(upload(form).await.unwrap().status(), 400);
+        }
+
+        let uploaded: UploadResponse = upload(form("gzip", Some(plaintext.len()), true))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        let info: FileInfo = client
+            .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+            .json(&DownloadRequest {
+                encryption_key: Some(key.to_base64()),
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info.original_size, plaintext.len() as i64);
+        assert_eq!(info.compression.as_deref(), Some("gzip"));
+
+        // Raw downloads leave decompression to the client
+        let response = client
+            .get(format!("{}/api/files/{}/raw", base, uploaded.file_id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[COMPRESSION_HEADER], "gzip");
+
+        let response = client
+            .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+            .form(&[("encryption_key", key.to_base64())])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), &plaintext[..]);
+    }
+
+    #[tokio::test]
+    async fn test_file_burns_after_first_download() {
+        use kusatsu_entity::file;
+        use sea_orm::{ActiveModelTrait, Set};
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let db = state.db.clone();
+
+        let file_id = Uuid::new_v4();
+        let file_path = state
+            .storage
+            .store_file(file_id, &ContainerHeader::plain(b"hello"), b"hello")
+            .await
+            .unwrap();
+        file_ops::create_unencrypted_file_record(
+            &state.db,
+            CreateUnencryptedFileParams {
+                burn_after_first_download_minutes: Some(1),
+                ..CreateUnencryptedFileParams::for_tests(file_id, file_path)
+            },
+        )
+        .await
+        .unwrap();
+
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+        let download = || async {
+            client
+                .get(format!("{}/api/files/{}/raw", base, file_id))
+                .send()
+                .await
+                .unwrap()
+        };
+
+        // The timer starts with the first download and caps the expiry
+        let response = download().await;
+        assert_eq!(response.status(), 200);
+        let stored = file_ops::get_file_by_id(&db, file_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let first_downloaded_at = stored.first_downloaded_at.unwrap();
+        assert!(stored.expires_at.unwrap() <= chrono::Utc::now() + chrono::Duration::minutes(1));
+
+        // Later downloads within the window still work and don't restart it
+        let response = download().await;
+        assert_eq!(response.status(), 200);
+        let stored = file_ops::get_file_by_id(&db, file_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.first_downloaded_at, Some(first_downloaded_at));
+
+        // Nor can the owner push the expiry back
+        let response = client
+            .post(format!("{}/api/files/{}/extend", base, file_id))
+            .json(&ExtendExpiryRequest {
+                owner_token: "owner".to_string(),
+                expires_in_hours: Some(24),
+                expires_at: None,
+                encryption_key: None,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        // Once the window has passed the file is gone, whatever expires_at says
+        let mut burned: file::ActiveModel = stored.into();
+        burned.first_downloaded_at = Set(Some(chrono::Utc::now() - chrono::Duration::minutes(2)));
+        burned.expires_at = Set(None);
+        burned.update(&db).await.unwrap();
+        let response = download().await;
+        assert_eq!(response.status(), 410);
+    }
+
+    #[tokio::test]
+    async fn test_burn_after_first_download_is_validated() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_expires_in_hours: 1,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        for minutes in [0, 61] {
+            let form = reqwest::multipart::Form::new()
+                .part(
+                    "file_data",
+                    reqwest::multipart::Part::bytes(b"hello".to_vec()),
+                )
+                .text("filename", "hello.txt");
+            let response = client
+                .post(format!(
+                    "{}/api/upload?burn_after_first_download_minutes={}",
+                    base, minutes
+                ))
+                .multipart(form)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 400, "direct upload with {}", minutes);
+
+            let response = client
+                .post(format!("{}/api/upload/start", base))
+                .json(&serde_json::json!({
+                    "filename": "hello.txt",
+                    "file_size": 5,
+                    "burn_after_first_download_minutes": minutes,
+                }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 422, "chunked upload with {}", minutes);
+        }
+
+        // The longest expiry is accepted
+        let response = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({
+                "filename": "hello.txt",
+                "file_size": 5,
+                "burn_after_first_download_minutes": 60,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_extending_expiry_is_checked() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_expires_in_hours: 24,
+            ..test_config(&temp_dir)
+        })
+        .await;
+
+        let expiring = chrono::Utc::now() + chrono::Duration::hours(1);
+        let mut file_ids = Vec::new();
+        for expires_at in [Some(expiring), None] {
+            let file_id = Uuid::new_v4();
+            let file_path = state
+                .storage
+                .store_file(file_id, &ContainerHeader::plain(b"hello"), b"hello")
+                .await
+                .unwrap();
+            file_ops::create_unencrypted_file_record(
+                &state.db,
+                CreateUnencryptedFileParams {
+                    expires_at,
+                    ..CreateUnencryptedFileParams::for_tests(file_id, file_path)
+                },
+            )
+            .await
+            .unwrap();
+            file_ids.push(file_id);
+        }
+        let (file_id, forever_id) = (file_ids[0], file_ids[1]);
+
+        let db = state.db.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+        let extend = |file_id: Uuid, owner_token: &str, expires_in_hours, expires_at| {
+            client
+                .post(format!("{}/api/files/{}/extend", base, file_id))
+                .json(&ExtendExpiryRequest {
+                    owner_token: owner_token.to_string(),
+                    expires_in_hours,
+                    expires_at,
+                    encryption_key: None,
+                })
+                .send()
+        };
+        let stored_expiry = || async {
+            file_ops::get_file_by_id(&db, file_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .expires_at
+        };
+
+        // Only the owner can extend the expiry
+        for owner_token in ["not-the-owner", ""] {
+            let response = extend(file_id, owner_token, Some(12), None).await.unwrap();
+            assert_eq!(response.status(), 403);
+        }
+        let response = client
+            .post(format!("{}/api/files/{}/extend", base, file_id))
+            .json(&serde_json::json!({ "expires_in_hours": 12 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 422);
+
+        // The expiry can't be brought forward, or pushed past the maximum lifetime
+        let earlier = expiring - chrono::Duration::minutes(30);
+        let response = extend(file_id, "owner", None, Some(earlier)).await.unwrap();
+        assert_eq!(response.status(), 400);
+        let response = extend(file_id, "owner", Some(48), None).await.unwrap();
+        assert_eq!(response.status(), 400);
+        let too_late = chrono::Utc::now() + chrono::Duration::hours(25);
+        let response = extend(file_id, "owner", None, Some(too_late))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        // Files that never expire have nothing to extend
+        let response = extend(forever_id, "owner", Some(12), None).await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert!(file_ops::get_file_by_id(&db, forever_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .expires_at
+            .is_none());
+
+        // None of the refused requests moved the expiry
+        let unchanged = stored_expiry().await.unwrap();
+        assert!((unchanged - expiring).num_seconds().abs() < 1);
+
+        let response = extend(file_id, "owner", Some(12), None).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let info: FileInfo = response.json().await.unwrap();
+        let expected = chrono::Utc::now() + chrono::Duration::hours(12);
+        assert!((info.expires_at.unwrap() - expected).num_seconds().abs() < 60);
+        assert_eq!(stored_expiry().await, info.expires_at);
+    }
+
+    #[tokio::test]
+    async fn test_extending_expiry_of_encrypted_files_needs_the_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let db = state.db.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let form = reqwest::multipart::Form::new()
+            .text("filename", "secret.txt")
+            .part("file", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload?expires_in_hours=1", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let owner_token = uploaded.owner_token.unwrap();
+        let extend = |encryption_key: Option<String>| {
+            client
+                .post(format!("{}/api/files/{}/extend", base, uploaded.file_id))
+                .json(&ExtendExpiryRequest {
+                    owner_token: owner_token.clone(),
+                    expires_in_hours: Some(12),
+                    expires_at: None,
+                    encryption_key,
+                })
+                .send()
+        };
+        let stored_expiry = || async {
+            file_ops::get_file_by_id(&db, uploaded.file_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .expires_at
+        };
+        let expires_at = stored_expiry().await;
+
+        // The filename can't be decrypted, so nothing is changed
+        let wrong_key = kusatsu_encrypt::Encryption::generate_key_string();
+        for encryption_key in [None, Some(wrong_key)] {
+            let response = extend(encryption_key).await.unwrap();
+            assert_eq!(response.status(), 400);
+            assert_eq!(stored_expiry().await, expires_at);
+        }
+
+        let response = extend(uploaded.encryption_key).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let info: FileInfo = response.json().await.unwrap();
+        assert_eq!(info.filename, "secret.txt");
+        assert!(info.expires_at > expires_at);
+        assert_eq!(stored_expiry().await, info.expires_at);
+    }
+
+    #[tokio::test]
+    async fn test_downloads_renew_inactivity_expiry_up_to_the_cap() {
+        use kusatsu_entity::file;
+        use sea_orm::{ActiveModelTrait, Set};
+
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_expires_in_hours: 24,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let db = state.db.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        // Uploads start out with the inactivity period as their expiry
+        let form = reqwest::multipart::Form::new()
+            .part(
+                "file_data",
+                reqwest::multipart::Part::bytes(b"hello".to_vec()),
+            )
+            .text("filename", "hello.txt");
+        let response = client
+            .post(format!(
+                "{}/api/upload?expire_after_inactivity_hours=2",
+                base
+            ))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let uploaded: UploadResponse = response.json().await.unwrap();
+        let key = uploaded.encryption_key.unwrap();
+        let near = |expires_at: chrono::DateTime<chrono::Utc>,
+                    expected: chrono::DateTime<chrono::Utc>| {
+            (expires_at - expected).num_seconds().abs() < 60
+        };
+        let expires_at = uploaded.expires_at.unwrap();
+        assert!(near(
+            expires_at,
+            chrono::Utc::now() + chrono::Duration::hours(2)
+        ));
+
+        let info: FileInfo = client
+            .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+            .json(&DownloadRequest {
+                encryption_key: Some(key.clone()),
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(info.expire_after_inactivity_hours, Some(2));
+
+        let set_times = |created_at: chrono::DateTime<chrono::Utc>, expires_at| {
+            let db = db.clone();
+            async move {
+                let stored = file_ops::get_file_by_id(&db, uploaded.file_id)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                let mut stored: file::ActiveModel = stored.into();
+                stored.created_at = Set(created_at);
+                stored.expires_at = Set(Some(expires_at));
+                stored.update(&db).await.unwrap();
+            }
+        };
+        let download = || async {
+            client
+                .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+                .form(&[("encryption_key", key.as_str())])
+                .send()
+                .await
+                .unwrap()
+                .status()
+        };
+        let stored_expiry = || async {
+            file_ops::get_file_by_id(&db, uploaded.file_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .expires_at
+                .unwrap()
+        };
+
+        // A download close to the deadline gives the file its full period back
+        let now = chrono::Utc::now();
+        set_times(
+            now - chrono::Duration::hours(5),
+            now + chrono::Duration::minutes(10),
+        )
+        .await;
+        assert_eq!(download().await, 200);
+        assert!(near(
+            stored_expiry().await,
+            chrono::Utc::now() + chrono::Duration::hours(2)
+        ));
+
+        // But never past the longest lifetime since the upload
+        let created_at = now - chrono::Duration::hours(23);
+        set_times(created_at, now + chrono::Duration::minutes(10)).await;
+        assert_eq!(download().await, 200);
+        assert!(near(
+            stored_expiry().await,
+            created_at + chrono::Duration::hours(24)
+        ));
+
+        // An inactive file stays expired
+        set_times(
+            now - chrono::Duration::hours(5),
+            now - chrono::Duration::minutes(1),
+        )
+        .await;
+        assert_eq!(download().await, 410);
+        assert!(stored_expiry().await < chrono::Utc::now());
+    }
+
+    #[tokio::test]
+    async fn test_inactivity_expiry_is_validated() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(Config {
+            max_expires_in_hours: 24,
+            ..test_config(&temp_dir)
+        })
+        .await;
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        for query in [
+            "expire_after_inactivity_hours=0",
+            "expire_after_inactivity_hours=25",
+            "expire_after_inactivity_hours=2&expires_in_hours=4",
+        ] {
+            let form = reqwest::multipart::Form::new()
+                .part(
+                    "file_data",
+                    reqwest::multipart::Part::bytes(b"hello".to_vec()),
+                )
+                .text("filename", "hello.txt");
+            let response = client
+                .post(format!("{}/api/upload?{}", base, query))
+                .multipart(form)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 400, "{}", query);
+        }
+
+        let response = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({
+                "filename": "hello.txt",
+                "file_size": 5,
+                "expires_in_hours": 4,
+                "expire_after_inactivity_hours": 2,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 422);
+
+        // Chunked uploads start with the inactivity period too
+        let response = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({
+                "filename": "hello.txt",
+                "file_size": 5,
+                "expire_after_inactivity_hours": 2,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let started: StartUploadResponse = response.json().await.unwrap();
+        assert_eq!(started.expires_in_hours, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_upload_status_flags_damaged_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let chunk_storage = state.chunk_storage.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let started: StartUploadResponse = client
+            .post(format!("{}/api/upload/start", base))
+            .json(&serde_json::json!({ "filename": "data.bin", "file_size": 8, "chunk_size": 4 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let put_chunk = |chunk_number: i32, body: &'static [u8]| {
+            client
+                .put(format!(
+                    "{}/api/upload/chunk/{}/{}",
+                    base, started.upload_id, chunk_number
+                ))
+                .header("Content-Type", "application/octet-stream")
+                .body(body)
+                .send()
+        };
+        let status = |verify: bool| {
+            client
+                .get(format!("{}/api/upload/status/{}", base, started.upload_id))
+                .query(&[("verify", verify)])
+                .send()
+        };
+
+        for (chunk_number, body) in [(0, b"abcd"), (1, b"efgh")] {
+            let response = put_chunk(chunk_number, body).await.unwrap();
+            assert_eq!(response.status(), 200);
+        }
+
+        // A short copy left behind, as a cut-off write would
+        chunk_storage
+            .store_chunk(started.upload_id, 0, b"ab")
+            .await
+            .unwrap();
+
+        let unverified: UploadStatusResponse = status(false).await.unwrap().json().await.unwrap();
+        assert_eq!(unverified.verified_chunks, None);
+        assert_eq!(unverified.valid_chunk_numbers(), vec![0, 1]);
+
+        let verified: UploadStatusResponse = status(true).await.unwrap().json().await.unwrap();
+        assert_eq!(
+            verified.verified_chunks.as_deref(),
+            Some(
+                &[
+                    ChunkCheck {
+                        chunk_number: 0,
+                        size: 2,
+                        expected_size: 4,
+                        is_valid: false,
+                    },
+                    ChunkCheck {
+                        chunk_number: 1,
+                        size: 4,
+                        expected_size: 4,
+                        is_valid: true,
+                    },
+                ][..]
+            )
+        );
+        assert_eq!(verified.valid_chunk_numbers(), vec![1]);
+
+        // Every chunk is counted, yet the damaged one can be sent again, without counting it twice
+        let replaced: ChunkUploadResponse =
+            put_chunk(0, b"abcd").await.unwrap().json().await.unwrap();
+        assert_eq!(replaced.uploaded_chunks, 2);
+
+        let verified: UploadStatusResponse = status(true).await.unwrap().json().await.unwrap();
+        assert_eq!(verified.valid_chunk_numbers(), vec![0, 1]);
+
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload/complete", base))
+            .json(&CompleteUploadRequest {
+                upload_id: started.upload_id,
+                encrypted_filename: None,
+                filename_nonce: None,
+                signature: None,
+            })
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let downloaded = client
+            .get(format!("{}/api/files/{}/raw", base, uploaded.file_id))
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(downloaded.as_ref(), b"abcdefgh");
+    }
+
+    #[tokio::test]
+    async fn test_download_of_unicode_filename() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(test_config(&temp_dir)).await).await;
+        let client = reqwest::Client::new();
+
+        let form = reqwest::multipart::Form::new()
+            .text("filename", "温泉 🎉.txt")
+            .part("file", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        let response = client
+            .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+            .form(&[("encryption_key", uploaded.encryption_key.unwrap())])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["content-disposition"],
+            "attachment; filename=\"__ _.txt\"; filename*=UTF-8''%E6%B8%A9%E6%B3%89%20%F0%9F%8E%89.txt"
+        );
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_plain_http_is_rejected_when_https_is_required() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(
+            test_state(Config {
+                require_https: true,
+                ..test_config(&temp_dir)
+            })
+            .await,
+        )
+        .await;
+        let client = reqwest::Client::new();
+        let download = |proto: Option<&'static str>| {
+            let request = client
+                .post(format!("{}/api/files/{}/download", base, Uuid::new_v4()))
+                .form(&[("encryption_key", "key")]);
+            match proto {
+                Some(proto) => request.header(https::FORWARDED_PROTO_HEADER, proto),
+                None => request,
+            }
+            .send()
+        };
+
+        // A client can't pass for HTTPS by sending the header itself, ahead of the proxy's
+        for proto in [Some("http"), Some("https, http"), None] {
+            let response = download(proto).await.unwrap();
+            assert_eq!(response.status(), 426, "{:?}", proto);
+            assert!(response.headers().contains_key("upgrade"));
+            let body: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(body.code, error_code::HTTPS_REQUIRED);
+        }
+
+        // Over HTTPS the request reaches the handler
+        for proto in ["HTTPS", "http, https"] {
+            let response = download(Some(proto)).await.unwrap();
+            assert_eq!(response.status(), 404, "{}", proto);
+        }
+
+        // Nothing secret goes through the health check
+        let response = client
+            .get(format!("{}/health", base))
+            .header(https::FORWARDED_PROTO_HEADER, "http")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_links_follow_forwarded_host_when_trusted() {
+        for trust_forwarded_host in [false, true] {
+            let temp_dir = TempDir::new().unwrap();
+            let base = spawn_app(
+                test_state(Config {
+                    base_url: "https://files.example.com".to_string(),
+                    api_url: "https://files.example.com".to_string(),
+                    trust_forwarded_host,
+                    ..test_config(&temp_dir)
+                })
+                .await,
+            )
+            .await;
+            let client = reqwest::Client::new();
+            let upload = |forwarded: bool| {
+                let form = reqwest::multipart::Form::new()
+                    .part(
+                        "file_data",
+                        reqwest::multipart::Part::bytes(b"hello".to_vec()),
+                    )
+                    .text("filename", "hello.txt");
+                let request = client.post(format!("{}/api/upload", base)).multipart(form);
+                if forwarded {
+                    request
+                        .header(public_url::FORWARDED_HOST_HEADER, "mirror.example.org")
+                        .header(https::FORWARDED_PROTO_HEADER, "http")
+                } else {
+                    request
+                }
+                .send()
+            };
+
+            let uploaded: UploadResponse = upload(true).await.unwrap().json().await.unwrap();
+            let expected = if trust_forwarded_host {
+                "http://mirror.example.org"
+            } else {
+                "https://files.example.com"
+            };
+            assert!(
+                uploaded
+                    .download_url
+                    .starts_with(&format!("{}/download/{}#", expected, uploaded.file_id)),
+                "{}",
+                uploaded.download_url
+            );
+            assert!(uploaded.curl_command.ends_with(&format!(
+                " {}/api/files/{}/download",
+                expected, uploaded.file_id
+            )));
+
+            // Without forwarded headers, the Host the client used goes with the configured scheme
+            let uploaded: UploadResponse = upload(false).await.unwrap().json().await.unwrap();
+            let expected = if trust_forwarded_host {
+                base.replace("http://", "https://")
+            } else {
+                "https://files.example.com".to_string()
+            };
+            assert!(
+                uploaded
+                    .download_url
+                    .starts_with(&format!("{}/download/", expected)),
+                "{}",
+                uploaded.download_url
+            );
+            assert!(uploaded
+                .curl_command
+                .contains(&format!(" {}/api/files/", expected)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_encrypted_upload_rejects_mixed_fields() {
+        use base64::{engine::general_purpose, Engine as _};
+        use kusatsu_encrypt::{Encryption, EncryptionKey};
+
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(test_state(test_config(&temp_dir)).await).await;
+        let client = reqwest::Client::new();
+
+        let key = EncryptionKey::generate();
+        let contents = Encryption::encrypt(b"zero knowledge", &key).unwrap();
+        let filename = Encryption::encrypt(b"notes.txt", &key).unwrap();
+        let nonce = general_purpose::STANDARD.encode(&contents.nonce);
+        let encrypted_form = || {
+            reqwest::multipart::Form::new()
+                .text("encrypted", "true")
+                .text("nonce", nonce.clone())
+                .text(
+                    "filename_ciphertext",
+                    general_purpose::STANDARD.encode(&filename.ciphertext),
+                )
+                .text(
+                    "filename_nonce",
+                    general_purpose::STANDARD.encode(&filename.nonce),
+                )
+                .part(
+                    "file",
+                    reqwest::multipart::Part::bytes(contents.ciphertext.clone()),
+                )
+        };
+        let upload = |form| {
+            client
+                .post(format!("{}/api/upload", base))
+                .multipart(form)
+                .send()
+        };
+
+        for form in [
+            // A nonce for content the server is asked to encrypt
+            reqwest::multipart::Form::new()
+                .text("filename", "notes.txt")
+                .text("nonce", nonce.clone())
+                .part("file", reqwest::multipart::Part::bytes(b"plain".to_vec())),
+            // A plain filename next to an encrypted one
+            encrypted_form().text("filename", "notes.txt"),
+            // A filename nonce of the wrong size
+            encrypted_form().text(
+                "filename_nonce",
+                general_purpose::STANDARD.encode([0u8; 16]),
+            ),
+        ] {
+            let response = upload(form).await.unwrap();
+            assert_eq!(response.status(), 400);
+        }
+
+        // The ciphertext is stored verbatim and only the client's key opens it
+        let uploaded: UploadResponse = upload(encrypted_form())
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(uploaded.encryption_key.is_none());
+
+        let response = client
+            .get(format!("{}/api/files/{}/raw", base, uploaded.file_id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.bytes().await.unwrap().as_ref(),
+            contents.ciphertext.as_slice()
+        );
+
+        let response = client
+            .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+            .form(&[("encryption_key", key.to_base64())])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["content-disposition"],
+            "attachment; filename=\"notes.txt\"; filename*=UTF-8''notes.txt"
+        );
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"zero knowledge");
+    }
+
+    #[tokio::test]
+    async fn test_uploads_require_proof_of_work_when_enabled() {
+        use kusatsu_encrypt::pow;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            require_upload_pow: true,
+            upload_pow_difficulty: 8,
+            ..test_config(&temp_dir)
+        };
+        let base = spawn_app(test_state(config).await).await;
+        let client = reqwest::Client::new();
+
+        let server_config: ServerConfigResponse = reqwest::get(format!("{}/api/config", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(server_config.upload_pow_difficulty, Some(8));
+
+        let challenge = || async {
+            let challenge: UploadChallengeResponse = client
+                .post(format!("{}/api/upload/challenge", base))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            assert_eq!(challenge.difficulty, 8);
+            challenge.challenge
+        };
+        let upload = |query: Vec<(&'static str, String)>| {
+            let form = reqwest::multipart::Form::new()
+                .text("filename", "notes.txt")
+                .part("file", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+            client
+                .post(format!("{}/api/upload", base))
+                .query(&query)
+                .multipart(form)
+                .send()
+        };
+        let assert_rejected = |response: reqwest::Response| async move {
+            assert_eq!(response.status(), 403);
+            let error: ErrorResponse = response.json().await.unwrap();
+            assert_eq!(error.code, error_code::PROOF_OF_WORK_REQUIRED);
+        };
+
+        assert_rejected(upload(Vec::new()).await.unwrap()).await;
+
+        // A wrong solution is rejected, the right one accepted once
+        let pow_challenge = challenge().await;
+        let nonce = pow::solve(&pow_challenge, 8);
+        let wrong_nonce = (0..).find(|&n| !pow::verify(&pow_challenge, n, 8)).unwrap();
+        for (nonce, status) in [(wrong_nonce, 403), (nonce, 200), (nonce, 403)] {
+            let response = upload(vec![
+                ("pow_challenge", pow_challenge.clone()),
+                ("pow_nonce", nonce.to_string()),
+            ])
+            .await
+            .unwrap();
+            assert_eq!(response.status(), status);
+        }
+
+        // Chunked uploads solve one when starting
+        let start = |pow_challenge: Option<String>, pow_nonce: Option<u64>| {
+            client
+                .post(format!("{}/api/upload/start", base))
+                .json(&serde_json::json!({
+                    "filename": "notes.txt",
+                    "file_size": 1024,
+                    "pow_challenge": pow_challenge,
+                    "pow_nonce": pow_nonce,
+                }))
+                .send()
+        };
+        assert_rejected(start(None, None).await.unwrap()).await;
+        let pow_challenge = challenge().await;
+        let nonce = pow::solve(&pow_challenge, 8);
+        let response = start(Some(pow_challenge), Some(nonce)).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_previews_are_refused_for_limited_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = test_state(test_config(&temp_dir)).await;
+        let db = state.db.clone();
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        for (query, previewable) in [
+            ("", true),
+            ("?max_downloads=2", false),
+            ("?burn_after_first_download_minutes=5", false),
+        ] {
+            let form = reqwest::multipart::Form::new()
+                .text("filename", "a.txt")
+                .part("file", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+            let uploaded: UploadResponse = client
+                .post(format!("{}/api/upload{}", base, query))
+                .multipart(form)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+
+            let response = client
+                .get(format!("{}/api/files/{}/preview", base, uploaded.file_id))
+                .send()
+                .await
+                .unwrap();
+            let expected = if previewable { 200 } else { 400 };
+            assert_eq!(response.status(), expected, "{}", query);
+
+            // Either way, the preview didn't count as a first download
+            let stored = file_ops::get_file_by_id(&db, uploaded.file_id)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(stored.download_count, 0);
+            assert!(stored.first_downloaded_at.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_info_cache_is_invalidated_by_downloads() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = spawn_app(
+            test_state(Config {
+                file_info_cache_ttl_secs: 60,
+                ..test_config(&temp_dir)
+            })
+            .await,
+        )
+        .await;
+        let client = reqwest::Client::new();
+
+        let form = reqwest::multipart::Form::new()
+            .text("filename", "popular.txt")
+            .part("file", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+        let uploaded: UploadResponse = client
+            .post(format!("{}/api/upload?max_downloads=2", base))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let key = uploaded.encryption_key.unwrap();
+        let info = || async {
+            client
+                .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+                .json(&DownloadRequest {
+                    encryption_key: Some(key.clone()),
+                })
+                .send()
+                .await
+                .unwrap()
+        };
+
+        // Served from the cache the second time, and still decrypted with the key
+        for _ in 0..2 {
+            let file_info: FileInfo = info().await.json().await.unwrap();
+            assert_eq!(file_info.download_count, 0);
+            assert_eq!(file_info.filename, "popular.txt");
+        }
+
+        let response = client
+            .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+            .form(&[("encryption_key", key.clone())])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let file_info: FileInfo = info().await.json().await.unwrap();
+        assert_eq!(file_info.download_count, 1);
+
+        // Deleted files are gone from the cache too
+        let response = client
+            .post(format!("{}/api/files/{}/delete", base, uploaded.file_id))
+            .json(&DeleteFileRequest {
+                owner_token: uploaded.owner_token.unwrap(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+        assert_eq!(info().await.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_file_info_reports_padded_sizes() {
+        let temp_dir = TempDir::new().unwrap();
+        let client = reqwest::Client::new();
+
+        for (size_padding, reported_size) in [
+            (kusatsu_encrypt::Padding::None, 5),
+            (kusatsu_encrypt::Padding::Bucket(4096), 4096),
+        ] {
+            let base = spawn_app(
+                test_state(Config {
+                    size_padding,
+                    ..test_config(&temp_dir)
+                })
+                .await,
+            )
+            .await;
+
+            let form = reqwest::multipart::Form::new()
+                .text("filename", "hello.txt")
+                .part("file", reqwest::multipart::Part::bytes(b"hello".to_vec()));
+            let uploaded: UploadResponse = client
+                .post(format!("{}/api/upload", base))
+                .multipart(form)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            let key = uploaded.encryption_key.unwrap();
+
+            let file_info: FileInfo = client
+                .post(format!("{}/api/files/{}/info", base, uploaded.file_id))
+                .json(&DownloadRequest {
+                    encryption_key: Some(key.clone()),
+                })
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            assert_eq!(file_info.original_size, reported_size);
+            assert!(file_info.encrypted_size >= file_info.original_size);
+            assert_eq!(
+                file_info.sizes_padded,
+                size_padding != kusatsu_encrypt::Padding::None
+            );
+
+            // Downloads still have the exact contents
+            let response = client
+                .post(format!("{}/api/files/{}/download", base, uploaded.file_id))
+                .form(&[("encryption_key", key)])
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.content_length(), Some(5));
+            assert_eq!(response.bytes().await.unwrap().as_ref(), b"hello");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_storage_is_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = test_state(test_config(&temp_dir)).await;
+        state.storage = Arc::new(InMemoryStorage::new().with_capacity(1024));
+        let base = spawn_app(state).await;
+        let client = reqwest::Client::new();
+
+        let upload = |size: usize| {
+            let form = reqwest::multipart::Form::new()
+                .text("filename", "data.bin")
+                .part("file", reqwest::multipart::Part::bytes(vec![0u8; size]));
+            client
+                .post(format!("{}/api/upload", base))
+                .multipart(form)
+                .send()
+        };
+        let health = || async {
+            client
+                .get(format!("{}/health", base))
+                .send()
+                .await
+                .unwrap()
+                .json::<HealthResponse>()
+                .await
+                .unwrap()
+        };
+
+        // A write that doesn't fit says so, with a time to retry after
+        let response = upload(2048).await.unwrap();
+        assert_eq!(response.status(), 507);
+        assert_eq!(response.headers()["retry-after"], "60");
+        let body: ErrorResponse = response.json().await.unwrap();
+        assert_eq!(body.code, error_code::STORAGE_FULL);
+
+        let health_response = health().await;
+        assert_eq!(health_response.status, "degraded");
+        assert_eq!(
+            health_response.storage_error.as_deref(),
+            Some(error_code::STORAGE_FULL)
+        );
+
+        // The next write that succeeds clears it
+        assert_eq!(upload(100).await.unwrap().status(), 200);
+        let health_response = health().await;
+        assert_eq!(health_response.status, "healthy");
+        assert!(health_response.storage_error.is_none());
+    }
+
+    // Multipart bodies as any client could send them, well-formed or not
+    mod upload_fuzz {
+        use super::*;
+        use base64::{engine::general_purpose, Engine as _};
+        use proptest::prelude::*;
+        use proptest::test_runner::{Config as ProptestConfig, TestRunner};
+
+        const BOUNDARY: &str = "kusatsu-fuzz-boundary";
+        // Small limits put the size boundaries within reach of generated inputs
+        const MAX_FILE_SIZE: usize = 4096;
+        const CHUNK_SIZE: i32 = 1000;
+        const CHUNKED_FILE_SIZE: i64 = 2500;
+
+        #[derive(Clone, Debug)]
+        struct Part {
+            name: String,
+            filename: Option<String>,
+            data: Vec<u8>,
+        }
+
+        #[derive(Clone, Debug)]
+        struct Body {
+            parts: Vec<Part>,
+            // Cut the encoded body after this many bytes, like a dropped connection
+            truncate_at: Option<usize>,
+            closed: bool,
+        }
+
+        #[derive(Clone, Debug)]
+        enum ChunkRequest {
+            Multipart(Body),
+            Raw {
+                content_type: Option<String>,
+                data: Vec<u8>,
+            },
+        }
+
+        fn part(name: &str, data: impl Into<Vec<u8>>) -> Part {
+            Part {
+                name: name.to_string(),
+                filename: None,
+                data: data.into(),
+            }
+        }
+
+        fn file_part(name: &str, data: Vec<u8>) -> Part {
+            Part {
+                filename: Some("fuzz.bin".to_string()),
+                ..part(name, data)
+            }
+        }
+
+        fn body(parts: Vec<Part>) -> Body {
+            Body {
+                parts,
+                truncate_at: None,
+                closed: true,
+            }
+        }
+
+        fn encode(body: &Body) -> Vec<u8> {
+            let mut encoded = Vec::new();
+            for part in &body.parts {
+                encoded.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+                let disposition = match &part.filename {
+                    Some(filename) => format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\r\n",
+                        part.name, filename
+                    ),
+                    None => format!(
+                        "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                        part.name
+                    ),
+                };
+                encoded.extend_from_slice(disposition.as_bytes());
+                encoded.extend_from_slice(&part.data);
+                encoded.extend_from_slice(b"\r\n");
+            }
+            if body.closed {
+                encoded.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+            }
+            if let Some(truncate_at) = body.truncate_at {
+                encoded.truncate(truncate_at);
+            }
+            encoded
+        }
+
+        fn sized(len: usize) -> Vec<u8> {
+            (0..len).map(|i| i as u8).collect()
+        }
+
+        fn data_strategy(sizes: &'static [usize]) -> impl Strategy<Value = Vec<u8>> {
+            prop_oneof![
+                proptest::collection::vec(any::<u8>(), 0..64),
+                proptest::sample::select(sizes).prop_map(sized),
+                proptest::sample::select(vec![
+                    "true",
+                    "false",
+                    "maybe",
+                    "-1",
+                    "0",
+                    "4096",
+                    "18446744073709551616",
+                    "gzip",
+                    "zstd",
+                    "",
+                ])
+                .prop_map(|text| text.as_bytes().to_vec()),
+                Just(general_purpose::STANDARD.encode([7u8; 12]).into_bytes()),
+            ]
+        }
+
+        fn body_strategy(
+            names: &'static [&'static str],
+            sizes: &'static [usize],
+        ) -> impl Strategy<Value = Body> {
+            let part = (
+                prop_oneof![
+                    4 => proptest::sample::select(names).prop_map(str::to_string),
+                    1 => "[a-z_]{0,12}",
+                ],
+                proptest::option::of(Just("fuzz.bin".to_string())),
+                data_strategy(sizes),
+            )
+                .prop_map(|(name, filename, data)| Part {
+                    name,
+                    filename,
+                    data,
+                });
+
+            (
+                proptest::collection::vec(part, 0..6),
+                proptest::option::weighted(0.2, 0..8192usize),
+                proptest::bool::weighted(0.9),
+            )
+                .prop_map(|(parts, truncate_at, closed)| Body {
+                    parts,
+                    truncate_at,
+                    closed,
+                })
+        }
+
+        fn proptest_config() -> ProptestConfig {
+            ProptestConfig {
+                cases: 128,
+                // Cases worth keeping go in the seed lists below
+                failure_persistence: None,
+                ..ProptestConfig::default()
+            }
+        }
+
+        fn fuzz_state(temp_dir: &TempDir) -> impl std::future::Future<Output = AppState> {
+            test_state(Config {
+                max_file_size: MAX_FILE_SIZE,
+                ..test_config(temp_dir)
+            })
+        }
+
+        // Everything left in the temporary or chunk directories once requests are done
+        fn leftover_temp_files(storage_dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+            fn walk(dir: &std::path::Path, found: &mut Vec<std::path::PathBuf>) {
+                let Ok(entries) = std::fs::read_dir(dir) else {
+                    return;
+                };
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path.is_dir() {
+                        walk(&path, found);
+                    } else if path
+                        .extension()
+                        .is_some_and(|ext| ext == "tmp" || ext == "upload")
+                    {
+                        found.push(path);
+                    }
+                }
+            }
+
+            let mut found = Vec::new();
+            walk(storage_dir, &mut found);
+            found
+        }
+
+        async fn check_upload(
+            client: &reqwest::Client,
+            base: &str,
+            state: &AppState,
+            body: &Body,
+        ) -> std::result::Result<(), TestCaseError> {
+            let stored_before = state.storage.list_stored_files().await.unwrap().len();
+
+            let response = client
+                .post(format!("{}/api/upload", base))
+                .header(
+                    "content-type",
+                    format!("multipart/form-data; boundary={}", BOUNDARY),
+                )
+                .body(encode(body))
+                .send()
+                .await
+                .unwrap();
+            let status = response.status();
+            prop_assert!(!status.is_server_error(), "{} for {:?}", status, body);
+
+            // A rejected upload stores nothing, an accepted one exactly one file
+            let stored_after = state.storage.list_stored_files().await.unwrap().len();
+            let expected = stored_before + usize::from(status.is_success());
+            prop_assert_eq!(stored_after, expected, "{} for {:?}", status, body);
+
+            let leftovers = leftover_temp_files(std::path::Path::new(&state.config.storage_dir));
+            prop_assert!(leftovers.is_empty(), "{:?} left by {:?}", leftovers, body);
+            Ok(())
+        }
+
+        async fn check_chunk(
+            client: &reqwest::Client,
+            base: &str,
+            state: &AppState,
+            upload_id: Uuid,
+            chunk_number: i32,
+            request: &ChunkRequest,
+        ) -> std::result::Result<(), TestCaseError> {
+            let url = format!("{}/api/upload/chunk/{}/{}", base, upload_id, chunk_number);
+            let response = match request {
+                ChunkRequest::Multipart(body) => client
+                    .post(&url)
+                    .header(
+                        "content-type",
+                        format!("multipart/form-data; boundary={}", BOUNDARY),
+                    )
+                    .body(encode(body)),
+                ChunkRequest::Raw { content_type, data } => {
+                    let request = client.put(&url).body(data.clone());
+                    match content_type {
+                        Some(content_type) => request.header("content-type", content_type),
+                        None => request,
+                    }
+                }
+            }
+            .send()
+            .await
+            .unwrap();
+            let status = response.status();
+            prop_assert!(
+                !status.is_server_error(),
+                "{} for chunk {} {:?}",
+                status,
+                chunk_number,
+                request
+            );
+
+            // Every stored chunk is whole, and counted exactly once
+            let session = upload_session_ops::get_upload_session_by_id(&state.db, upload_id)
+                .await
+                .unwrap()
+                .unwrap();
+            let mut stored_chunks = 0;
+            for number in 0..session.total_chunks {
+                match state.chunk_storage.get_chunk_size(upload_id, number).await {
+                    Ok(size) => {
+                        prop_assert_eq!(size, session.chunk_len(number) as u64);
+                        stored_chunks += 1;
+                    }
+                    Err(AppError::FileNotFound) => {}
+                    Err(e) => panic!("Failed to check chunk {}: {}", number, e),
+                }
+            }
+            prop_assert_eq!(session.uploaded_chunks, stored_chunks);
+
+            let leftovers = leftover_temp_files(std::path::Path::new(&state.config.storage_dir));
+            prop_assert!(
+                leftovers.is_empty(),
+                "{:?} left by {:?}",
+                leftovers,
+                request
+            );
+            Ok(())
+        }
+
+        // Inputs that broke, or nearly broke, the upload handler before
+        fn upload_seeds() -> Vec<Body> {
+            let nonce = general_purpose::STANDARD.encode([7u8; 12]);
+            vec![
+                body(vec![]),
+                Body {
+                    closed: false,
+                    ..body(vec![
+                        part("filename", "a.txt"),
+                        file_part("file", sized(10)),
+                    ])
+                },
+                Body {
+                    truncate_at: Some(150),
+                    ..body(vec![
+                        part("filename", "a.txt"),
+                        file_part("file", sized(1000)),
+                    ])
+                },
+                body(vec![file_part("file", sized(10))]),
+                body(vec![
+                    part("filename", "a.txt"),
+                    file_part("file", Vec::new()),
+                ]),
+                body(vec![
+                    part("filename", "a.txt"),
+                    file_part("file", sized(10)),
+                    file_part("file_data", sized(20)),
+                ]),
+                body(vec![
+                    part("filename", "a.txt"),
+                    part("file_size", "5"),
+                    file_part("file", sized(4)),
+                ]),
+                body(vec![
+                    part("filename", "a.txt"),
+                    part("file_size", "-1"),
+                    file_part("file", sized(4)),
+                ]),
+                body(vec![
+                    part("encrypted", "maybe"),
+                    part("filename", "a.txt"),
+                    file_part("file", sized(4)),
+                ]),
+                body(vec![
+                    part("filename", "a.txt"),
+                    part("nonce", nonce.as_str()),
+                    file_part("file", sized(32)),
+                ]),
+                body(vec![
+                    part("encrypted", "true"),
+                    part("nonce", nonce.as_str()),
+                    part("filename", "a.txt"),
+                    file_part("file", sized(15)),
+                ]),
+                body(vec![
+                    part("encrypted", "true"),
+                    part("nonce", nonce.as_str()),
+                    part("compression", "gzip"),
+                    part("filename", "a.txt"),
+                    file_part("file", sized(32)),
+                ]),
+                body(vec![
+                    part("filename", "a.txt"),
+                    file_part("file", sized(MAX_FILE_SIZE + 1)),
+                ]),
+                // Fields after the file, and a file right at the limit
+                body(vec![
+                    file_part("file", sized(MAX_FILE_SIZE)),
+                    part("filename", "a.txt"),
+                    part("file_size", MAX_FILE_SIZE.to_string()),
+                ]),
+            ]
+        }
+
+        // Inputs exercising chunk numbering and size checks
+        fn chunk_seeds() -> Vec<(i32, ChunkRequest)> {
+            let last_chunk = (CHUNKED_FILE_SIZE as usize) % CHUNK_SIZE as usize;
+            let multipart = |parts| ChunkRequest::Multipart(body(parts));
+            let raw = |data| ChunkRequest::Raw {
+                content_type: Some("application/octet-stream".to_string()),
+                data,
+            };
+            vec![
+                (3, raw(sized(last_chunk))),
+                (-1, raw(sized(CHUNK_SIZE as usize))),
+                (i32::MAX, raw(sized(CHUNK_SIZE as usize))),
+                (2, raw(sized(last_chunk - 1))),
+                (2, raw(sized(last_chunk + 1))),
+                (0, raw(Vec::new())),
+                (
+                    0,
+                    ChunkRequest::Raw {
+                        content_type: Some("text/plain".to_string()),
+                        data: sized(CHUNK_SIZE as usize),
+                    },
+                ),
+                (0, multipart(vec![part("other", "x")])),
+                (
+                    0,
+                    ChunkRequest::Multipart(Body {
+                        truncate_at: Some(200),
+                        ..body(vec![file_part("chunk", sized(CHUNK_SIZE as usize))])
+                    }),
+                ),
+                (
+                    1,
+                    multipart(vec![
+                        part("other", "x"),
+                        file_part("chunk", sized(CHUNK_SIZE as usize)),
+                    ]),
+                ),
+                // The same chunk again, then again at a different size
+                (2, raw(sized(last_chunk))),
+                (2, raw(sized(last_chunk))),
+                (2, raw(sized(CHUNK_SIZE as usize))),
+            ]
+        }
+
+        #[test]
+        fn test_fuzzed_uploads_never_fail_on_the_server() {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let temp_dir = TempDir::new().unwrap();
+            let state = runtime.block_on(fuzz_state(&temp_dir));
+            let base = runtime.block_on(spawn_app(state.clone()));
+            let client = reqwest::Client::new();
+
+            for seed in upload_seeds() {
+                runtime
+                    .block_on(check_upload(&client, &base, &state, &seed))
+                    .unwrap();
+            }
+
+            let names: &'static [&'static str] = &[
+                "file",
+                "file_data",
+                "filename",
+                "mime_type",
+                "file_size",
+                "encrypted",
+                "nonce",
+                "encrypted_filename",
+                "filename_nonce",
+                "compression",
+                "original_size",
+                "signature",
+            ];
+            let sizes: &'static [usize] = &[0, 1, 15, 16, MAX_FILE_SIZE, MAX_FILE_SIZE + 1];
+            TestRunner::new(proptest_config())
+                .run(&body_strategy(names, sizes), |body| {
+                    runtime.block_on(check_upload(&client, &base, &state, &body))
+                })
+                .unwrap();
+        }
+
+        #[test]
+        fn test_fuzzed_chunks_never_fail_on_the_server() {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let temp_dir = TempDir::new().unwrap();
+            let state = runtime.block_on(fuzz_state(&temp_dir));
+            let base = runtime.block_on(spawn_app(state.clone()));
+            let client = reqwest::Client::new();
+
+            let start_upload = || {
+                runtime.block_on(async {
+                    let response: StartUploadResponse = client
+                        .post(format!("{}/api/upload/start", base))
+                        .json(&StartUploadRequest {
+                            filename: "fuzz.bin".to_string(),
+                            file_size: CHUNKED_FILE_SIZE,
+                            mime_type: None,
+                            chunk_size: Some(CHUNK_SIZE),
+                            expires_in_hours: None,
+                            max_downloads: None,
+                            slug: None,
+                            burn_after_first_download_minutes: None,
+                            expire_after_inactivity_hours: None,
+                            pow_challenge: None,
+                            pow_nonce: None,
+                            filename_encrypted: false,
+                        })
+                        .send()
+                        .await
+                        .unwrap()
+                        .json()
+                        .await
+                        .unwrap();
+                    response.upload_id
+                })
+            };
+
+            let upload_id = start_upload();
+            for (chunk_number, request) in chunk_seeds() {
+                runtime
+                    .block_on(check_chunk(
+                        &client,
+                        &base,
+                        &state,
+                        upload_id,
+                        chunk_number,
+                        &request,
+                    ))
+                    .unwrap();
+            }
+
+            let sizes: &'static [usize] = &[0, 1, 499, 500, 501, 999, 1000, 1001];
+            let request = prop_oneof![
+                body_strategy(&["chunk", "file", ""], sizes).prop_map(ChunkRequest::Multipart),
+                (
+                    proptest::option::of(proptest::sample::select(vec![
+                        "application/octet-stream".to_string(),
+                        "application/octet-stream; charset=binary".to_string(),
+                        "multipart/form-data".to_string(),
+                        "text/plain".to_string(),
+                    ])),
+                    data_strategy(sizes),
+                )
+                    .prop_map(|(content_type, data)| ChunkRequest::Raw { content_type, data }),
+            ];
+
+            // A fresh session, so chunks that are already complete don't hide the rest
+            let upload_id = start_upload();
+            TestRunner::new(proptest_config())
+                .run(&(-1..5i32, request), |(chunk_number, request)| {
+                    runtime.block_on(check_chunk(
+                        &client,
+                        &base,
+                        &state,
+                        upload_id,
+                        chunk_number,
+                        &request,
+                    ))
+                })
+                .unwrap();
+        }
+    }
+}
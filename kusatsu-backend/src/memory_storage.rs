@@ -0,0 +1,130 @@
+use crate::container::ContainerHeader;
+use crate::error::{AppError, Result};
+use crate::storage::{relative_file_path, FileStorage, StorageStats, StoredFile, StoredFileReader};
+use crate::storage_health::write_error;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use std::io::{Cursor, ErrorKind};
+use std::sync::Arc;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+struct MemoryFile {
+    file_id: Uuid,
+    data: Vec<u8>,
+    modified: SystemTime,
+}
+
+/// Keeps files in memory, for tests
+///
+/// Uses the same relative paths as [`crate::storage::LocalFileStorage`], so
+/// records created against one are valid against the other. Clones share the
+/// same files.
+#[derive(Clone, Default)]
+pub struct InMemoryStorage {
+    files: Arc<DashMap<String, MemoryFile>>,
+    capacity: Option<usize>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail writes that would take more than `bytes` in total, like a full disk
+    pub fn with_capacity(mut self, bytes: usize) -> Self {
+        self.capacity = Some(bytes);
+        self
+    }
+
+    fn used_bytes(&self) -> usize {
+        self.files.iter().map(|file| file.data.len()).sum()
+    }
+
+    fn ensure_room(&self, bytes: usize) -> Result<()> {
+        match self.capacity {
+            Some(capacity) if self.used_bytes() + bytes > capacity => Err(write_error(
+                "Failed to write file",
+                ErrorKind::StorageFull.into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl FileStorage for InMemoryStorage {
+    async fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn store_file(
+        &self,
+        file_id: Uuid,
+        header: &ContainerHeader,
+        ciphertext: &[u8],
+    ) -> Result<String> {
+        let relative_path = relative_file_path(file_id).to_string_lossy().to_string();
+        let mut data = header.encode()?;
+        data.extend_from_slice(ciphertext);
+        self.ensure_room(data.len())?;
+
+        self.files.insert(
+            relative_path.clone(),
+            MemoryFile {
+                file_id,
+                data,
+                modified: SystemTime::now(),
+            },
+        );
+
+        Ok(relative_path)
+    }
+
+    async fn retrieve_file(&self, relative_path: &str) -> Result<Vec<u8>> {
+        self.files
+            .get(relative_path)
+            .map(|file| file.data.clone())
+            .ok_or(AppError::FileNotFound)
+    }
+
+    async fn open_file(&self, relative_path: &str) -> Result<(StoredFileReader, u64)> {
+        let data = self.retrieve_file(relative_path).await?;
+        let size = data.len() as u64;
+
+        Ok((Box::new(Cursor::new(data)), size))
+    }
+
+    async fn file_exists(&self, relative_path: &str) -> bool {
+        self.files.contains_key(relative_path)
+    }
+
+    async fn list_stored_files(&self) -> Result<Vec<StoredFile>> {
+        Ok(self
+            .files
+            .iter()
+            .map(|entry| StoredFile {
+                file_id: entry.file_id,
+                relative_path: entry.key().clone(),
+                modified: entry.modified,
+            })
+            .collect())
+    }
+
+    async fn delete_file(&self, relative_path: &str) -> Result<()> {
+        self.files.remove(relative_path);
+        Ok(())
+    }
+
+    async fn get_stats(&self) -> Result<StorageStats> {
+        let total_size = self.files.iter().map(|entry| entry.data.len() as u64).sum();
+
+        Ok(StorageStats {
+            total_files: self.files.len() as u64,
+            total_size,
+        })
+    }
+    async fn check_writable(&self) -> Result<()> {
+        self.ensure_room(1)
+    }
+}
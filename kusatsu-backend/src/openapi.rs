@@ -0,0 +1,1715 @@
+use crate::config::Config;
+use kusatsu_types::{MAX_CHUNK_SIZE, REQUEST_ID_HEADER};
+use serde_json::{json, Map, Value};
+
+/// OpenAPI 3 description of the public API, served at `/api/openapi.json`
+///
+/// Kept next to the router by hand; a route added in `create_app` should be
+/// described here too. The schemas are checked against the `kusatsu_types`
+/// they describe by the tests below, which need an example of each.
+pub fn spec(config: &Config) -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Kusatsu API",
+            "description": "Encrypted file sharing. Every response carries an X-Request-Id header, \
+                            echoed from the request when the client sends one.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": config.api_url }],
+        "paths": paths(),
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "adminToken": { "type": "http", "scheme": "bearer" }
+            }
+        }
+    })
+}
+
+fn paths() -> Map<String, Value> {
+    let mut paths = Map::new();
+    let mut add = |path: &str, operations: Value| {
+        paths.insert(path.to_string(), operations);
+    };
+
+    add(
+        "/health",
+        json!({
+            "get": {
+                "tags": ["system"],
+                "summary": "Service health and maintenance status",
+                "responses": {
+                    "200": json_response("Service is up", schema_ref("HealthResponse"))
+                }
+            }
+        }),
+    );
+
+    add(
+        "/api/config",
+        json!({
+            "get": {
+                "tags": ["system"],
+                "summary": "Limits and optional features of this server",
+                "responses": {
+                    "200": json_response("Server configuration", schema_ref("ServerConfigResponse"))
+                }
+            }
+        }),
+    );
+
+    add(
+        "/api/upload",
+        json!({
+            "post": {
+                "tags": ["upload"],
+                "summary": "Upload a whole file, encrypted server-side or already encrypted by the client",
+                "parameters": [
+                    query_param("expires_in_hours", "Hours until the file expires, at most max_expires_in_hours", integer()),
+                    query_param("max_downloads", "Downloads allowed before the file is gone", integer()),
+                    query_param("slug", "Human-readable name to download the file by", string()),
+                    query_param(
+                        "burn_after_first_download_minutes",
+                        "Minutes the file stays available after its first download",
+                        integer()
+                    ),
+                    query_param(
+                        "expire_after_inactivity_hours",
+                        "Hours without downloads after which the file expires; each download renews them",
+                        integer()
+                    ),
+                    query_param(
+                        "pow_challenge",
+                        "Challenge from /api/upload/challenge, when the server requires proof of work",
+                        string()
+                    ),
+                    query_param("pow_nonce", "Solution of pow_challenge", integer())
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "multipart/form-data": {
+                            "schema": {
+                                "type": "object",
+                                "required": ["file"],
+                                "properties": {
+                                    "file": { "type": "string", "format": "binary" },
+                                    "filename": {
+                                        "type": "string",
+                                        "description": "Required unless encrypted_filename is sent, and rejected with it"
+                                    },
+                                    "mime_type": { "type": "string" },
+                                    "file_size": {
+                                        "type": "integer",
+                                        "description": "Size of the file in bytes; uploads of any other size are rejected"
+                                    },
+                                    "signature": {
+                                        "type": "string",
+                                        "description": "Detached ed25519 signature of the file (base64)"
+                                    },
+                                    "encrypted": {
+                                        "type": "boolean",
+                                        "description": "The file is ciphertext encrypted by the client, stored as-is and answered without a key; required when server-side encryption is off"
+                                    },
+                                    "nonce": {
+                                        "type": "string",
+                                        "description": "Nonce of the encrypted file (12 bytes, base64); required with encrypted=true and rejected without it"
+                                    },
+                                    "encrypted_filename": {
+                                        "type": "string",
+                                        "description": "Filename encrypted with the file's key (base64), with encrypted=true"
+                                    },
+                                    "filename_ciphertext": {
+                                        "type": "string",
+                                        "description": "Alias of encrypted_filename"
+                                    },
+                                    "filename_nonce": {
+                                        "type": "string",
+                                        "description": "Nonce of encrypted_filename (12 bytes, base64)"
+                                    },
+                                    "compression": {
+                                        "type": "string",
+                                        "enum": ["gzip"],
+                                        "description": "Format the client compressed the file with before encrypting it, with encrypted=true; downloads decompress it"
+                                    },
+                                    "original_size": {
+                                        "type": "integer",
+                                        "description": "Size of the file before compression, required with compression"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                "responses": with_errors(
+                    json!({ "200": json_response("File stored", schema_ref("UploadResponse")) }),
+                    &[400, 403, 409, 413, 415, 422, 503],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/upload/challenge",
+        json!({
+            "post": {
+                "tags": ["upload"],
+                "summary": "Get a proof-of-work challenge to solve before uploading",
+                "description": "Only on servers that require proof of work, see `upload_pow_difficulty` in /api/config. Find a nonce such that the SHA-256 of `{challenge}:{nonce}`, with the nonce in decimal, starts with `difficulty` zero bits, and send both with the upload. A challenge is good for one upload.",
+                "responses": with_errors(
+                    json!({ "200": json_response("Challenge issued", schema_ref("UploadChallengeResponse")) }),
+                    &[400],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/upload/plan",
+        json!({
+            "post": {
+                "tags": ["upload"],
+                "summary": "Validate an upload and tell how to send it, without starting it",
+                "requestBody": json_body(schema_ref("StartUploadRequest")),
+                "responses": with_errors(
+                    json!({ "200": json_response("Upload plan", schema_ref("UploadPlanResponse")) }),
+                    &[409, 413, 415, 422, 503],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/upload/start",
+        json!({
+            "post": {
+                "tags": ["upload"],
+                "summary": "Start a chunked upload",
+                "requestBody": json_body(schema_ref("StartUploadRequest")),
+                "responses": with_errors(
+                    json!({ "200": json_response("Upload session created", schema_ref("StartUploadResponse")) }),
+                    &[403, 409, 413, 415, 422, 503],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/upload/chunk/{upload_id}/{chunk_number}",
+        json!({
+            "post": {
+                "tags": ["upload"],
+                "summary": "Upload one chunk of a chunked upload",
+                "parameters": [
+                    path_param("upload_id", "Upload session id", uuid()),
+                    path_param("chunk_number", "Zero-based chunk index", integer())
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "multipart/form-data": {
+                            "schema": {
+                                "type": "object",
+                                "required": ["chunk"],
+                                "properties": {
+                                    "chunk": {
+                                        "type": "string",
+                                        "format": "binary",
+                                        "description": format!("At most {} bytes", MAX_CHUNK_SIZE)
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                "responses": with_errors(
+                    json!({ "200": json_response("Chunk stored", schema_ref("ChunkUploadResponse")) }),
+                    &[400, 413, 429, 503],
+                )
+            },
+            "put": {
+                "tags": ["upload"],
+                "summary": "Upload one chunk of a chunked upload as the raw request body",
+                "parameters": [
+                    path_param("upload_id", "Upload session id", uuid()),
+                    path_param("chunk_number", "Zero-based chunk index", integer())
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/octet-stream": {
+                            "schema": {
+                                "type": "string",
+                                "format": "binary",
+                                "description": format!("At most {} bytes", MAX_CHUNK_SIZE)
+                            }
+                        }
+                    }
+                },
+                "responses": with_errors(
+                    json!({ "200": json_response("Chunk stored", schema_ref("ChunkUploadResponse")) }),
+                    &[400, 413, 503],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/upload/complete",
+        json!({
+            "post": {
+                "tags": ["upload"],
+                "summary": "Assemble the chunks of an upload into a file",
+                "requestBody": json_body(schema_ref("CompleteUploadRequest")),
+                "responses": with_errors(
+                    json!({ "200": json_response("File stored", schema_ref("UploadResponse")) }),
+                    &[400, 422, 503],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/upload/status/{upload_id}",
+        json!({
+            "get": {
+                "tags": ["upload"],
+                "summary": "Progress of a chunked upload",
+                "parameters": [
+                    path_param("upload_id", "Upload session id", uuid()),
+                    query_param(
+                        "verify",
+                        "Also check the stored size of every received chunk",
+                        json!({ "type": "boolean" })
+                    )
+                ],
+                "responses": with_errors(
+                    json!({
+                        "200": json_response("Upload progress", schema_ref("UploadStatusResponse"))
+                    }),
+                    &[400],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/upload/{upload_id}/keepalive",
+        json!({
+            "post": {
+                "tags": ["upload"],
+                "summary": "Push back the expiry of a slow chunked upload",
+                "parameters": [path_param("upload_id", "Upload session id", uuid())],
+                "responses": with_errors(
+                    json!({
+                        "200": json_response("New session deadline", schema_ref("UploadKeepaliveResponse"))
+                    }),
+                    &[400],
+                )
+            }
+        }),
+    );
+
+    for (prefix, param) in [
+        ("/api/files/{file_id}", file_id_param()),
+        ("/api/files/by-slug/{slug}", slug_param()),
+    ] {
+        add(
+            prefix,
+            json!({
+                "head": {
+                    "tags": ["files"],
+                    "summary": "Check that a file exists and can still be downloaded",
+                    "description": "Never counted as a download.",
+                    "parameters": [param.clone()],
+                    "responses": {
+                        "200": { "description": "File is available" },
+                        "404": { "description": "File not found" },
+                        "410": { "description": "File expired or its download limit was reached" }
+                    }
+                }
+            }),
+        );
+
+        add(
+            &format!("{}/info", prefix),
+            json!({
+                "post": {
+                    "tags": ["files"],
+                    "summary": "File metadata, with the filename decrypted when the key is given",
+                    "parameters": [param.clone()],
+                    "requestBody": json_body(schema_ref("DownloadRequest")),
+                    "responses": with_errors(
+                        json!({ "200": json_response("File metadata", schema_ref("FileInfo")) }),
+                        &[400, 404, 410],
+                    )
+                }
+            }),
+        );
+
+        add(
+            &format!("{}/preview", prefix),
+            json!({
+                "get": {
+                    "tags": ["files"],
+                    "summary": "Stored bytes of a file for in-browser preview, not counted as a download",
+                    "parameters": [param.clone()],
+                    "responses": with_errors(
+                        json!({ "200": json_response("File contents", schema_ref("FilePreviewResponse")) }),
+                        &[404, 410],
+                    )
+                }
+            }),
+        );
+
+        add(
+            &format!("{}/download", prefix),
+            json!({
+                "post": {
+                    "tags": ["files"],
+                    "summary": "Download and decrypt a file",
+                    "parameters": [param.clone()],
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/x-www-form-urlencoded": {
+                                "schema": object_schema(json!({ "encryption_key": string() }))
+                            }
+                        }
+                    },
+                    "responses": with_errors(
+                        json!({ "200": binary_response("Decrypted file contents") }),
+                        &[400, 404, 410],
+                    )
+                }
+            }),
+        );
+
+        add(
+            &format!("{}/raw", prefix),
+            json!({
+                "get": {
+                    "tags": ["files"],
+                    "summary": "Download the stored bytes, left for the client to decrypt",
+                    "description": "Encrypted files carry their nonce in X-Content-Nonce; encrypted \
+                                    filenames come as X-Filename-Ciphertext and X-Filename-Nonce, \
+                                    signed files their signature as X-Signature, and contents \
+                                    compressed before encryption their format as X-Compression.",
+                    "parameters": [param.clone()],
+                    "responses": with_errors(
+                        json!({ "200": binary_response("Stored file contents") }),
+                        &[404, 410],
+                    )
+                }
+            }),
+        );
+    }
+
+    add(
+        "/api/files/{file_id}/download-json",
+        json!({
+            "post": {
+                "tags": ["files"],
+                "summary": "Download and decrypt a small file, returned as JSON with its metadata",
+                "description": "Counted as a download. Files over KUSATSU_JSON_DOWNLOAD_MAX_SIZE \
+                                get a 413 and must use the streaming download endpoint.",
+                "parameters": [file_id_param()],
+                "requestBody": json_body(schema_ref("DownloadRequest")),
+                "responses": with_errors(
+                    json!({ "200": json_response("File contents and metadata", schema_ref("JsonDownloadResponse")) }),
+                    &[400, 404, 410, 413],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/files/{file_id}/extend",
+        json!({
+            "post": {
+                "tags": ["files"],
+                "summary": "Change when a file expires, using its owner token",
+                "parameters": [file_id_param()],
+                "requestBody": json_body(schema_ref("ExtendExpiryRequest")),
+                "responses": with_errors(
+                    json!({ "200": json_response("Updated file metadata", schema_ref("FileInfo")) }),
+                    &[400, 403, 404, 409, 410, 422],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/files/{file_id}/wrapped-key",
+        json!({
+            "get": {
+                "tags": ["files"],
+                "summary": "Get the passphrase-wrapped key of a file, without counting a download",
+                "parameters": [file_id_param()],
+                "responses": with_errors(
+                    json!({ "200": json_response("Wrapped key", schema_ref("WrappedKeyResponse")) }),
+                    &[404, 410],
+                )
+            },
+            "post": {
+                "tags": ["files"],
+                "summary": "Store the file key wrapped with a passphrase, using the owner token",
+                "parameters": [file_id_param()],
+                "requestBody": json_body(schema_ref("WrapKeyRequest")),
+                "responses": with_errors(
+                    json!({ "200": json_response("Wrapped key stored", schema_ref("WrappedKeyResponse")) }),
+                    &[400, 403, 404, 410, 422],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/files/{file_id}/listing",
+        json!({
+            "get": {
+                "tags": ["files"],
+                "summary": "Get the encrypted listing of a bundle's contents, without counting a download",
+                "parameters": [file_id_param()],
+                "responses": with_errors(
+                    json!({ "200": json_response("Encrypted listing", schema_ref("ListingResponse")) }),
+                    &[404, 410],
+                )
+            },
+            "post": {
+                "tags": ["files"],
+                "summary": "Attach a listing of a bundle's contents, encrypted with the file key, using the owner token",
+                "parameters": [file_id_param()],
+                "requestBody": json_body(schema_ref("ListingRequest")),
+                "responses": with_errors(
+                    json!({ "200": json_response("Listing stored", schema_ref("ListingResponse")) }),
+                    &[400, 403, 404, 410, 422],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/files/{file_id}/sign",
+        json!({
+            "post": {
+                "tags": ["files"],
+                "summary": "Create a time-limited signed download URL, using the owner token",
+                "parameters": [file_id_param()],
+                "requestBody": json_body(schema_ref("SignDownloadRequest")),
+                "responses": with_errors(
+                    json!({ "200": json_response("Signed URL", schema_ref("SignDownloadResponse")) }),
+                    &[400, 403, 404, 410, 500],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/files/{file_id}/rotate",
+        json!({
+            "post": {
+                "tags": ["files"],
+                "summary": "Give a file a new ID, invalidating links with the old one, using the owner token",
+                "description": "The stored data is not re-encrypted, so the key stays valid. \
+                                A slug keeps pointing at the file.",
+                "parameters": [file_id_param()],
+                "requestBody": json_body(schema_ref("RotateFileRequest")),
+                "responses": with_errors(
+                    json!({ "200": json_response("New file ID and download URL", schema_ref("RotateFileResponse")) }),
+                    &[403, 404, 410],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/files/{file_id}/delete",
+        json!({
+            "post": {
+                "tags": ["files"],
+                "summary": "Delete a file and its stored data, using the owner token",
+                "parameters": [file_id_param()],
+                "requestBody": json_body(schema_ref("DeleteFileRequest")),
+                "responses": with_errors(
+                    json!({ "204": { "description": "File deleted" } }),
+                    &[403, 404],
+                )
+            }
+        }),
+    );
+
+    let signed_params = json!([
+        file_id_param(),
+        query_param(
+            "expires",
+            "Unix timestamp the signature is valid until",
+            integer()
+        ),
+        query_param(
+            "signature",
+            "Signature from /api/files/{file_id}/sign",
+            string()
+        )
+    ]);
+    add(
+        "/api/files/{file_id}/signed-download",
+        json!({
+            "get": {
+                "tags": ["files"],
+                "summary": "Download an unencrypted file through a signed URL",
+                "parameters": signed_params.clone(),
+                "responses": with_errors(
+                    json!({ "200": binary_response("File contents") }),
+                    &[403, 404, 410],
+                )
+            },
+            "post": {
+                "tags": ["files"],
+                "summary": "Download an encrypted file through a signed URL",
+                "parameters": signed_params.clone(),
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/x-www-form-urlencoded": {
+                            "schema": object_schema(json!({ "encryption_key": string() }))
+                        }
+                    }
+                },
+                "responses": with_errors(
+                    json!({ "200": binary_response("Decrypted file contents") }),
+                    &[400, 403, 404, 410],
+                )
+            }
+        }),
+    );
+
+    for (path, summary) in [
+        ("/api/admin/cleanup/files", "Delete expired files now"),
+        (
+            "/api/admin/cleanup/upload-sessions",
+            "Delete expired upload sessions now",
+        ),
+    ] {
+        add(
+            path,
+            json!({
+                "post": {
+                    "tags": ["admin"],
+                    "summary": summary,
+                    "responses": with_errors(
+                        json!({ "200": json_response("Cleanup result", schema_ref("CleanupResponse")) }),
+                        &[500],
+                    )
+                }
+            }),
+        );
+    }
+
+    add(
+        "/api/admin/cleanup/chunks",
+        json!({
+            "post": {
+                "tags": ["admin"],
+                "summary": "Delete stored chunks of uploads that have no session left",
+                "security": [{ "adminToken": [] }],
+                "responses": with_errors(
+                    json!({ "200": json_response("Cleanup result", schema_ref("CleanupResponse")) }),
+                    &[401, 500],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/admin/maintenance",
+        json!({
+            "post": {
+                "tags": ["admin"],
+                "summary": "Turn maintenance mode on or off",
+                "security": [{ "adminToken": [] }],
+                "requestBody": json_body(object_schema(json!({ "enabled": { "type": "boolean" } }))),
+                "responses": with_errors(
+                    json!({
+                        "200": json_response(
+                            "Current maintenance mode",
+                            object_schema(json!({ "maintenance": { "type": "boolean" } })),
+                        )
+                    }),
+                    &[401],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/admin/fsck",
+        json!({
+            "post": {
+                "tags": ["admin"],
+                "summary": "Compare stored files with the database, optionally repairing",
+                "security": [{ "adminToken": [] }],
+                "parameters": [
+                    query_param("repair", "Delete orphans and dangling records", json!({ "type": "boolean" }))
+                ],
+                "responses": with_errors(
+                    json!({ "200": json_response("Storage report", json!({ "type": "object" })) }),
+                    &[401, 500],
+                )
+            }
+        }),
+    );
+
+    add(
+        "/api/admin/files/delete",
+        json!({
+            "post": {
+                "tags": ["admin"],
+                "summary": "Delete every file matching all the given filters",
+                "security": [{ "adminToken": [] }],
+                "requestBody": json_body(object_schema(json!({
+                    "created_before": date_time(),
+                    "mime_type": string(),
+                    "min_size": integer(),
+                    "file_ids": {
+                        "type": "array",
+                        "maxItems": 1000,
+                        "items": uuid()
+                    },
+                    "dry_run": { "type": "boolean", "description": "Only report the matching files" }
+                }))),
+                "responses": with_errors(
+                    json!({
+                        "200": json_response(
+                            "Files matched and deleted",
+                            object_schema(json!({
+                                "files_matched": integer(),
+                                "files_deleted": integer(),
+                                "sample_file_ids": {
+                                    "type": "array",
+                                    "items": uuid()
+                                },
+                                "dry_run": { "type": "boolean" },
+                                "timestamp": date_time()
+                            })),
+                        )
+                    }),
+                    &[400, 401, 500],
+                )
+            }
+        }),
+    );
+
+    paths
+}
+
+fn schemas() -> Value {
+    let nullable_integer = json!({ "type": "integer", "nullable": true });
+    let nullable_string = json!({ "type": "string", "nullable": true });
+    let nullable_date_time = json!({ "type": "string", "format": "date-time", "nullable": true });
+    let storage_error = json!({
+        "type": "string",
+        "nullable": true,
+        "enum": ["STORAGE_FULL", "STORAGE_UNAVAILABLE"],
+        "description": "Why storage doesn't take writes, while status is degraded"
+    });
+    let database_pool = json!({
+        "type": "object",
+        "nullable": true,
+        "description": "Use of the database connection pool",
+        "required": ["connections", "idle", "max_connections"],
+        "properties": {
+            "connections": integer(),
+            "idle": integer(),
+            "max_connections": integer()
+        }
+    });
+    let chunk_check = json!({
+        "type": "object",
+        "required": ["chunk_number", "size", "expected_size", "is_valid"],
+        "properties": {
+            "chunk_number": integer(),
+            "size": integer(),
+            "expected_size": integer(),
+            "is_valid": { "type": "boolean" }
+        }
+    });
+
+    let mut schemas = json!({
+        "StartUploadRequest": {
+            "type": "object",
+            "required": ["filename", "file_size"],
+            "properties": {
+                "filename": string(),
+                "file_size": integer(),
+                "mime_type": nullable_string,
+                "chunk_size": nullable_integer,
+                "expires_in_hours": nullable_integer,
+                "max_downloads": nullable_integer,
+                "slug": nullable_string,
+                "burn_after_first_download_minutes": {
+                    "type": "integer",
+                    "nullable": true,
+                    "description": "Minutes the file stays available after its first download"
+                },
+                "expire_after_inactivity_hours": {
+                    "type": "integer",
+                    "nullable": true,
+                    "description": "Hours without downloads after which the file expires; each download renews them"
+                },
+                "pow_challenge": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Challenge from /api/upload/challenge, when the server requires proof of work"
+                },
+                "pow_nonce": {
+                    "type": "integer",
+                    "nullable": true,
+                    "description": "Solution of pow_challenge"
+                },
+                "filename_encrypted": {
+                    "type": "boolean",
+                    "description": "The filename will be sent encrypted on completion; only the extensions of filename are kept"
+                }
+            }
+        },
+        "CompleteUploadRequest": {
+            "type": "object",
+            "required": ["upload_id"],
+            "properties": {
+                "upload_id": uuid(),
+                "encrypted_filename": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Filename encrypted client-side (base64), sent with filename_nonce"
+                },
+                "filename_nonce": nullable_string,
+                "signature": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Detached ed25519 signature of the file (base64)"
+                }
+            }
+        },
+        "DownloadRequest": {
+            "type": "object",
+            "properties": { "encryption_key": nullable_string }
+        },
+        "ExtendExpiryRequest": {
+            "type": "object",
+            "required": ["owner_token"],
+            "properties": {
+                "owner_token": string(),
+                "expires_in_hours": nullable_integer,
+                "expires_at": nullable_date_time,
+                "encryption_key": nullable_string
+            }
+        },
+        "SignDownloadRequest": {
+            "type": "object",
+            "required": ["owner_token", "expires_in_seconds"],
+            "properties": {
+                "owner_token": string(),
+                "expires_in_seconds": integer()
+            }
+        },
+        "RotateFileRequest": {
+            "type": "object",
+            "required": ["owner_token"],
+            "properties": {
+                "owner_token": string()
+            }
+        },
+        "DeleteFileRequest": {
+            "type": "object",
+            "required": ["owner_token"],
+            "properties": {
+                "owner_token": string()
+            }
+        },
+        "RotateFileResponse": {
+            "type": "object",
+            "required": ["file_id", "download_url"],
+            "properties": {
+                "file_id": uuid(),
+                "download_url": {
+                    "type": "string",
+                    "description": "Download URL without the key anchor; append the unchanged key"
+                }
+            }
+        },
+        "WrapKeyRequest": {
+            "type": "object",
+            "required": ["owner_token", "wrapped_key", "key_salt"],
+            "properties": {
+                "owner_token": string(),
+                "wrapped_key": {
+                    "type": "string",
+                    "format": "byte",
+                    "description": "File key encrypted with a passphrase-derived key, as nonce then ciphertext"
+                },
+                "key_salt": {
+                    "type": "string",
+                    "format": "byte",
+                    "description": "Salt the passphrase was derived with (at least 8 bytes)"
+                }
+            }
+        },
+        "WrappedKeyResponse": {
+            "type": "object",
+            "required": ["wrapped_key", "key_salt"],
+            "properties": {
+                "wrapped_key": { "type": "string", "format": "byte" },
+                "key_salt": { "type": "string", "format": "byte" }
+            }
+        },
+        "SignDownloadResponse": {
+            "type": "object",
+            "required": ["url", "expires_at"],
+            "properties": {
+                "url": string(),
+                "expires_at": date_time()
+            }
+        },
+        "UploadResponse": {
+            "type": "object",
+            "required": ["file_id", "download_url", "curl_command"],
+            "properties": {
+                "file_id": uuid(),
+                "download_url": string(),
+                "encryption_key": nullable_string,
+                "curl_command": string(),
+                "owner_token": nullable_string,
+                "slug": nullable_string,
+                "expires_at": nullable_date_time,
+                "content_sha256": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "SHA-256 of the stored contents (hex), for chunked uploads"
+                }
+            }
+        },
+        "UploadPlanResponse": {
+            "type": "object",
+            "required": ["method", "file_size", "max_file_size"],
+            "properties": {
+                "method": { "type": "string", "enum": ["single", "chunked"] },
+                "file_size": integer(),
+                "chunk_size": nullable_integer,
+                "total_chunks": nullable_integer,
+                "expires_at": nullable_date_time,
+                "max_downloads": nullable_integer,
+                "max_file_size": integer()
+            }
+        },
+        "StartUploadResponse": {
+            "type": "object",
+            "required": ["upload_id", "chunk_size", "total_chunks"],
+            "properties": {
+                "upload_id": uuid(),
+                "chunk_size": integer(),
+                "total_chunks": integer(),
+                "expires_in_hours": {
+                    "type": "integer",
+                    "nullable": true,
+                    "description": "Lifetime of the file, counted from completion"
+                }
+            }
+        },
+        "ChunkUploadResponse": {
+            "type": "object",
+            "required": ["chunk_number", "uploaded_chunks", "total_chunks", "progress"],
+            "properties": {
+                "chunk_number": integer(),
+                "uploaded_chunks": integer(),
+                "total_chunks": integer(),
+                "progress": { "type": "number" }
+            }
+        },
+        "FileInfo": {
+            "type": "object",
+            "required": [
+                "file_id", "original_size", "encrypted_size", "created_at",
+                "download_count", "filename", "is_encrypted"
+            ],
+            "properties": {
+                "file_id": uuid(),
+                "original_size": integer(),
+                "encrypted_size": integer(),
+                "mime_type": nullable_string,
+                "created_at": date_time(),
+                "expires_at": nullable_date_time,
+                "download_count": integer(),
+                "max_downloads": nullable_integer,
+                "filename": string(),
+                "is_encrypted": { "type": "boolean" },
+                "signature": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Uploader's detached ed25519 signature of the plaintext (base64)"
+                },
+                "content_sha256": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "SHA-256 of the stored contents (hex), if recorded"
+                },
+                "compression": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Format the contents were compressed with before encryption; downloads decompress them"
+                },
+                "burn_after_first_download_minutes": {
+                    "type": "integer",
+                    "nullable": true,
+                    "description": "Minutes the file stays available after its first download"
+                },
+                "first_downloaded_at": {
+                    "type": "string",
+                    "format": "date-time",
+                    "nullable": true,
+                    "description": "When the file was first downloaded; with a burn time, expires_at is moved up to match"
+                },
+                "expire_after_inactivity_hours": {
+                    "type": "integer",
+                    "nullable": true,
+                    "description": "Hours without downloads after which the file expires; each download moves expires_at back to this far ahead"
+                },
+                "has_listing": {
+                    "type": "boolean",
+                    "description": "Whether a listing of the bundle's contents can be fetched"
+                },
+                "sizes_padded": {
+                    "type": "boolean",
+                    "description": "Whether original_size and encrypted_size were rounded up to hide the exact sizes"
+                }
+            }
+        },
+        "FilePreviewResponse": {
+            "type": "object",
+            "required": ["data", "nonce", "is_encrypted"],
+            "properties": {
+                "data": { "type": "string", "format": "byte" },
+                "nonce": { "type": "string", "format": "byte" },
+                "is_encrypted": { "type": "boolean" }
+            }
+        },
+        "UploadKeepaliveResponse": {
+            "type": "object",
+            "required": ["upload_id", "expires_at"],
+            "properties": {
+                "upload_id": uuid(),
+                "expires_at": date_time()
+            }
+        },
+        "UploadStatusResponse": {
+            "type": "object",
+            "required": [
+                "upload_id", "filename", "total_size", "total_chunks", "chunk_size",
+                "uploaded_chunks", "uploaded_chunk_numbers", "progress", "is_complete", "is_expired",
+                "created_at", "expires_at"
+            ],
+            "properties": {
+                "upload_id": uuid(),
+                "filename": string(),
+                "total_size": integer(),
+                "total_chunks": integer(),
+                "chunk_size": integer(),
+                "uploaded_chunks": integer(),
+                "uploaded_chunk_numbers": { "type": "array", "items": integer() },
+                "progress": { "type": "number" },
+                "bytes_per_second": {
+                    "type": "number",
+                    "nullable": true,
+                    "description": "Throughput over the latest chunks, null until a couple have arrived and while the upload is paused"
+                },
+                "eta_seconds": {
+                    "type": "integer",
+                    "nullable": true,
+                    "description": "Estimated seconds until the remaining chunks are uploaded"
+                },
+                "is_complete": { "type": "boolean" },
+                "is_expired": { "type": "boolean" },
+                "created_at": date_time(),
+                "expires_at": date_time(),
+                "verified_chunks": {
+                    "type": "array",
+                    "description": "Only with verify=true; chunks that aren't valid should be uploaded again",
+                    "items": schema_ref("ChunkCheck")
+                }
+            }
+        },
+        "ChunkCheck": chunk_check,
+        "JsonDownloadResponse": {
+            "type": "object",
+            "required": ["filename", "size", "data_base64"],
+            "properties": {
+                "filename": string(),
+                "mime_type": nullable_string,
+                "size": integer(),
+                "data_base64": {
+                    "type": "string",
+                    "format": "byte",
+                    "description": "File contents, standard base64"
+                }
+            }
+        },
+        "HealthResponse": {
+            "type": "object",
+            "required": ["status", "service", "maintenance", "timestamp"],
+            "properties": {
+                "status": { "type": "string", "enum": ["healthy", "degraded"] },
+                "service": string(),
+                "maintenance": { "type": "boolean" },
+                "timestamp": date_time(),
+                "database_pool": database_pool,
+                "storage_error": storage_error
+            }
+        },
+        "CleanupResponse": {
+            "type": "object",
+            "required": ["items_cleaned", "cleanup_type", "timestamp"],
+            "properties": {
+                "items_cleaned": integer(),
+                "cleanup_type": string(),
+                "timestamp": date_time()
+            }
+        },
+        "ErrorResponse": {
+            "type": "object",
+            "required": ["error", "code", "message"],
+            "properties": {
+                "error": string(),
+                "code": string(),
+                "message": string(),
+                "fields": {
+                    "type": "array",
+                    "items": schema_ref("FieldError")
+                }
+            }
+        },
+        "FieldError": {
+            "type": "object",
+            "required": ["field", "message"],
+            "properties": {
+                "field": string(),
+                "message": string()
+            }
+        }
+    });
+
+    // Added on their own, the object above being at the json! macro's recursion limit
+    schemas["ListingRequest"] = json!({
+        "type": "object",
+        "required": ["owner_token", "ciphertext", "nonce"],
+        "properties": {
+            "owner_token": string(),
+            "ciphertext": {
+                "type": "string",
+                "format": "byte",
+                "description": "JSON listing ({\"entries\": [{\"name\", \"size\"}]}) encrypted with the file key, at most 64 KiB"
+            },
+            "nonce": { "type": "string", "format": "byte" }
+        }
+    });
+    schemas["ListingResponse"] = json!({
+        "type": "object",
+        "required": ["ciphertext", "nonce"],
+        "properties": {
+            "ciphertext": { "type": "string", "format": "byte" },
+            "nonce": { "type": "string", "format": "byte" }
+        }
+    });
+    schemas["ServerConfigResponse"] = json!({
+        "type": "object",
+        "required": [
+            "schema_version", "max_file_size", "single_upload_max", "default_chunk_size",
+            "max_chunk_size", "max_chunks", "client_encryption_supported",
+            "server_side_encryption", "password_protection_supported", "signed_urls_supported",
+            "max_expires_in_hours", "max_downloads_limit"
+        ],
+        "properties": {
+            "schema_version": integer(),
+            "max_file_size": integer(),
+            "single_upload_max": integer(),
+            "default_chunk_size": integer(),
+            "max_chunk_size": integer(),
+            "max_chunks": integer(),
+            "client_encryption_supported": { "type": "boolean" },
+            "server_side_encryption": { "type": "boolean" },
+            "password_protection_supported": { "type": "boolean" },
+            "signed_urls_supported": { "type": "boolean" },
+            "default_expiry_hours": nullable_integer,
+            "max_expires_in_hours": integer(),
+            "max_downloads_limit": integer(),
+            "upload_pow_difficulty": {
+                "type": "integer",
+                "nullable": true,
+                "description": "Difficulty of the proof of work uploads must include, null when not required"
+            }
+        }
+    });
+    schemas["UploadChallengeResponse"] = json!({
+        "type": "object",
+        "required": ["challenge", "difficulty", "expires_at"],
+        "properties": {
+            "challenge": string(),
+            "difficulty": integer(),
+            "expires_at": date_time()
+        }
+    });
+
+    schemas
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
+
+fn uuid() -> Value {
+    json!({ "type": "string", "format": "uuid" })
+}
+
+fn date_time() -> Value {
+    json!({ "type": "string", "format": "date-time" })
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn object_schema(properties: Value) -> Value {
+    json!({ "type": "object", "properties": properties })
+}
+
+fn path_param(name: &str, description: &str, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": schema
+    })
+}
+
+fn query_param(name: &str, description: &str, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": schema
+    })
+}
+
+fn file_id_param() -> Value {
+    path_param("file_id", "File id", uuid())
+}
+
+fn slug_param() -> Value {
+    path_param("slug", "Custom name chosen at upload", string())
+}
+
+fn json_body(schema: Value) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "headers": request_id_header(),
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+fn binary_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "headers": request_id_header(),
+        "content": {
+            "application/octet-stream": { "schema": { "type": "string", "format": "binary" } }
+        }
+    })
+}
+
+fn request_id_header() -> Value {
+    json!({
+        REQUEST_ID_HEADER: {
+            "description": "Id correlating client and server logs",
+            "schema": string()
+        }
+    })
+}
+
+// Adds the given error statuses, all of which use the ErrorResponse body
+fn with_errors(mut responses: Value, statuses: &[u16]) -> Value {
+    let responses_map = responses
+        .as_object_mut()
+        .expect("responses are a JSON object");
+    for status in statuses {
+        responses_map.insert(
+            status.to_string(),
+            json!({
+                "description": error_description(*status),
+                "content": { "application/json": { "schema": schema_ref("ErrorResponse") } }
+            }),
+        );
+    }
+    responses
+}
+
+fn error_description(status: u16) -> &'static str {
+    match status {
+        400 => "Malformed request, or a missing or wrong encryption key",
+        401 => "Missing or wrong admin token, or none is configured",
+        403 => "Wrong owner token, an invalid or expired signature, or a missing or unsolved proof-of-work challenge",
+        404 => "File not found",
+        409 => "Slug already taken, or the file changed while it was being updated",
+        410 => "File expired or its download limit was reached",
+        413 => "File too large",
+        415 => "File extension or mime type not accepted by the server",
+        422 => "Invalid request fields, listed in `fields`, or malware detected",
+        429 => "Too many chunks of the upload being written, see `Retry-After`",
+        503 => "Server in maintenance mode",
+        _ => "Server error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use kusatsu_types::*;
+    use serde::{de::DeserializeOwned, Serialize};
+    use uuid::Uuid;
+
+    // Serialized example of a type, with every optional field filled in
+    struct Example {
+        value: Value,
+        // Whether a JSON value is accepted as the type
+        deserializes: fn(Value) -> bool,
+    }
+
+    fn example<T: Serialize + DeserializeOwned>(value: T) -> Example {
+        Example {
+            value: serde_json::to_value(value).unwrap(),
+            deserializes: |value| serde_json::from_value::<T>(value).is_ok(),
+        }
+    }
+
+    fn examples() -> Vec<(&'static str, Example)> {
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        let text = || "text".to_string();
+        let chunk_check = ChunkCheck {
+            chunk_number: 0,
+            size: 5,
+            expected_size: 5,
+            is_valid: true,
+        };
+
+        vec![
+            (
+                "StartUploadRequest",
+                example(StartUploadRequest {
+                    filename: text(),
+                    file_size: 1,
+                    mime_type: Some(text()),
+                    chunk_size: Some(1),
+                    expires_in_hours: Some(1),
+                    max_downloads: Some(1),
+                    slug: Some(text()),
+                    burn_after_first_download_minutes: Some(1),
+                    expire_after_inactivity_hours: Some(1),
+                    pow_challenge: Some(text()),
+                    pow_nonce: Some(1),
+                    filename_encrypted: true,
+                }),
+            ),
+            (
+                "CompleteUploadRequest",
+                example(CompleteUploadRequest {
+                    upload_id: id,
+                    encrypted_filename: Some(text()),
+                    filename_nonce: Some(text()),
+                    signature: Some(text()),
+                }),
+            ),
+            (
+                "DownloadRequest",
+                example(DownloadRequest {
+                    encryption_key: Some(text()),
+                }),
+            ),
+            (
+                "ExtendExpiryRequest",
+                example(ExtendExpiryRequest {
+                    owner_token: text(),
+                    expires_in_hours: Some(1),
+                    expires_at: Some(now),
+                    encryption_key: Some(text()),
+                }),
+            ),
+            (
+                "SignDownloadRequest",
+                example(SignDownloadRequest {
+                    owner_token: text(),
+                    expires_in_seconds: 1,
+                }),
+            ),
+            (
+                "RotateFileRequest",
+                example(RotateFileRequest {
+                    owner_token: text(),
+                }),
+            ),
+            (
+                "DeleteFileRequest",
+                example(DeleteFileRequest {
+                    owner_token: text(),
+                }),
+            ),
+            (
+                "RotateFileResponse",
+                example(RotateFileResponse {
+                    file_id: id,
+                    download_url: text(),
+                }),
+            ),
+            (
+                "WrapKeyRequest",
+                example(WrapKeyRequest {
+                    owner_token: text(),
+                    wrapped_key: text(),
+                    key_salt: text(),
+                }),
+            ),
+            (
+                "WrappedKeyResponse",
+                example(WrappedKeyResponse {
+                    wrapped_key: text(),
+                    key_salt: text(),
+                }),
+            ),
+            (
+                "SignDownloadResponse",
+                example(SignDownloadResponse {
+                    url: text(),
+                    expires_at: now,
+                }),
+            ),
+            (
+                "UploadResponse",
+                example(UploadResponse {
+                    file_id: id,
+                    download_url: text(),
+                    encryption_key: Some(text()),
+                    curl_command: text(),
+                    owner_token: Some(text()),
+                    slug: Some(text()),
+                    expires_at: Some(now),
+                    content_sha256: Some(text()),
+                }),
+            ),
+            (
+                "UploadPlanResponse",
+                example(UploadPlanResponse {
+                    method: UploadMethod::Chunked,
+                    file_size: 1,
+                    chunk_size: Some(1),
+                    total_chunks: Some(1),
+                    expires_at: Some(now),
+                    max_downloads: Some(1),
+                    max_file_size: 1,
+                }),
+            ),
+            (
+                "StartUploadResponse",
+                example(StartUploadResponse {
+                    upload_id: id,
+                    chunk_size: 1,
+                    total_chunks: 1,
+                    expires_in_hours: Some(1),
+                }),
+            ),
+            (
+                "ChunkUploadResponse",
+                example(ChunkUploadResponse {
+                    chunk_number: 0,
+                    uploaded_chunks: 1,
+                    total_chunks: 1,
+                    progress: 100.0,
+                }),
+            ),
+            (
+                "FileInfo",
+                example(FileInfo {
+                    file_id: id,
+                    original_size: 1,
+                    encrypted_size: 1,
+                    mime_type: Some(text()),
+                    created_at: now,
+                    expires_at: Some(now),
+                    download_count: 0,
+                    max_downloads: Some(1),
+                    filename: text(),
+                    is_encrypted: true,
+                    signature: Some(text()),
+                    content_sha256: Some(text()),
+                    compression: Some(text()),
+                    burn_after_first_download_minutes: Some(1),
+                    first_downloaded_at: Some(now),
+                    expire_after_inactivity_hours: Some(1),
+                    has_listing: true,
+                    sizes_padded: true,
+                }),
+            ),
+            (
+                "FilePreviewResponse",
+                example(FilePreviewResponse {
+                    data: text(),
+                    nonce: text(),
+                    is_encrypted: true,
+                }),
+            ),
+            (
+                "UploadKeepaliveResponse",
+                example(UploadKeepaliveResponse {
+                    upload_id: id,
+                    expires_at: now,
+                }),
+            ),
+            (
+                "UploadStatusResponse",
+                example(UploadStatusResponse {
+                    upload_id: id,
+                    filename: text(),
+                    total_size: 5,
+                    total_chunks: 1,
+                    chunk_size: 5,
+                    uploaded_chunks: 1,
+                    uploaded_chunk_numbers: vec![0],
+                    progress: 100.0,
+                    bytes_per_second: Some(1.5),
+                    eta_seconds: Some(1),
+                    is_complete: false,
+                    is_expired: false,
+                    created_at: now,
+                    expires_at: now,
+                    verified_chunks: Some(vec![chunk_check.clone()]),
+                }),
+            ),
+            ("ChunkCheck", example(chunk_check)),
+            (
+                "JsonDownloadResponse",
+                example(JsonDownloadResponse {
+                    filename: text(),
+                    mime_type: Some(text()),
+                    size: 1,
+                    data_base64: text(),
+                }),
+            ),
+            (
+                "HealthResponse",
+                example(HealthResponse {
+                    status: "degraded".to_string(),
+                    service: text(),
+                    maintenance: false,
+                    timestamp: now,
+                    database_pool: Some(DatabasePoolStatus {
+                        connections: 1,
+                        idle: 1,
+                        max_connections: 1,
+                    }),
+                    storage_error: Some(error_code::STORAGE_FULL.to_string()),
+                }),
+            ),
+            (
+                "CleanupResponse",
+                example(CleanupResponse {
+                    items_cleaned: 1,
+                    cleanup_type: text(),
+                    timestamp: now,
+                }),
+            ),
+            (
+                "ErrorResponse",
+                example(ErrorResponse {
+                    error: text(),
+                    code: text(),
+                    message: text(),
+                    fields: vec![FieldError::new("field", "message")],
+                }),
+            ),
+            ("FieldError", example(FieldError::new("field", "message"))),
+            (
+                "ListingRequest",
+                example(ListingRequest {
+                    owner_token: text(),
+                    ciphertext: text(),
+                    nonce: text(),
+                }),
+            ),
+            (
+                "ListingResponse",
+                example(ListingResponse {
+                    ciphertext: text(),
+                    nonce: text(),
+                }),
+            ),
+            (
+                "ServerConfigResponse",
+                example(ServerConfigResponse {
+                    schema_version: SERVER_CONFIG_SCHEMA_VERSION,
+                    max_file_size: 1,
+                    single_upload_max: 1,
+                    default_chunk_size: 1,
+                    max_chunk_size: 1,
+                    max_chunks: 1,
+                    client_encryption_supported: true,
+                    server_side_encryption: true,
+                    password_protection_supported: true,
+                    signed_urls_supported: true,
+                    default_expiry_hours: Some(1),
+                    max_expires_in_hours: 1,
+                    max_downloads_limit: 1,
+                    upload_pow_difficulty: Some(1),
+                }),
+            ),
+            (
+                "UploadChallengeResponse",
+                example(UploadChallengeResponse {
+                    challenge: text(),
+                    difficulty: 1,
+                    expires_at: now,
+                }),
+            ),
+        ]
+    }
+
+    // Whether `value` is what `schema` describes, down to every property
+    fn check(schemas: &Value, schema: &Value, value: &Value, path: &str) {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            return check(schemas, &schemas[name], value, path);
+        }
+        if value.is_null() {
+            assert_eq!(schema["nullable"], true, "{} isn't nullable", path);
+            return;
+        }
+        if let Some(allowed) = schema["enum"].as_array() {
+            assert!(
+                allowed.contains(value),
+                "{} isn't one of {:?}",
+                path,
+                allowed
+            );
+        }
+
+        match schema["type"].as_str() {
+            Some("object") => {
+                let object = value.as_object().expect(path);
+                let properties = schema["properties"].as_object().expect(path);
+                let mut keys: Vec<_> = object.keys().collect();
+                let mut documented: Vec<_> = properties.keys().collect();
+                keys.sort();
+                documented.sort();
+                assert_eq!(keys, documented, "properties of {}", path);
+                for required in schema["required"].as_array().into_iter().flatten() {
+                    let required = required.as_str().unwrap();
+                    assert!(
+                        !object[required].is_null(),
+                        "{}.{} is required",
+                        path,
+                        required
+                    );
+                }
+                for (key, value) in object {
+                    let path = format!("{}.{}", path, key);
+                    check(schemas, &properties[key], value, &path);
+                }
+            }
+            Some("array") => {
+                for (index, item) in value.as_array().expect(path).iter().enumerate() {
+                    check(
+                        schemas,
+                        &schema["items"],
+                        item,
+                        &format!("{}[{}]", path, index),
+                    );
+                }
+            }
+            Some("string") => {
+                let string = value.as_str().expect(path);
+                match schema["format"].as_str() {
+                    Some("uuid") => assert!(Uuid::parse_str(string).is_ok(), "{}", path),
+                    Some("date-time") => assert!(
+                        chrono::DateTime::parse_from_rfc3339(string).is_ok(),
+                        "{}",
+                        path
+                    ),
+                    _ => {}
+                }
+            }
+            Some("integer") => assert!(value.is_i64() || value.is_u64(), "{}", path),
+            Some("number") => assert!(value.is_number(), "{}", path),
+            Some("boolean") => assert!(value.is_boolean(), "{}", path),
+            other => panic!("{} has unexpected type {:?}", path, other),
+        }
+    }
+
+    #[test]
+    fn test_schemas_match_types() {
+        let schemas = schemas();
+        let examples = examples();
+
+        // Every schema is checked against the type it describes
+        let mut names: Vec<_> = examples.iter().map(|(name, _)| *name).collect();
+        let mut documented: Vec<_> = schemas
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        names.sort();
+        documented.sort();
+        assert_eq!(names, documented);
+
+        for (
+            name,
+            Example {
+                value,
+                deserializes,
+            },
+        ) in examples
+        {
+            let schema = &schemas[name];
+            check(&schemas, schema, &value, name);
+
+            // Fields the schema doesn't require can be left out
+            let required: Map<String, Value> = value
+                .as_object()
+                .unwrap()
+                .iter()
+                .filter(|(key, _)| {
+                    schema["required"]
+                        .as_array()
+                        .is_some_and(|required| required.contains(&json!(key)))
+                })
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            assert!(
+                deserializes(Value::Object(required)),
+                "{} with only required fields",
+                name
+            );
+        }
+    }
+}
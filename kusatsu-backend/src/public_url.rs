@@ -0,0 +1,144 @@
+use crate::config::Config;
+use crate::https::FORWARDED_PROTO_HEADER;
+use axum::http::{header, HeaderMap};
+
+/// Header a proxy sets to the host the client asked for
+pub const FORWARDED_HOST_HEADER: &str = "x-forwarded-host";
+
+/// Public URLs that links handed out in a response are built from
+///
+/// By default these are the configured `base_url` and `api_url`. With
+/// `trust_forwarded_host` set, the scheme and host come from the request
+/// instead: `X-Forwarded-Host` or else `Host`, and `X-Forwarded-Proto`, so
+/// links match whichever hostname the client reached the service by. Paths of
+/// the configured URLs are kept, and headers that don't hold a plausible host
+/// or scheme are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicUrls {
+    pub base_url: String,
+    pub api_url: String,
+}
+
+impl PublicUrls {
+    pub fn for_request(config: &Config, headers: &HeaderMap) -> Self {
+        let configured = Self {
+            base_url: config.base_url.clone(),
+            api_url: config.api_url.clone(),
+        };
+        if !config.trust_forwarded_host {
+            return configured;
+        }
+
+        let Some(host) = first_value(headers, FORWARDED_HOST_HEADER)
+            .or_else(|| first_value(headers, header::HOST.as_str()))
+            .filter(|host| is_valid_host(host))
+        else {
+            return configured;
+        };
+        let scheme = first_value(headers, FORWARDED_PROTO_HEADER)
+            .map(str::to_ascii_lowercase)
+            .filter(|scheme| scheme == "http" || scheme == "https");
+
+        Self {
+            base_url: with_origin(&configured.base_url, scheme.as_deref(), host),
+            api_url: with_origin(&configured.api_url, scheme.as_deref(), host),
+        }
+    }
+}
+
+// First entry of a header; chained proxies append theirs after the client's
+fn first_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+// Hostnames, IPv4 and bracketed IPv6 addresses, with an optional port
+fn is_valid_host(host: &str) -> bool {
+    host.len() <= 255
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+}
+
+// `url` with its host, and its scheme when one is given, replaced
+fn with_origin(url: &str, scheme: Option<&str>, host: &str) -> String {
+    let (configured_scheme, rest) = url.split_once("://").unwrap_or(("http", url));
+    let path = rest.find('/').map_or("", |index| &rest[index..]);
+    format!("{}://{}{}", scheme.unwrap_or(configured_scheme), host, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn config(trust_forwarded_host: bool) -> Config {
+        Config {
+            base_url: "https://files.example.com/share".to_string(),
+            api_url: "https://files.example.com".to_string(),
+            trust_forwarded_host,
+            ..Config::for_tests()
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_configured_urls_are_used_unless_trusted() {
+        let urls = PublicUrls::for_request(
+            &config(false),
+            &headers(&[("host", "other.example.com"), ("x-forwarded-proto", "http")]),
+        );
+        assert_eq!(urls.base_url, "https://files.example.com/share");
+        assert_eq!(urls.api_url, "https://files.example.com");
+    }
+
+    #[test]
+    fn test_forwarded_host_and_proto_take_precedence() {
+        let urls = PublicUrls::for_request(
+            &config(true),
+            &headers(&[
+                ("host", "127.0.0.1:3000"),
+                ("x-forwarded-host", "mirror.example.org, proxy.internal"),
+                ("x-forwarded-proto", "HTTP"),
+            ]),
+        );
+        assert_eq!(urls.base_url, "http://mirror.example.org/share");
+        assert_eq!(urls.api_url, "http://mirror.example.org");
+
+        // Without forwarded headers, the Host header and the configured scheme are used
+        let urls = PublicUrls::for_request(&config(true), &headers(&[("host", "[::1]:8443")]));
+        assert_eq!(urls.base_url, "https://[::1]:8443/share");
+    }
+
+    #[test]
+    fn test_implausible_headers_are_ignored() {
+        let urls = PublicUrls::for_request(
+            &config(true),
+            &headers(&[
+                ("x-forwarded-host", "evil.example.com/phish?"),
+                ("x-forwarded-proto", "javascript"),
+            ]),
+        );
+        assert_eq!(
+            urls,
+            PublicUrls::for_request(&config(false), &HeaderMap::new())
+        );
+
+        let urls = PublicUrls::for_request(
+            &config(true),
+            &headers(&[("host", "files.example.net"), ("x-forwarded-proto", "ftp")]),
+        );
+        assert_eq!(urls.api_url, "https://files.example.net");
+    }
+}
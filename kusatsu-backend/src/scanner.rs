@@ -0,0 +1,160 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// Size of each INSTREAM chunk sent to clamd
+const SCAN_CHUNK_SIZE: usize = 64 * 1024;
+
+// Upper bound for a whole scan, including connecting to the daemon
+const SCAN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Outcome of a ClamAV scan
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScanResult {
+    Clean,
+    Infected(String),
+}
+
+/// Scan plaintext upload data if a ClamAV daemon is configured
+///
+/// Returns `AppError::MalwareDetected` when the data is flagged. If the daemon
+/// can't be reached the upload is rejected, unless `clamav_fail_open` is set.
+pub async fn check_upload(config: &Config, data: &[u8]) -> Result<()> {
+    let Some(address) = &config.clamav_address else {
+        return Ok(());
+    };
+
+    let result = match tokio::time::timeout(SCAN_TIMEOUT, scan(address, data)).await {
+        Ok(result) => result,
+        Err(_) => Err(AppError::ServerError("Virus scan timed out".to_string())),
+    };
+
+    match result {
+        Ok(ScanResult::Clean) => Ok(()),
+        Ok(ScanResult::Infected(signature)) => {
+            tracing::warn!("🦠 Rejected upload, malware detected: {}", signature);
+            Err(AppError::MalwareDetected(signature))
+        }
+        Err(e) if config.clamav_fail_open => {
+            tracing::warn!("Virus scan failed, accepting upload (fail-open): {}", e);
+            Ok(())
+        }
+        Err(e) => {
+            tracing::error!("Virus scan failed, rejecting upload: {}", e);
+            Err(AppError::ServerError("Virus scan unavailable".to_string()))
+        }
+    }
+}
+
+/// Stream data to clamd using the INSTREAM protocol
+pub async fn scan(address: &str, data: &[u8]) -> Result<ScanResult> {
+    let mut stream = TcpStream::connect(address).await.map_err(|e| {
+        AppError::ServerError(format!("Failed to connect to ClamAV at {}: {}", address, e))
+    })?;
+
+    stream.write_all(b"zINSTREAM\0").await?;
+
+    // Each chunk is prefixed with its length; a zero length ends the stream
+    for chunk in data.chunks(SCAN_CHUNK_SIZE) {
+        stream
+            .write_all(&(chunk.len() as u32).to_be_bytes())
+            .await?;
+        stream.write_all(chunk).await?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+
+    parse_response(&response)
+}
+
+// Parse a reply such as "stream: OK" or "stream: Eicar-Signature FOUND"
+fn parse_response(response: &[u8]) -> Result<ScanResult> {
+    let response = String::from_utf8_lossy(response);
+    let response = response.trim_end_matches(['\0', '\n']).trim();
+    let status = response.strip_prefix("stream:").unwrap_or(response).trim();
+
+    if status == "OK" {
+        Ok(ScanResult::Clean)
+    } else if let Some(signature) = status.strip_suffix("FOUND") {
+        Ok(ScanResult::Infected(signature.trim().to_string()))
+    } else {
+        Err(AppError::ServerError(format!(
+            "Unexpected ClamAV response: {}",
+            response
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    // Minimal clamd stand-in that flags any stream containing `needle`
+    async fn spawn_fake_clamd(needle: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            let mut command = [0u8; 10];
+            socket.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+
+            let mut received = Vec::new();
+            loop {
+                let mut len = [0u8; 4];
+                socket.read_exact(&mut len).await.unwrap();
+                let len = u32::from_be_bytes(len) as usize;
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0u8; len];
+                socket.read_exact(&mut chunk).await.unwrap();
+                received.extend_from_slice(&chunk);
+            }
+
+            let reply: &[u8] = if received.windows(needle.len()).any(|w| w == needle) {
+                b"stream: Test-Signature FOUND\0"
+            } else {
+                b"stream: OK\0"
+            };
+            socket.write_all(reply).await.unwrap();
+        });
+
+        address
+    }
+
+    #[tokio::test]
+    async fn test_scan_clean_and_infected() {
+        let address = spawn_fake_clamd(b"EVIL").await;
+        let result = scan(&address, b"harmless data").await.unwrap();
+        assert_eq!(result, ScanResult::Clean);
+
+        let address = spawn_fake_clamd(b"EVIL").await;
+        let result = scan(&address, b"some EVIL payload").await.unwrap();
+        assert_eq!(result, ScanResult::Infected("Test-Signature".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_check_upload_fail_open_and_closed() {
+        // Nothing listens on this port
+        let config = Config {
+            clamav_address: Some("127.0.0.1:1".to_string()),
+            clamav_fail_open: false,
+            ..Config::for_tests()
+        };
+        assert!(check_upload(&config, b"data").await.is_err());
+
+        let config = Config {
+            clamav_fail_open: true,
+            ..config
+        };
+        assert!(check_upload(&config, b"data").await.is_ok());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::error::{AppError, Result};
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// The signed message binds the link to one file and one expiry
+fn signing_mac(secret: &str, file_id: Uuid, expires: i64) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}:{}", file_id, expires).as_bytes());
+    mac
+}
+
+/// Sign a download link for `file_id` valid until the `expires` unix timestamp
+pub fn sign(secret: &str, file_id: Uuid, expires: i64) -> String {
+    let signature = signing_mac(secret, file_id, expires)
+        .finalize()
+        .into_bytes();
+    general_purpose::URL_SAFE_NO_PAD.encode(signature)
+}
+
+/// Check a signed download link
+///
+/// Fails if the signature doesn't match or the link has expired.
+pub fn verify(secret: &str, file_id: Uuid, expires: i64, signature: &str) -> Result<()> {
+    let signature = general_purpose::URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| AppError::InvalidSignature)?;
+
+    // Constant-time comparison
+    signing_mac(secret, file_id, expires)
+        .verify_slice(&signature)
+        .map_err(|_| AppError::InvalidSignature)?;
+
+    if chrono::Utc::now().timestamp() > expires {
+        return Err(AppError::FileExpired);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let file_id = Uuid::new_v4();
+        let expires = chrono::Utc::now().timestamp() + 60;
+        let signature = sign("secret", file_id, expires);
+
+        assert!(verify("secret", file_id, expires, &signature).is_ok());
+
+        // Tampering with any part of the link invalidates it
+        assert!(verify("other-secret", file_id, expires, &signature).is_err());
+        assert!(verify("secret", Uuid::new_v4(), expires, &signature).is_err());
+        assert!(verify("secret", file_id, expires + 1, &signature).is_err());
+        assert!(verify("secret", file_id, expires, "garbage").is_err());
+    }
+
+    #[test]
+    fn test_expired_link_is_rejected() {
+        let file_id = Uuid::new_v4();
+        let expires = chrono::Utc::now().timestamp() - 1;
+        let signature = sign("secret", file_id, expires);
+
+        assert!(matches!(
+            verify("secret", file_id, expires, &signature),
+            Err(AppError::FileExpired)
+        ));
+    }
+}
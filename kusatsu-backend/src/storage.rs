@@ -1,28 +1,328 @@
+use crate::container::{self, ContainerHeader};
 use crate::error::{AppError, Result};
+use crate::storage_health::write_error;
+use async_trait::async_trait;
 use std::path::{Path, PathBuf};
 use tokio::fs;
+use tokio::io::{AsyncRead, AsyncWriteExt};
 use uuid::Uuid;
 
-/// File storage manager that handles storing and retrieving encrypted files
+/// A stored file opened for streaming
+pub type StoredFileReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Long-term storage of the (encrypted) file data
+///
+/// Files are keyed by the relative path returned from [`FileStorage::store_file`],
+/// which is what the database records. Each is stored in the container format
+/// of [`crate::container`], so it can be recovered without its record.
+#[async_trait]
+pub trait FileStorage: Send + Sync {
+    /// Prepare the store (create directories)
+    async fn init(&self) -> Result<()>;
+
+    /// Store `ciphertext` behind `header`, replacing any previous copy, and return
+    /// its relative path
+    async fn store_file(
+        &self,
+        file_id: Uuid,
+        header: &ContainerHeader,
+        ciphertext: &[u8],
+    ) -> Result<String>;
+
+    /// Read a whole stored file, header included, failing with
+    /// [`AppError::FileNotFound`] if missing
+    async fn retrieve_file(&self, relative_path: &str) -> Result<Vec<u8>>;
+
+    /// Open a stored file for streaming, returning it with its size in bytes
+    async fn open_file(&self, relative_path: &str) -> Result<(StoredFileReader, u64)>;
+
+    /// Open a stored file and check its container header, returning the header,
+    /// the ciphertext for streaming and its size in bytes
+    ///
+    /// Files stored before the container format have no header and are returned whole.
+    async fn read_container(
+        &self,
+        relative_path: &str,
+    ) -> Result<(Option<ContainerHeader>, StoredFileReader, u64)> {
+        let (reader, size) = self.open_file(relative_path).await?;
+        container::read_header(reader, size).await
+    }
+
+    /// Check whether a stored file exists
+    async fn file_exists(&self, relative_path: &str) -> bool;
+
+    /// List every stored file
+    async fn list_stored_files(&self) -> Result<Vec<StoredFile>>;
+
+    /// Delete a stored file; deleting a missing file succeeds
+    async fn delete_file(&self, relative_path: &str) -> Result<()>;
+
+    /// Count the stored files and their total size
+    async fn get_stats(&self) -> Result<StorageStats>;
+
+    /// Check that the store takes writes, failing like a write would
+    async fn check_writable(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Relative path of a file: `<ab>/<cd>/<file_id>.enc`
+///
+/// The two levels come from the first four hex digits of the id (16^4 = 65,536
+/// directories), which keeps directories small on disk.
+pub fn relative_file_path(file_id: Uuid) -> PathBuf {
+    let file_id_str = file_id.to_string().replace('-', "");
+
+    let level1 = &file_id_str[0..2]; // First 2 chars (256 dirs)
+    let level2 = &file_id_str[2..4]; // Next 2 chars (256 subdirs each)
+
+    Path::new(level1)
+        .join(level2)
+        .join(format!("{}.enc", file_id))
+}
+
+/// Create an empty temporary file under `<storage_dir>/tmp`
+///
+/// The file is removed when the returned [`TempFile`] is dropped. With
+/// `strict_permissions`, see [`create_dir_all`] and [`create_file`].
+pub async fn create_temp_file(
+    storage_dir: impl AsRef<Path>,
+    strict_permissions: bool,
+) -> Result<(TempFile, fs::File)> {
+    let temp_dir = storage_dir.as_ref().join("tmp");
+    create_dir_all(&temp_dir, strict_permissions)
+        .await
+        .map_err(|e| write_error("Failed to create temp directory", e))?;
+
+    let path = temp_dir.join(format!("{}.upload", Uuid::new_v4()));
+    let file = create_file(&path, strict_permissions)
+        .await
+        .map_err(|e| write_error("Failed to create temp file", e))?;
+
+    Ok((TempFile { path }, file))
+}
+
+/// Create a directory and its missing parents
+///
+/// With `strict_permissions`, the directories created are only accessible by
+/// their owner (0700) on Unix. Existing directories are left as they are.
+pub(crate) async fn create_dir_all(path: &Path, strict_permissions: bool) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    if strict_permissions {
+        builder.mode(0o700);
+    }
+    #[cfg(not(unix))]
+    let _ = strict_permissions;
+
+    builder.create(path).await
+}
+
+/// Create or truncate a file for writing
+///
+/// With `strict_permissions`, a new file is only readable by its owner (0600)
+/// on Unix.
+pub(crate) async fn create_file(
+    path: &Path,
+    strict_permissions: bool,
+) -> std::io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if strict_permissions {
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = strict_permissions;
+
+    options.open(path).await
+}
+
+/// Temporary file [`write_atomic`] writes `path` through
+pub(crate) fn atomic_temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    PathBuf::from(temp_path)
+}
+
+/// Temporary file of one [`write_new`] to `path`, unique to that write
+pub(crate) fn unique_temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(".{}.tmp", Uuid::new_v4()));
+    PathBuf::from(temp_path)
+}
+
+/// Write `parts` one after the other to `path` through a temporary `<path>.tmp` file
+///
+/// The final rename is atomic, so a crash or an aborted request mid-write never
+/// leaves a truncated file at `path`.
+pub(crate) async fn write_atomic(
+    path: &Path,
+    parts: &[&[u8]],
+    strict_permissions: bool,
+) -> std::io::Result<()> {
+    let temp_path = atomic_temp_path(path);
+
+    // A leftover temp file would keep its old mode, so start from a fresh one
+    let _ = fs::remove_file(&temp_path).await;
+
+    let written = async {
+        let mut file = create_file(&temp_path, strict_permissions).await?;
+        for part in parts {
+            file.write_all(part).await?;
+        }
+        file.flush().await
+    };
+    if let Err(e) = written.await {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(e);
+    }
+
+    fs::rename(&temp_path, path).await
+}
+
+/// Write `data` to `path` unless a file is already there, returning whether it was
+///
+/// The data goes to a temporary file of this write's own, which is then linked to
+/// `path`; linking fails when `path` exists. Of concurrent writes to one path,
+/// exactly one lands, and none ever sees another's partial data.
+pub(crate) async fn write_new(
+    path: &Path,
+    data: &[u8],
+    strict_permissions: bool,
+) -> std::io::Result<bool> {
+    let temp_path = unique_temp_path(path);
+
+    let written = async {
+        let mut file = create_file(&temp_path, strict_permissions).await?;
+        file.write_all(data).await?;
+        file.flush().await?;
+
+        match fs::hard_link(&temp_path, path).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+    .await;
+    let _ = fs::remove_file(&temp_path).await;
+
+    written
+}
+
+// Subdirectories named with two hex digits, as created by `relative_file_path`
+async fn read_hex_dirs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    let mut entries = fs::read_dir(dir)
+        .await
+        .map_err(|e| AppError::ServerError(format!("Failed to read storage directory: {}", e)))?;
+
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| AppError::ServerError(format!("Failed to read directory entry: {}", e)))?
+    {
+        let name = entry.file_name();
+        let is_hex_name = name
+            .to_str()
+            .is_some_and(|name| name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit()));
+
+        if is_hex_name && entry.file_type().await?.is_dir() {
+            dirs.push(entry.path());
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// A temporary file that is deleted when dropped
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Location of the temporary file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// A file found by [`FileStorage::list_stored_files`]
+#[derive(Debug)]
+pub struct StoredFile {
+    pub file_id: Uuid,
+    pub relative_path: String,
+    pub modified: std::time::SystemTime,
+}
+
+/// Keeps files on the local filesystem
 #[derive(Clone)]
-pub struct FileStorage {
+pub struct LocalFileStorage {
     storage_root: PathBuf,
+    strict_permissions: bool,
 }
 
-impl FileStorage {
+impl LocalFileStorage {
     /// Create a new file storage instance
     pub fn new(storage_root: impl AsRef<Path>) -> Self {
         Self {
             storage_root: storage_root.as_ref().to_path_buf(),
+            strict_permissions: false,
         }
     }
 
-    /// Initialize the storage directory structure
-    pub async fn init(&self) -> Result<()> {
+    /// Create files (0600) and directories (0700) accessible only by their owner, on Unix
+    pub fn with_strict_permissions(mut self, strict_permissions: bool) -> Self {
+        self.strict_permissions = strict_permissions;
+        self
+    }
+
+    /// Absolute location of a file, see [`relative_file_path`]
+    pub fn generate_file_path(&self, file_id: Uuid) -> PathBuf {
+        self.storage_root.join(relative_file_path(file_id))
+    }
+
+    /// Clean up empty parent directories after file deletion
+    fn cleanup_empty_dirs<'a>(
+        &'a self,
+        file_path: &'a Path,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send + 'a>> {
+        Box::pin(async move {
+            if let Some(parent) = file_path.parent() {
+                // Only clean up directories within our storage root
+                if parent.starts_with(&self.storage_root) && parent != self.storage_root {
+                    if let Ok(mut entries) = fs::read_dir(parent).await {
+                        // Check if directory is empty
+                        if entries.next_entry().await.unwrap_or(None).is_none()
+                            && fs::remove_dir(parent).await.is_ok()
+                        {
+                            tracing::debug!("🧹 Cleaned up empty directory: {}", parent.display());
+                            // Recursively clean up parent directories
+                            Box::pin(self.cleanup_empty_dirs(parent)).await;
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl FileStorage for LocalFileStorage {
+    async fn init(&self) -> Result<()> {
         if !self.storage_root.exists() {
-            fs::create_dir_all(&self.storage_root).await.map_err(|e| {
-                AppError::ServerError(format!("Failed to create storage directory: {}", e))
-            })?;
+            create_dir_all(&self.storage_root, self.strict_permissions)
+                .await
+                .map_err(|e| {
+                    AppError::ServerError(format!("Failed to create storage directory: {}", e))
+                })?;
             tracing::info!(
                 "📁 Created storage directory: {}",
                 self.storage_root.display()
@@ -31,37 +331,26 @@ impl FileStorage {
         Ok(())
     }
 
-    /// Generate a performant file path for a given file ID
-    /// Uses a hierarchical structure: storage/ab/cd/abcd1234-5678-9abc-def0-123456789abc.enc
-    /// This distributes files across subdirectories to avoid filesystem performance issues
-    pub fn generate_file_path(&self, file_id: Uuid) -> PathBuf {
-        let file_id_str = file_id.to_string().replace('-', "");
-
-        // Take first 4 characters for two-level directory structure
-        // This gives us 16^4 = 65,536 possible directories
-        let level1 = &file_id_str[0..2]; // First 2 chars (256 dirs)
-        let level2 = &file_id_str[2..4]; // Next 2 chars (256 subdirs each)
-
-        let filename = format!("{}.enc", file_id);
-
-        self.storage_root.join(level1).join(level2).join(filename)
-    }
-
-    /// Store encrypted file data to disk
-    pub async fn store_file(&self, file_id: Uuid, encrypted_data: &[u8]) -> Result<String> {
+    async fn store_file(
+        &self,
+        file_id: Uuid,
+        header: &ContainerHeader,
+        ciphertext: &[u8],
+    ) -> Result<String> {
         let file_path = self.generate_file_path(file_id);
+        let header = header.encode()?;
 
         // Create parent directories if they don't exist
         if let Some(parent) = file_path.parent() {
-            fs::create_dir_all(parent)
+            create_dir_all(parent, self.strict_permissions)
                 .await
-                .map_err(|e| AppError::ServerError(format!("Failed to create directory: {}", e)))?;
+                .map_err(|e| write_error("Failed to create directory", e))?;
         }
 
-        // Write encrypted data to file
-        fs::write(&file_path, encrypted_data)
+        // Write the header and encrypted data to file
+        write_atomic(&file_path, &[&header, ciphertext], self.strict_permissions)
             .await
-            .map_err(|e| AppError::ServerError(format!("Failed to write file: {}", e)))?;
+            .map_err(|e| write_error("Failed to write file", e))?;
 
         // Return relative path for database storage
         let relative_path = file_path
@@ -74,8 +363,7 @@ impl FileStorage {
         Ok(relative_path)
     }
 
-    /// Retrieve encrypted file data from disk
-    pub async fn retrieve_file(&self, relative_path: &str) -> Result<Vec<u8>> {
+    async fn retrieve_file(&self, relative_path: &str) -> Result<Vec<u8>> {
         let file_path = self.storage_root.join(relative_path);
 
         let data = fs::read(&file_path).await.map_err(|e| match e.kind() {
@@ -91,8 +379,76 @@ impl FileStorage {
         Ok(data)
     }
 
-    /// Delete a file from disk
-    pub async fn delete_file(&self, relative_path: &str) -> Result<()> {
+    async fn open_file(&self, relative_path: &str) -> Result<(StoredFileReader, u64)> {
+        let file_path = self.storage_root.join(relative_path);
+
+        let file = fs::File::open(&file_path)
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => AppError::FileNotFound,
+                _ => AppError::ServerError(format!("Failed to open file: {}", e)),
+            })?;
+        let size = file
+            .metadata()
+            .await
+            .map_err(|e| AppError::ServerError(format!("Failed to read file metadata: {}", e)))?
+            .len();
+
+        Ok((Box::new(file), size))
+    }
+
+    async fn file_exists(&self, relative_path: &str) -> bool {
+        fs::try_exists(self.storage_root.join(relative_path))
+            .await
+            .unwrap_or(false)
+    }
+
+    // Only the `<ab>/<cd>/<file_id>.enc` tree is listed; other entries (chunks,
+    // temporary uploads, in-flight `.tmp` writes) are skipped
+    async fn list_stored_files(&self) -> Result<Vec<StoredFile>> {
+        let mut stored_files = Vec::new();
+
+        for level1 in read_hex_dirs(&self.storage_root).await? {
+            for level2 in read_hex_dirs(&level1).await? {
+                let mut entries = fs::read_dir(&level2).await.map_err(|e| {
+                    AppError::ServerError(format!("Failed to read storage directory: {}", e))
+                })?;
+
+                while let Some(entry) = entries.next_entry().await.map_err(|e| {
+                    AppError::ServerError(format!("Failed to read directory entry: {}", e))
+                })? {
+                    let path = entry.path();
+                    let Some(file_id) = path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .and_then(|name| name.strip_suffix(".enc"))
+                        .and_then(|stem| Uuid::parse_str(stem).ok())
+                    else {
+                        continue;
+                    };
+
+                    let modified = entry.metadata().await?.modified()?;
+                    let relative_path = path
+                        .strip_prefix(&self.storage_root)
+                        .map_err(|e| {
+                            AppError::ServerError(format!("Failed to get relative path: {}", e))
+                        })?
+                        .to_string_lossy()
+                        .to_string();
+
+                    stored_files.push(StoredFile {
+                        file_id,
+                        relative_path,
+                        modified,
+                    });
+                }
+            }
+        }
+
+        Ok(stored_files)
+    }
+
+    async fn delete_file(&self, relative_path: &str) -> Result<()> {
         let file_path = self.storage_root.join(relative_path);
 
         match fs::remove_file(&file_path).await {
@@ -114,32 +470,7 @@ impl FileStorage {
         }
     }
 
-    /// Clean up empty parent directories after file deletion
-    fn cleanup_empty_dirs<'a>(
-        &'a self,
-        file_path: &'a Path,
-    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send + 'a>> {
-        Box::pin(async move {
-            if let Some(parent) = file_path.parent() {
-                // Only clean up directories within our storage root
-                if parent.starts_with(&self.storage_root) && parent != self.storage_root {
-                    if let Ok(mut entries) = fs::read_dir(parent).await {
-                        // Check if directory is empty
-                        if entries.next_entry().await.unwrap_or(None).is_none()
-                            && fs::remove_dir(parent).await.is_ok()
-                        {
-                            tracing::debug!("🧹 Cleaned up empty directory: {}", parent.display());
-                            // Recursively clean up parent directories
-                            Box::pin(self.cleanup_empty_dirs(parent)).await;
-                        }
-                    }
-                }
-            }
-        })
-    }
-
-    /// Get storage statistics
-    pub async fn get_stats(&self) -> Result<StorageStats> {
+    async fn get_stats(&self) -> Result<StorageStats> {
         let mut total_files = 0u64;
         let mut total_size = 0u64;
 
@@ -177,6 +508,16 @@ impl FileStorage {
             total_size,
         })
     }
+
+    async fn check_writable(&self) -> Result<()> {
+        // A block of data, as an empty file can still be created on a full disk
+        let probe_path = self.storage_root.join(".write-probe");
+        write_atomic(&probe_path, &[&[0u8; 4096]], self.strict_permissions)
+            .await
+            .map_err(|e| write_error("Failed to write storage probe", e))?;
+        let _ = fs::remove_file(&probe_path).await;
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -188,23 +529,124 @@ pub struct StorageStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::memory_storage::InMemoryStorage;
     use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+
+    // Behaviour every implementation must share, so tests against the
+    // in-memory storage hold for the local one
+    async fn check_storage_contract(storage: &dyn FileStorage) {
+        storage.init().await.unwrap();
+
+        let file_id = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+        let path = storage
+            .store_file(file_id, &ContainerHeader::plain(b"first"), b"first")
+            .await
+            .unwrap();
+        assert_eq!(path, relative_file_path(file_id).to_string_lossy());
+
+        // Storing again replaces the data under the same path
+        let path = storage
+            .store_file(file_id, &ContainerHeader::plain(b"second"), b"second")
+            .await
+            .unwrap();
+        assert!(storage.file_exists(&path).await);
+
+        // Data is stored behind its header
+        let header = ContainerHeader::plain(b"second").encode().unwrap();
+        let stored = storage.retrieve_file(&path).await.unwrap();
+        assert_eq!(stored, [header.as_slice(), b"second"].concat());
+        let (_, size) = storage.open_file(&path).await.unwrap();
+        assert_eq!(size, stored.len() as u64);
+
+        let (read_header, mut reader, size) = storage.read_container(&path).await.unwrap();
+        let mut streamed = Vec::new();
+        reader.read_to_end(&mut streamed).await.unwrap();
+        assert_eq!(read_header, Some(ContainerHeader::plain(b"second")));
+        assert_eq!(size, 6);
+        assert_eq!(streamed, b"second");
+
+        let other_path = storage
+            .store_file(Uuid::new_v4(), &ContainerHeader::plain(b"other"), b"other")
+            .await
+            .unwrap();
+        let other_header = ContainerHeader::plain(b"other").encode().unwrap();
+        let stats = storage.get_stats().await.unwrap();
+        assert_eq!(
+            (stats.total_files, stats.total_size),
+            (2, (header.len() + 6 + other_header.len() + 5) as u64)
+        );
+
+        let listed = storage.list_stored_files().await.unwrap();
+        assert_eq!(listed.len(), 2);
+        assert!(listed
+            .iter()
+            .any(|stored| stored.file_id == file_id && stored.relative_path == path));
+
+        storage.delete_file(&path).await.unwrap();
+        assert!(!storage.file_exists(&path).await);
+        assert!(matches!(
+            storage.retrieve_file(&path).await,
+            Err(AppError::FileNotFound)
+        ));
+        assert!(matches!(
+            storage.open_file(&path).await,
+            Err(AppError::FileNotFound)
+        ));
+
+        // Deleting a missing file is not an error
+        storage.delete_file(&path).await.unwrap();
+
+        storage.delete_file(&other_path).await.unwrap();
+        let stats = storage.get_stats().await.unwrap();
+        assert_eq!((stats.total_files, stats.total_size), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_contract() {
+        let temp_dir = TempDir::new().unwrap();
+        check_storage_contract(&LocalFileStorage::new(temp_dir.path())).await;
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_storage_contract() {
+        check_storage_contract(&InMemoryStorage::new()).await;
+    }
 
     #[tokio::test]
     async fn test_file_storage() {
         let temp_dir = TempDir::new().unwrap();
-        let storage = FileStorage::new(temp_dir.path());
+        let storage = LocalFileStorage::new(temp_dir.path());
         storage.init().await.unwrap();
 
         let file_id = Uuid::new_v4();
         let test_data = b"Hello, World!";
 
         // Test store
-        let path = storage.store_file(file_id, test_data).await.unwrap();
+        let path = storage
+            .store_file(file_id, &ContainerHeader::plain(test_data), test_data)
+            .await
+            .unwrap();
         assert!(!path.is_empty());
 
+        // The temporary file is renamed into place
+        let temp_path = temp_dir.path().join(format!("{}.tmp", path));
+        assert!(!temp_path.exists());
+
         // Test retrieve
-        let retrieved_data = storage.retrieve_file(&path).await.unwrap();
+        let (_, mut reader, _) = storage.read_container(&path).await.unwrap();
+        let mut retrieved_data = Vec::new();
+        reader.read_to_end(&mut retrieved_data).await.unwrap();
+        assert_eq!(test_data, &retrieved_data[..]);
+
+        // Files written before the container format are read whole
+        fs::write(temp_dir.path().join(&path), test_data)
+            .await
+            .unwrap();
+        let (header, mut reader, size) = storage.read_container(&path).await.unwrap();
+        let mut retrieved_data = Vec::new();
+        reader.read_to_end(&mut retrieved_data).await.unwrap();
+        assert_eq!((header, size), (None, test_data.len() as u64));
         assert_eq!(test_data, &retrieved_data[..]);
 
         // Test delete
@@ -217,7 +659,7 @@ mod tests {
     #[test]
     fn test_path_generation() {
         let temp_dir = TempDir::new().unwrap();
-        let storage = FileStorage::new(temp_dir.path());
+        let storage = LocalFileStorage::new(temp_dir.path());
 
         let file_id = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
         let path = storage.generate_file_path(file_id);
@@ -229,4 +671,43 @@ mod tests {
 
         assert!(path.ends_with(&expected_suffix));
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_strict_permissions() {
+        use crate::chunk_storage::{ChunkStorage, LocalChunkStorage};
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("storage");
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let storage = LocalFileStorage::new(&root).with_strict_permissions(true);
+        storage.init().await.unwrap();
+        let file_id = Uuid::new_v4();
+        storage
+            .store_file(file_id, &ContainerHeader::plain(b"secret"), b"secret")
+            .await
+            .unwrap();
+
+        let file_path = storage.generate_file_path(file_id);
+        assert_eq!(mode(&file_path), 0o600);
+        assert_eq!(mode(file_path.parent().unwrap()), 0o700);
+        assert_eq!(mode(&root), 0o700);
+
+        let chunk_storage = LocalChunkStorage::new(&root).with_strict_permissions(true);
+        chunk_storage.init().await.unwrap();
+        let upload_id = Uuid::new_v4();
+        chunk_storage
+            .store_chunk(upload_id, 0, b"chunk")
+            .await
+            .unwrap();
+        let upload_dir = root.join("chunks").join(upload_id.to_string());
+        assert_eq!(mode(&upload_dir.join("chunk_000000")), 0o600);
+        assert_eq!(mode(&upload_dir), 0o700);
+
+        let (temp_file, _) = create_temp_file(&root, true).await.unwrap();
+        assert_eq!(mode(temp_file.path()), 0o600);
+        assert_eq!(mode(temp_file.path().parent().unwrap()), 0o700);
+    }
 }
@@ -0,0 +1,123 @@
+use crate::error::{AppError, Result};
+use crate::storage::FileStorage;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Seconds a client is told to wait when storage is full or unavailable
+pub const RETRY_AFTER_SECS: u64 = 60;
+
+/// How often the background probe checks that storage takes writes
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Classify a failed write to storage
+///
+/// A full disk or quota and a storage directory that can't be written to are
+/// told apart from other failures, so clients get a status they can act on.
+pub fn write_error(action: &str, e: std::io::Error) -> AppError {
+    use std::io::ErrorKind;
+
+    match e.kind() {
+        ErrorKind::StorageFull | ErrorKind::QuotaExceeded => {
+            AppError::StorageFull(RETRY_AFTER_SECS)
+        }
+        ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => {
+            AppError::StorageUnavailable(RETRY_AFTER_SECS)
+        }
+        _ => AppError::ServerError(format!("{}: {}", action, e)),
+    }
+}
+
+/// Whether storage took the latest write, as reported by `/health`
+///
+/// Updated by the background probe and by the writes of uploads, so a disk
+/// that fills up shows as soon as a write fails, and recovery as soon as one
+/// succeeds again.
+#[derive(Clone, Default)]
+pub struct StorageHealth {
+    error_code: Arc<Mutex<Option<&'static str>>>,
+}
+
+impl StorageHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note the outcome of a write; failures other than full or unavailable
+    /// storage say nothing about it and are ignored
+    pub fn record<T>(&self, result: &Result<T>) {
+        let error_code = match result {
+            Ok(_) => None,
+            Err(e @ (AppError::StorageFull(_) | AppError::StorageUnavailable(_))) => Some(e.code()),
+            Err(_) => return,
+        };
+
+        let mut current = self
+            .error_code
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *current != error_code {
+            match error_code {
+                Some(error_code) => tracing::error!("💥 Storage is unhealthy: {}", error_code),
+                None => tracing::info!("✅ Storage accepts writes again"),
+            }
+            *current = error_code;
+        }
+    }
+
+    /// Code of the error storage failed with, while it is unhealthy
+    pub fn error_code(&self) -> Option<&'static str> {
+        *self
+            .error_code
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Probe storage every [`PROBE_INTERVAL`] until the server stops
+pub fn spawn_probe(storage: Arc<dyn FileStorage>, health: StorageHealth) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PROBE_INTERVAL);
+        loop {
+            interval.tick().await;
+            health.record(&storage.check_writable().await);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_code;
+    use std::io::{Error, ErrorKind};
+
+    #[test]
+    fn test_write_errors_are_classified() {
+        assert!(matches!(
+            write_error("write", Error::from(ErrorKind::StorageFull)),
+            AppError::StorageFull(RETRY_AFTER_SECS)
+        ));
+        assert!(matches!(
+            write_error("write", Error::from(ErrorKind::PermissionDenied)),
+            AppError::StorageUnavailable(RETRY_AFTER_SECS)
+        ));
+        assert!(matches!(
+            write_error("write", Error::from(ErrorKind::InvalidData)),
+            AppError::ServerError(_)
+        ));
+    }
+
+    #[test]
+    fn test_health_follows_the_latest_write() {
+        let health = StorageHealth::new();
+
+        health.record(&Err::<(), _>(AppError::StorageFull(RETRY_AFTER_SECS)));
+        assert_eq!(health.error_code(), Some(error_code::STORAGE_FULL));
+
+        // Unrelated failures leave it as it is
+        health.record(&Err::<(), _>(AppError::FileNotFound));
+        assert_eq!(health.error_code(), Some(error_code::STORAGE_FULL));
+
+        health.record(&Ok(()));
+        assert_eq!(health.error_code(), None);
+    }
+}
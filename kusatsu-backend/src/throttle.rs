@@ -0,0 +1,78 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+// Reads are split into slices of a tenth of a second's worth of data, so the
+// pacing stays smooth instead of sending a full second in one burst
+const SLICES_PER_SECOND: u64 = 10;
+
+/// Reader that paces `inner` to at most `bytes_per_sec`
+///
+/// Each read is only allowed once the bytes already sent fit within the rate
+/// since the first read, like a token bucket refilled continuously with a
+/// burst of one slice.
+pub struct ThrottledReader<R> {
+    inner: R,
+    bytes_per_sec: u64,
+    started: Option<Instant>,
+    sent: u64,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<R> ThrottledReader<R> {
+    pub fn new(inner: R, bytes_per_sec: u64) -> Self {
+        Self {
+            inner,
+            bytes_per_sec: bytes_per_sec.max(1),
+            started: None,
+            sent: 0,
+            sleep: None,
+        }
+    }
+
+    // When the bytes sent so far are within the rate
+    fn next_read_at(&self, started: Instant) -> Instant {
+        let elapsed = self.sent as f64 / self.bytes_per_sec as f64;
+        started + std::time::Duration::from_secs_f64(elapsed)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ThrottledReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let started = *this.started.get_or_insert_with(Instant::now);
+
+        // Wait until the previous reads are paid for
+        let next_read_at = this.next_read_at(started);
+        if next_read_at > Instant::now() {
+            let sleep = this
+                .sleep
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(next_read_at)));
+            sleep.as_mut().reset(next_read_at);
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        // Never read more than one slice at a time
+        let slice = (this.bytes_per_sec / SLICES_PER_SECOND).max(1) as usize;
+        let mut chunk = vec![0u8; buf.remaining().min(slice)];
+        let mut limited = ReadBuf::new(&mut chunk);
+
+        match Pin::new(&mut this.inner).poll_read(cx, &mut limited) {
+            Poll::Ready(Ok(())) => {
+                buf.put_slice(limited.filled());
+                this.sent += limited.filled().len() as u64;
+                Poll::Ready(Ok(()))
+            }
+            other => other,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// Reservations are counted in KiB, so budgets of several terabytes still fit
+// the semaphore's u32 permit counts
+const UNIT: u64 = 1024;
+
+/// Seconds a client is told to wait when the upload memory budget is used up
+pub const BUSY_RETRY_AFTER_SECS: u64 = 5;
+
+/// Memory budget shared by uploads that hold a whole file in memory
+///
+/// Each such upload reserves its size before loading the file and releases it
+/// when the reservation is dropped. An upload larger than the whole budget
+/// reserves all of it, so it still goes through, on its own.
+#[derive(Clone)]
+pub struct UploadMemory {
+    semaphore: Arc<Semaphore>,
+    units: u32,
+}
+
+impl UploadMemory {
+    pub fn new(budget_bytes: u64) -> Self {
+        let units = budget_bytes.div_ceil(UNIT).clamp(1, u32::MAX as u64) as u32;
+        Self {
+            semaphore: Arc::new(Semaphore::new(units as usize)),
+            units,
+        }
+    }
+
+    /// Reserve memory for `bytes`, or None when the budget is in use by other uploads
+    pub fn try_reserve(&self, bytes: u64) -> Option<OwnedSemaphorePermit> {
+        let units = bytes.div_ceil(UNIT).clamp(1, self.units as u64) as u32;
+        self.semaphore.clone().try_acquire_many_owned(units).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservations_share_the_budget() {
+        let memory = UploadMemory::new(10 * UNIT);
+
+        let first = memory.try_reserve(6 * UNIT).unwrap();
+        assert!(memory.try_reserve(5 * UNIT).is_none());
+        let second = memory.try_reserve(4 * UNIT).unwrap();
+
+        // Released when dropped
+        drop(first);
+        drop(second);
+
+        // Larger than the budget: takes all of it
+        let whole = memory.try_reserve(100 * UNIT).unwrap();
+        assert!(memory.try_reserve(1).is_none());
+        drop(whole);
+        assert!(memory.try_reserve(1).is_some());
+    }
+}
@@ -0,0 +1,171 @@
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use kusatsu_encrypt::pow;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long a challenge can be solved and redeemed once issued
+pub const CHALLENGE_TTL: Duration = Duration::from_secs(10 * 60);
+
+// Past this many redeemed challenges, expired ones are swept before recording another
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// Proof-of-work challenges handed out to uploaders
+///
+/// A challenge is `{random}.{issued_at}.{mac}`, signed with a key generated at
+/// startup, so issuing one keeps no state and can't be refused by a flood of
+/// requests. Only redeemed challenges are remembered, until they expire after
+/// [`CHALLENGE_TTL`], so a solution can't be replayed; each of them cost its
+/// uploader a solved proof of work. A restart invalidates outstanding
+/// challenges, and replicas only accept their own.
+#[derive(Clone)]
+pub struct UploadChallenges {
+    key: Arc<[u8; 32]>,
+    // Redeemed challenges, with the unix timestamp they expire at
+    redeemed: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl Default for UploadChallenges {
+    fn default() -> Self {
+        let mut key = [0; 32];
+        key[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+        key[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+        Self {
+            key: Arc::new(key),
+            redeemed: Arc::default(),
+        }
+    }
+}
+
+impl UploadChallenges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hand out a new challenge
+    pub fn issue(&self) -> String {
+        self.issue_at(chrono::Utc::now().timestamp())
+    }
+
+    /// Take back `challenge` if `nonce` solves it at `difficulty` before it expired
+    ///
+    /// A wrong solution leaves the challenge to be solved again.
+    pub fn redeem(&self, challenge: &str, nonce: u64, difficulty: u8) -> bool {
+        self.redeem_at(challenge, nonce, difficulty, chrono::Utc::now().timestamp())
+    }
+
+    fn mac(&self, random: &str, issued_at: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.key.as_slice())
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{}.{}", random, issued_at).as_bytes());
+        mac
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, i64>> {
+        self.redeemed.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn issue_at(&self, now: i64) -> String {
+        let random = Uuid::new_v4().simple().to_string();
+        let issued_at = now.to_string();
+        let mac = self.mac(&random, &issued_at).finalize().into_bytes();
+        format!(
+            "{}.{}.{}",
+            random,
+            issued_at,
+            general_purpose::URL_SAFE_NO_PAD.encode(mac)
+        )
+    }
+
+    // The time a challenge expires at, if this server issued it
+    fn expires_at(&self, challenge: &str) -> Option<i64> {
+        let mut parts = challenge.split('.');
+        let (Some(random), Some(issued_at), Some(mac), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+        let mac = general_purpose::URL_SAFE_NO_PAD.decode(mac).ok()?;
+        // Constant-time comparison
+        self.mac(random, issued_at).verify_slice(&mac).ok()?;
+
+        Some(issued_at.parse::<i64>().ok()? + CHALLENGE_TTL.as_secs() as i64)
+    }
+
+    fn redeem_at(&self, challenge: &str, nonce: u64, difficulty: u8, now: i64) -> bool {
+        let Some(expires_at) = self.expires_at(challenge) else {
+            return false;
+        };
+        if now >= expires_at || !pow::verify(challenge, nonce, difficulty) {
+            return false;
+        }
+
+        let mut redeemed = self.lock();
+        if redeemed.len() >= SWEEP_THRESHOLD {
+            redeemed.retain(|_, expires_at| now < *expires_at);
+        }
+        redeemed.insert(challenge.to_string(), expires_at).is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_challenges_are_redeemed_once() {
+        let challenges = UploadChallenges::new();
+        let challenge = challenges.issue();
+        let nonce = pow::solve(&challenge, 8);
+        let wrong_nonce = (0..).find(|&n| !pow::verify(&challenge, n, 8)).unwrap();
+
+        // A wrong solution can be corrected
+        assert!(!challenges.redeem(&challenge, wrong_nonce, 8));
+        assert!(challenges.redeem(&challenge, nonce, 8));
+        assert!(!challenges.redeem(&challenge, nonce, 8));
+        assert!(!challenges.redeem("unknown", nonce, 0));
+    }
+
+    #[test]
+    fn test_challenges_must_be_issued_here() {
+        let challenges = UploadChallenges::new();
+        let challenge = challenges.issue();
+
+        // Another key, or any tampering, invalidates the challenge
+        assert!(!UploadChallenges::new().redeem(&challenge, 0, 0));
+        let (_, signed) = challenge.split_once('.').unwrap();
+        let forged = format!("{}.{}", Uuid::new_v4().simple(), signed);
+        assert!(!challenges.redeem(&forged, 0, 0));
+        assert!(!challenges.redeem(&format!("{}.", challenge), 0, 0));
+        assert!(challenges.redeem(&challenge, 0, 0));
+    }
+
+    #[test]
+    fn test_challenges_expire() {
+        let challenges = UploadChallenges::new();
+        let now = chrono::Utc::now().timestamp();
+        let challenge = challenges.issue_at(now);
+        let ttl = CHALLENGE_TTL.as_secs() as i64;
+
+        assert!(!challenges.redeem_at(&challenge, 0, 0, now + ttl));
+        assert!(challenges.redeem_at(&challenge, 0, 0, now + ttl - 1));
+    }
+
+    #[test]
+    fn test_expired_redemptions_are_swept() {
+        let challenges = UploadChallenges::new();
+        let now = chrono::Utc::now().timestamp();
+        for _ in 0..SWEEP_THRESHOLD {
+            assert!(challenges.redeem_at(&challenges.issue_at(now), 0, 0, now));
+        }
+
+        let later = now + CHALLENGE_TTL.as_secs() as i64;
+        assert!(challenges.redeem_at(&challenges.issue_at(later), 0, 0, later));
+        assert_eq!(challenges.lock().len(), 1);
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// Only the most recent chunks count towards the rate, so it follows changes in
+// the connection speed
+const WINDOW_CHUNKS: usize = 8;
+
+// A chunk arriving longer than this after the previous one starts a new window:
+// the upload was paused or the client reconnected, and the gap says nothing
+// about its speed
+const PAUSE_THRESHOLD: Duration = Duration::from_secs(30);
+
+// Arrival time and size of each chunk in the window, oldest first
+type Samples = VecDeque<(Instant, u64)>;
+
+/// Upload throughput and time left, estimated from recent chunks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UploadEstimate {
+    pub bytes_per_second: f64,
+    pub eta_seconds: u64,
+}
+
+/// Arrival times and sizes of the latest chunks of each upload session
+///
+/// Kept in memory only: after a restart, estimates come back once a couple of
+/// chunks have arrived again.
+#[derive(Clone, Default)]
+pub struct UploadRates {
+    sessions: Arc<Mutex<HashMap<Uuid, Samples>>>,
+}
+
+impl UploadRates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note that a chunk of `bytes` was received for `upload_id`
+    pub fn record(&self, upload_id: Uuid, bytes: u64) {
+        self.record_at(upload_id, bytes, Instant::now());
+    }
+
+    /// Estimate the throughput of `upload_id` and the time to send `remaining_bytes`
+    ///
+    /// None until two chunks have arrived since the last pause, and while the
+    /// upload is paused.
+    pub fn estimate(&self, upload_id: Uuid, remaining_bytes: u64) -> Option<UploadEstimate> {
+        self.estimate_at(upload_id, remaining_bytes, Instant::now())
+    }
+
+    /// Forget an upload session once it is completed or discarded
+    pub fn remove(&self, upload_id: Uuid) {
+        self.lock().remove(&upload_id);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Uuid, Samples>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn record_at(&self, upload_id: Uuid, bytes: u64, now: Instant) {
+        let mut sessions = self.lock();
+
+        // Sessions that went quiet have nothing left to estimate from, which also
+        // drops the ones abandoned without being completed
+        sessions.retain(|_, samples| {
+            samples
+                .back()
+                .is_some_and(|(at, _)| now.duration_since(*at) <= PAUSE_THRESHOLD)
+        });
+
+        let samples = sessions.entry(upload_id).or_default();
+        samples.push_back((now, bytes));
+        // One more sample than chunks, as the first only marks the window's start
+        while samples.len() > WINDOW_CHUNKS + 1 {
+            samples.pop_front();
+        }
+    }
+
+    fn estimate_at(
+        &self,
+        upload_id: Uuid,
+        remaining_bytes: u64,
+        now: Instant,
+    ) -> Option<UploadEstimate> {
+        let sessions = self.lock();
+        let samples = sessions.get(&upload_id)?;
+        let (first, _) = samples.front()?;
+        let (last, _) = samples.back()?;
+
+        if now.duration_since(*last) > PAUSE_THRESHOLD {
+            return None;
+        }
+
+        // The bytes of the first chunk arrived before the window started
+        let elapsed = last.duration_since(*first).as_secs_f64();
+        let bytes: u64 = samples.iter().skip(1).map(|(_, bytes)| bytes).sum();
+        if elapsed <= 0.0 || bytes == 0 {
+            return None;
+        }
+
+        let bytes_per_second = bytes as f64 / elapsed;
+        Some(UploadEstimate {
+            bytes_per_second,
+            eta_seconds: (remaining_bytes as f64 / bytes_per_second).ceil() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_from_recent_chunks() {
+        let rates = UploadRates::new();
+        let upload_id = Uuid::new_v4();
+        let start = Instant::now();
+
+        // A single chunk gives no rate yet
+        rates.record_at(upload_id, 1000, start);
+        assert_eq!(rates.estimate_at(upload_id, 5000, start), None);
+
+        // 1000 bytes a second
+        for i in 1..=3 {
+            rates.record_at(upload_id, 1000, start + Duration::from_secs(i));
+        }
+        let estimate = rates
+            .estimate_at(upload_id, 5000, start + Duration::from_secs(3))
+            .unwrap();
+        assert_eq!(estimate.bytes_per_second, 1000.0);
+        assert_eq!(estimate.eta_seconds, 5);
+
+        // No estimate while paused
+        let resumed = start + Duration::from_secs(3) + PAUSE_THRESHOLD * 4;
+        assert_eq!(rates.estimate_at(upload_id, 5000, resumed), None);
+
+        // After the pause, only chunks since the resume count: 500 bytes a second
+        rates.record_at(upload_id, 1000, resumed);
+        rates.record_at(upload_id, 1000, resumed + Duration::from_secs(2));
+        let estimate = rates
+            .estimate_at(upload_id, 5000, resumed + Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(estimate.bytes_per_second, 500.0);
+        assert_eq!(estimate.eta_seconds, 10);
+
+        rates.remove(upload_id);
+        assert_eq!(
+            rates.estimate_at(upload_id, 5000, resumed + Duration::from_secs(2)),
+            None
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::OutputFormat;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_SERVER: &str = "http://localhost:3000";
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Defaults read from the config file, all optional
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub server: Option<String>,
+    pub timeout: Option<u64>,
+    pub expires_in_hours: Option<i32>,
+    pub max_downloads: Option<i32>,
+    pub output: Option<OutputFormat>,
+}
+
+/// The config file that was looked for, and its contents if it exists
+pub struct LoadedConfig {
+    pub path: Option<PathBuf>,
+    pub file: Option<FileConfig>,
+}
+
+/// Values given on the command line, which take precedence over the file
+#[derive(Default)]
+pub struct Overrides {
+    pub server: Option<String>,
+    pub timeout: Option<u64>,
+    pub expires_in_hours: Option<i32>,
+    pub max_downloads: Option<i32>,
+    pub output: Option<OutputFormat>,
+}
+
+/// Where a setting's value came from
+#[derive(Clone, Copy)]
+pub enum Source {
+    CommandLine,
+    ConfigFile,
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::CommandLine => write!(f, "command line"),
+            Source::ConfigFile => write!(f, "config file"),
+            Source::Default => write!(f, "default"),
+        }
+    }
+}
+
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+/// Settings after applying the command line over the file over the built-in defaults
+pub struct Settings {
+    pub server: Setting<String>,
+    pub timeout: Setting<u64>,
+    pub expires_in_hours: Setting<Option<i32>>,
+    pub max_downloads: Setting<Option<i32>>,
+    pub output: Setting<OutputFormat>,
+}
+
+// `$XDG_CONFIG_HOME/kusatsu/config.toml`, falling back to `~/.config/kusatsu/config.toml`
+fn default_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_dir.join("kusatsu").join("config.toml"))
+}
+
+/// Read the config file at `path`, or at the default location
+///
+/// A missing file at the default location is not an error; an explicitly given
+/// one must exist.
+pub fn load(path: Option<&Path>) -> Result<LoadedConfig> {
+    let explicit = path.is_some();
+    let Some(path) = path.map(Path::to_path_buf).or_else(default_config_path) else {
+        return Ok(LoadedConfig {
+            path: None,
+            file: None,
+        });
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => {
+            return Ok(LoadedConfig {
+                path: Some(path),
+                file: None,
+            });
+        }
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read config file: {}", path.display()))
+        }
+    };
+
+    let file = toml::from_str(&contents)
+        .with_context(|| format!("Invalid config file: {}", path.display()))?;
+
+    Ok(LoadedConfig {
+        path: Some(path),
+        file: Some(file),
+    })
+}
+
+fn resolve<T>(flag: Option<T>, file: Option<T>, default: T) -> Setting<T> {
+    match (flag, file) {
+        (Some(value), _) => Setting {
+            value,
+            source: Source::CommandLine,
+        },
+        (None, Some(value)) => Setting {
+            value,
+            source: Source::ConfigFile,
+        },
+        (None, None) => Setting {
+            value: default,
+            source: Source::Default,
+        },
+    }
+}
+
+// Like `resolve` for settings that may stay unset
+fn resolve_optional<T>(flag: Option<T>, file: Option<T>) -> Setting<Option<T>> {
+    resolve(flag.map(Some), file.map(Some), None)
+}
+
+impl Settings {
+    pub fn resolve(overrides: Overrides, loaded: &LoadedConfig) -> Self {
+        let file = loaded.file.as_ref();
+
+        Settings {
+            server: resolve(
+                overrides.server,
+                file.and_then(|file| file.server.clone()),
+                DEFAULT_SERVER.to_string(),
+            ),
+            timeout: resolve(
+                overrides.timeout,
+                file.and_then(|file| file.timeout),
+                DEFAULT_TIMEOUT_SECS,
+            ),
+            expires_in_hours: resolve_optional(
+                overrides.expires_in_hours,
+                file.and_then(|file| file.expires_in_hours),
+            ),
+            max_downloads: resolve_optional(
+                overrides.max_downloads,
+                file.and_then(|file| file.max_downloads),
+            ),
+            output: resolve(
+                overrides.output,
+                file.and_then(|file| file.output.clone()),
+                OutputFormat::Url,
+            ),
+        }
+    }
+}
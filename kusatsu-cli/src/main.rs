@@ -1,18 +1,26 @@
 use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
 use clap::{Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
+use kusatsu_encrypt::{key_wrap, pow, signing, Encryption, EncryptionKey, WrappedKey};
 use reqwest::multipart;
-use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write as _;
+use std::path::{Component, Path, PathBuf};
 use std::time::Duration;
 use tokio::fs as async_fs;
-use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::task::JoinSet;
 
 // Import shared types
 use kusatsu_types::*;
 
-// Constants for chunked uploads
-const MAX_SINGLE_UPLOAD_SIZE: usize = 5 * 1024 * 1024; // 5MB
-const CHUNK_SIZE: usize = 5 * 1024 * 1024; // 5MB chunks
+mod config;
+
+// How often a chunked upload pushes back its session's expiry, well within the
+// server's shortest session TTL (1 hour by default)
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
 #[derive(Parser)]
 #[command(name = "kusatsu")]
@@ -22,13 +30,42 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Backend server URL
-    #[arg(long, default_value = "http://localhost:3000")]
-    server: String,
+    /// Backend server URL [default: http://localhost:3000]
+    #[arg(long)]
+    server: Option<String>,
 
-    /// Timeout for requests in seconds
-    #[arg(long, default_value_t = 30)]
-    timeout: u64,
+    /// Timeout for requests in seconds [default: 30]
+    #[arg(long)]
+    timeout: Option<u64>,
+
+    /// Config file with defaults for the options above and upload options
+    /// [default: ~/.config/kusatsu/config.toml]
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Additional PEM root certificate to trust (e.g. an internal CA)
+    #[arg(long)]
+    ca_cert: Option<PathBuf>,
+
+    /// Accept invalid TLS certificates (DANGEROUS, for testing only)
+    #[arg(long)]
+    insecure: bool,
+
+    /// PEM client certificate for mutual TLS
+    #[arg(long, requires = "client_key")]
+    client_cert: Option<PathBuf>,
+
+    /// PEM PKCS#8 private key for mutual TLS
+    #[arg(long, requires = "client_cert")]
+    client_key: Option<PathBuf>,
+
+    /// Use HTTP/2: negotiated over HTTPS, assumed without negotiation (h2c) over plain HTTP
+    #[arg(long)]
+    http2: bool,
+
+    /// Connections to keep open to the server; chunked uploads send this many chunks at once
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=64))]
+    pool_size: u16,
 }
 
 #[derive(Subcommand)]
@@ -46,18 +83,164 @@ enum Commands {
         #[arg(long)]
         max_downloads: Option<i32>,
 
-        /// Output format (json or url)
-        #[arg(long, default_value = "url")]
-        output: OutputFormat,
+        /// Output format (json or url) [default: url]
+        #[arg(long)]
+        output: Option<OutputFormat>,
+
+        /// Print the download URL and encryption key separately instead of combined
+        #[arg(long)]
+        split_key: bool,
+
+        /// Ask the server how the upload would be handled, without uploading
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Stable name to download the file by (lowercase letters, digits and hyphens)
+        #[arg(long)]
+        slug: Option<String>,
+
+        /// Make the file unavailable this many minutes after its first download
+        #[arg(long, value_name = "MINUTES")]
+        burn_after_minutes: Option<i32>,
+
+        /// Expire the file this many hours after its last download (or the upload)
+        #[arg(long, value_name = "HOURS", conflicts_with = "expires_in_hours")]
+        expire_after_inactivity: Option<i32>,
+
+        /// Sign the file with the key in this file (see `keygen`)
+        #[arg(long)]
+        sign_key: Option<PathBuf>,
+
+        /// Leave the key out of the link and protect it with a passphrase instead
+        /// (read from KUSATSU_PASSPHRASE, or prompted)
+        #[arg(long, conflicts_with = "split_key")]
+        wrap_key_with_password: bool,
+
+        /// Continue an interrupted chunked upload, sending only the missing or damaged chunks
+        #[arg(long, value_name = "UPLOAD_ID", conflicts_with = "dry_run")]
+        resume: Option<uuid::Uuid>,
+
+        /// Also write a manifest of the upload to this file (see `manifest`)
+        #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+        manifest: Option<PathBuf>,
+
+        /// Attach an encrypted listing of the files in this tar archive, shown to
+        /// recipients before they download it (see `download --list`)
+        #[arg(long, conflicts_with = "dry_run")]
+        listing: bool,
+    },
+    /// Extend the expiry of an uploaded file
+    Extend {
+        /// ID of the file to extend
+        file_id: String,
+
+        /// Owner token returned when the file was uploaded
+        #[arg(long)]
+        token: String,
+
+        /// Hours from now until the file expires
+        #[arg(long)]
+        expires_in_hours: i32,
+
+        /// Encryption key (required for encrypted files)
+        #[arg(long)]
+        key: Option<String>,
+    },
+    /// Download a file, resuming an interrupted download when possible
+    Download {
+        /// Download URL, including the key anchor for encrypted files (links protected
+        /// with a passphrase prompt for it, or read KUSATSU_PASSPHRASE)
+        url: String,
+
+        /// Where to save the file (defaults to the original filename)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Uploader's public key; the download fails unless the file is signed by it
+        #[arg(long)]
+        verify_key: Option<String>,
+
+        /// Skip checking the download against the content hash recorded by the server
+        #[arg(long)]
+        no_verify: bool,
+
+        /// Show the files in a bundle from its listing, without downloading it
+        #[arg(long, conflicts_with_all = ["output", "verify_key", "no_verify"])]
+        list: bool,
+
+        /// Extract a tar or tar.gz download into this directory as it arrives,
+        /// instead of saving the archive
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["output", "verify_key", "list"])]
+        extract: Option<PathBuf>,
+    },
+    /// Check that a download link is still valid, without consuming a download
+    Verify {
+        /// Download URL, including the key anchor for encrypted files
+        url: String,
+
+        /// Print the link state as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Only check that the file is available, without fetching its details
+        #[arg(long)]
+        quick: bool,
+    },
+    /// Write a JSON record of an uploaded file (link, key fingerprint, size, hashes,
+    /// expiry) for archiving, without downloading it
+    Manifest {
+        /// Download URL, including the key anchor for encrypted files
+        url: String,
+
+        /// Where to write the manifest (defaults to standard output)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Measure upload and download throughput against the server with throwaway files
+    Bench {
+        /// Size of each test file in MB; sizes above the single upload limit use chunked uploads
+        #[arg(long, default_value_t = 10)]
+        size_mb: usize,
+
+        /// Number of upload and download rounds
+        #[arg(long, default_value_t = 5)]
+        iterations: usize,
+    },
+    /// Print the settings in effect and where each one comes from
+    Config,
+    /// Generate a key for signing uploads
+    Keygen {
+        /// Where to write the signing key
+        output: PathBuf,
+    },
+    /// Print freshly generated encryption keys, one per line, e.g. to share out of band
+    EncryptionKey {
+        /// Number of keys to print
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        count: u32,
+
+        /// Print the keys as hex instead of URL-safe base64
+        #[arg(long)]
+        raw: bool,
     },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum OutputFormat {
     Json,
     Url,
 }
 
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Url => write!(f, "url"),
+        }
+    }
+}
+
 impl std::str::FromStr for OutputFormat {
     type Err = String;
 
@@ -75,48 +258,365 @@ struct UploadConfig {
     expires_in_hours: Option<i32>,
     max_downloads: Option<i32>,
     output_format: OutputFormat,
+    split_key: bool,
+    slug: Option<String>,
+    burn_after_minutes: Option<i32>,
+    expire_after_inactivity_hours: Option<i32>,
+    signing_key: Option<signing::SigningKey>,
+    wrap_key_passphrase: Option<String>,
+    resume_upload_id: Option<uuid::Uuid>,
+    manifest_path: Option<PathBuf>,
+    listing: bool,
+    // Sent with every request of the upload, see build_client
+    request_id: String,
+    // Chunks of a chunked upload in flight at once
+    parallel_chunks: usize,
 }
 
 // All API types are now defined in kusatsu-types and imported above
 
+// Every request of one command carries the same request id, so the server's logs
+// for an upload session (start, chunks, complete) can be matched with ours
+fn build_client(
+    cli: &Cli,
+    server: &str,
+    timeout: u64,
+    request_id: &str,
+) -> Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        REQUEST_ID_HEADER,
+        reqwest::header::HeaderValue::from_str(request_id).context("Invalid request ID")?,
+    );
+
+    // Idle connections are kept for the next chunk instead of reconnecting each time
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(timeout))
+        .default_headers(headers)
+        .pool_max_idle_per_host(cli.pool_size as usize)
+        .tcp_keepalive(Duration::from_secs(60));
+
+    // One HTTP/2 connection multiplexes every chunk in flight. HTTPS offers it
+    // through ALPN; plain HTTP has no negotiation, so the server must accept it outright
+    if cli.http2 {
+        builder = builder
+            .http2_adaptive_window(true)
+            .http2_keep_alive_interval(Duration::from_secs(30))
+            .http2_keep_alive_while_idle(true);
+        if server.starts_with("http://") {
+            builder = builder.http2_prior_knowledge();
+        }
+    } else {
+        builder = builder.http1_only();
+    }
+
+    if let Some(ca_cert) = &cli.ca_cert {
+        let pem = read_pem_file(ca_cert, "CA certificate")?;
+        let certificate = reqwest::Certificate::from_pem(&pem)
+            .with_context(|| format!("Invalid CA certificate: {}", ca_cert.display()))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    if let (Some(client_cert), Some(client_key)) = (&cli.client_cert, &cli.client_key) {
+        let cert = read_pem_file(client_cert, "client certificate")?;
+        let key = read_pem_file(client_key, "client key")?;
+        let identity = reqwest::Identity::from_pkcs8_pem(&cert, &key).with_context(|| {
+            format!(
+                "Invalid client certificate or key: {} / {}",
+                client_cert.display(),
+                client_key.display()
+            )
+        })?;
+        builder = builder.identity(identity);
+    }
+
+    if cli.insecure {
+        eprintln!("⚠️  WARNING: TLS certificate verification is disabled (--insecure).");
+        eprintln!("⚠️  Connections can be intercepted. Do not use this in production.");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    builder.build().context("Failed to create HTTP client")
+}
+
+fn read_pem_file(path: &Path, description: &str) -> Result<Vec<u8>> {
+    if !path.exists() {
+        return Err(anyhow::anyhow!(
+            "{} file does not exist: {}",
+            description,
+            path.display()
+        ));
+    }
+
+    std::fs::read(path)
+        .with_context(|| format!("Failed to read {}: {}", description, path.display()))
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(cli.timeout))
-        .build()
-        .context("Failed to create HTTP client")?;
+    // Flags win over the config file, which wins over the built-in defaults
+    let loaded_config = config::load(cli.config.as_deref())?;
+    let mut overrides = config::Overrides {
+        server: cli.server.clone(),
+        timeout: cli.timeout,
+        ..Default::default()
+    };
+    if let Commands::Upload {
+        expires_in_hours,
+        max_downloads,
+        output,
+        ..
+    } = &cli.command
+    {
+        overrides.expires_in_hours = *expires_in_hours;
+        overrides.max_downloads = *max_downloads;
+        overrides.output = output.clone();
+    }
+    let settings = config::Settings::resolve(overrides, &loaded_config);
+    let server = settings.server.value.as_str();
+
+    match &cli.command {
+        Commands::Config => {
+            print_settings(&loaded_config, &settings);
+            return Ok(());
+        }
+        Commands::Keygen { output } => return generate_key(output),
+        Commands::EncryptionKey { count, raw } => {
+            print_encryption_keys(*count, *raw);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let client = build_client(&cli, server, settings.timeout.value, &request_id)?;
 
     match cli.command {
         Commands::Upload {
             file,
+            split_key,
+            dry_run,
+            slug,
+            burn_after_minutes,
+            expire_after_inactivity,
+            sign_key,
+            wrap_key_with_password,
+            resume,
+            manifest,
+            listing,
+            ..
+        } => {
+            let signing_key = sign_key.as_deref().map(read_signing_key).transpose()?;
+            let wrap_key_passphrase = if wrap_key_with_password && !dry_run {
+                Some(read_passphrase(true)?)
+            } else {
+                None
+            };
+            let config = UploadConfig {
+                // An expiry from the config file gives way to the inactivity one
+                expires_in_hours: settings
+                    .expires_in_hours
+                    .value
+                    .filter(|_| expire_after_inactivity.is_none()),
+                max_downloads: settings.max_downloads.value,
+                output_format: settings.output.value.clone(),
+                split_key,
+                slug,
+                burn_after_minutes,
+                expire_after_inactivity_hours: expire_after_inactivity,
+                signing_key,
+                wrap_key_passphrase,
+                resume_upload_id: resume,
+                manifest_path: manifest,
+                listing,
+                request_id: request_id.clone(),
+                parallel_chunks: cli.pool_size as usize,
+            };
+            if dry_run {
+                plan_upload(&client, server, &file, &config).await?;
+            } else {
+                upload_file(&client, server, &file, &config).await?;
+            }
+        }
+        Commands::Extend {
+            file_id,
+            token,
             expires_in_hours,
-            max_downloads,
+            key,
+        } => {
+            extend_file(&client, server, &file_id, token, expires_in_hours, key).await?;
+        }
+        Commands::Download {
+            url,
             output,
+            verify_key,
+            no_verify,
+            list,
+            extract,
         } => {
-            upload_file(
-                &client,
-                &cli.server,
-                &file,
-                expires_in_hours,
-                max_downloads,
-                output,
-            )
-            .await?;
+            if list {
+                show_listing(&client, server, &url).await?;
+                return Ok(());
+            }
+            if let Some(dir) = extract {
+                extract_download(&client, server, &url, &dir, !no_verify).await?;
+                return Ok(());
+            }
+            let verifying_key = verify_key
+                .as_deref()
+                .map(signing::verifying_key_from_base64)
+                .transpose()
+                .context("Invalid --verify-key")?;
+            download_file(&client, server, &url, output, verifying_key, !no_verify).await?;
         }
+        Commands::Verify { url, json, quick } => {
+            if quick {
+                check_link_exists(&client, server, &url, json).await?;
+            } else {
+                verify_link(&client, server, &url, json).await?;
+            }
+        }
+        Commands::Manifest { url, output } => {
+            write_link_manifest(&client, server, &url, output.as_deref()).await?;
+        }
+        Commands::Bench {
+            size_mb,
+            iterations,
+        } => {
+            let config = UploadConfig {
+                // Files left behind by a failed cleanup don't linger
+                expires_in_hours: Some(1),
+                max_downloads: None,
+                output_format: OutputFormat::Url,
+                split_key: false,
+                slug: None,
+                burn_after_minutes: None,
+                expire_after_inactivity_hours: None,
+                signing_key: None,
+                wrap_key_passphrase: None,
+                resume_upload_id: None,
+                manifest_path: None,
+                listing: false,
+                request_id: request_id.clone(),
+                parallel_chunks: cli.pool_size as usize,
+            };
+            run_bench(&client, server, size_mb, iterations, &config).await?;
+        }
+        Commands::Config | Commands::Keygen { .. } | Commands::EncryptionKey { .. } => {
+            unreachable!("handled before building the client")
+        }
+    }
+
+    Ok(())
+}
+
+fn print_settings(loaded_config: &config::LoadedConfig, settings: &config::Settings) {
+    match (&loaded_config.path, &loaded_config.file) {
+        (Some(path), Some(_)) => println!("📄 Config file: {}", path.display()),
+        (Some(path), None) => println!("📄 Config file: {} (not found)", path.display()),
+        (None, _) => println!("📄 Config file: none (HOME is not set)"),
+    }
+
+    let unset = || "(unset)".to_string();
+    println!(
+        "server = {} ({})",
+        settings.server.value, settings.server.source
+    );
+    println!(
+        "timeout = {} ({})",
+        settings.timeout.value, settings.timeout.source
+    );
+    println!(
+        "expires_in_hours = {} ({})",
+        settings
+            .expires_in_hours
+            .value
+            .map_or_else(unset, |hours| hours.to_string()),
+        settings.expires_in_hours.source
+    );
+    println!(
+        "max_downloads = {} ({})",
+        settings
+            .max_downloads
+            .value
+            .map_or_else(unset, |max_downloads| max_downloads.to_string()),
+        settings.max_downloads.source
+    );
+    println!(
+        "output = {} ({})",
+        settings.output.value, settings.output.source
+    );
+}
+
+fn generate_key(output: &Path) -> Result<()> {
+    let signing_key = signing::generate_signing_key();
+
+    // Only the owner may read the key, and an existing key is never overwritten
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
     }
+    let mut file = options
+        .open(output)
+        .with_context(|| format!("Failed to create {}", output.display()))?;
+    writeln!(file, "{}", signing::signing_key_to_base64(&signing_key))
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    println!("🔏 Signing key written to {}", output.display());
+    println!(
+        "🔑 Public key: {}",
+        signing::verifying_key_to_base64(&signing_key.verifying_key())
+    );
+    println!("ℹ️  Share the public key with recipients so they can pass it to --verify-key");
 
     Ok(())
 }
 
+// Keys only, without decoration, so the output can be piped
+fn print_encryption_keys(count: u32, raw: bool) {
+    for _ in 0..count {
+        if raw {
+            let key = EncryptionKey::generate();
+            let hex: String = key
+                .as_bytes()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            println!("{}", hex);
+        } else {
+            println!("{}", Encryption::generate_key_string());
+        }
+    }
+}
+
+fn read_signing_key(path: &Path) -> Result<signing::SigningKey> {
+    let encoded = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read signing key: {}", path.display()))?;
+
+    signing::signing_key_from_base64(&encoded)
+        .with_context(|| format!("Invalid signing key: {}", path.display()))
+}
+
+// Detached signature of the whole file, as sent to the server
+async fn sign_file(file_path: &Path, signing_key: &signing::SigningKey) -> Result<String> {
+    let data = async_fs::read(file_path)
+        .await
+        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+
+    let signature = signing::sign(&data, signing_key);
+    Ok(signing::signature_to_base64(&signature))
+}
+
 async fn upload_file(
     client: &reqwest::Client,
     server: &str,
     file_path: &Path,
-    expires_in_hours: Option<i32>,
-    max_downloads: Option<i32>,
-    output_format: OutputFormat,
+    config: &UploadConfig,
 ) -> Result<()> {
     // Get file metadata
     let metadata = async_fs::metadata(file_path)
@@ -131,143 +631,478 @@ async fn upload_file(
         .to_string();
 
     println!("📁 Uploading file: {} ({} bytes)", filename, file_size);
+    println!("🔖 Request ID: {}", config.request_id);
+
+    // Read before uploading, so a file that isn't a tar archive fails early
+    let listing = if config.listing {
+        Some(read_bundle_listing(file_path).await?)
+    } else {
+        None
+    };
 
     // Detect MIME type
     let mime_type = mime_guess::from_path(file_path)
         .first()
         .map(|mime| mime.to_string());
 
-    let config = UploadConfig {
-        expires_in_hours,
-        max_downloads,
-        output_format,
+    // Servers that don't advertise their limits get the defaults
+    let server_config = fetch_server_config(client, server).await;
+    if let Some(server_config) = &server_config {
+        if file_size as i64 > server_config.max_file_size {
+            return Err(anyhow::anyhow!(
+                "File is too large: the server accepts files up to {} bytes",
+                server_config.max_file_size
+            ));
+        }
+        if config.wrap_key_passphrase.is_some() && !server_config.password_protection_supported {
+            return Err(anyhow::anyhow!(
+                "The server doesn't support passphrase-protected links"
+            ));
+        }
+    }
+    let single_upload_max = server_config
+        .as_ref()
+        .map_or(MAX_SINGLE_UPLOAD_SIZE, |server_config| {
+            server_config.single_upload_max
+        });
+
+    // Decide between single and chunked upload (files above the threshold use
+    // chunked uploads, with a chunk size scaling with the file size)
+    let upload_response =
+        if file_size as i64 <= single_upload_max && config.resume_upload_id.is_none() {
+            println!("📦 Using single upload (file size: {} bytes)", file_size);
+            perform_single_upload(client, server, file_path, &filename, mime_type, config).await?
+        } else {
+            println!("🧩 Using chunked upload (file size: {} bytes)", file_size);
+            perform_chunked_upload(
+                client, server, file_path, &filename, file_size, mime_type, config,
+            )
+            .await?
+        };
+
+    // Encrypted with the key, which wrapping drops from the response
+    if let Some(listing) = &listing {
+        attach_listing(client, server, &upload_response, listing).await?;
+    }
+
+    // Taken before wrapping drops the key from the response
+    let manifest = match &config.manifest_path {
+        Some(_) => Some(
+            upload_manifest(
+                client,
+                server,
+                file_path,
+                &upload_response,
+                signing_public_key(config),
+                config.wrap_key_passphrase.is_some(),
+            )
+            .await?,
+        ),
+        None => None,
     };
 
-    // Decide between single and chunked upload
-    if file_size <= MAX_SINGLE_UPLOAD_SIZE {
-        println!("📦 Using single upload (file size: {} bytes)", file_size);
-        perform_single_upload(client, server, file_path, &filename, mime_type, &config).await
-    } else {
-        println!("🧩 Using chunked upload (file size: {} bytes)", file_size);
-        perform_chunked_upload(
-            client, server, file_path, &filename, file_size, mime_type, &config,
-        )
-        .await
+    let upload_response = match &config.wrap_key_passphrase {
+        Some(passphrase) => wrap_file_key(client, server, upload_response, passphrase).await?,
+        None => upload_response,
+    };
+
+    if let (Some(manifest), Some(manifest_path)) = (&manifest, &config.manifest_path) {
+        write_manifest(manifest, Some(manifest_path.as_path())).await?;
     }
+
+    print_upload_result(upload_response, config)
 }
 
-async fn perform_single_upload(
+// Files in a tar archive, as listed for recipients of the bundle
+async fn read_bundle_listing(file_path: &Path) -> Result<BundleListing> {
+    let path = file_path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&path)
+            .with_context(|| format!("Failed to open file: {}", path.display()))?;
+        let not_a_tar = || format!("{} is not a tar archive", path.display());
+
+        let mut entries = Vec::new();
+        for entry in tar::Archive::new(file).entries().with_context(not_a_tar)? {
+            let entry = entry.with_context(not_a_tar)?;
+            if entry.header().entry_type().is_file() {
+                entries.push(BundleEntry {
+                    name: entry.path().with_context(not_a_tar)?.display().to_string(),
+                    size: entry.size(),
+                });
+            }
+        }
+
+        if entries.is_empty() {
+            return Err(anyhow::anyhow!("{} holds no files to list", path.display()));
+        }
+        Ok(BundleListing { entries })
+    })
+    .await
+    .context("Failed to read the archive")?
+}
+
+// Encrypt the listing with the file key and attach it to the upload
+async fn attach_listing(
     client: &reqwest::Client,
     server: &str,
-    file_path: &Path,
-    filename: &str,
-    mime_type: Option<String>,
-    config: &UploadConfig,
+    upload_response: &UploadResponse,
+    listing: &BundleListing,
 ) -> Result<()> {
-    // Read the entire file
-    let file_data = async_fs::read(file_path)
+    let encryption_key = upload_response
+        .encryption_key
+        .as_deref()
+        .context("The upload has no encryption key to encrypt the listing with")?;
+    let owner_token = upload_response
+        .owner_token
+        .clone()
+        .context("The server returned no owner token, the listing can't be attached")?;
+
+    let key = EncryptionKey::from_url_encoded(encryption_key).context("Invalid encryption key")?;
+    let encrypted = Encryption::encrypt(&serde_json::to_vec(listing)?, &key)
+        .context("Failed to encrypt the listing")?;
+    if encrypted.ciphertext.len() > MAX_LISTING_SIZE {
+        return Err(anyhow::anyhow!(
+            "The archive holds too many files to list ({} entries)",
+            listing.entries.len()
+        ));
+    }
+
+    let response = client
+        .post(format!(
+            "{}/api/files/{}/listing",
+            server, upload_response.file_id
+        ))
+        .json(&ListingRequest {
+            owner_token,
+            ciphertext: general_purpose::STANDARD.encode(&encrypted.ciphertext),
+            nonce: general_purpose::STANDARD.encode(&encrypted.nonce),
+        })
+        .send()
         .await
-        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+        .context("Failed to send the listing")?;
 
-    // Create multipart form
-    let mut form = multipart::Form::new()
-        .part(
-            "file_data",
-            multipart::Part::bytes(file_data).file_name(filename.to_string()),
-        )
-        .part("filename", multipart::Part::text(filename.to_string()));
+    if !response.status().is_success() {
+        return Err(api_error(response, "Failed to attach the listing").await);
+    }
 
-    if let Some(mime) = mime_type {
-        form = form.part("mime_type", multipart::Part::text(mime));
+    println!("📋 Attached a listing of {} files", listing.entries.len());
+    Ok(())
+}
+
+// Store the file key wrapped with the passphrase, and drop it from the upload result
+async fn wrap_file_key(
+    client: &reqwest::Client,
+    server: &str,
+    mut upload_response: UploadResponse,
+    passphrase: &str,
+) -> Result<UploadResponse> {
+    let encryption_key = upload_response
+        .encryption_key
+        .take()
+        .context("The upload has no encryption key to wrap")?;
+    let owner_token = upload_response
+        .owner_token
+        .clone()
+        .context("The server returned no owner token, the key can't be wrapped")?;
+
+    let salt = key_wrap::generate_salt();
+    let kek = EncryptionKey::from_passphrase(passphrase, &salt)?;
+    let wrapped_key = EncryptionKey::from_base64(&encryption_key)?.wrap(&kek)?;
+
+    let response = client
+        .post(format!(
+            "{}/api/files/{}/wrapped-key",
+            server, upload_response.file_id
+        ))
+        .json(&WrapKeyRequest {
+            owner_token,
+            wrapped_key: general_purpose::STANDARD.encode(wrapped_key.to_bytes()),
+            key_salt: general_purpose::STANDARD.encode(salt),
+        })
+        .send()
+        .await
+        .context("Failed to send wrapped key")?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, "Failed to store the wrapped key").await);
     }
 
-    // Build URL with query parameters
-    let mut url = format!("{}/api/upload", server);
-    let mut params = Vec::new();
+    // Nothing printed for sharing may carry the key itself
+    upload_response.download_url = bare_download_url(&upload_response.download_url).to_string();
+    upload_response.curl_command = upload_response
+        .curl_command
+        .replace(&encryption_key, KEY_PLACEHOLDER);
 
-    if let Some(hours) = config.expires_in_hours {
-        params.push(format!("expires_in_hours={}", hours));
+    Ok(upload_response)
+}
+
+// Passphrase from KUSATSU_PASSPHRASE, or prompted (twice when setting a new one)
+fn read_passphrase(confirm: bool) -> Result<String> {
+    if let Some(passphrase) = std::env::var("KUSATSU_PASSPHRASE")
+        .ok()
+        .filter(|passphrase| !passphrase.is_empty())
+    {
+        return Ok(passphrase);
     }
 
-    if let Some(max_dl) = config.max_downloads {
-        params.push(format!("max_downloads={}", max_dl));
+    let passphrase =
+        rpassword::prompt_password("🔑 Passphrase: ").context("Failed to read passphrase")?;
+    if passphrase.is_empty() {
+        return Err(anyhow::anyhow!("The passphrase can't be empty"));
     }
 
-    if !params.is_empty() {
-        url.push('?');
-        url.push_str(&params.join("&"));
+    if confirm {
+        let repeated = rpassword::prompt_password("🔑 Repeat passphrase: ")
+            .context("Failed to read passphrase")?;
+        if repeated != passphrase {
+            return Err(anyhow::anyhow!("The passphrases don't match"));
+        }
     }
 
-    // Send the request
+    Ok(passphrase)
+}
+
+// Key of a passphrase-protected link, or None when the file's key isn't wrapped
+async fn unwrap_file_key(
+    client: &reqwest::Client,
+    server: &str,
+    file_id: &str,
+) -> Result<Option<String>> {
     let response = client
-        .post(&url)
-        .multipart(form)
+        .get(format!("{}/api/files/{}/wrapped-key", server, file_id))
         .send()
         .await
-        .context("Failed to send upload request")?;
+        .context("Failed to send wrapped key request")?;
 
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
     if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(anyhow::anyhow!(
-            "Upload failed with status {}: {}",
-            status,
-            error_text
-        ));
+        return Err(api_error(response, "Download failed").await);
     }
 
-    let upload_response: UploadResponse = response
+    let stored: WrappedKeyResponse = response
         .json()
         .await
-        .context("Failed to parse upload response")?;
-
-    print_upload_result(upload_response, &config.output_format)?;
-    Ok(())
+        .context("Failed to parse wrapped key response")?;
+    let wrapped_key = WrappedKey::from_bytes(
+        &general_purpose::STANDARD
+            .decode(&stored.wrapped_key)
+            .context("Invalid wrapped key")?,
+    )?;
+    let salt = general_purpose::STANDARD
+        .decode(&stored.key_salt)
+        .context("Invalid key salt")?;
+
+    println!("🔒 This link is protected with a passphrase");
+    let kek = EncryptionKey::from_passphrase(&read_passphrase(false)?, &salt)?;
+    let encryption_key = EncryptionKey::unwrap(&wrapped_key, &kek).context("Wrong passphrase")?;
+
+    Ok(Some(encryption_key.to_base64()))
 }
 
-async fn perform_chunked_upload(
+async fn plan_upload(
     client: &reqwest::Client,
     server: &str,
     file_path: &Path,
-    filename: &str,
-    file_size: usize,
-    mime_type: Option<String>,
     config: &UploadConfig,
 ) -> Result<()> {
-    // Step 1: Start upload session
-    println!("🚀 Starting chunked upload session...");
+    let metadata = async_fs::metadata(file_path)
+        .await
+        .with_context(|| format!("Failed to read file metadata: {}", file_path.display()))?;
 
-    let start_request = StartUploadRequest {
-        filename: filename.to_string(),
-        file_size: file_size as i64,
-        mime_type,
-        chunk_size: Some(CHUNK_SIZE as i32),
+    let filename = file_path
+        .file_name()
+        .context("Invalid filename")?
+        .to_string_lossy()
+        .to_string();
+
+    // Chunked uploads encrypt the filename, so the server only gets its extensions
+    let plan_request = StartUploadRequest {
+        filename: filename_placeholder(&filename),
+        file_size: metadata.len() as i64,
+        mime_type: mime_guess::from_path(file_path)
+            .first()
+            .map(|mime| mime.to_string()),
+        chunk_size: Some(recommended_chunk_size(metadata.len() as i64)),
         expires_in_hours: config.expires_in_hours,
         max_downloads: config.max_downloads,
+        slug: config.slug.clone(),
+        burn_after_first_download_minutes: config.burn_after_minutes,
+        expire_after_inactivity_hours: config.expire_after_inactivity_hours,
+        pow_challenge: None,
+        pow_nonce: None,
+        filename_encrypted: true,
     };
 
-    let start_url = format!("{}/api/upload/start", server);
-    let start_response = client
-        .post(&start_url)
-        .json(&start_request)
+    let response = client
+        .post(format!("{}/api/upload/plan", server))
+        .json(&plan_request)
         .send()
         .await
-        .context("Failed to start upload session")?;
+        .context("Failed to send upload plan request")?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, "Upload would be rejected").await);
+    }
+
+    let plan: UploadPlanResponse = response
+        .json()
+        .await
+        .context("Failed to parse upload plan response")?;
+
+    match config.output_format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&plan)?);
+        }
+        OutputFormat::Url => {
+            println!("🔍 Dry run for {} (nothing was uploaded)", filename);
+            match (plan.method, plan.chunk_size, plan.total_chunks) {
+                (UploadMethod::Chunked, Some(chunk_size), Some(total_chunks)) => println!(
+                    "🧩 Chunked upload: {} chunks of {} bytes",
+                    total_chunks, chunk_size
+                ),
+                _ => println!("📦 Single upload"),
+            }
+            println!(
+                "📏 Size: {} bytes (server limit: {} bytes)",
+                plan.file_size, plan.max_file_size
+            );
+            print_expiry(plan.expires_at);
+            if let Some(max_downloads) = plan.max_downloads {
+                println!("⬇️  Max downloads: {}", max_downloads);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn perform_single_upload(
+    client: &reqwest::Client,
+    server: &str,
+    file_path: &Path,
+    filename: &str,
+    mime_type: Option<String>,
+    config: &UploadConfig,
+) -> Result<UploadResponse> {
+    // Read the entire file
+    let file_data = async_fs::read(file_path)
+        .await
+        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+
+    let signature = config
+        .signing_key
+        .as_ref()
+        .map(|signing_key| signing::signature_to_base64(&signing::sign(&file_data, signing_key)));
+
+    // Create multipart form, declaring the size so the server can detect truncation
+    let file_size = file_data.len();
+    let mut form = multipart::Form::new()
+        .part(
+            "file_data",
+            multipart::Part::bytes(file_data).file_name(filename.to_string()),
+        )
+        .part("filename", multipart::Part::text(filename.to_string()))
+        .part("file_size", multipart::Part::text(file_size.to_string()));
+
+    if let Some(mime) = mime_type {
+        form = form.part("mime_type", multipart::Part::text(mime));
+    }
+
+    if let Some(signature) = signature {
+        form = form.part("signature", multipart::Part::text(signature));
+    }
+
+    // Build URL with query parameters
+    let mut url = format!("{}/api/upload", server);
+    let mut params = Vec::new();
+
+    if let Some(hours) = config.expires_in_hours {
+        params.push(format!("expires_in_hours={}", hours));
+    }
+
+    if let Some(max_dl) = config.max_downloads {
+        params.push(format!("max_downloads={}", max_dl));
+    }
+
+    if let Some(slug) = &config.slug {
+        params.push(format!("slug={}", slug));
+    }
+
+    if let Some(minutes) = config.burn_after_minutes {
+        params.push(format!("burn_after_first_download_minutes={}", minutes));
+    }
+
+    if let Some(hours) = config.expire_after_inactivity_hours {
+        params.push(format!("expire_after_inactivity_hours={}", hours));
+    }
+
+    if let Some((challenge, nonce)) = solve_upload_challenge(client, server).await? {
+        params.push(format!("pow_challenge={}&pow_nonce={}", challenge, nonce));
+    }
+
+    if !params.is_empty() {
+        url.push('?');
+        url.push_str(&params.join("&"));
+    }
+
+    // Send the request
+    let response = client
+        .post(&url)
+        .multipart(form)
+        .send()
+        .await
+        .context("Failed to send upload request")?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, "Upload failed").await);
+    }
+
+    response
+        .json()
+        .await
+        .context("Failed to parse upload response")
+}
+
+// Open a chunked upload session for the file
+async fn start_chunked_upload(
+    client: &reqwest::Client,
+    server: &str,
+    filename: &str,
+    file_size: usize,
+    mime_type: Option<String>,
+    config: &UploadConfig,
+) -> Result<StartUploadResponse> {
+    println!("🚀 Starting chunked upload session...");
+
+    let (pow_challenge, pow_nonce) = solve_upload_challenge(client, server).await?.unzip();
+    // The filename is only sent encrypted, on completion
+    let start_request = StartUploadRequest {
+        filename: filename_placeholder(filename),
+        file_size: file_size as i64,
+        mime_type,
+        chunk_size: Some(recommended_chunk_size(file_size as i64)),
+        expires_in_hours: config.expires_in_hours,
+        max_downloads: config.max_downloads,
+        slug: config.slug.clone(),
+        burn_after_first_download_minutes: config.burn_after_minutes,
+        expire_after_inactivity_hours: config.expire_after_inactivity_hours,
+        pow_challenge,
+        pow_nonce,
+        filename_encrypted: true,
+    };
+
+    let start_url = format!("{}/api/upload/start", server);
+    let start_response = client
+        .post(&start_url)
+        .json(&start_request)
+        .send()
+        .await
+        .context("Failed to start upload session")?;
 
     if !start_response.status().is_success() {
-        let status = start_response.status();
-        let error_text = start_response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(anyhow::anyhow!(
-            "Failed to start upload session with status {}: {}",
-            status,
-            error_text
-        ));
+        return Err(api_error(start_response, "Failed to start upload session").await);
     }
 
     let start_upload_response: StartUploadResponse = start_response
@@ -275,14 +1110,285 @@ async fn perform_chunked_upload(
         .await
         .context("Failed to parse start upload response")?;
 
-    let upload_id = start_upload_response.upload_id;
-    let total_chunks = start_upload_response.total_chunks;
-    let chunk_size = start_upload_response.chunk_size as usize;
-
     println!(
         "📊 Upload session started: {} chunks of {} bytes each",
-        total_chunks, chunk_size
+        start_upload_response.total_chunks, start_upload_response.chunk_size
+    );
+    println!(
+        "⏯️  If interrupted, continue with: --resume {}",
+        start_upload_response.upload_id
+    );
+
+    Ok(start_upload_response)
+}
+
+// Push back the expiry of an upload session; a failure only warns, as the
+// next chunk reports any real problem
+async fn keep_upload_alive(
+    client: &reqwest::Client,
+    server: &str,
+    upload_id: uuid::Uuid,
+    pb: &ProgressBar,
+) {
+    let result = client
+        .post(format!("{}/api/upload/{}/keepalive", server, upload_id))
+        .send()
+        .await;
+
+    match result {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => {
+            let error = api_error(response, "Keepalive failed").await;
+            pb.println(format!("⚠️  {}", error));
+        }
+        Err(e) => pb.println(format!("⚠️  Keepalive failed: {}", e)),
+    }
+}
+
+// Challenge and its solution for servers requiring proof of work before an
+// upload, None for the others
+async fn solve_upload_challenge(
+    client: &reqwest::Client,
+    server: &str,
+) -> Result<Option<(String, u64)>> {
+    let required = fetch_server_config(client, server)
+        .await
+        .is_some_and(|server_config| server_config.upload_pow_difficulty.is_some());
+    if !required {
+        return Ok(None);
+    }
+
+    let response = client
+        .post(format!("{}/api/upload/challenge", server))
+        .send()
+        .await
+        .context("Failed to request an upload challenge")?;
+    if !response.status().is_success() {
+        return Err(api_error(response, "Failed to get an upload challenge").await);
+    }
+    let challenge: UploadChallengeResponse = response
+        .json()
+        .await
+        .context("Failed to parse upload challenge response")?;
+
+    println!(
+        "🧮 Solving the server's proof-of-work challenge (difficulty {})...",
+        challenge.difficulty
+    );
+    let started = std::time::Instant::now();
+    let nonce = tokio::task::spawn_blocking({
+        let (challenge, difficulty) = (challenge.challenge.clone(), challenge.difficulty);
+        move || pow::solve(&challenge, difficulty)
+    })
+    .await
+    .context("Failed to solve the upload challenge")?;
+    println!("✅ Solved in {:.1}s", started.elapsed().as_secs_f64());
+
+    Ok(Some((challenge.challenge, nonce)))
+}
+
+// Limits and features of the server, None when it predates `/api/config`
+async fn fetch_server_config(
+    client: &reqwest::Client,
+    server: &str,
+) -> Option<ServerConfigResponse> {
+    let response = client
+        .get(format!("{}/api/config", server))
+        .send()
+        .await
+        .ok()?;
+
+    if !response.status().is_success() {
+        return None;
+    }
+
+    response.json().await.ok()
+}
+
+async fn fetch_upload_status(
+    client: &reqwest::Client,
+    server: &str,
+    upload_id: uuid::Uuid,
+) -> Result<UploadStatusResponse> {
+    let response = client
+        .get(format!("{}/api/upload/status/{}", server, upload_id))
+        .query(&[("verify", "true")])
+        .send()
+        .await
+        .context("Failed to get upload status")?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, "Failed to get upload status").await);
+    }
+
+    response
+        .json()
+        .await
+        .context("Failed to parse upload status response")
+}
+
+fn print_resume_status(status: &UploadStatusResponse) {
+    println!(
+        "⏯️  Resuming upload {}: {}/{} chunks already uploaded ({:.0}%)",
+        status.upload_id,
+        status.uploaded_chunks,
+        status.total_chunks,
+        status.progress * 100.0
+    );
+
+    let damaged = status.uploaded_chunk_numbers.len() - status.valid_chunk_numbers().len();
+    if damaged > 0 {
+        println!(
+            "⚠️  {} stored chunk(s) have the wrong size and will be uploaded again",
+            damaged
+        );
+    }
+
+    // Only known when chunks arrived in the last few seconds, e.g. after a dropped connection
+    if let (Some(bytes_per_second), Some(eta_seconds)) =
+        (status.bytes_per_second, status.eta_seconds)
+    {
+        println!(
+            "📈 Uploading at {}/s, about {} left",
+            indicatif::HumanBytes(bytes_per_second as u64),
+            indicatif::HumanDuration(Duration::from_secs(eta_seconds))
+        );
+    }
+}
+
+// Size of a chunk, the last one being shorter
+fn chunk_len(file_size: usize, chunk_size: usize, chunk_number: i32) -> usize {
+    std::cmp::min(chunk_size, file_size - chunk_number as usize * chunk_size)
+}
+
+// Times a chunk is sent while the server asks to back off, before giving up
+const MAX_CHUNK_ATTEMPTS: u32 = 30;
+
+// Upload one chunk as the raw request body, returning the server's response and
+// the chunk's size
+async fn send_chunk(
+    client: reqwest::Client,
+    chunk_url: String,
+    chunk_number: i32,
+    chunk_data: Vec<u8>,
+) -> Result<(ChunkUploadResponse, usize)> {
+    let chunk_size = chunk_data.len();
+
+    // The server bounds the chunks of one upload written at once; chunks over
+    // its limit are sent again after the wait it asks for
+    let mut attempt = 1;
+    let chunk_response = loop {
+        let response = client
+            .put(&chunk_url)
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(chunk_data.clone())
+            .send()
+            .await
+            .with_context(|| format!("Failed to upload chunk {}", chunk_number))?;
+        if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS
+            || attempt == MAX_CHUNK_ATTEMPTS
+        {
+            break response;
+        }
+
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(1);
+        tokio::time::sleep(Duration::from_secs(retry_after)).await;
+        attempt += 1;
+    };
+
+    if !chunk_response.status().is_success() {
+        return Err(api_error(
+            chunk_response,
+            &format!("Failed to upload chunk {}", chunk_number),
+        )
+        .await);
+    }
+
+    let chunk_upload_response = chunk_response
+        .json()
+        .await
+        .with_context(|| format!("Failed to parse chunk {} upload response", chunk_number))?;
+
+    Ok((chunk_upload_response, chunk_size))
+}
+
+// Wait for the next chunk in flight to finish and report it on the progress bar
+async fn finish_chunk(
+    in_flight: &mut JoinSet<Result<(ChunkUploadResponse, usize)>>,
+    pb: &ProgressBar,
+    uploaded_bytes: &mut usize,
+) -> Result<()> {
+    let Some(joined) = in_flight.join_next().await else {
+        return Ok(());
+    };
+    let (chunk_upload_response, chunk_size) = joined.context("Chunk upload task failed")??;
+
+    // Responses of parallel chunks can arrive out of order
+    *uploaded_bytes += chunk_size;
+    pb.set_position(
+        pb.position()
+            .max(chunk_upload_response.uploaded_chunks as u64),
     );
+    pb.set_message(format!("Uploaded {} bytes", uploaded_bytes));
+
+    Ok(())
+}
+
+async fn perform_chunked_upload(
+    client: &reqwest::Client,
+    server: &str,
+    file_path: &Path,
+    filename: &str,
+    file_size: usize,
+    mime_type: Option<String>,
+    config: &UploadConfig,
+) -> Result<UploadResponse> {
+    // Step 1: Start upload session, or pick up the one being resumed
+    let (upload_id, total_chunks, chunk_size, uploaded_chunk_numbers) =
+        match config.resume_upload_id {
+            Some(upload_id) => {
+                let status = fetch_upload_status(client, server, upload_id).await?;
+                if status.total_size != file_size as i64 {
+                    return Err(anyhow::anyhow!(
+                        "Upload {} is for a file of {} bytes, but {} has {}",
+                        upload_id,
+                        status.total_size,
+                        file_path.display(),
+                        file_size
+                    ));
+                }
+                if status.is_expired {
+                    return Err(anyhow::anyhow!(
+                        "Upload {} has expired, start a new upload",
+                        upload_id
+                    ));
+                }
+                print_resume_status(&status);
+
+                (
+                    upload_id,
+                    status.total_chunks,
+                    status.chunk_size as usize,
+                    status.valid_chunk_numbers(),
+                )
+            }
+            None => {
+                let started =
+                    start_chunked_upload(client, server, filename, file_size, mime_type, config)
+                        .await?;
+                (
+                    started.upload_id,
+                    started.total_chunks,
+                    started.chunk_size as usize,
+                    Vec::new(),
+                )
+            }
+        };
 
     // Create progress bar
     let pb = ProgressBar::new(total_chunks as u64);
@@ -293,16 +1399,40 @@ async fn perform_chunked_upload(
             .progress_chars("#>-")
     );
     pb.set_message("Uploading chunks...");
+    pb.set_position(uploaded_chunk_numbers.len() as u64);
 
     // Step 2: Upload chunks
     let mut file = async_fs::File::open(file_path)
         .await
         .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
 
+    let mut last_keepalive = std::time::Instant::now();
+
+    // Up to `parallel_chunks` chunks are sent at once, each on a pooled connection
+    // (or stream, with HTTP/2); returning early drops the set, aborting the rest
+    let mut in_flight = JoinSet::new();
+    let mut uploaded_bytes: usize = uploaded_chunk_numbers
+        .iter()
+        .map(|&chunk_number| chunk_len(file_size, chunk_size, chunk_number))
+        .sum();
+
     for chunk_number in 0..total_chunks {
+        if uploaded_chunk_numbers.contains(&chunk_number) {
+            continue;
+        }
+
+        // Slow uploads would otherwise outlive their session
+        if last_keepalive.elapsed() >= KEEPALIVE_INTERVAL {
+            keep_upload_alive(client, server, upload_id, &pb).await;
+            last_keepalive = std::time::Instant::now();
+        }
+
+        if in_flight.len() >= config.parallel_chunks {
+            finish_chunk(&mut in_flight, &pb, &mut uploaded_bytes).await?;
+        }
+
         let start_offset = chunk_number as usize * chunk_size;
-        let remaining_size = file_size - start_offset;
-        let current_chunk_size = std::cmp::min(chunk_size, remaining_size);
+        let current_chunk_size = chunk_len(file_size, chunk_size, chunk_number);
 
         // Read chunk from file
         let mut chunk_data = vec![0u8; current_chunk_size];
@@ -314,55 +1444,42 @@ async fn perform_chunked_upload(
             .await
             .with_context(|| format!("Failed to read chunk {}", chunk_number))?;
 
-        // Upload chunk
         let chunk_url = format!("{}/api/upload/chunk/{}/{}", server, upload_id, chunk_number);
-
-        let chunk_form = multipart::Form::new().part(
-            "chunk",
-            multipart::Part::bytes(chunk_data).file_name(format!("chunk_{}", chunk_number)),
-        );
-
-        let chunk_response = client
-            .post(&chunk_url)
-            .multipart(chunk_form)
-            .send()
-            .await
-            .with_context(|| format!("Failed to upload chunk {}", chunk_number))?;
-
-        if !chunk_response.status().is_success() {
-            let status = chunk_response.status();
-            let error_text = chunk_response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(anyhow::anyhow!(
-                "Failed to upload chunk {} with status {}: {}",
-                chunk_number,
-                status,
-                error_text
-            ));
-        }
-
-        let chunk_upload_response: ChunkUploadResponse = chunk_response
-            .json()
-            .await
-            .with_context(|| format!("Failed to parse chunk {} upload response", chunk_number))?;
-
-        // Update progress bar
-        pb.set_position(chunk_upload_response.uploaded_chunks as u64);
-        pb.set_message(format!(
-            "Uploaded {} bytes",
-            start_offset + current_chunk_size
+        in_flight.spawn(send_chunk(
+            client.clone(),
+            chunk_url,
+            chunk_number,
+            chunk_data,
         ));
     }
 
+    while !in_flight.is_empty() {
+        finish_chunk(&mut in_flight, &pb, &mut uploaded_bytes).await?;
+    }
+
     // Finish progress bar
     pb.finish_with_message("All chunks uploaded successfully!");
 
     // Step 3: Complete upload
     println!("🏁 Completing upload...");
 
-    let complete_request = CompleteUploadRequest { upload_id };
+    // The contents stay unencrypted, but the filename is encrypted with a key kept in the link
+    let encryption_key = EncryptionKey::generate();
+    let encrypted_filename = Encryption::encrypt(filename.as_bytes(), &encryption_key)
+        .context("Failed to encrypt filename")?;
+    let encryption_key = encryption_key.to_base64();
+
+    let signature = match &config.signing_key {
+        Some(signing_key) => Some(sign_file(file_path, signing_key).await?),
+        None => None,
+    };
+
+    let complete_request = CompleteUploadRequest {
+        upload_id,
+        encrypted_filename: Some(general_purpose::STANDARD.encode(&encrypted_filename.ciphertext)),
+        filename_nonce: Some(general_purpose::STANDARD.encode(&encrypted_filename.nonce)),
+        signature,
+    };
 
     let complete_url = format!("{}/api/upload/complete", server);
     let complete_response = client
@@ -373,16 +1490,7 @@ async fn perform_chunked_upload(
         .context("Failed to complete upload")?;
 
     if !complete_response.status().is_success() {
-        let status = complete_response.status();
-        let error_text = complete_response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(anyhow::anyhow!(
-            "Failed to complete upload with status {}: {}",
-            status,
-            error_text
-        ));
+        return Err(api_error(complete_response, "Failed to complete upload").await);
     }
 
     let complete_upload_response: UploadResponse = complete_response
@@ -390,37 +1498,55 @@ async fn perform_chunked_upload(
         .await
         .context("Failed to parse complete upload response")?;
 
+    println!("ℹ️  Note: Only the filename is encrypted, large files are stored without encryption");
+
     // Convert to standard UploadResponse format for consistent output
-    let upload_response = UploadResponse {
+    Ok(UploadResponse {
         file_id: complete_upload_response.file_id,
         download_url: complete_upload_response.download_url,
-        encryption_key: complete_upload_response.encryption_key,
-        curl_command: complete_upload_response.curl_command,
-    };
-
-    print_upload_result(upload_response, &config.output_format)?;
-    Ok(())
+        curl_command: complete_upload_response
+            .curl_command
+            .replace(KEY_PLACEHOLDER, &encryption_key),
+        encryption_key: Some(encryption_key),
+        owner_token: complete_upload_response.owner_token,
+        slug: complete_upload_response.slug,
+        expires_at: complete_upload_response.expires_at,
+        content_sha256: complete_upload_response.content_sha256,
+    })
 }
 
-fn print_upload_result(
-    upload_response: UploadResponse,
-    output_format: &OutputFormat,
-) -> Result<()> {
+fn print_upload_result(upload_response: UploadResponse, config: &UploadConfig) -> Result<()> {
+    if config.split_key {
+        return print_split_upload_result(upload_response, config);
+    }
+
+    let public_key = signing_public_key(config);
+
     // Create the complete shareable URL with encryption key (if available)
     let shareable_url = if let Some(ref encryption_key) = upload_response.encryption_key {
-        format!("{}#{}", upload_response.download_url, encryption_key)
+        format!(
+            "{}#{}",
+            bare_download_url(&upload_response.download_url),
+            encryption_key
+        )
     } else {
         upload_response.download_url.to_string()
     };
 
-    match output_format {
+    match config.output_format {
         OutputFormat::Json => {
             let json_output = serde_json::json!({
                 "file_id": upload_response.file_id,
                 "download_url": upload_response.download_url,
                 "encryption_key": upload_response.encryption_key,
                 "shareable_url": shareable_url,
-                "curl_command": upload_response.curl_command
+                "curl_command": upload_response.curl_command,
+                "owner_token": upload_response.owner_token,
+                "slug": upload_response.slug,
+                "expires_at": upload_response.expires_at,
+                "content_sha256": upload_response.content_sha256,
+                "public_key": public_key,
+                "passphrase_protected": config.wrap_key_passphrase.is_some()
             });
             println!("{}", serde_json::to_string_pretty(&json_output)?);
         }
@@ -428,13 +1554,1421 @@ fn print_upload_result(
             println!("✅ File uploaded successfully!");
             if upload_response.encryption_key.is_some() {
                 println!("📎 Shareable URL: {}", shareable_url);
+            } else if config.wrap_key_passphrase.is_some() {
+                println!("📎 Shareable URL: {}", shareable_url);
+                println!("🔒 Recipients need the passphrase to download it");
             } else {
                 println!("📎 Download URL: {}", shareable_url);
                 println!("ℹ️  Note: This file was uploaded without encryption");
             }
             println!("💻 Download with curl: {}", upload_response.curl_command);
+            if let Some(ref slug) = upload_response.slug {
+                println!("🏷️  Slug: {}", slug);
+            }
+            print_expiry(upload_response.expires_at);
+            print_burn_note(config.burn_after_minutes);
+            if let Some(ref content_sha256) = upload_response.content_sha256 {
+                println!("🧾 SHA-256: {}", content_sha256);
+            }
+            if let Some(ref owner_token) = upload_response.owner_token {
+                println!(
+                    "🔑 Owner token (keep it to manage the file): {}",
+                    owner_token
+                );
+            }
+            print_public_key(public_key.as_deref());
         }
     }
 
     Ok(())
 }
+
+// Public key matching the signing key of the upload, for recipients to verify with
+fn signing_public_key(config: &UploadConfig) -> Option<String> {
+    config
+        .signing_key
+        .as_ref()
+        .map(|signing_key| signing::verifying_key_to_base64(&signing_key.verifying_key()))
+}
+
+fn print_public_key(public_key: Option<&str>) {
+    if let Some(public_key) = public_key {
+        println!(
+            "🔏 Signed, recipients can check with: --verify-key {}",
+            public_key
+        );
+    }
+}
+
+fn print_expiry(expires_at: Option<chrono::DateTime<chrono::Utc>>) {
+    match expires_at {
+        Some(expires_at) => println!(
+            "⏰ Expires at: {}",
+            expires_at.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+        None => println!("⏰ Expires: never"),
+    }
+}
+
+fn print_burn_note(burn_after_minutes: Option<i32>) {
+    if let Some(minutes) = burn_after_minutes {
+        println!("🔥 Burns {} minute(s) after its first download", minutes);
+    }
+}
+
+// The server may already embed the key as an anchor; strip it
+fn bare_download_url(download_url: &str) -> &str {
+    download_url.split('#').next().unwrap_or(download_url)
+}
+
+// Print the URL and key separately, for sharing the key out-of-band
+fn print_split_upload_result(upload_response: UploadResponse, config: &UploadConfig) -> Result<()> {
+    let download_url = bare_download_url(&upload_response.download_url);
+    let public_key = signing_public_key(config);
+
+    match config.output_format {
+        OutputFormat::Json => {
+            let json_output = serde_json::json!({
+                "file_id": upload_response.file_id,
+                "download_url": download_url,
+                "encryption_key": upload_response.encryption_key,
+                "curl_command": upload_response.curl_command,
+                "owner_token": upload_response.owner_token,
+                "slug": upload_response.slug,
+                "expires_at": upload_response.expires_at,
+                "public_key": public_key
+            });
+            println!("{}", serde_json::to_string_pretty(&json_output)?);
+        }
+        OutputFormat::Url => {
+            println!("✅ File uploaded successfully!");
+            println!("📎 Download URL: {}", download_url);
+            if let Some(ref encryption_key) = upload_response.encryption_key {
+                println!("🔐 Encryption key: {}", encryption_key);
+                println!("ℹ️  Share the key separately; it must be entered on the download page");
+            } else {
+                println!("ℹ️  Note: This file was uploaded without encryption");
+            }
+            println!("💻 Download with curl: {}", upload_response.curl_command);
+            if let Some(ref slug) = upload_response.slug {
+                println!("🏷️  Slug: {}", slug);
+            }
+            print_expiry(upload_response.expires_at);
+            print_burn_note(config.burn_after_minutes);
+            if let Some(ref owner_token) = upload_response.owner_token {
+                println!(
+                    "🔑 Owner token (keep it to manage the file): {}",
+                    owner_token
+                );
+            }
+            print_public_key(public_key.as_deref());
+        }
+    }
+
+    Ok(())
+}
+
+async fn extend_file(
+    client: &reqwest::Client,
+    server: &str,
+    file_id: &str,
+    owner_token: String,
+    expires_in_hours: i32,
+    encryption_key: Option<String>,
+) -> Result<()> {
+    let extend_request = ExtendExpiryRequest {
+        owner_token,
+        expires_in_hours: Some(expires_in_hours),
+        expires_at: None,
+        encryption_key,
+    };
+
+    let extend_url = format!("{}/api/files/{}/extend", server, file_id);
+    let response = client
+        .post(&extend_url)
+        .json(&extend_request)
+        .send()
+        .await
+        .context("Failed to send extend request")?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, "Extend failed").await);
+    }
+
+    let file_info: FileInfo = response
+        .json()
+        .await
+        .context("Failed to parse extend response")?;
+
+    println!("✅ Expiry extended for {}", file_info.filename);
+    if let Some(expires_at) = file_info.expires_at {
+        println!("⏰ New expiry: {}", expires_at);
+    }
+
+    Ok(())
+}
+
+// Build an error from a failed API response, including the server's error code
+async fn api_error(response: reqwest::Response, action: &str) -> anyhow::Error {
+    let status = response.status();
+    // Quoting it lets an operator find the server-side logs of the failed request
+    let request_id = response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| format!(" [request id {}]", value))
+        .unwrap_or_default();
+    let body = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        // List each rejected field on its own line, by the option that sets it
+        Ok(error) if !error.fields.is_empty() => {
+            let fields: String = error
+                .fields
+                .iter()
+                .map(|field| format!("\n  • {}: {}", option_name(&field.field), field.message))
+                .collect();
+            anyhow::anyhow!(
+                "{} with status {} ({}){}{}",
+                action,
+                status,
+                error.code,
+                request_id,
+                fields
+            )
+        }
+        Ok(error) => anyhow::anyhow!(
+            "{} with status {} ({}): {}{}",
+            action,
+            status,
+            error.code,
+            error.message,
+            request_id
+        ),
+        Err(_) => anyhow::anyhow!("{} with status {}: {}{}", action, status, body, request_id),
+    }
+}
+
+// The upload option behind a request field, for fields a user can set directly
+fn option_name(field: &str) -> &str {
+    match field {
+        "expires_in_hours" => "--expires-in-hours",
+        "max_downloads" => "--max-downloads",
+        "slug" => "--slug",
+        other => other,
+    }
+}
+
+// Extract the file ID and optional key from a `.../download/<id>#<key>` URL
+fn parse_download_url(url: &str) -> Result<(String, Option<String>)> {
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("Invalid URL: {}", url))?;
+
+    let file_id = parsed
+        .path_segments()
+        .and_then(|segments| segments.rev().find(|segment| !segment.is_empty()))
+        .context("URL does not contain a file ID")?
+        .to_string();
+
+    let encryption_key = parsed
+        .fragment()
+        .filter(|fragment| !fragment.is_empty())
+        .map(|fragment| fragment.to_string());
+
+    Ok((file_id, encryption_key))
+}
+
+async fn fetch_file_info(
+    client: &reqwest::Client,
+    server: &str,
+    file_id: &str,
+    encryption_key: Option<String>,
+    action: &str,
+) -> Result<FileInfo> {
+    let response = client
+        .post(format!("{}/api/files/{}/info", server, file_id))
+        .json(&DownloadRequest { encryption_key })
+        .send()
+        .await
+        .context("Failed to send info request")?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, action).await);
+    }
+
+    response
+        .json()
+        .await
+        .context("Failed to parse info response")
+}
+
+// Print the files of a bundle from its listing; nothing is downloaded
+async fn show_listing(client: &reqwest::Client, server: &str, url: &str) -> Result<()> {
+    let (file_id, encryption_key) = parse_download_url(url)?;
+
+    let encryption_key = match encryption_key {
+        Some(encryption_key) => encryption_key,
+        None => unwrap_file_key(client, server, &file_id)
+            .await?
+            .context("The link has no key, the listing can't be decrypted")?,
+    };
+    let listing = fetch_listing(client, server, &file_id, &encryption_key).await?;
+
+    print_entries(&listing.entries);
+    println!(
+        "📦 {} files, {} bytes in total",
+        listing.entries.len(),
+        listing.entries.iter().map(|entry| entry.size).sum::<u64>()
+    );
+    Ok(())
+}
+
+fn print_entries(entries: &[BundleEntry]) {
+    for entry in entries {
+        println!("{:>12}  {}", entry.size, entry.name);
+    }
+}
+
+// Fetch the listing of a bundle and decrypt it with the file key
+async fn fetch_listing(
+    client: &reqwest::Client,
+    server: &str,
+    file_id: &str,
+    encryption_key: &str,
+) -> Result<BundleListing> {
+    let key = EncryptionKey::from_url_encoded(encryption_key).context("Invalid encryption key")?;
+
+    let response = client
+        .get(format!("{}/api/files/{}/listing", server, file_id))
+        .send()
+        .await
+        .context("Failed to send listing request")?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(anyhow::anyhow!(
+            "This file has no listing (it may not be a bundle, or no longer exist)"
+        ));
+    } else if !response.status().is_success() {
+        return Err(api_error(response, "Failed to fetch the listing").await);
+    }
+
+    let encrypted: ListingResponse = response
+        .json()
+        .await
+        .context("Failed to parse listing response")?;
+    let plaintext = Encryption::decrypt(
+        &kusatsu_encrypt::EncryptedData {
+            ciphertext: general_purpose::STANDARD
+                .decode(&encrypted.ciphertext)
+                .context("Invalid listing encoding")?,
+            nonce: general_purpose::STANDARD
+                .decode(&encrypted.nonce)
+                .context("Invalid listing nonce")?,
+            key_size: key.size(),
+        },
+        &key,
+    )
+    .context("Failed to decrypt the listing, is the key right?")?;
+    serde_json::from_slice(&plaintext).context("Failed to parse the listing")
+}
+
+// Path of the partial download kept next to `output` until it completes
+fn part_path(output: &Path) -> PathBuf {
+    let mut part = output.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+async fn download_file(
+    client: &reqwest::Client,
+    server: &str,
+    url: &str,
+    output: Option<PathBuf>,
+    verifying_key: Option<signing::VerifyingKey>,
+    verify_hash: bool,
+) -> Result<()> {
+    let (file_id, encryption_key) = parse_download_url(url)?;
+
+    // Without a key in the link, it may be protected with a passphrase
+    let encryption_key = match encryption_key {
+        Some(encryption_key) => Some(encryption_key),
+        None => unwrap_file_key(client, server, &file_id).await?,
+    };
+
+    // The info endpoint doesn't count as a download; it gives the name and expected size
+    let file_info = fetch_file_info(
+        client,
+        server,
+        &file_id,
+        encryption_key.clone(),
+        "Download failed",
+    )
+    .await?;
+
+    // Never let a server-provided name escape the current directory
+    let output = match output {
+        Some(output) => output,
+        None => Path::new(&file_info.filename)
+            .file_name()
+            .map(PathBuf::from)
+            .context("File has no usable filename, pass --output")?,
+    };
+    let part = part_path(&output);
+    let expected_size = file_info.original_size as u64;
+
+    // Encrypted files are decrypted server-side as a whole, so only plain files can resume
+    let mut resume_from = match async_fs::metadata(&part).await {
+        Ok(metadata) if !file_info.is_encrypted => metadata.len(),
+        _ => 0,
+    };
+    if resume_from > expected_size {
+        resume_from = 0;
+    }
+
+    let download_url = format!("{}/api/files/{}/download", server, file_id);
+    let mut request = client.post(&download_url).form(&[(
+        "encryption_key",
+        encryption_key.as_deref().unwrap_or_default(),
+    )]);
+    if resume_from > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+    }
+
+    let mut response = request
+        .send()
+        .await
+        .context("Failed to send download request")?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, "Download failed").await);
+    }
+
+    // A full response means the server ignored the range, so start over
+    let resuming = resume_from > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let part_file = if resuming {
+        println!("⏯️  Resuming download at {} bytes", resume_from);
+        async_fs::OpenOptions::new().append(true).open(&part).await
+    } else {
+        resume_from = 0;
+        async_fs::File::create(&part).await
+    }
+    .with_context(|| format!("Failed to open {}", part.display()))?;
+
+    // Padded sizes are only an upper bound, so go by what the server sends
+    let expected_size = match response.content_length() {
+        Some(length) if file_info.sizes_padded => resume_from + length,
+        _ => expected_size,
+    };
+
+    // Hash the data as it is written; only a resumed prefix has to be read back
+    let expected_sha256 = file_info.content_sha256.as_deref().filter(|_| verify_hash);
+    let mut part_file = HashingWriter::new(part_file, expected_sha256.is_some());
+    if resuming && expected_sha256.is_some() {
+        part_file.hash_existing(&part).await?;
+    }
+
+    let progress_bar = ProgressBar::new(expected_size);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+            .expect("Failed to set progress bar template")
+            .progress_chars("#>-"),
+    );
+    progress_bar.set_position(resume_from);
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .context("Download interrupted, re-run the command to resume")?
+    {
+        part_file
+            .write_all(&chunk)
+            .await
+            .with_context(|| format!("Failed to write {}", part.display()))?;
+        progress_bar.inc(chunk.len() as u64);
+    }
+    let (downloaded_size, actual_sha256) = part_file.finish().await?;
+    progress_bar.finish_and_clear();
+
+    // Only a complete file gets its final name
+    let downloaded_size = resume_from + downloaded_size;
+    if downloaded_size != expected_size {
+        return Err(anyhow::anyhow!(
+            "Downloaded {} bytes but expected {}, re-run the command to resume",
+            downloaded_size,
+            expected_size
+        ));
+    }
+
+    // A file that fails verification is discarded rather than left for a resume
+    if let (Some(expected), Some(actual)) = (expected_sha256, actual_sha256) {
+        if !actual.eq_ignore_ascii_case(expected) {
+            let _ = async_fs::remove_file(&part).await;
+            return Err(anyhow::anyhow!(
+                "Content hash mismatch: the server recorded SHA-256 {} but the download has {}, the file was discarded",
+                expected,
+                actual
+            ));
+        }
+        println!("🧾 Content hash verified");
+    }
+
+    if let Some(verifying_key) = &verifying_key {
+        if let Err(e) = verify_download(&part, file_info.signature.as_deref(), verifying_key).await
+        {
+            let _ = async_fs::remove_file(&part).await;
+            return Err(e);
+        }
+        println!("🔏 Signature verified");
+    }
+
+    async_fs::rename(&part, &output)
+        .await
+        .with_context(|| format!("Failed to move download to {}", output.display()))?;
+
+    println!(
+        "✅ Downloaded {} to {}",
+        file_info.filename,
+        output.display()
+    );
+
+    Ok(())
+}
+
+// Writes a download to disk, counting the bytes and hashing them on the way
+struct HashingWriter {
+    file: async_fs::File,
+    hasher: Option<Sha256>,
+    written: u64,
+}
+
+impl HashingWriter {
+    fn new(file: async_fs::File, hash: bool) -> Self {
+        Self {
+            file,
+            hasher: hash.then(Sha256::new),
+            written: 0,
+        }
+    }
+
+    // Feed the part of the file kept from an earlier attempt into the hash
+    async fn hash_existing(&mut self, path: &Path) -> Result<()> {
+        match self.hasher.as_mut() {
+            Some(hasher) => hash_file(path, hasher).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.file.write_all(data).await?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(data);
+        }
+        self.written += data.len() as u64;
+        Ok(())
+    }
+
+    // Bytes written by this writer, and the hex SHA-256 of the whole file when hashing
+    async fn finish(mut self) -> Result<(u64, Option<String>)> {
+        self.file.flush().await?;
+        Ok((
+            self.written,
+            self.hasher.map(|hasher| format!("{:x}", hasher.finalize())),
+        ))
+    }
+}
+
+async fn hash_file(path: &Path, hasher: &mut Sha256) -> Result<()> {
+    let mut file = async_fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            return Ok(());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+async fn verify_download(
+    path: &Path,
+    signature: Option<&str>,
+    verifying_key: &signing::VerifyingKey,
+) -> Result<()> {
+    let signature = signature.context("File is not signed, refusing to keep it")?;
+    let signature =
+        signing::signature_from_base64(signature).context("File has an invalid signature")?;
+
+    let data = async_fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    signing::verify(&data, &signature, verifying_key)
+        .context("Signature does not match the given public key, the file was discarded")
+}
+
+// Mime types archives are stored with; generic binary data is checked by its bytes alone
+const ARCHIVE_MIME_TYPES: &[&str] = &[
+    "application/x-tar",
+    "application/gzip",
+    "application/x-gzip",
+    "application/x-gtar",
+    "application/x-compressed-tar",
+    "application/x-compressed",
+];
+
+// Download a tar or tar.gz archive and extract it into `dir` as it arrives
+async fn extract_download(
+    client: &reqwest::Client,
+    server: &str,
+    url: &str,
+    dir: &Path,
+    verify_hash: bool,
+) -> Result<()> {
+    let (file_id, encryption_key) = parse_download_url(url)?;
+
+    let encryption_key = match encryption_key {
+        Some(encryption_key) => Some(encryption_key),
+        None => unwrap_file_key(client, server, &file_id).await?,
+    };
+
+    let file_info = fetch_file_info(
+        client,
+        server,
+        &file_id,
+        encryption_key.clone(),
+        "Download failed",
+    )
+    .await?;
+
+    // Rule out files that clearly aren't archives before consuming a download
+    let mime_type = file_info.mime_type.clone();
+    let is_archive_mime = mime_type
+        .as_deref()
+        .is_some_and(|mime_type| ARCHIVE_MIME_TYPES.contains(&mime_type));
+    if let Some(mime_type) = mime_type.as_deref() {
+        if !is_archive_mime && mime_type != "application/octet-stream" {
+            return Err(anyhow::anyhow!(
+                "{} is not a tar archive ({}), download it without --extract",
+                file_info.filename,
+                mime_type
+            ));
+        }
+    }
+
+    // Fetched first, so a listing that can't be read doesn't waste the download
+    let listing = match encryption_key.as_deref() {
+        Some(encryption_key) if file_info.has_listing => {
+            Some(fetch_listing(client, server, &file_id, encryption_key).await?)
+        }
+        _ => None,
+    };
+
+    async_fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let mut response = client
+        .post(format!("{}/api/files/{}/download", server, file_id))
+        .form(&[(
+            "encryption_key",
+            encryption_key.as_deref().unwrap_or_default(),
+        )])
+        .send()
+        .await
+        .context("Failed to send download request")?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, "Download failed").await);
+    }
+
+    // Padded sizes are only an upper bound, so go by what the server sends
+    let expected_size = match response.content_length() {
+        Some(length) if file_info.sizes_padded => length,
+        _ => file_info.original_size as u64,
+    };
+
+    // The archive is read on a blocking thread, fed the data as it is downloaded
+    let (sender, receiver) = tokio::sync::mpsc::channel(16);
+    let extraction = {
+        let dir = dir.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let mut extracted = Vec::new();
+            let result = extract_archive(
+                ChannelReader::new(receiver),
+                &dir,
+                is_archive_mime,
+                &mut extracted,
+            );
+            (extracted, result)
+        })
+    };
+
+    let expected_sha256 = file_info.content_sha256.as_deref().filter(|_| verify_hash);
+    let mut hasher = expected_sha256.map(|_| Sha256::new());
+    let mut downloaded_size = 0u64;
+    let progress_bar = ProgressBar::new(expected_size);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+            .expect("Failed to set progress bar template")
+            .progress_chars("#>-"),
+    );
+
+    let download: Result<()> = async {
+        while let Some(chunk) = response.chunk().await.context("Download interrupted")? {
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update(&chunk);
+            }
+            downloaded_size += chunk.len() as u64;
+            progress_bar.inc(chunk.len() as u64);
+            // The extractor only stops listening when it failed, which it reports itself
+            if sender.send(chunk.to_vec()).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+    .await;
+    drop(sender);
+    progress_bar.finish_and_clear();
+
+    let (extracted, extraction) = extraction.await.context("Failed to extract the archive")?;
+    let extraction =
+        extraction.with_context(|| format!("Failed to extract into {}", dir.display()));
+
+    // Files written before a failure are as untrustworthy as a mismatched download
+    if let Err(e) = download.and(extraction) {
+        remove_extracted(dir, &extracted).await;
+        return Err(e.context(
+            "Failed to download and extract the archive, the files extracted so far were removed",
+        ));
+    }
+
+    if downloaded_size != expected_size {
+        remove_extracted(dir, &extracted).await;
+        return Err(anyhow::anyhow!(
+            "Downloaded {} bytes but expected {}, the extracted files were removed",
+            downloaded_size,
+            expected_size
+        ));
+    }
+
+    if let (Some(expected), Some(hasher)) = (expected_sha256, hasher) {
+        let actual = format!("{:x}", hasher.finalize());
+        if !actual.eq_ignore_ascii_case(expected) {
+            remove_extracted(dir, &extracted).await;
+            return Err(anyhow::anyhow!(
+                "Content hash mismatch: the server recorded SHA-256 {} but the download has {}, the extracted files were removed",
+                expected,
+                actual
+            ));
+        }
+        println!("🧾 Content hash verified");
+    }
+
+    print_entries(&extracted);
+    if let Some(listing) = listing {
+        check_against_listing(&listing, &extracted);
+    }
+    println!(
+        "✅ Extracted {} files, {} bytes in total, from {} to {}",
+        extracted.len(),
+        extracted.iter().map(|entry| entry.size).sum::<u64>(),
+        file_info.filename,
+        dir.display()
+    );
+
+    Ok(())
+}
+
+// Reads the chunks of a download sent from the async side
+struct ChannelReader {
+    receiver: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl ChannelReader {
+    fn new(receiver: tokio::sync::mpsc::Receiver<Vec<u8>>) -> Self {
+        Self {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl std::io::Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let read = buf.len().min(self.chunk.len() - self.position);
+        buf[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+// Extract a tar archive, gzipped or not, into `dir`, adding the files written to
+// `extracted` as it goes, so they are known even when it fails partway
+//
+// The format is told by the gzip magic bytes or the ustar signature of the first
+// header; old archives without one are only accepted when stored as a tar.
+fn extract_archive(
+    mut reader: impl std::io::Read,
+    dir: &Path,
+    is_archive_mime: bool,
+    extracted: &mut Vec<BundleEntry>,
+) -> Result<()> {
+    use std::io::Read as _;
+
+    let mut head = Vec::with_capacity(512);
+    (&mut reader).take(512).read_to_end(&mut head)?;
+    let gzipped = head.starts_with(&[0x1f, 0x8b]);
+    let ustar = head.get(257..262) == Some(b"ustar".as_slice());
+    if !gzipped && !ustar && !is_archive_mime {
+        return Err(anyhow::anyhow!(
+            "The download is not a tar archive, download it without --extract"
+        ));
+    }
+
+    let reader = std::io::Read::chain(std::io::Cursor::new(head), reader);
+    // Whatever follows the archive is still read, so the whole download gets hashed
+    if gzipped {
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(reader));
+        extract_entries(&mut archive, dir, extracted)?;
+        let mut decoder = archive.into_inner();
+        std::io::copy(&mut decoder, &mut std::io::sink())?;
+        std::io::copy(&mut decoder.into_inner(), &mut std::io::sink())?;
+    } else {
+        let mut archive = tar::Archive::new(reader);
+        extract_entries(&mut archive, dir, extracted)?;
+        std::io::copy(&mut archive.into_inner(), &mut std::io::sink())?;
+    }
+    Ok(())
+}
+
+fn extract_entries<R: std::io::Read>(
+    archive: &mut tar::Archive<R>,
+    dir: &Path,
+    extracted: &mut Vec<BundleEntry>,
+) -> Result<()> {
+    for entry in archive.entries().context("Invalid tar archive")? {
+        let mut entry = entry.context("Invalid tar archive")?;
+        let path = entry
+            .path()
+            .context("Invalid path in the archive")?
+            .into_owned();
+
+        // Entries may only name paths inside the target directory
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(anyhow::anyhow!(
+                "Refusing to extract {}, it points outside the target directory",
+                path.display()
+            ));
+        }
+
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            println!(
+                "⚠️  Skipped {}, only files and directories are extracted",
+                path.display()
+            );
+            continue;
+        }
+        // Noted before unpacking, so a file left half-written is removed too
+        if entry_type.is_file() {
+            extracted.push(BundleEntry {
+                name: path.display().to_string(),
+                size: entry.size(),
+            });
+        }
+        if !entry
+            .unpack_in(dir)
+            .with_context(|| format!("Failed to extract {}", path.display()))?
+        {
+            // Nothing was written, and the path must not be touched
+            if entry_type.is_file() {
+                extracted.pop();
+            }
+            return Err(anyhow::anyhow!(
+                "Refusing to extract {}, it points outside the target directory",
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Remove the files of an extraction that turned out not to be trustworthy
+async fn remove_extracted(dir: &Path, extracted: &[BundleEntry]) {
+    for entry in extracted {
+        let _ = async_fs::remove_file(dir.join(&entry.name)).await;
+    }
+}
+
+// Warn about files that differ from the listing the uploader attached
+fn check_against_listing(listing: &BundleListing, extracted: &[BundleEntry]) {
+    let mismatched: Vec<&BundleEntry> = listing
+        .entries
+        .iter()
+        .filter(|listed| {
+            !extracted
+                .iter()
+                .any(|entry| entry.name == listed.name && entry.size == listed.size)
+        })
+        .collect();
+    let unlisted = extracted
+        .iter()
+        .filter(|entry| {
+            !listing
+                .entries
+                .iter()
+                .any(|listed| listed.name == entry.name)
+        })
+        .count();
+
+    if mismatched.is_empty() && unlisted == 0 {
+        println!("📋 All files match the bundle listing");
+        return;
+    }
+    for entry in mismatched {
+        println!(
+            "⚠️  Listed but not extracted as such: {} ({} bytes)",
+            entry.name, entry.size
+        );
+    }
+    if unlisted > 0 {
+        println!("⚠️  {} extracted files are not in the listing", unlisted);
+    }
+}
+
+// Lightweight check with a HEAD request, which reveals nothing but availability
+async fn check_link_exists(
+    client: &reqwest::Client,
+    server: &str,
+    url: &str,
+    json: bool,
+) -> Result<()> {
+    let (file_id, _) = parse_download_url(url)?;
+
+    let response = client
+        .head(format!("{}/api/files/{}", server, file_id))
+        .send()
+        .await
+        .context("Failed to send existence check")?;
+
+    let reason = match response.status() {
+        status if status.is_success() => None,
+        reqwest::StatusCode::NOT_FOUND => Some("File not found".to_string()),
+        reqwest::StatusCode::GONE => {
+            Some("File has expired or reached its download limit".to_string())
+        }
+        status => Some(format!("Server returned {}", status)),
+    };
+
+    if json {
+        let json_output = serde_json::json!({
+            "file_id": file_id,
+            "valid": reason.is_none(),
+            "reason": reason,
+        });
+        println!("{}", serde_json::to_string_pretty(&json_output)?);
+    } else if let Some(ref reason) = reason {
+        println!("❌ Link is not valid: {}", reason);
+    } else {
+        println!("✅ Link is valid");
+    }
+
+    if reason.is_some() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+async fn verify_link(client: &reqwest::Client, server: &str, url: &str, json: bool) -> Result<()> {
+    let (file_id, encryption_key) = parse_download_url(url)?;
+
+    let info_url = format!("{}/api/files/{}/info", server, file_id);
+    let response = client
+        .post(&info_url)
+        .json(&DownloadRequest { encryption_key })
+        .send()
+        .await
+        .context("Failed to send info request")?;
+
+    // The info endpoint never counts as a download, so this is a safe probe
+    let (file_info, reason) = if response.status().is_success() {
+        let file_info: FileInfo = response
+            .json()
+            .await
+            .context("Failed to parse info response")?;
+
+        (Some(file_info), None)
+    } else if response.status() == reqwest::StatusCode::GONE {
+        // Links that stopped working come with a code saying why
+        let error: Option<ErrorResponse> = response.json().await.ok();
+        let reason = match error.as_ref().map(|error| error.code.as_str()) {
+            Some(error_code::FILE_EXPIRED) => "File has expired",
+            Some(error_code::DOWNLOAD_LIMIT_EXCEEDED) => "Download limit reached",
+            _ => "File is no longer available",
+        };
+
+        (None, Some(reason.to_string()))
+    } else {
+        let error = api_error(response, "Server returned an error").await;
+        (None, Some(error.to_string()))
+    };
+
+    if json {
+        let json_output = serde_json::json!({
+            "file_id": file_id,
+            "valid": reason.is_none(),
+            "reason": reason,
+            "filename": file_info.as_ref().map(|info| &info.filename),
+            "expires_at": file_info.as_ref().and_then(|info| info.expires_at),
+            "download_count": file_info.as_ref().map(|info| info.download_count),
+            "max_downloads": file_info.as_ref().and_then(|info| info.max_downloads),
+            "burn_after_first_download_minutes": file_info
+                .as_ref()
+                .and_then(|info| info.burn_after_first_download_minutes),
+            "expire_after_inactivity_hours": file_info
+                .as_ref()
+                .and_then(|info| info.expire_after_inactivity_hours),
+            "first_downloaded_at": file_info.as_ref().and_then(|info| info.first_downloaded_at),
+        });
+        println!("{}", serde_json::to_string_pretty(&json_output)?);
+    } else if let Some(ref reason) = reason {
+        println!("❌ Link is not valid: {}", reason);
+    } else if let Some(ref file_info) = file_info {
+        println!("✅ Link is valid: {}", file_info.filename);
+        if let Some(expires_at) = file_info.expires_at {
+            println!("⏰ Expires: {}", expires_at);
+        }
+        if let Some(max_downloads) = file_info.max_downloads {
+            println!(
+                "📊 Downloads: {}/{}",
+                file_info.download_count, max_downloads
+            );
+        }
+        if let Some(minutes) = file_info.burn_after_first_download_minutes {
+            match file_info.first_downloaded_at {
+                Some(first_downloaded_at) => println!(
+                    "🔥 Burning: first downloaded {}, gone {} minutes later",
+                    first_downloaded_at, minutes
+                ),
+                None => println!("🔥 Burns {} minutes after its first download", minutes),
+            }
+        }
+        if let Some(hours) = file_info.expire_after_inactivity_hours {
+            println!("🔄 Each download keeps it for another {} hours", hours);
+        }
+    }
+
+    if reason.is_some() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+// Portable record of an upload. The key itself is left out so the manifest can be
+// archived anywhere; the shareable URL is `download_url#<key>`, and the fingerprint
+// tells whether a key at hand is the right one
+#[derive(Serialize)]
+struct Manifest {
+    manifest_version: u32,
+    file_id: uuid::Uuid,
+    filename: String,
+    download_url: String,
+    slug: Option<String>,
+    is_encrypted: bool,
+    passphrase_protected: bool,
+    // SHA-256 of the raw key bytes (lowercase hex)
+    key_fingerprint: Option<String>,
+    original_size: i64,
+    encrypted_size: i64,
+    mime_type: Option<String>,
+    // What `download` checks the received file against, as `sha256sum` prints it
+    content_sha256: Option<String>,
+    signature: Option<String>,
+    public_key: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    max_downloads: Option<i32>,
+    burn_after_first_download_minutes: Option<i32>,
+}
+
+const MANIFEST_VERSION: u32 = 1;
+
+impl Manifest {
+    fn new(
+        file_info: FileInfo,
+        download_url: &str,
+        slug: Option<String>,
+        encryption_key: Option<&str>,
+        public_key: Option<String>,
+        passphrase_protected: bool,
+    ) -> Result<Self> {
+        let key_fingerprint = encryption_key
+            .map(|encryption_key| {
+                let key = EncryptionKey::from_url_encoded(encryption_key)
+                    .context("Invalid encryption key")?;
+                Ok::<_, anyhow::Error>(format!("{:x}", Sha256::digest(key.as_bytes())))
+            })
+            .transpose()?;
+
+        Ok(Manifest {
+            manifest_version: MANIFEST_VERSION,
+            file_id: file_info.file_id,
+            filename: file_info.filename,
+            download_url: bare_download_url(download_url).to_string(),
+            slug,
+            is_encrypted: file_info.is_encrypted,
+            passphrase_protected,
+            key_fingerprint,
+            original_size: file_info.original_size,
+            encrypted_size: file_info.encrypted_size,
+            mime_type: file_info.mime_type,
+            content_sha256: file_info.content_sha256,
+            signature: file_info.signature,
+            public_key,
+            created_at: file_info.created_at,
+            expires_at: file_info.expires_at,
+            max_downloads: file_info.max_downloads,
+            burn_after_first_download_minutes: file_info.burn_after_first_download_minutes,
+        })
+    }
+}
+
+// Manifest of a file just uploaded, from its details as the server stored them
+async fn upload_manifest(
+    client: &reqwest::Client,
+    server: &str,
+    file_path: &Path,
+    upload_response: &UploadResponse,
+    public_key: Option<String>,
+    passphrase_protected: bool,
+) -> Result<Manifest> {
+    let file_info = fetch_file_info(
+        client,
+        server,
+        &upload_response.file_id.to_string(),
+        upload_response.encryption_key.clone(),
+        "Failed to fetch details for the manifest",
+    )
+    .await?;
+
+    let mut manifest = Manifest::new(
+        file_info,
+        &upload_response.download_url,
+        upload_response.slug.clone(),
+        upload_response.encryption_key.as_deref(),
+        public_key,
+        passphrase_protected,
+    )?;
+
+    // The server only records a hash for chunked uploads; the local file gives the same one
+    if manifest.content_sha256.is_none() {
+        let mut hasher = Sha256::new();
+        hash_file(file_path, &mut hasher).await?;
+        manifest.content_sha256 = Some(format!("{:x}", hasher.finalize()));
+    }
+
+    Ok(manifest)
+}
+
+async fn write_link_manifest(
+    client: &reqwest::Client,
+    server: &str,
+    url: &str,
+    output: Option<&Path>,
+) -> Result<()> {
+    let (file_id, encryption_key) = parse_download_url(url)?;
+
+    let (encryption_key, passphrase_protected) = match encryption_key {
+        Some(encryption_key) => (Some(encryption_key), false),
+        None => match unwrap_file_key(client, server, &file_id).await? {
+            Some(encryption_key) => (Some(encryption_key), true),
+            None => (None, false),
+        },
+    };
+
+    let file_info = fetch_file_info(
+        client,
+        server,
+        &file_id,
+        encryption_key.clone(),
+        "Failed to fetch file details",
+    )
+    .await?;
+
+    // Links by slug keep it, so the manifest rebuilds the same link
+    let slug = uuid::Uuid::parse_str(&file_id).is_err().then_some(file_id);
+
+    let manifest = Manifest::new(
+        file_info,
+        url,
+        slug,
+        encryption_key.as_deref(),
+        None,
+        passphrase_protected,
+    )?;
+    write_manifest(&manifest, output).await
+}
+
+async fn write_manifest(manifest: &Manifest, output: Option<&Path>) -> Result<()> {
+    let json = serde_json::to_string_pretty(manifest)?;
+
+    match output {
+        Some(output) => {
+            async_fs::write(output, format!("{}\n", json))
+                .await
+                .with_context(|| format!("Failed to write manifest {}", output.display()))?;
+            println!("🗂️  Manifest written to {}", output.display());
+        }
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
+
+// Measurements of one benchmark round
+struct BenchRound {
+    upload_mb_per_sec: f64,
+    download_mb_per_sec: f64,
+    latency_ms: f64,
+}
+
+// Upload and download throwaway files through the regular upload and download
+// paths, then delete them with their owner tokens, whether the rounds succeeded or not
+async fn run_bench(
+    client: &reqwest::Client,
+    server: &str,
+    size_mb: usize,
+    iterations: usize,
+    config: &UploadConfig,
+) -> Result<()> {
+    if size_mb == 0 || iterations == 0 {
+        return Err(anyhow::anyhow!(
+            "--size-mb and --iterations must be at least 1"
+        ));
+    }
+
+    let bench_dir = std::env::temp_dir().join(format!("kusatsu-bench-{}", config.request_id));
+    async_fs::create_dir_all(&bench_dir)
+        .await
+        .with_context(|| format!("Failed to create {}", bench_dir.display()))?;
+
+    let mut created = Vec::new();
+    let result = bench_rounds(
+        client,
+        server,
+        size_mb * 1024 * 1024,
+        iterations,
+        config,
+        &bench_dir,
+        &mut created,
+    )
+    .await;
+
+    println!("🧹 Deleting {} test file(s)...", created.len());
+    for (file_id, owner_token) in &created {
+        if let Err(e) = delete_file(client, server, file_id, owner_token).await {
+            eprintln!("⚠️  Failed to delete test file {}: {:#}", file_id, e);
+        }
+    }
+    let _ = async_fs::remove_dir_all(&bench_dir).await;
+
+    let rounds = result?;
+    println!();
+    println!(
+        "📊 {} round(s) of {} MB against {}",
+        rounds.len(),
+        size_mb,
+        server
+    );
+    print_bench_stats(
+        "Upload",
+        "MB/s",
+        rounds.iter().map(|round| round.upload_mb_per_sec),
+    );
+    print_bench_stats(
+        "Download",
+        "MB/s",
+        rounds.iter().map(|round| round.download_mb_per_sec),
+    );
+    print_bench_stats("Latency", "ms", rounds.iter().map(|round| round.latency_ms));
+    Ok(())
+}
+
+// Whether files of this size go up in one request, as `upload` decides
+async fn bench_uses_single_upload(client: &reqwest::Client, server: &str, size: usize) -> bool {
+    let single_upload_max = fetch_server_config(client, server)
+        .await
+        .map_or(MAX_SINGLE_UPLOAD_SIZE, |server_config| {
+            server_config.single_upload_max
+        });
+    size as i64 <= single_upload_max
+}
+
+async fn bench_rounds(
+    client: &reqwest::Client,
+    server: &str,
+    size: usize,
+    iterations: usize,
+    config: &UploadConfig,
+    bench_dir: &Path,
+    created: &mut Vec<(String, String)>,
+) -> Result<Vec<BenchRound>> {
+    use rand::RngCore;
+
+    // Random data, so compression anywhere along the way can't flatter the numbers
+    let input = bench_dir.join("kusatsu-bench.bin");
+    let mut data = vec![0u8; size];
+    rand::thread_rng().fill_bytes(&mut data);
+    async_fs::write(&input, &data)
+        .await
+        .with_context(|| format!("Failed to write {}", input.display()))?;
+    drop(data);
+
+    let single_upload = bench_uses_single_upload(client, server, size).await;
+    println!(
+        "📦 Benchmarking with {} bytes per file, sent as {}",
+        size,
+        if single_upload {
+            "single uploads"
+        } else {
+            "chunked uploads"
+        }
+    );
+
+    let megabytes = size as f64 / (1024.0 * 1024.0);
+    let mut rounds = Vec::with_capacity(iterations);
+
+    for iteration in 1..=iterations {
+        println!("🏁 Round {}/{}", iteration, iterations);
+
+        let started = std::time::Instant::now();
+        let upload_response = if single_upload {
+            perform_single_upload(client, server, &input, "kusatsu-bench.bin", None, config).await?
+        } else {
+            perform_chunked_upload(
+                client,
+                server,
+                &input,
+                "kusatsu-bench.bin",
+                size,
+                None,
+                config,
+            )
+            .await?
+        };
+        let upload_secs = started.elapsed().as_secs_f64();
+
+        let file_id = upload_response.file_id.to_string();
+        if let Some(owner_token) = &upload_response.owner_token {
+            created.push((file_id.clone(), owner_token.clone()));
+        } else {
+            eprintln!("⚠️  No owner token for {}, it can't be deleted", file_id);
+        }
+
+        // An existence check is the cheapest request that reaches the database
+        let started = std::time::Instant::now();
+        let response = client
+            .head(format!("{}/api/files/{}", server, file_id))
+            .send()
+            .await
+            .context("Failed to send existence check")?;
+        if !response.status().is_success() {
+            return Err(api_error(response, "Existence check failed").await);
+        }
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let url = match &upload_response.encryption_key {
+            Some(encryption_key) => format!(
+                "{}#{}",
+                bare_download_url(&upload_response.download_url),
+                encryption_key
+            ),
+            None => upload_response.download_url.clone(),
+        };
+        let output = bench_dir.join(format!("download-{}.bin", iteration));
+        let started = std::time::Instant::now();
+        download_file(client, server, &url, Some(output.clone()), None, true).await?;
+        let download_secs = started.elapsed().as_secs_f64();
+        let _ = async_fs::remove_file(&output).await;
+
+        rounds.push(BenchRound {
+            upload_mb_per_sec: megabytes / upload_secs,
+            download_mb_per_sec: megabytes / download_secs,
+            latency_ms,
+        });
+    }
+
+    Ok(rounds)
+}
+
+// Print min, average, max and 95th percentile (nearest rank) of the samples
+fn print_bench_stats(label: &str, unit: &str, samples: impl Iterator<Item = f64>) {
+    let mut samples: Vec<f64> = samples.collect();
+    if samples.is_empty() {
+        return;
+    }
+    samples.sort_by(f64::total_cmp);
+
+    let count = samples.len();
+    let average = samples.iter().sum::<f64>() / count as f64;
+    let p95 = samples[(count * 95).div_ceil(100) - 1];
+
+    println!(
+        "   {:<9} min {:>9.2}  avg {:>9.2}  max {:>9.2}  p95 {:>9.2} {}",
+        label,
+        samples[0],
+        average,
+        samples[count - 1],
+        p95,
+        unit
+    );
+}
+
+async fn delete_file(
+    client: &reqwest::Client,
+    server: &str,
+    file_id: &str,
+    owner_token: &str,
+) -> Result<()> {
+    let response = client
+        .post(format!("{}/api/files/{}/delete", server, file_id))
+        .json(&DeleteFileRequest {
+            owner_token: owner_token.to_string(),
+        })
+        .send()
+        .await
+        .context("Failed to send delete request")?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, "Delete failed").await);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! Throughput of the encryption hot path
+//!
+//! Run with `cargo bench -p kusatsu-encrypt`; compare suites with e.g.
+//! `cargo bench -p kusatsu-encrypt -- encrypt/` and read the HTML report in
+//! `target/criterion`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use kusatsu_encrypt::{CipherSuite, Encryption, EncryptionKey, KeySize};
+
+const KIB: usize = 1024;
+const MIB: usize = 1024 * KIB;
+
+const PAYLOAD_SIZES: [usize; 5] = [KIB, 64 * KIB, MIB, 16 * MIB, 64 * MIB];
+
+// Every cipher a key can be used with, labelled for the report
+fn cipher_suites() -> Vec<(&'static str, CipherSuite, KeySize)> {
+    vec![
+        ("aes-256-gcm", CipherSuite::Aes256Gcm, KeySize::Bits256),
+        ("aes-128-gcm", CipherSuite::Aes256Gcm, KeySize::Bits128),
+        (
+            "chacha20-poly1305",
+            CipherSuite::ChaCha20Poly1305,
+            KeySize::Bits256,
+        ),
+    ]
+}
+
+fn size_label(size: usize) -> String {
+    if size >= MIB {
+        format!("{}MiB", size / MIB)
+    } else {
+        format!("{}KiB", size / KIB)
+    }
+}
+
+// Large payloads take long per iteration, keep the sample count reasonable
+fn sample_size(size: usize) -> usize {
+    if size >= 16 * MIB {
+        10
+    } else {
+        100
+    }
+}
+
+fn bench_encrypt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encrypt");
+
+    for size in PAYLOAD_SIZES {
+        let data = vec![0x5a; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.sample_size(sample_size(size));
+
+        for (name, suite, key_size) in cipher_suites() {
+            let key = EncryptionKey::generate_with_size(key_size);
+            group.bench_with_input(
+                BenchmarkId::new(name, size_label(size)),
+                &data,
+                |b, data| b.iter(|| Encryption::encrypt_with_suite(data, &key, suite).unwrap()),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_decrypt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decrypt");
+
+    for size in PAYLOAD_SIZES {
+        let data = vec![0x5a; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.sample_size(sample_size(size));
+
+        for (name, suite, key_size) in cipher_suites() {
+            let key = EncryptionKey::generate_with_size(key_size);
+            let encrypted = Encryption::encrypt_with_suite(&data, &key, suite).unwrap();
+            group.bench_with_input(
+                BenchmarkId::new(name, size_label(size)),
+                &encrypted,
+                |b, encrypted| {
+                    b.iter(|| Encryption::decrypt_with_suite(encrypted, &key, suite).unwrap())
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_key_generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("key_generation");
+
+    group.bench_function("128-bit", |b| b.iter(EncryptionKey::generate_128));
+    group.bench_function("256-bit", |b| b.iter(EncryptionKey::generate_256));
+    group.bench_function("256-bit-base64", |b| {
+        b.iter(Encryption::generate_key_string)
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_encrypt, bench_decrypt, bench_key_generation);
+criterion_main!(benches);
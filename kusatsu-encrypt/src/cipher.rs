@@ -0,0 +1,267 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes128Gcm, Aes256Gcm,
+};
+use chacha20poly1305::ChaCha20Poly1305;
+use serde::{Deserialize, Serialize};
+
+use crate::{EncryptionError, KeySize};
+
+/// Key size of the 256-bit ciphers
+pub const KEY_SIZE: usize = 32;
+
+/// Nonce size shared by all supported ciphers (96 bits)
+pub const NONCE_SIZE: usize = 12;
+
+/// Authentication tag size shared by all supported ciphers, appended to each ciphertext
+pub const TAG_SIZE: usize = 16;
+
+/// An AEAD cipher with a 96-bit nonce
+///
+/// Implement this trait to plug an alternative cipher into [`crate::Encryption`].
+/// Implementations reject keys of a length they don't support with
+/// [`EncryptionError::InvalidKeyLength`].
+pub trait Aead256: Send + Sync {
+    /// Encrypt `plaintext`, authenticating `aad` alongside it
+    fn encrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError>;
+
+    /// Decrypt `ciphertext`, verifying it against `aad`
+    fn decrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError>;
+}
+
+fn invalid_key_length(expected: usize, key: &[u8]) -> EncryptionError {
+    EncryptionError::InvalidKeyLength {
+        expected,
+        got: key.len(),
+    }
+}
+
+/// AES-256-GCM
+pub struct Aes256GcmCipher;
+
+impl Aead256 for Aes256GcmCipher {
+    fn encrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        Aes256Gcm::new_from_slice(key)
+            .map_err(|_| invalid_key_length(32, key))?
+            .encrypt(
+                nonce.into(),
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::EncryptionFailed)
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        Aes256Gcm::new_from_slice(key)
+            .map_err(|_| invalid_key_length(32, key))?
+            .decrypt(
+                nonce.into(),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::DecryptionFailed)
+    }
+}
+
+/// AES-128-GCM
+pub struct Aes128GcmCipher;
+
+impl Aead256 for Aes128GcmCipher {
+    fn encrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        Aes128Gcm::new_from_slice(key)
+            .map_err(|_| invalid_key_length(16, key))?
+            .encrypt(
+                nonce.into(),
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::EncryptionFailed)
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        Aes128Gcm::new_from_slice(key)
+            .map_err(|_| invalid_key_length(16, key))?
+            .decrypt(
+                nonce.into(),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::DecryptionFailed)
+    }
+}
+
+/// ChaCha20-Poly1305
+pub struct ChaCha20Poly1305Cipher;
+
+impl Aead256 for ChaCha20Poly1305Cipher {
+    fn encrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        ChaCha20Poly1305::new_from_slice(key)
+            .map_err(|_| invalid_key_length(32, key))?
+            .encrypt(
+                nonce.into(),
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::EncryptionFailed)
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        ChaCha20Poly1305::new_from_slice(key)
+            .map_err(|_| invalid_key_length(32, key))?
+            .decrypt(
+                nonce.into(),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::DecryptionFailed)
+    }
+}
+
+/// The built-in ciphers that [`crate::Encryption`] can dispatch to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CipherSuite {
+    #[default]
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl CipherSuite {
+    /// Get the cipher implementation for this suite with a 256-bit key
+    pub fn cipher(&self) -> &'static dyn Aead256 {
+        match self {
+            CipherSuite::Aes256Gcm => &Aes256GcmCipher,
+            CipherSuite::ChaCha20Poly1305 => &ChaCha20Poly1305Cipher,
+        }
+    }
+
+    /// Get the cipher implementation for this suite and key size
+    ///
+    /// AES-GCM with a 128-bit key selects AES-128-GCM; ChaCha20-Poly1305 only
+    /// supports 256-bit keys.
+    pub fn cipher_for_key_size(
+        &self,
+        key_size: KeySize,
+    ) -> Result<&'static dyn Aead256, EncryptionError> {
+        match (self, key_size) {
+            (_, KeySize::Bits256) => Ok(self.cipher()),
+            (CipherSuite::Aes256Gcm, KeySize::Bits128) => Ok(&Aes128GcmCipher),
+            (CipherSuite::ChaCha20Poly1305, KeySize::Bits128) => {
+                Err(EncryptionError::UnsupportedKeySize)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_aead256<T: Aead256>() {}
+
+    #[test]
+    fn test_implementors_satisfy_trait() {
+        assert_aead256::<Aes128GcmCipher>();
+        assert_aead256::<Aes256GcmCipher>();
+        assert_aead256::<ChaCha20Poly1305Cipher>();
+    }
+
+    #[test]
+    fn test_round_trip_through_trait_object() {
+        let key = [7u8; KEY_SIZE];
+        let nonce = [1u8; NONCE_SIZE];
+        let aad = b"file-id";
+        let plaintext = b"Hello, World!";
+
+        for suite in [CipherSuite::Aes256Gcm, CipherSuite::ChaCha20Poly1305] {
+            let cipher: &dyn Aead256 = suite.cipher();
+
+            let ciphertext = cipher.encrypt(&key, &nonce, aad, plaintext).unwrap();
+            assert_ne!(&ciphertext[..], &plaintext[..]);
+
+            let decrypted = cipher.decrypt(&key, &nonce, aad, &ciphertext).unwrap();
+            assert_eq!(&decrypted[..], &plaintext[..]);
+
+            // Mismatched AAD must fail authentication
+            assert!(cipher.decrypt(&key, &nonce, b"other", &ciphertext).is_err());
+        }
+    }
+
+    #[test]
+    fn test_aes128_rejects_wrong_key_length() {
+        let nonce = [1u8; NONCE_SIZE];
+
+        let ciphertext = Aes128GcmCipher
+            .encrypt(&[7u8; 16], &nonce, &[], b"data")
+            .unwrap();
+        assert_eq!(
+            Aes128GcmCipher
+                .decrypt(&[7u8; 16], &nonce, &[], &ciphertext)
+                .unwrap(),
+            b"data"
+        );
+
+        assert!(Aes128GcmCipher
+            .encrypt(&[7u8; KEY_SIZE], &nonce, &[], b"data")
+            .is_err());
+    }
+}
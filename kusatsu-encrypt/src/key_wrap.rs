@@ -0,0 +1,137 @@
+//! Wrapping a file key with a key-encryption key (KEK) derived from a passphrase
+//!
+//! The file keeps its random key; only the wrapped form is stored, so a link
+//! without the key is useless without the passphrase.
+
+use aes_gcm::aead::OsRng;
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{EncryptedData, Encryption, EncryptionError, EncryptionKey, KeySize};
+
+/// Length of the salt used to derive a KEK
+pub const SALT_LENGTH: usize = 16;
+
+// Argon2id with the parameters recommended by OWASP (19 MiB, 2 passes). They are
+// fixed rather than taken from the crate defaults, which may change and would
+// then derive different keys for existing files.
+const ARGON2_M_COST: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+
+/// A file key encrypted with a KEK
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WrappedKey {
+    pub ciphertext: Vec<u8>,
+    pub nonce: Vec<u8>,
+}
+
+impl WrappedKey {
+    /// Nonce followed by ciphertext, as stored
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.nonce[..], &self.ciphertext[..]].concat()
+    }
+
+    /// Split the stored form back into nonce and ciphertext
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncryptionError> {
+        if bytes.len() < 12 {
+            return Err(EncryptionError::InvalidNonceLength);
+        }
+
+        Ok(Self {
+            nonce: bytes[..12].to_vec(),
+            ciphertext: bytes[12..].to_vec(),
+        })
+    }
+}
+
+/// Generate a random salt for [`EncryptionKey::from_passphrase`]
+pub fn generate_salt() -> [u8; SALT_LENGTH] {
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+impl EncryptionKey {
+    /// Derive a 256-bit KEK from a passphrase with Argon2id
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self, EncryptionError> {
+        let params = Params::new(
+            ARGON2_M_COST,
+            ARGON2_T_COST,
+            ARGON2_P_COST,
+            Some(KeySize::Bits256.bytes()),
+        )
+        .map_err(|_| EncryptionError::KeyDerivationFailed)?;
+
+        let mut key = vec![0u8; KeySize::Bits256.bytes()];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|_| EncryptionError::KeyDerivationFailed)?;
+
+        Ok(Self { key })
+    }
+
+    /// Encrypt this key with `kek`
+    pub fn wrap(&self, kek: &EncryptionKey) -> Result<WrappedKey, EncryptionError> {
+        let encrypted = Encryption::encrypt(self.as_bytes(), kek)?;
+
+        Ok(WrappedKey {
+            ciphertext: encrypted.ciphertext,
+            nonce: encrypted.nonce,
+        })
+    }
+
+    /// Recover a key wrapped with `kek`
+    ///
+    /// Fails with [`EncryptionError::KeyUnwrapFailed`] when `kek` is not the key it
+    /// was wrapped with, e.g. because it came from the wrong passphrase.
+    pub fn unwrap(wrapped: &WrappedKey, kek: &EncryptionKey) -> Result<Self, EncryptionError> {
+        let encrypted = EncryptedData {
+            ciphertext: wrapped.ciphertext.clone(),
+            nonce: wrapped.nonce.clone(),
+            key_size: kek.size(),
+        };
+
+        let key =
+            Encryption::decrypt(&encrypted, kek).map_err(|_| EncryptionError::KeyUnwrapFailed)?;
+        if KeySize::from_len(key.len()).is_none() {
+            return Err(EncryptionError::KeyUnwrapFailed);
+        }
+
+        Ok(Self { key })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_with_passphrase() {
+        let file_key = EncryptionKey::generate();
+        let salt = generate_salt();
+
+        let kek = EncryptionKey::from_passphrase("correct horse battery staple", &salt).unwrap();
+        let wrapped = file_key.wrap(&kek).unwrap();
+        let stored = WrappedKey::from_bytes(&wrapped.to_bytes()).unwrap();
+
+        // The same passphrase and salt give back the file key
+        let kek = EncryptionKey::from_passphrase("correct horse battery staple", &salt).unwrap();
+        let unwrapped = EncryptionKey::unwrap(&stored, &kek).unwrap();
+        assert_eq!(unwrapped.as_bytes(), file_key.as_bytes());
+
+        // A wrong passphrase fails cleanly
+        let wrong_kek = EncryptionKey::from_passphrase("wrong", &salt).unwrap();
+        assert!(matches!(
+            EncryptionKey::unwrap(&stored, &wrong_kek),
+            Err(EncryptionError::KeyUnwrapFailed)
+        ));
+
+        // So does the right passphrase with another salt
+        let other_kek =
+            EncryptionKey::from_passphrase("correct horse battery staple", &generate_salt())
+                .unwrap();
+        assert!(EncryptionKey::unwrap(&stored, &other_kek).is_err());
+    }
+}
@@ -1,52 +1,122 @@
-use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
-    Aes256Gcm, Key, Nonce,
-};
+use aes_gcm::aead::OsRng;
 use base64::{engine::general_purpose, Engine as _};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use zeroize::ZeroizeOnDrop;
 
+pub mod cipher;
+pub mod key_wrap;
+pub mod nonce;
+pub mod padding;
+pub mod pow;
+pub mod signing;
+
+pub use cipher::{Aead256, Aes128GcmCipher, Aes256GcmCipher, ChaCha20Poly1305Cipher, CipherSuite};
+pub use key_wrap::WrappedKey;
+pub use nonce::{NonceSequence, NonceStrategy, RandomNonces};
+pub use padding::Padding;
+
 #[derive(Error, Debug)]
 pub enum EncryptionError {
     #[error("Encryption failed")]
     EncryptionFailed,
     #[error("Decryption failed")]
     DecryptionFailed,
-    #[error("Invalid key format")]
-    InvalidKeyFormat,
+    #[error("Invalid key length: expected {expected} bytes, got {got}")]
+    InvalidKeyLength { expected: usize, got: usize },
     #[error("Invalid nonce length")]
     InvalidNonceLength,
+    #[error("Nonce sequence exhausted, the key must not encrypt more messages")]
+    NonceExhausted,
+    #[error("Key size not supported by this cipher")]
+    UnsupportedKeySize,
+    #[error("Invalid public key")]
+    InvalidPublicKey,
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Key derivation failed")]
+    KeyDerivationFailed,
+    #[error("Failed to unwrap key: wrong passphrase or corrupted key")]
+    KeyUnwrapFailed,
+    #[error("Invalid padding")]
+    InvalidPadding,
     #[error("Base64 decode error: {0}")]
     Base64Error(#[from] base64::DecodeError),
 }
 
+/// Length of an encryption key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeySize {
+    /// 128-bit key (AES-128)
+    Bits128,
+    /// 256-bit key (AES-256, ChaCha20)
+    #[default]
+    Bits256,
+}
+
+impl KeySize {
+    /// Key length in bytes
+    pub fn bytes(&self) -> usize {
+        match self {
+            KeySize::Bits128 => 16,
+            KeySize::Bits256 => 32,
+        }
+    }
+
+    /// Get the key size matching a length in bytes
+    pub fn from_len(len: usize) -> Option<Self> {
+        match len {
+            16 => Some(KeySize::Bits128),
+            32 => Some(KeySize::Bits256),
+            _ => None,
+        }
+    }
+}
+
 /// A secure encryption key that zeroes itself when dropped
 #[derive(Clone, ZeroizeOnDrop, Debug)]
 pub struct EncryptionKey {
-    key: [u8; 32], // 256-bit key for AES-256
+    key: Vec<u8>, // 128 or 256 bits, see KeySize
 }
 
 impl EncryptionKey {
-    /// Generate a new random encryption key
+    /// Generate a new random 256-bit encryption key
     pub fn generate() -> Self {
-        let mut key = [0u8; 32];
+        Self::generate_256()
+    }
+
+    /// Generate a new random 128-bit encryption key (AES-128)
+    pub fn generate_128() -> Self {
+        Self::generate_with_size(KeySize::Bits128)
+    }
+
+    /// Generate a new random 256-bit encryption key (AES-256)
+    pub fn generate_256() -> Self {
+        Self::generate_with_size(KeySize::Bits256)
+    }
+
+    /// Generate a new random encryption key of the given size
+    pub fn generate_with_size(size: KeySize) -> Self {
+        let mut key = vec![0u8; size.bytes()];
         OsRng.fill_bytes(&mut key);
         Self { key }
     }
 
     /// Create an encryption key from a base64-encoded string
+    ///
+    /// Accepts both 128-bit and 256-bit keys.
     pub fn from_base64(encoded: &str) -> Result<Self, EncryptionError> {
         let decoded = general_purpose::URL_SAFE_NO_PAD.decode(encoded)?;
-        if decoded.len() != 32 {
-            println!("Invalid key format: {}", encoded);
-            return Err(EncryptionError::InvalidKeyFormat);
+        if KeySize::from_len(decoded.len()).is_none() {
+            // Report the length only, never the key material itself
+            return Err(EncryptionError::InvalidKeyLength {
+                expected: KeySize::default().bytes(),
+                got: decoded.len(),
+            });
         }
 
-        let mut key = [0u8; 32];
-        key.copy_from_slice(&decoded);
-        Ok(Self { key })
+        Ok(Self { key: decoded })
     }
 
     /// Create an encryption key from a URL-encoded string (alias for from_base64)
@@ -56,11 +126,16 @@ impl EncryptionKey {
 
     /// Convert the encryption key to a base64-encoded string for URL anchors
     pub fn to_base64(&self) -> String {
-        general_purpose::URL_SAFE_NO_PAD.encode(self.key)
+        general_purpose::URL_SAFE_NO_PAD.encode(&self.key)
+    }
+
+    /// Get the size of this key
+    pub fn size(&self) -> KeySize {
+        KeySize::from_len(self.key.len()).expect("key length is validated on construction")
     }
 
     /// Get the raw key bytes (use with caution)
-    pub fn as_bytes(&self) -> &[u8; 32] {
+    pub fn as_bytes(&self) -> &[u8] {
         &self.key
     }
 }
@@ -72,6 +147,9 @@ pub struct EncryptedData {
     pub ciphertext: Vec<u8>,
     /// The nonce used for encryption (stored alongside the data)
     pub nonce: Vec<u8>,
+    /// Size of the key the data was encrypted with, used to pick the cipher
+    #[serde(default)]
+    pub key_size: KeySize,
 }
 
 /// Generic encryption and decryption functions
@@ -80,41 +158,109 @@ pub struct Encryption;
 impl Encryption {
     /// Encrypt arbitrary data with a given key
     pub fn encrypt(data: &[u8], key: &EncryptionKey) -> Result<EncryptedData, EncryptionError> {
-        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_bytes()));
+        Self::encrypt_with_suite(data, key, CipherSuite::default())
+    }
+
+    /// Decrypt data with a given key
+    pub fn decrypt(
+        encrypted_data: &EncryptedData,
+        key: &EncryptionKey,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        Self::decrypt_with_suite(encrypted_data, key, CipherSuite::default())
+    }
+
+    /// Encrypt data padded as [`padding`] describes, so its exact size stays hidden
+    ///
+    /// The result must be decrypted with [`Encryption::decrypt_padded`].
+    pub fn encrypt_padded(
+        data: &[u8],
+        key: &EncryptionKey,
+        padding: Padding,
+    ) -> Result<EncryptedData, EncryptionError> {
+        Self::encrypt(&padding::pad(data, padding)?, key)
+    }
+
+    /// Decrypt data encrypted with [`Encryption::encrypt_padded`], without its padding
+    pub fn decrypt_padded(
+        encrypted_data: &EncryptedData,
+        key: &EncryptionKey,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        padding::unpad(Self::decrypt(encrypted_data, key)?)
+    }
 
-        // Generate random nonce
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    /// Encrypt data with one of the built-in cipher suites
+    pub fn encrypt_with_suite(
+        data: &[u8],
+        key: &EncryptionKey,
+        suite: CipherSuite,
+    ) -> Result<EncryptedData, EncryptionError> {
+        Self::encrypt_with_cipher(data, key, suite.cipher_for_key_size(key.size())?)
+    }
 
-        // Encrypt data
-        let ciphertext = cipher
-            .encrypt(&nonce, data)
-            .map_err(|_| EncryptionError::EncryptionFailed)?;
+    /// Decrypt data with one of the built-in cipher suites
+    pub fn decrypt_with_suite(
+        encrypted_data: &EncryptedData,
+        key: &EncryptionKey,
+        suite: CipherSuite,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        Self::decrypt_with_cipher(
+            encrypted_data,
+            key,
+            suite.cipher_for_key_size(encrypted_data.key_size)?,
+        )
+    }
+
+    /// Encrypt data with any [`Aead256`] implementation, under a random nonce
+    pub fn encrypt_with_cipher(
+        data: &[u8],
+        key: &EncryptionKey,
+        cipher: &dyn Aead256,
+    ) -> Result<EncryptedData, EncryptionError> {
+        Self::encrypt_with_nonces(data, key, cipher, &mut RandomNonces)
+    }
+
+    /// Encrypt one of several messages under `key`, taking its nonce from `nonces`
+    ///
+    /// Use a [`NonceSequence`] when the key encrypts more than a few messages,
+    /// see [`nonce`] for the limits of each strategy.
+    pub fn encrypt_with_nonces(
+        data: &[u8],
+        key: &EncryptionKey,
+        cipher: &dyn Aead256,
+        nonces: &mut dyn NonceStrategy,
+    ) -> Result<EncryptedData, EncryptionError> {
+        let nonce = nonces.next_nonce()?;
+
+        let ciphertext = cipher.encrypt(key.as_bytes(), &nonce, &[], data)?;
 
         Ok(EncryptedData {
             ciphertext,
             nonce: nonce.to_vec(),
+            key_size: key.size(),
         })
     }
 
-    /// Decrypt data with a given key
-    pub fn decrypt(
+    /// Decrypt data with any [`Aead256`] implementation
+    pub fn decrypt_with_cipher(
         encrypted_data: &EncryptedData,
         key: &EncryptionKey,
+        cipher: &dyn Aead256,
     ) -> Result<Vec<u8>, EncryptionError> {
-        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_bytes()));
-
-        // Reconstruct nonce
-        if encrypted_data.nonce.len() != 12 {
-            return Err(EncryptionError::InvalidNonceLength);
+        if encrypted_data.key_size != key.size() {
+            return Err(EncryptionError::InvalidKeyLength {
+                expected: encrypted_data.key_size.bytes(),
+                got: key.size().bytes(),
+            });
         }
-        let nonce = Nonce::from_slice(&encrypted_data.nonce);
 
-        // Decrypt data
-        let plaintext = cipher
-            .decrypt(nonce, encrypted_data.ciphertext.as_ref())
-            .map_err(|_| EncryptionError::DecryptionFailed)?;
+        // Reconstruct nonce
+        let nonce: &[u8; cipher::NONCE_SIZE] = encrypted_data
+            .nonce
+            .as_slice()
+            .try_into()
+            .map_err(|_| EncryptionError::InvalidNonceLength)?;
 
-        Ok(plaintext)
+        cipher.decrypt(key.as_bytes(), nonce, &[], &encrypted_data.ciphertext)
     }
 
     /// Encrypt data with a specific nonce (use with caution - nonces should be unique)
@@ -123,16 +269,14 @@ impl Encryption {
         key: &EncryptionKey,
         nonce: &[u8; 12],
     ) -> Result<EncryptedData, EncryptionError> {
-        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_bytes()));
-        let nonce_obj = Nonce::from_slice(nonce);
-
-        let ciphertext = cipher
-            .encrypt(nonce_obj, data)
-            .map_err(|_| EncryptionError::EncryptionFailed)?;
+        let ciphertext = CipherSuite::default()
+            .cipher_for_key_size(key.size())?
+            .encrypt(key.as_bytes(), nonce, &[], data)?;
 
         Ok(EncryptedData {
             ciphertext,
             nonce: nonce.to_vec(),
+            key_size: key.size(),
         })
     }
 
@@ -169,6 +313,7 @@ impl Encryption {
         let encrypted_data = EncryptedData {
             nonce: combined[..12].to_vec(),
             ciphertext: combined[12..].to_vec(),
+            key_size: key.size(),
         };
 
         let decrypted = Self::decrypt(&encrypted_data, key)?;
@@ -189,6 +334,66 @@ mod tests {
         assert_eq!(key.as_bytes(), restored_key.as_bytes());
     }
 
+    #[test]
+    fn test_key_sizes_round_trip() {
+        for (key, size) in [
+            (EncryptionKey::generate_128(), KeySize::Bits128),
+            (EncryptionKey::generate_256(), KeySize::Bits256),
+        ] {
+            assert_eq!(key.size(), size);
+            assert_eq!(key.as_bytes().len(), size.bytes());
+
+            let restored_key = EncryptionKey::from_base64(&key.to_base64()).unwrap();
+            assert_eq!(restored_key.size(), size);
+            assert_eq!(key.as_bytes(), restored_key.as_bytes());
+
+            let data = b"Hello, World!";
+            let encrypted = Encryption::encrypt(data, &key).unwrap();
+            assert_eq!(encrypted.key_size, size);
+            assert_eq!(
+                Encryption::decrypt(&encrypted, &restored_key).unwrap(),
+                data
+            );
+        }
+
+        // The default stays 256-bit
+        assert_eq!(EncryptionKey::generate().size(), KeySize::Bits256);
+    }
+
+    #[test]
+    fn test_key_size_mismatch_fails() {
+        let key_128 = EncryptionKey::generate_128();
+        let key_256 = EncryptionKey::generate_256();
+
+        let encrypted = Encryption::encrypt(b"Secret data", &key_128).unwrap();
+        assert!(matches!(
+            Encryption::decrypt(&encrypted, &key_256),
+            Err(EncryptionError::InvalidKeyLength {
+                expected: 16,
+                got: 32
+            })
+        ));
+
+        // ChaCha20-Poly1305 only takes 256-bit keys
+        assert!(matches!(
+            Encryption::encrypt_with_suite(b"data", &key_128, CipherSuite::ChaCha20Poly1305),
+            Err(EncryptionError::UnsupportedKeySize)
+        ));
+
+        // Other lengths are rejected
+        let encoded = general_purpose::URL_SAFE_NO_PAD.encode([0u8; 24]);
+        let error = EncryptionKey::from_base64(&encoded).unwrap_err();
+        assert!(matches!(
+            error,
+            EncryptionError::InvalidKeyLength {
+                expected: 32,
+                got: 24
+            }
+        ));
+        // The message carries the lengths, not the key
+        assert!(!error.to_string().contains(&encoded));
+    }
+
     #[test]
     fn test_encryption_decryption() {
         let key = EncryptionKey::generate();
@@ -229,6 +434,48 @@ mod tests {
         assert!(Encryption::decrypt(&encrypted, &key2).is_err());
     }
 
+    #[test]
+    fn test_chacha20poly1305_suite() {
+        let key = EncryptionKey::generate();
+        let data = b"Secret data";
+
+        let encrypted =
+            Encryption::encrypt_with_suite(data, &key, CipherSuite::ChaCha20Poly1305).unwrap();
+        let decrypted =
+            Encryption::decrypt_with_suite(&encrypted, &key, CipherSuite::ChaCha20Poly1305)
+                .unwrap();
+        assert_eq!(&decrypted[..], data);
+
+        // Ciphertext from one suite does not decrypt under another
+        assert!(Encryption::decrypt(&encrypted, &key).is_err());
+    }
+
+    #[test]
+    fn test_frames_with_nonce_sequence() {
+        let key = EncryptionKey::generate();
+        let cipher = CipherSuite::default()
+            .cipher_for_key_size(key.size())
+            .unwrap();
+        let mut nonces = NonceSequence::new();
+
+        let frames: Vec<_> = [&b"first"[..], b"second", b"third"]
+            .iter()
+            .map(|frame| Encryption::encrypt_with_nonces(frame, &key, cipher, &mut nonces).unwrap())
+            .collect();
+
+        // Each frame's nonce follows from the prefix and its index
+        for (index, frame) in frames.iter().enumerate() {
+            assert_eq!(
+                frame.nonce,
+                NonceSequence::nonce_at(nonces.prefix(), index as u64)
+            );
+        }
+        assert_eq!(
+            Encryption::decrypt(&frames[1], &key).unwrap(),
+            b"second".to_vec()
+        );
+    }
+
     #[test]
     fn test_nonce_uniqueness() {
         let key = EncryptionKey::generate();
@@ -243,4 +490,61 @@ mod tests {
         assert_eq!(Encryption::decrypt(&encrypted1, &key).unwrap(), data);
         assert_eq!(Encryption::decrypt(&encrypted2, &key).unwrap(), data);
     }
+
+    #[test]
+    fn test_padded_round_trip() {
+        let key = EncryptionKey::generate();
+
+        for (padding, data_len, padded_len) in [
+            (Padding::None, 0, 8),
+            (Padding::None, 100, 108),
+            (Padding::PowerOfTwo, 0, 8),
+            (Padding::PowerOfTwo, 100, 128),
+            (Padding::PowerOfTwo, 120, 128),
+            (Padding::PowerOfTwo, 121, 256),
+            (Padding::Bucket(4096), 1, 4096),
+            (Padding::Bucket(4096), 4088, 4096),
+            (Padding::Bucket(4096), 4089, 8192),
+        ] {
+            // Trailing zeros in the data must survive the unpadding
+            let mut data = vec![0xAB; data_len];
+            if let Some(last) = data.last_mut() {
+                *last = 0;
+            }
+
+            let encrypted = Encryption::encrypt_padded(&data, &key, padding).unwrap();
+            assert_eq!(
+                encrypted.ciphertext.len(),
+                padded_len + 16,
+                "{:?} of {} bytes",
+                padding,
+                data_len
+            );
+            assert_eq!(Encryption::decrypt_padded(&encrypted, &key).unwrap(), data);
+        }
+
+        // Messages of similar sizes can't be told apart
+        let short = Encryption::encrypt_padded(b"yes", &key, Padding::Bucket(256)).unwrap();
+        let long = Encryption::encrypt_padded(&[1; 200], &key, Padding::Bucket(256)).unwrap();
+        assert_eq!(short.ciphertext.len(), long.ciphertext.len());
+    }
+
+    #[test]
+    fn test_invalid_padding_is_rejected() {
+        let key = EncryptionKey::generate();
+
+        assert!(matches!(
+            Encryption::encrypt_padded(b"data", &key, Padding::Bucket(0)),
+            Err(EncryptionError::InvalidPadding)
+        ));
+
+        // Unpadded data, or a header claiming more data than there is
+        for plaintext in [&b"short"[..], &[0, 0, 0, 0, 0, 0, 0, 9, 1, 2, 3][..]] {
+            let encrypted = Encryption::encrypt(plaintext, &key).unwrap();
+            assert!(matches!(
+                Encryption::decrypt_padded(&encrypted, &key),
+                Err(EncryptionError::InvalidPadding)
+            ));
+        }
+    }
 }
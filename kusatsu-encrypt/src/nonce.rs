@@ -0,0 +1,132 @@
+//! Nonce management for encrypting several messages under one key
+//!
+//! AES-GCM and ChaCha20-Poly1305 lose their guarantees as soon as a nonce is
+//! reused with the same key. Two strategies are provided:
+//!
+//! - [`RandomNonces`] draws each 96-bit nonce at random. Collisions follow the
+//!   birthday bound, so a key must not encrypt more than 2^32 messages this way
+//!   (NIST SP 800-38D). Every file gets a fresh key today, far below that.
+//! - [`NonceSequence`] counts up from a random prefix and fails with
+//!   [`EncryptionError::NonceExhausted`] instead of wrapping around, allowing up
+//!   to 2^64 messages. Meant for many frames under one key, such as the chunks
+//!   of one stream; use a single sequence per key, since two sequences could
+//!   draw the same prefix.
+
+use aes_gcm::aead::OsRng;
+use rand::RngCore;
+
+use crate::{cipher::NONCE_SIZE, EncryptionError};
+
+/// Bytes of a [`NonceSequence`] nonce taken by its fixed prefix
+pub const NONCE_PREFIX_SIZE: usize = NONCE_SIZE - 8;
+
+/// Supplies a nonce for each message encrypted under a key
+pub trait NonceStrategy {
+    /// The nonce for the next message, never one returned before
+    fn next_nonce(&mut self) -> Result<[u8; NONCE_SIZE], EncryptionError>;
+}
+
+/// Random nonces, for keys that encrypt few messages (at most 2^32)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomNonces;
+
+impl NonceStrategy for RandomNonces {
+    fn next_nonce(&mut self) -> Result<[u8; NONCE_SIZE], EncryptionError> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+        Ok(nonce)
+    }
+}
+
+/// Counter-based nonces: a 32-bit prefix followed by a 64-bit big-endian counter
+///
+/// The nonce of message `n` is [`NonceSequence::nonce_at`]`(prefix, n)`, so a
+/// reader can decrypt any frame from the prefix and the frame's index.
+#[derive(Clone, Debug)]
+pub struct NonceSequence {
+    prefix: [u8; NONCE_PREFIX_SIZE],
+    // None once the last counter value was handed out
+    next: Option<u64>,
+}
+
+impl NonceSequence {
+    /// Start a sequence with a random prefix
+    pub fn new() -> Self {
+        let mut prefix = [0u8; NONCE_PREFIX_SIZE];
+        OsRng.fill_bytes(&mut prefix);
+        Self::with_prefix(prefix)
+    }
+
+    /// Start a sequence with a given prefix, at counter 0
+    pub fn with_prefix(prefix: [u8; NONCE_PREFIX_SIZE]) -> Self {
+        Self {
+            prefix,
+            next: Some(0),
+        }
+    }
+
+    /// The prefix to store alongside the frames, for decryption
+    pub fn prefix(&self) -> [u8; NONCE_PREFIX_SIZE] {
+        self.prefix
+    }
+
+    /// Nonce of message `counter` in the sequence with `prefix`
+    pub fn nonce_at(prefix: [u8; NONCE_PREFIX_SIZE], counter: u64) -> [u8; NONCE_SIZE] {
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce[..NONCE_PREFIX_SIZE].copy_from_slice(&prefix);
+        nonce[NONCE_PREFIX_SIZE..].copy_from_slice(&counter.to_be_bytes());
+        nonce
+    }
+}
+
+impl Default for NonceSequence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NonceStrategy for NonceSequence {
+    fn next_nonce(&mut self) -> Result<[u8; NONCE_SIZE], EncryptionError> {
+        let counter = self.next.ok_or(EncryptionError::NonceExhausted)?;
+        self.next = counter.checked_add(1);
+        Ok(Self::nonce_at(self.prefix, counter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_counts_up() {
+        let mut sequence = NonceSequence::with_prefix([1, 2, 3, 4]);
+
+        let first = sequence.next_nonce().unwrap();
+        let second = sequence.next_nonce().unwrap();
+        assert_eq!(first, [1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(second, [1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(second, NonceSequence::nonce_at(sequence.prefix(), 1));
+    }
+
+    #[test]
+    fn test_sequence_errors_at_overflow() {
+        let mut sequence = NonceSequence {
+            prefix: [0; NONCE_PREFIX_SIZE],
+            next: Some(u64::MAX - 1),
+        };
+
+        assert!(sequence.next_nonce().is_ok());
+        assert_eq!(
+            sequence.next_nonce().unwrap(),
+            NonceSequence::nonce_at([0; NONCE_PREFIX_SIZE], u64::MAX)
+        );
+
+        // The counter never wraps back to a nonce already used
+        for _ in 0..2 {
+            assert!(matches!(
+                sequence.next_nonce(),
+                Err(EncryptionError::NonceExhausted)
+            ));
+        }
+    }
+}
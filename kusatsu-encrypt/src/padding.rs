@@ -0,0 +1,73 @@
+//! Padding of plaintext before encryption, so ciphertext sizes don't reveal it
+//!
+//! The ciphertext of a message is exactly as long as its plaintext plus the
+//! tag, so the size of a file leaks through even when its contents don't.
+//! Padded messages start with a header holding the true length as a 64-bit
+//! big-endian integer, followed by the data and zeros up to the padded size.
+//! The header is encrypted and authenticated along with the data, so the true
+//! length can't be read or altered without the key.
+
+use serde::{Deserialize, Serialize};
+
+use crate::EncryptionError;
+
+/// Bytes of the header in front of padded data
+pub const HEADER_SIZE: usize = 8;
+
+/// Size a message is padded up to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Padding {
+    /// Only the header is added
+    #[default]
+    None,
+    /// The next power of two; hides all but the order of magnitude, at the
+    /// cost of up to doubling the size
+    PowerOfTwo,
+    /// The next multiple of this many bytes
+    Bucket(u64),
+}
+
+impl Padding {
+    /// Size of `len` bytes of data once padded, header included
+    pub fn padded_len(&self, len: usize) -> Result<usize, EncryptionError> {
+        let len = len
+            .checked_add(HEADER_SIZE)
+            .ok_or(EncryptionError::InvalidPadding)?;
+
+        match *self {
+            Padding::None => Some(len),
+            Padding::PowerOfTwo => len.checked_next_power_of_two(),
+            Padding::Bucket(0) => None,
+            Padding::Bucket(bucket) => usize::try_from(bucket)
+                .ok()
+                .and_then(|bucket| len.div_ceil(bucket).checked_mul(bucket)),
+        }
+        .ok_or(EncryptionError::InvalidPadding)
+    }
+}
+
+/// Prefix `data` with its length and append zeros up to the padded size
+pub fn pad(data: &[u8], padding: Padding) -> Result<Vec<u8>, EncryptionError> {
+    let padded_len = padding.padded_len(data.len())?;
+    let mut padded = Vec::with_capacity(padded_len);
+    padded.extend_from_slice(&(data.len() as u64).to_be_bytes());
+    padded.extend_from_slice(data);
+    padded.resize(padded_len, 0);
+    Ok(padded)
+}
+
+/// Recover the data from padded plaintext, checking its header
+pub fn unpad(mut padded: Vec<u8>) -> Result<Vec<u8>, EncryptionError> {
+    let header: [u8; HEADER_SIZE] = padded
+        .get(..HEADER_SIZE)
+        .and_then(|header| header.try_into().ok())
+        .ok_or(EncryptionError::InvalidPadding)?;
+    let len = usize::try_from(u64::from_be_bytes(header))
+        .ok()
+        .filter(|len| *len <= padded.len() - HEADER_SIZE)
+        .ok_or(EncryptionError::InvalidPadding)?;
+
+    padded.truncate(HEADER_SIZE + len);
+    padded.drain(..HEADER_SIZE);
+    Ok(padded)
+}
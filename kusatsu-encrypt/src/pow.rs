@@ -0,0 +1,69 @@
+//! Proof of work for uploads, making bulk uploads to an open instance costly
+//!
+//! The server hands out a random challenge and a difficulty. The client finds a
+//! nonce such that the SHA-256 of `"{challenge}:{nonce}"` (the nonce in decimal)
+//! starts with at least `difficulty` zero bits, which takes about
+//! 2^difficulty hashes; checking it takes one.
+
+use sha2::{Digest, Sha256};
+
+/// Hardest difficulty a server may ask for, four billion hashes on average
+pub const MAX_DIFFICULTY: u8 = 32;
+
+/// Whether `nonce` solves `challenge` at `difficulty`
+pub fn verify(challenge: &str, nonce: u64, difficulty: u8) -> bool {
+    leading_zero_bits(&hash(challenge, nonce)) >= u32::from(difficulty)
+}
+
+/// Find the smallest nonce solving `challenge` at `difficulty`, which should not
+/// exceed [`MAX_DIFFICULTY`]
+pub fn solve(challenge: &str, difficulty: u8) -> u64 {
+    let mut nonce = 0;
+    while !verify(challenge, nonce, difficulty) {
+        nonce += 1;
+    }
+    nonce
+}
+
+fn hash(challenge: &str, nonce: u64) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(challenge.as_bytes())
+        .chain_update(b":")
+        .chain_update(nonce.to_string().as_bytes())
+        .finalize()
+        .into()
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solutions_meet_the_difficulty() {
+        let nonce = solve("challenge", 12);
+        assert!(verify("challenge", nonce, 12));
+        assert!(leading_zero_bits(&hash("challenge", nonce)) >= 12);
+
+        // Bound to its challenge, and nothing below it solves it
+        assert!((0..nonce).all(|nonce| !verify("challenge", nonce, 12)));
+        assert!(verify("challenge", 0, 0));
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0x80, 0]), 0);
+        assert_eq!(leading_zero_bits(&[0, 0x0f]), 12);
+        assert_eq!(leading_zero_bits(&[0, 0]), 16);
+    }
+}
@@ -0,0 +1,119 @@
+//! Detached ed25519 signatures, letting recipients check who uploaded a file
+//!
+//! This is separate from the AEAD layer: encryption keeps a file confidential,
+//! a signature over its plaintext proves it came from the holder of a signing key.
+
+use aes_gcm::aead::OsRng;
+use base64::{engine::general_purpose, Engine as _};
+use ed25519_dalek::{Signer, Verifier};
+
+pub use ed25519_dalek::{Signature, SigningKey, VerifyingKey, SIGNATURE_LENGTH};
+
+use crate::EncryptionError;
+
+/// Generate a new random signing key
+pub fn generate_signing_key() -> SigningKey {
+    SigningKey::generate(&mut OsRng)
+}
+
+/// Sign `data` with `signing_key`
+pub fn sign(data: &[u8], signing_key: &SigningKey) -> Signature {
+    signing_key.sign(data)
+}
+
+/// Check that `signature` was made over `data` by the owner of `verifying_key`
+pub fn verify(
+    data: &[u8],
+    signature: &Signature,
+    verifying_key: &VerifyingKey,
+) -> Result<(), EncryptionError> {
+    verifying_key
+        .verify(data, signature)
+        .map_err(|_| EncryptionError::InvalidSignature)
+}
+
+/// Encode a signing key as URL-safe base64, like encryption keys
+pub fn signing_key_to_base64(signing_key: &SigningKey) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(signing_key.to_bytes())
+}
+
+/// Decode a signing key from URL-safe base64
+pub fn signing_key_from_base64(encoded: &str) -> Result<SigningKey, EncryptionError> {
+    let bytes = decode_fixed::<32>(general_purpose::URL_SAFE_NO_PAD.decode(encoded.trim())?)?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Encode a public key as URL-safe base64, for sharing with recipients
+pub fn verifying_key_to_base64(verifying_key: &VerifyingKey) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(verifying_key.as_bytes())
+}
+
+/// Decode a public key from URL-safe base64
+pub fn verifying_key_from_base64(encoded: &str) -> Result<VerifyingKey, EncryptionError> {
+    let bytes = decode_fixed::<32>(general_purpose::URL_SAFE_NO_PAD.decode(encoded.trim())?)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| EncryptionError::InvalidPublicKey)
+}
+
+/// Encode a signature as standard base64, as sent to and from the API
+pub fn signature_to_base64(signature: &Signature) -> String {
+    general_purpose::STANDARD.encode(signature.to_bytes())
+}
+
+/// Decode a signature from standard base64
+pub fn signature_from_base64(encoded: &str) -> Result<Signature, EncryptionError> {
+    let bytes = general_purpose::STANDARD.decode(encoded)?;
+    Signature::from_slice(&bytes).map_err(|_| EncryptionError::InvalidSignature)
+}
+
+fn decode_fixed<const N: usize>(bytes: Vec<u8>) -> Result<[u8; N], EncryptionError> {
+    let got = bytes.len();
+    bytes
+        .try_into()
+        .map_err(|_| EncryptionError::InvalidKeyLength { expected: N, got })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signing_key = generate_signing_key();
+        let signature = sign(b"report.pdf contents", &signing_key);
+
+        assert!(verify(
+            b"report.pdf contents",
+            &signature,
+            &signing_key.verifying_key()
+        )
+        .is_ok());
+
+        // Tampered data or another key is rejected
+        assert!(verify(b"tampered", &signature, &signing_key.verifying_key()).is_err());
+        let other_key = generate_signing_key();
+        assert!(verify(
+            b"report.pdf contents",
+            &signature,
+            &other_key.verifying_key()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_base64_round_trips() {
+        let signing_key = generate_signing_key();
+        let decoded = signing_key_from_base64(&signing_key_to_base64(&signing_key)).unwrap();
+        assert_eq!(decoded.to_bytes(), signing_key.to_bytes());
+
+        let verifying_key = signing_key.verifying_key();
+        let decoded = verifying_key_from_base64(&verifying_key_to_base64(&verifying_key)).unwrap();
+        assert_eq!(decoded, verifying_key);
+
+        let signature = sign(b"data", &signing_key);
+        let decoded = signature_from_base64(&signature_to_base64(&signature)).unwrap();
+        assert_eq!(decoded, signature);
+
+        assert!(verifying_key_from_base64("c2hvcnQ").is_err());
+        assert!(signature_from_base64("c2hvcnQ=").is_err());
+    }
+}
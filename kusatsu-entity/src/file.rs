@@ -28,6 +28,10 @@ pub struct Model {
     /// Nonce used for file encryption
     pub nonce: Vec<u8>,
 
+    /// Whether the contents are encrypted; when not, `nonce` is empty
+    #[sea_orm(default_value = false)]
+    pub is_encrypted: bool,
+
     /// Encrypted filename
     pub encrypted_filename: Vec<u8>,
 
@@ -46,6 +50,46 @@ pub struct Model {
 
     /// Maximum number of downloads allowed (optional)
     pub max_downloads: Option<i32>,
+
+    /// SHA-256 (lowercase hex) of the token proving ownership of the file, used
+    /// to manage it after upload
+    pub owner_token: Option<String>,
+
+    /// Human-readable name the file can be downloaded by (optional)
+    #[sea_orm(indexed)]
+    pub slug: Option<String>,
+
+    /// Uploader's detached ed25519 signature of the plaintext (optional)
+    pub signature: Option<Vec<u8>>,
+
+    /// File key wrapped with a passphrase-derived key, as nonce then ciphertext (optional)
+    pub wrapped_key: Option<Vec<u8>>,
+
+    /// Salt of the passphrase derivation for `wrapped_key`
+    pub key_salt: Option<Vec<u8>>,
+
+    /// SHA-256 of the contents, for files stored unencrypted (optional)
+    pub content_sha256: Option<Vec<u8>>,
+
+    /// Stored data of a small file kept in the record; `file_path` is then empty
+    pub inline_data: Option<Vec<u8>>,
+
+    /// Format the client compressed the plaintext with before encrypting it (optional)
+    pub compression: Option<String>,
+
+    /// Minutes the file stays available after its first download (optional)
+    pub burn_after_first_download_minutes: Option<i32>,
+
+    /// When the file was first downloaded
+    pub first_downloaded_at: Option<ChronoDateTimeUtc>,
+
+    /// Hours without downloads after which the file expires; each download renews
+    /// them (optional)
+    pub expire_after_inactivity_hours: Option<i32>,
+
+    /// Listing of the files in a bundle, encrypted with the file key, as nonce then
+    /// ciphertext (optional)
+    pub listing: Option<Vec<u8>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -62,13 +106,50 @@ impl ActiveModelBehavior for ActiveModel {
 }
 
 impl Model {
-    /// Check if the file has expired
+    /// Check if the file has expired, or burned after its first download
     pub fn is_expired(&self) -> bool {
-        if let Some(expires_at) = self.expires_at {
-            chrono::Utc::now() > expires_at
-        } else {
-            false
-        }
+        self.is_expired_at(chrono::Utc::now())
+    }
+
+    /// Check if the file is expired or burned at a given time
+    pub fn is_expired_at(&self, now: ChronoDateTimeUtc) -> bool {
+        self.expires_at.is_some_and(|expires_at| now > expires_at)
+            || self.burns_at().is_some_and(|burns_at| now > burns_at)
+    }
+
+    /// When the file becomes unavailable because of its first download, once it happened
+    pub fn burns_at(&self) -> Option<ChronoDateTimeUtc> {
+        Some(
+            self.first_downloaded_at?
+                + chrono::Duration::minutes(self.burn_after_first_download_minutes?.into()),
+        )
+    }
+
+    /// Expiry once the file is downloaded at `now`: the burn deadline, unless the
+    /// file expires earlier anyway
+    pub fn expiry_after_first_download(&self, now: ChronoDateTimeUtc) -> Option<ChronoDateTimeUtc> {
+        let burns_at =
+            now + chrono::Duration::minutes(self.burn_after_first_download_minutes?.into());
+        Some(
+            self.expires_at
+                .map_or(burns_at, |expires_at| expires_at.min(burns_at)),
+        )
+    }
+
+    /// Expiry once the file is downloaded at `now`, for a file that expires after
+    /// inactivity: the inactivity period from now, unless it already expires later,
+    /// and never more than `max_lifetime` after the upload
+    pub fn expiry_after_download(
+        &self,
+        now: ChronoDateTimeUtc,
+        max_lifetime: chrono::Duration,
+    ) -> Option<ChronoDateTimeUtc> {
+        let renewed = now + chrono::Duration::hours(self.expire_after_inactivity_hours?.into());
+        Some(
+            self.expires_at
+                .map_or(renewed, |expires_at| expires_at.max(renewed))
+                .min(self.created_at + max_lifetime),
+        )
     }
 
     /// Check if the file has reached maximum downloads
@@ -80,6 +161,11 @@ impl Model {
         }
     }
 
+    /// Check if the data is kept in the record rather than in file storage
+    pub fn is_inline(&self) -> bool {
+        self.inline_data.is_some()
+    }
+
     /// Check if the file is accessible (not expired and not over download limit)
     pub fn is_accessible(&self) -> bool {
         !self.is_expired() && !self.is_download_limit_reached()
@@ -41,8 +41,17 @@ pub struct Model {
     /// When the upload session was created
     pub created_at: ChronoDateTimeUtc,
 
-    /// When the upload session expires (1 hour by default)
+    /// When the upload session expires, see `KUSATSU_UPLOAD_SESSION_TTL_HOURS`
     pub expires_at: ChronoDateTimeUtc,
+
+    /// Slug for the final file (optional)
+    pub slug: Option<String>,
+
+    /// Minutes the final file stays available after its first download (optional)
+    pub burn_after_first_download_minutes: Option<i32>,
+
+    /// Hours without downloads after which the final file expires (optional)
+    pub expire_after_inactivity_hours: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -53,7 +62,6 @@ impl ActiveModelBehavior for ActiveModel {
         Self {
             upload_id: Set(Uuid::new_v4()),
             created_at: Set(chrono::Utc::now()),
-            expires_at: Set(chrono::Utc::now() + chrono::Duration::hours(1)),
             ..ActiveModelTrait::default()
         }
     }
@@ -83,4 +91,13 @@ impl Model {
     pub fn next_chunk_number(&self) -> i32 {
         self.uploaded_chunks
     }
+
+    /// Size in bytes of a chunk (the last chunk can be smaller)
+    pub fn chunk_len(&self, chunk_number: i32) -> i64 {
+        if chunk_number == self.total_chunks - 1 {
+            self.total_size - chunk_number as i64 * self.chunk_size as i64
+        } else {
+            self.chunk_size as i64
+        }
+    }
 }
@@ -1,19 +1,39 @@
+use base64::{engine::general_purpose, Engine as _};
+use kusatsu_encrypt::{EncryptionKey, WrappedKey};
 use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
 use crate::components::back::Back;
 use crate::components::errors::Errors;
-use crate::services::api::ApiClient;
+use crate::components::listing::FileListing;
+use crate::components::preview::{is_previewable, FilePreview};
+use crate::services::api::{error_code, ApiClient, FileInfo, WrappedKeyResponse};
+use crate::services::crypto;
 use crate::utils::file_utils;
 
 #[derive(Clone, PartialEq)]
 enum DownloadState {
     Loading,
+    // No key in the URL anchor, ask the recipient to enter it
+    KeyRequired {
+        error: Option<String>,
+    },
+    // The link was shared without the key, which is wrapped with a passphrase
+    PassphraseRequired {
+        wrapped_key: WrappedKeyResponse,
+        error: Option<String>,
+    },
+    // The key in the URL anchor can't be the file's key
+    MalformedLink,
     Ready {
         filename: String,
         size: usize,
         is_encrypted: bool,
     },
+    Expired,
+    LimitReached,
+    NotFound,
     Error(String),
 }
 
@@ -22,79 +42,230 @@ pub struct DownloadProps {
     pub file_id: AttrValue,
 }
 
+// Fetch the file info and move to the matching state
+async fn load_file_info(
+    file_id: String,
+    key: Option<String>,
+    entered_manually: bool,
+    state: UseStateHandle<DownloadState>,
+    file_info: UseStateHandle<Option<FileInfo>>,
+) {
+    // Catch mistyped or truncated keys before a round trip to the server
+    if key
+        .as_deref()
+        .is_some_and(|key| crypto::key_from_anchor(key).is_err())
+    {
+        state.set(if entered_manually {
+            DownloadState::KeyRequired {
+                error: Some(
+                    "This key appears malformed, check that it was copied completely".to_string(),
+                ),
+            }
+        } else {
+            DownloadState::MalformedLink
+        });
+        return;
+    }
+
+    let api_client = ApiClient::new();
+
+    // The server decides whether the link still works, and says why not with an error code
+    match api_client.get_file_info(&file_id, key.as_deref()).await {
+        Ok(info) => {
+            file_info.set(Some(info.clone()));
+            state.set(DownloadState::Ready {
+                filename: info.filename,
+                size: info.original_size as usize,
+                is_encrypted: info.is_encrypted,
+            });
+        }
+        // The file is encrypted and the key was not in the URL: it may be wrapped with
+        // a passphrase, otherwise the recipient has to enter it
+        Err(e) if e.code() == Some(error_code::ENCRYPTION_KEY_REQUIRED) => {
+            state.set(match api_client.get_wrapped_key(&file_id).await {
+                Ok(wrapped_key) => DownloadState::PassphraseRequired {
+                    wrapped_key,
+                    error: None,
+                },
+                Err(_) => DownloadState::KeyRequired { error: None },
+            });
+        }
+        // A manually entered key was rejected, let the user try again
+        Err(e) if e.code() == Some(error_code::INVALID_ENCRYPTION_KEY) => {
+            state.set(if entered_manually {
+                DownloadState::KeyRequired {
+                    error: Some("Invalid encryption key".to_string()),
+                }
+            } else {
+                DownloadState::MalformedLink
+            });
+        }
+        Err(e) if e.code() == Some(error_code::FILE_EXPIRED) => {
+            state.set(DownloadState::Expired);
+        }
+        Err(e) if e.code() == Some(error_code::DOWNLOAD_LIMIT_EXCEEDED) => {
+            state.set(DownloadState::LimitReached);
+        }
+        Err(e) if e.code() == Some(error_code::FILE_NOT_FOUND) => {
+            state.set(DownloadState::NotFound);
+        }
+        Err(e) => {
+            state.set(DownloadState::Error(format!(
+                "Failed to load file info: {}",
+                e
+            )));
+        }
+    }
+}
+
+// Recover the file key from its wrapped form, None when the passphrase is wrong
+fn unwrap_key(wrapped_key: &WrappedKeyResponse, passphrase: &str) -> Option<String> {
+    let wrapped = general_purpose::STANDARD
+        .decode(&wrapped_key.wrapped_key)
+        .ok()
+        .and_then(|bytes| WrappedKey::from_bytes(&bytes).ok())?;
+    let salt = general_purpose::STANDARD
+        .decode(&wrapped_key.key_salt)
+        .ok()?;
+    let kek = EncryptionKey::from_passphrase(passphrase, &salt).ok()?;
+
+    EncryptionKey::unwrap(&wrapped, &kek)
+        .ok()
+        .map(|key| key.to_base64())
+}
+
+// Full-width notice for links that can't be downloaded, with what the recipient can do
+fn unavailable_view(icon: &str, title: &str, message: &str, action: Html) -> Html {
+    html! {
+        <div class="space-y-6">
+            <div class="text-center py-8">
+                <div class="text-5xl mb-4">{icon}</div>
+                <h3 class="text-lg font-semibold text-gray-900 dark:text-gray-100 mb-2">{title}</h3>
+                <p class="text-sm text-gray-600 dark:text-gray-400">{message}</p>
+            </div>
+            {action}
+            <Back />
+        </div>
+    }
+}
+
 #[function_component(Download)]
 pub fn download(props: &DownloadProps) -> Html {
     let file_id = props.file_id.to_string();
     let state = use_state(|| DownloadState::Loading);
     let encryption_key = use_state(|| None::<String>);
-    let file_info = use_state(|| None::<crate::services::api::FileInfo>);
+    let file_info = use_state(|| None::<FileInfo>);
+    let key_input_ref = use_node_ref();
+    let passphrase_input_ref = use_node_ref();
 
     {
         let state = state.clone();
         let file_info = file_info.clone();
         let encryption_key = encryption_key.clone();
+        let file_id = file_id.clone();
 
         use_effect_with((), move |_| {
             let window = web_sys::window().unwrap();
             let location = window.location();
 
-            let key_from_url = if let Ok(hash) = location.hash() {
-                if hash.len() > 1 {
-                    // Remove the # prefix
-                    let key_str = &hash[1..];
-                    Some(key_str.to_string())
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
+            let key_from_url = location
+                .hash()
+                .ok()
+                .and_then(|hash| crypto::anchor_key(&hash).map(str::to_string));
 
             encryption_key.set(key_from_url.clone());
 
-            spawn_local(async move {
-                let api_client = ApiClient::new();
-
-                match api_client
-                    .get_file_info(&file_id, key_from_url.as_deref())
-                    .await
-                {
-                    Ok(info) => {
-                        let max_downloads = info.max_downloads;
-                        let download_count = info.download_count;
-                        let is_expired = info.expires_at.is_some()
-                            && info.expires_at.unwrap() < chrono::Utc::now();
-
-                        file_info.set(Some(info.clone()));
-
-                        if max_downloads.is_some() && max_downloads.unwrap() == download_count {
-                            state.set(DownloadState::Error(format!(
-                                "The maximum download limit of {} has been reached",
-                                max_downloads.unwrap()
-                            )));
-                        } else if is_expired {
-                            state.set(DownloadState::Error("The file has expired".to_string()));
-                        } else {
-                            state.set(DownloadState::Ready {
-                                filename: info.filename,
-                                size: info.original_size as usize,
-                                is_encrypted: info.is_encrypted,
-                            });
-                        }
-                    }
-                    Err(e) => {
-                        state.set(DownloadState::Error(format!(
-                            "Failed to load file info: {}",
-                            e
-                        )));
-                    }
-                }
-            });
+            spawn_local(load_file_info(
+                file_id,
+                key_from_url,
+                false,
+                state,
+                file_info,
+            ));
 
             || ()
         });
     }
 
+    let on_key_submit = {
+        let state = state.clone();
+        let file_info = file_info.clone();
+        let encryption_key = encryption_key.clone();
+        let key_input_ref = key_input_ref.clone();
+        let file_id = file_id.clone();
+
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+
+            let Some(input) = key_input_ref.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let key = input.value().trim().to_string();
+            if key.is_empty() {
+                return;
+            }
+
+            encryption_key.set(Some(key.clone()));
+            state.set(DownloadState::Loading);
+            spawn_local(load_file_info(
+                file_id.clone(),
+                Some(key),
+                true,
+                state.clone(),
+                file_info.clone(),
+            ));
+        })
+    };
+
+    let on_passphrase_submit = {
+        let state = state.clone();
+        let file_info = file_info.clone();
+        let encryption_key = encryption_key.clone();
+        let passphrase_input_ref = passphrase_input_ref.clone();
+        let file_id = file_id.clone();
+
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+
+            let DownloadState::PassphraseRequired { wrapped_key, .. } = &*state else {
+                return;
+            };
+            let Some(input) = passphrase_input_ref.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let passphrase = input.value();
+            if passphrase.is_empty() {
+                return;
+            }
+
+            match unwrap_key(wrapped_key, &passphrase) {
+                Some(key) => {
+                    encryption_key.set(Some(key.clone()));
+                    state.set(DownloadState::Loading);
+                    spawn_local(load_file_info(
+                        file_id.clone(),
+                        Some(key),
+                        true,
+                        state.clone(),
+                        file_info.clone(),
+                    ));
+                }
+                None => state.set(DownloadState::PassphraseRequired {
+                    wrapped_key: wrapped_key.clone(),
+                    error: Some("Wrong passphrase".to_string()),
+                }),
+            }
+        })
+    };
+
+    // Lets the recipient type the key when the one in the link is damaged
+    let on_enter_key = {
+        let state = state.clone();
+        Callback::from(move |_: MouseEvent| {
+            state.set(DownloadState::KeyRequired { error: None });
+        })
+    };
+
     let api_client = ApiClient::new();
     let base_url = api_client.base_url;
 
@@ -116,12 +287,142 @@ pub fn download(props: &DownloadProps) -> Html {
                     </div>
                 },
 
-                DownloadState::Ready { filename, size, .. } => html! {
+                DownloadState::KeyRequired { error } => html! {
+                    <div class="space-y-4">
+                        <form class="space-y-4" onsubmit={on_key_submit}>
+                            <p class="text-gray-700 dark:text-gray-300">
+                                {"🔐 This file is encrypted. Enter the encryption key you received to continue."}
+                            </p>
+                            <input
+                                ref={key_input_ref.clone()}
+                                type="password"
+                                autocomplete="off"
+                                placeholder="Encryption key"
+                                class="w-full px-4 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 font-mono"
+                            />
+                            if let Some(error) = error {
+                                <p class="text-sm text-red-600 dark:text-red-400">{error}</p>
+                            }
+                            <button
+                                type="submit"
+                                class="w-full bg-blue-600 text-white py-3 px-6 rounded-lg hover:bg-blue-700 transition-colors font-medium"
+                            >
+                                {"Unlock"}
+                            </button>
+                        </form>
+                        <Back />
+                    </div>
+                },
+
+                DownloadState::PassphraseRequired { error, .. } => html! {
+                    <div class="space-y-4">
+                        <form class="space-y-4" onsubmit={on_passphrase_submit}>
+                            <p class="text-gray-700 dark:text-gray-300">
+                                {"🔒 This link is protected with a passphrase. Enter the passphrase you received to continue."}
+                            </p>
+                            <input
+                                ref={passphrase_input_ref.clone()}
+                                type="password"
+                                autocomplete="off"
+                                placeholder="Passphrase"
+                                class="w-full px-4 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100"
+                            />
+                            if let Some(error) = error {
+                                <p class="text-sm text-red-600 dark:text-red-400">{error}</p>
+                            }
+                            <button
+                                type="submit"
+                                class="w-full bg-blue-600 text-white py-3 px-6 rounded-lg hover:bg-blue-700 transition-colors font-medium"
+                            >
+                                {"Unlock"}
+                            </button>
+                        </form>
+                        <Back />
+                    </div>
+                },
+
+                DownloadState::MalformedLink => unavailable_view(
+                    "🔗",
+                    "This link looks incomplete",
+                    "The key at the end of the link can't be used. Check that the full link was copied, or enter the key you received.",
+                    html! {
+                        <button
+                            class="w-full bg-blue-600 text-white py-3 px-6 rounded-lg hover:bg-blue-700 transition-colors font-medium"
+                            onclick={on_enter_key}
+                        >
+                            {"Enter the key"}
+                        </button>
+                    },
+                ),
+
+                DownloadState::Expired => unavailable_view(
+                    "⏰",
+                    "This file has expired",
+                    "The sender set this link to stop working after a while. Ask them for a new link.",
+                    html! {},
+                ),
+
+                DownloadState::LimitReached => unavailable_view(
+                    "🚫",
+                    "Download limit reached",
+                    "This file can't be downloaded any more times. Ask the sender to share it again.",
+                    html! {},
+                ),
+
+                DownloadState::NotFound => unavailable_view(
+                    "🔍",
+                    "File not found",
+                    "No file matches this link. It may have been deleted, or the link was mistyped.",
+                    html! {},
+                ),
+
+                DownloadState::Ready { filename, size, .. } => {
+                    let info = (*file_info).clone();
+                    let mime_type = info.as_ref().and_then(|info| info.mime_type.clone());
+                    // Previews would bypass the download limit and the burn timer, so skip them
+                    // for those files, and the server doesn't decompress them
+                    let has_listing = info.as_ref().is_some_and(|info| info.has_listing);
+                    let show_preview = info.as_ref().is_some_and(|info| {
+                        info.max_downloads.is_none()
+                            && info.burn_after_first_download_minutes.is_none()
+                            && info.compression.is_none()
+                            && is_previewable(mime_type.as_deref(), info.original_size)
+                    });
+
+                    let now = chrono::Utc::now();
+                    let remaining_downloads = info.as_ref().and_then(|info| {
+                        info.max_downloads
+                            .map(|max| (max - info.download_count).max(0))
+                    });
+                    let expires_at = info.as_ref().and_then(|info| info.expires_at);
+                    let inactivity_hours =
+                        info.as_ref().and_then(|info| info.expire_after_inactivity_hours);
+                    // Once downloaded, the burn time is already part of expires_at
+                    let burn_minutes = info.as_ref().and_then(|info| {
+                        info.burn_after_first_download_minutes
+                            .filter(|_| info.first_downloaded_at.is_none())
+                    });
+
+                    // Warn when the link is about to stop working
+                    let warning = if remaining_downloads == Some(1) {
+                        Some("This is the last allowed download for this file.".to_string())
+                    } else {
+                        expires_at
+                            .filter(|at| *at - now < chrono::Duration::hours(1))
+                            .map(|at| {
+                                format!(
+                                    "This file expires {}.",
+                                    file_utils::format_relative_time(at, now)
+                                )
+                            })
+                    };
+
+                    html! {
                     <div class="space-y-6">
                         // File preview card
                         <div class="bg-gray-50 dark:bg-gray-700 rounded-lg p-6">
                             <div class="flex items-center space-x-4">
-                                <span class="text-4xl">{file_utils::get_file_icon(filename, None)}</span>
+                                <span class="text-4xl">{file_utils::get_file_icon(filename, mime_type.as_deref())}</span>
                                 <div class="flex-1 min-w-0">
                                     <h3 class="text-lg font-semibold text-gray-900 dark:text-gray-100 truncate">{filename}</h3>
                                     <p class="text-sm text-gray-500 dark:text-gray-400 mt-1">
@@ -129,8 +430,72 @@ pub fn download(props: &DownloadProps) -> Html {
                                     </p>
                                 </div>
                             </div>
+
+                            if remaining_downloads.is_some() || expires_at.is_some() || burn_minutes.is_some() {
+                                <div class="mt-4 pt-4 border-t border-gray-200 dark:border-gray-600 space-y-1 text-sm text-gray-600 dark:text-gray-300">
+                                    if let Some(remaining) = remaining_downloads {
+                                        <p>
+                                            {format!(
+                                                "⬇️ {} download{} remaining",
+                                                remaining,
+                                                if remaining == 1 { "" } else { "s" }
+                                            )}
+                                        </p>
+                                    }
+                                    if let Some(expires_at) = expires_at {
+                                        <p>
+                                            {format!(
+                                                "⏰ Expires {} ({})",
+                                                file_utils::format_relative_time(expires_at, now),
+                                                expires_at.format("%Y-%m-%d %H:%M UTC")
+                                            )}
+                                        </p>
+                                    }
+                                    if let Some(hours) = inactivity_hours {
+                                        <p>
+                                            {format!(
+                                                "🔄 Each download keeps it for another {} hour{}",
+                                                hours,
+                                                if hours == 1 { "" } else { "s" }
+                                            )}
+                                        </p>
+                                    }
+                                    if let Some(minutes) = burn_minutes {
+                                        <p>
+                                            {format!(
+                                                "🔥 Burns {} minute{} after the first download",
+                                                minutes,
+                                                if minutes == 1 { "" } else { "s" }
+                                            )}
+                                        </p>
+                                    }
+                                </div>
+                            }
                         </div>
 
+                        if let Some(warning) = warning {
+                            <div class="p-4 bg-yellow-50 dark:bg-yellow-900/50 border border-yellow-200 dark:border-yellow-800 rounded-lg">
+                                <p class="text-yellow-800 dark:text-yellow-300 text-sm">
+                                    {format!("⚠️ {}", warning)}
+                                </p>
+                            </div>
+                        }
+
+                        if has_listing {
+                            <FileListing
+                                file_id={props.file_id.clone()}
+                                encryption_key={(*encryption_key).clone().map(AttrValue::from)}
+                            />
+                        }
+
+                        if show_preview {
+                            <FilePreview
+                                file_id={props.file_id.clone()}
+                                encryption_key={(*encryption_key).clone().map(AttrValue::from)}
+                                mime_type={mime_type.clone().unwrap_or_default()}
+                            />
+                        }
+
                         // Download section
                         <div class="space-y-4">
                             {
@@ -171,7 +536,8 @@ pub fn download(props: &DownloadProps) -> Html {
 
                         <Back />
                     </div>
-                },
+                    }
+                }
 
                 DownloadState::Error(error) => html! {
                     <Errors error={error.clone()} />
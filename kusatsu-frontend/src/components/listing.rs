@@ -0,0 +1,102 @@
+use base64::{engine::general_purpose, Engine as _};
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::components::progress::Spinner;
+use crate::services::{
+    api::{ApiClient, BundleListing},
+    crypto,
+};
+use crate::utils::file_utils;
+
+#[derive(Clone, PartialEq)]
+enum ListingState {
+    Loading,
+    Loaded(BundleListing),
+    Unavailable,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct FileListingProps {
+    pub file_id: AttrValue,
+    pub encryption_key: Option<AttrValue>,
+}
+
+/// Files of a bundle, from the listing its uploader attached
+#[function_component(FileListing)]
+pub fn file_listing(props: &FileListingProps) -> Html {
+    let state = use_state(|| ListingState::Loading);
+
+    {
+        let state = state.clone();
+        let file_id = props.file_id.to_string();
+        let encryption_key = props.encryption_key.as_ref().map(|key| key.to_string());
+
+        use_effect_with(file_id.clone(), move |_| {
+            spawn_local(async move {
+                match load_listing(&file_id, encryption_key.as_deref()).await {
+                    Ok(listing) => state.set(ListingState::Loaded(listing)),
+                    Err(e) => {
+                        log::warn!("Listing unavailable for {}: {}", file_id, e);
+                        state.set(ListingState::Unavailable);
+                    }
+                }
+            });
+
+            || ()
+        });
+    }
+
+    match &*state {
+        ListingState::Loading => html! {
+            <Spinner message={Some(AttrValue::from("Loading file list..."))} />
+        },
+        ListingState::Loaded(listing) => html! {
+            <div class="bg-gray-50 dark:bg-gray-700 rounded-lg p-4">
+                <p class="text-sm font-medium text-gray-700 dark:text-gray-300 mb-2">
+                    {format!(
+                        "📦 {} file{} in this bundle",
+                        listing.entries.len(),
+                        if listing.entries.len() == 1 { "" } else { "s" }
+                    )}
+                </p>
+                <ul class="max-h-64 overflow-y-auto divide-y divide-gray-200 dark:divide-gray-600 text-sm">
+                    { for listing.entries.iter().map(|entry| html! {
+                        <li class="flex justify-between py-1 space-x-4">
+                            <span class="text-gray-800 dark:text-gray-200 font-mono truncate">{&entry.name}</span>
+                            <span class="text-gray-500 dark:text-gray-400 whitespace-nowrap">
+                                {file_utils::format_file_size(entry.size as usize)}
+                            </span>
+                        </li>
+                    }) }
+                </ul>
+            </div>
+        },
+        ListingState::Unavailable => html! {},
+    }
+}
+
+// Fetch the encrypted listing and decrypt it with the key from the link
+async fn load_listing(
+    file_id: &str,
+    encryption_key: Option<&str>,
+) -> Result<BundleListing, String> {
+    let listing = ApiClient::new()
+        .get_listing(file_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let encryption_key = crypto::key_from_anchor(encryption_key.ok_or("Missing encryption key")?)
+        .map_err(|e| format!("Invalid encryption key: {}", e))?;
+    let ciphertext = general_purpose::STANDARD
+        .decode(&listing.ciphertext)
+        .map_err(|e| format!("Invalid listing data: {}", e))?;
+    let nonce = general_purpose::STANDARD
+        .decode(&listing.nonce)
+        .map_err(|e| format!("Invalid nonce: {}", e))?;
+
+    let plaintext = crypto::decrypt(ciphertext, nonce, &encryption_key)
+        .map_err(|e| format!("Failed to decrypt listing: {}", e))?;
+
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Invalid listing: {}", e))
+}
@@ -2,5 +2,8 @@ pub mod back;
 pub mod download;
 pub mod errors;
 pub mod header;
+pub mod listing;
+pub mod preview;
 pub mod progress;
+pub mod toast;
 pub mod upload;
@@ -0,0 +1,149 @@
+use base64::{engine::general_purpose, Engine as _};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Blob, BlobPropertyBag, Url};
+use yew::prelude::*;
+
+use crate::components::progress::Spinner;
+use crate::services::{api::ApiClient, crypto};
+
+// Constants
+pub const MAX_PREVIEW_SIZE: i64 = 1024 * 1024; // 1MB, matches the backend limit
+const MAX_PREVIEW_LINES: usize = 20;
+
+#[derive(Clone, PartialEq)]
+enum PreviewState {
+    Loading,
+    Image(String), // Object URL of the decrypted image
+    Text(String),
+    Unavailable,
+}
+
+/// Check whether a file can be previewed inline.
+/// SVG is excluded since it can carry scripts.
+pub fn is_previewable(mime_type: Option<&str>, size: i64) -> bool {
+    let mime = mime_type.unwrap_or("").to_lowercase();
+
+    let previewable_mime =
+        (mime.starts_with("image/") && mime != "image/svg+xml") || mime.starts_with("text/");
+
+    previewable_mime && size <= MAX_PREVIEW_SIZE
+}
+
+#[derive(Properties, PartialEq)]
+pub struct FilePreviewProps {
+    pub file_id: AttrValue,
+    pub encryption_key: Option<AttrValue>,
+    pub mime_type: AttrValue,
+}
+
+#[function_component(FilePreview)]
+pub fn file_preview(props: &FilePreviewProps) -> Html {
+    let state = use_state(|| PreviewState::Loading);
+
+    {
+        let state = state.clone();
+        let file_id = props.file_id.to_string();
+        let encryption_key = props.encryption_key.as_ref().map(|key| key.to_string());
+        let mime_type = props.mime_type.to_string();
+
+        use_effect_with(file_id.clone(), move |_| {
+            spawn_local(async move {
+                match load_preview(&file_id, encryption_key.as_deref(), &mime_type).await {
+                    Ok(preview) => state.set(preview),
+                    Err(e) => {
+                        log::warn!("Preview unavailable for {}: {}", file_id, e);
+                        state.set(PreviewState::Unavailable);
+                    }
+                }
+            });
+
+            || ()
+        });
+    }
+
+    // Release the object URL once the image preview goes away
+    use_effect_with((*state).clone(), |preview| {
+        let object_url = match preview {
+            PreviewState::Image(url) => Some(url.clone()),
+            _ => None,
+        };
+
+        move || {
+            if let Some(url) = object_url {
+                let _ = Url::revoke_object_url(&url);
+            }
+        }
+    });
+
+    match &*state {
+        PreviewState::Loading => html! {
+            <Spinner message={Some(AttrValue::from("Loading preview..."))} />
+        },
+        PreviewState::Image(url) => html! {
+            <div class="bg-gray-50 dark:bg-gray-700 rounded-lg p-4 flex justify-center">
+                <img src={url.clone()} alt="Preview" class="max-h-64 rounded" />
+            </div>
+        },
+        // Rendered as a text node, never as HTML
+        PreviewState::Text(text) => html! {
+            <pre class="bg-gray-50 dark:bg-gray-700 text-gray-800 dark:text-gray-200 rounded-lg p-4 text-xs font-mono overflow-x-auto max-h-64">
+                {text}
+            </pre>
+        },
+        PreviewState::Unavailable => html! {},
+    }
+}
+
+// Fetch the stored bytes, decrypt them client-side and build the preview
+async fn load_preview(
+    file_id: &str,
+    encryption_key: Option<&str>,
+    mime_type: &str,
+) -> Result<PreviewState, String> {
+    let preview = ApiClient::new()
+        .get_file_preview(file_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let data = general_purpose::STANDARD
+        .decode(&preview.data)
+        .map_err(|e| format!("Invalid preview data: {}", e))?;
+
+    let plaintext = if preview.is_encrypted {
+        let encryption_key =
+            crypto::key_from_anchor(encryption_key.ok_or("Missing encryption key")?)
+                .map_err(|e| format!("Invalid encryption key: {}", e))?;
+
+        let nonce = general_purpose::STANDARD
+            .decode(&preview.nonce)
+            .map_err(|e| format!("Invalid nonce: {}", e))?;
+
+        crypto::decrypt(data, nonce, &encryption_key)
+            .map_err(|e| format!("Failed to decrypt preview: {}", e))?
+    } else {
+        data
+    };
+
+    if mime_type.starts_with("image/") {
+        let array = js_sys::Uint8Array::from(&plaintext[..]);
+        let options = BlobPropertyBag::new();
+        options.set_type(mime_type);
+
+        let blob =
+            Blob::new_with_u8_array_sequence_and_options(&js_sys::Array::of1(&array), &options)
+                .map_err(|e| format!("Failed to create blob: {:?}", e))?;
+
+        let url = Url::create_object_url_with_blob(&blob)
+            .map_err(|e| format!("Failed to create object URL: {:?}", e))?;
+
+        Ok(PreviewState::Image(url))
+    } else {
+        let text = String::from_utf8_lossy(&plaintext)
+            .lines()
+            .take(MAX_PREVIEW_LINES)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(PreviewState::Text(text))
+    }
+}
@@ -0,0 +1,160 @@
+use gloo::timers::callback::Timeout;
+use std::rc::Rc;
+use yew::prelude::*;
+
+const TOAST_DURATION_MS: u32 = 4_000;
+const MAX_VISIBLE_TOASTS: usize = 5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ToastKind {
+    Success,
+    Error,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Toast {
+    pub id: usize,
+    pub kind: ToastKind,
+    pub message: String,
+}
+
+#[derive(Default, PartialEq)]
+pub struct ToastList {
+    toasts: Vec<Toast>,
+    next_id: usize,
+}
+
+pub enum ToastAction {
+    Push(ToastKind, String),
+    Dismiss(usize),
+}
+
+impl Reducible for ToastList {
+    type Action = ToastAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let mut toasts = self.toasts.clone();
+        let mut next_id = self.next_id;
+
+        match action {
+            ToastAction::Push(kind, message) => {
+                toasts.push(Toast {
+                    id: next_id,
+                    kind,
+                    message,
+                });
+                next_id += 1;
+                // Oldest toasts make room for new ones
+                if toasts.len() > MAX_VISIBLE_TOASTS {
+                    toasts.remove(0);
+                }
+            }
+            ToastAction::Dismiss(id) => toasts.retain(|t| t.id != id),
+        }
+
+        Rc::new(ToastList { toasts, next_id })
+    }
+}
+
+// Handle shared through the context; it only dispatches, so holding it
+// doesn't re-render a component each time a toast comes or goes
+#[derive(Clone, PartialEq)]
+pub struct Toaster(UseReducerDispatcher<ToastList>);
+
+impl Toaster {
+    pub fn success(&self, message: impl Into<String>) {
+        self.0
+            .dispatch(ToastAction::Push(ToastKind::Success, message.into()));
+    }
+
+    pub fn error(&self, message: impl Into<String>) {
+        self.0
+            .dispatch(ToastAction::Push(ToastKind::Error, message.into()));
+    }
+}
+
+#[hook]
+pub fn use_toaster() -> Toaster {
+    use_context::<Toaster>().expect("use_toaster called outside of a ToastProvider")
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ToastProviderProps {
+    #[prop_or_default]
+    pub children: Html,
+}
+
+// Provides the Toaster to its children and renders the toast stack
+#[function_component(ToastProvider)]
+pub fn toast_provider(props: &ToastProviderProps) -> Html {
+    let list = use_reducer(ToastList::default);
+    let toaster = Toaster(list.dispatcher());
+
+    html! {
+        <ContextProvider<Toaster> context={toaster}>
+            {props.children.clone()}
+
+            <div class="fixed bottom-4 right-4 z-50 flex flex-col items-end space-y-2" aria-live="polite">
+                { for list.toasts.iter().map(|toast| html! {
+                    <ToastItem
+                        key={toast.id}
+                        toast={toast.clone()}
+                        on_dismiss={
+                            let dispatcher = list.dispatcher();
+                            let id = toast.id;
+                            Callback::from(move |_| dispatcher.dispatch(ToastAction::Dismiss(id)))
+                        }
+                    />
+                }) }
+            </div>
+        </ContextProvider<Toaster>>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct ToastItemProps {
+    toast: Toast,
+    on_dismiss: Callback<()>,
+}
+
+// A single toast, dismissed on click or once its time is up
+#[function_component(ToastItem)]
+fn toast_item(props: &ToastItemProps) -> Html {
+    {
+        let on_dismiss = props.on_dismiss.clone();
+        use_effect_with((), move |_| {
+            let timeout = Timeout::new(TOAST_DURATION_MS, move || on_dismiss.emit(()));
+            // Dropping the timeout cancels it if the toast goes away first
+            move || drop(timeout)
+        });
+    }
+
+    let (colors, icon_path) = match props.toast.kind {
+        ToastKind::Success => (
+            "bg-green-50 dark:bg-green-900/80 border-green-200 dark:border-green-800 text-green-800 dark:text-green-200",
+            "M5 13l4 4L19 7",
+        ),
+        ToastKind::Error => (
+            "bg-red-50 dark:bg-red-900/80 border-red-200 dark:border-red-800 text-red-800 dark:text-red-200",
+            "M12 8v4m0 4h.01M21 12a9 9 0 11-18 0 9 9 0 0118 0z",
+        ),
+    };
+
+    let onclick = {
+        let on_dismiss = props.on_dismiss.clone();
+        Callback::from(move |_: MouseEvent| on_dismiss.emit(()))
+    };
+
+    html! {
+        <div
+            class={classes!("flex", "items-center", "max-w-sm", "p-3", "border", "rounded-lg", "shadow-lg", "text-sm", "cursor-pointer", colors)}
+            role="status"
+            {onclick}
+        >
+            <svg class="w-5 h-5 mr-3 flex-shrink-0" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+                <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d={icon_path} />
+            </svg>
+            <span>{&props.toast.message}</span>
+        </div>
+    }
+}
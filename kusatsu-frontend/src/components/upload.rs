@@ -1,19 +1,85 @@
 use crate::{
-    services::api::{ApiClient, StartUploadRequest},
+    components::toast::{use_toaster, Toaster},
+    services::{
+        api::{
+            filename_placeholder, recommended_chunk_size, ApiClient, ClientEncryption,
+            ServerConfigResponse, StartUploadRequest, UploadOptions, KEY_PLACEHOLDER,
+            MAX_SINGLE_UPLOAD_SIZE,
+        },
+        chunk_reader::ChunkReader,
+        compression, crypto,
+    },
     utils::url_utils,
 };
 use gloo::file::File;
+use kusatsu_encrypt::pow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
 use web_sys::{DragEvent, Event, HtmlInputElement};
 use yew::prelude::*;
 
 // Constants
-const MAX_SINGLE_UPLOAD_SIZE: usize = 5 * 1024 * 1024; // 5MB
-const CHUNK_SIZE: i32 = 5 * 1024 * 1024; // 5MB chunks
-const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024 * 1024; // 5GB max
+const DEFAULT_MAX_FILE_SIZE: u64 = 5 * 1024 * 1024 * 1024; // 5GB, until the server says otherwise
+const MAX_CONCURRENT_UPLOADS: usize = 2;
+
+// Size limits of the server, from /api/config (defaults for servers that predate it)
+#[derive(Clone, Copy, PartialEq)]
+pub struct UploadLimits {
+    pub max_file_size: u64,
+    pub single_upload_max: u64,
+    // Whether each upload must carry a solved proof-of-work challenge
+    pub pow_required: bool,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            single_upload_max: MAX_SINGLE_UPLOAD_SIZE as u64,
+            pow_required: false,
+        }
+    }
+}
+
+impl From<&ServerConfigResponse> for UploadLimits {
+    fn from(config: &ServerConfigResponse) -> Self {
+        Self {
+            max_file_size: config.max_file_size.max(0) as u64,
+            single_upload_max: config.single_upload_max.max(0) as u64,
+            pow_required: config.upload_pow_difficulty.is_some(),
+        }
+    }
+}
+
+impl UploadLimits {
+    // Files too big for a single upload go through chunked upload, which the
+    // server currently stores unencrypted (only their filename is encrypted)
+    fn uses_chunked_upload(&self, file: &File) -> bool {
+        file.size() > self.single_upload_max
+    }
+
+    fn accepts(&self, file: &File) -> bool {
+        file.size() <= self.max_file_size
+    }
+
+    // Oversized files stay in the queue so the user sees why they were skipped
+    fn initial_state(&self, file: &File) -> UploadState {
+        if self.accepts(file) {
+            UploadState::Idle
+        } else {
+            UploadState::Error(format!(
+                "File too large. Maximum size is {} MB.",
+                self.max_file_size / (1024 * 1024)
+            ))
+        }
+    }
+}
 
 #[derive(Clone, PartialEq)]
 pub enum UploadState {
     Idle,
+    Queued,
     Preparing,
     StartingUpload,
     UploadingChunks {
@@ -33,47 +99,165 @@ pub enum UploadState {
     Error(String),
 }
 
+impl UploadState {
+    fn is_active(&self) -> bool {
+        matches!(
+            self,
+            UploadState::Queued
+                | UploadState::Preparing
+                | UploadState::StartingUpload
+                | UploadState::UploadingChunks { .. }
+                | UploadState::Completing
+        )
+    }
+
+    // Fraction of this file that has been sent
+    fn progress(&self) -> f32 {
+        match self {
+            UploadState::UploadingChunks { progress, .. } => *progress,
+            UploadState::Completing | UploadState::Completed { .. } => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            UploadState::Idle => "Ready",
+            UploadState::Queued => "Waiting...",
+            UploadState::Preparing => "Preparing...",
+            UploadState::StartingUpload => "Starting...",
+            UploadState::UploadingChunks { .. } => "Uploading...",
+            UploadState::Completing => "Finalizing...",
+            UploadState::Completed { .. } => "Completed",
+            UploadState::Error(_) => "Error",
+        }
+    }
+}
+
+// A file waiting in (or done with) the upload queue
+#[derive(Clone, PartialEq)]
+pub struct QueuedFile {
+    pub id: usize,
+    pub file: File,
+    pub state: UploadState,
+}
+
+#[derive(Default, PartialEq)]
+pub struct UploadQueue {
+    files: Vec<QueuedFile>,
+    next_id: usize,
+    limits: UploadLimits,
+}
+
+pub enum QueueAction {
+    Add(Vec<File>),
+    Remove(usize),
+    Clear,
+    SetState(usize, UploadState),
+    SetLimits(UploadLimits),
+}
+
+impl Reducible for UploadQueue {
+    type Action = QueueAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let mut files = self.files.clone();
+        let mut next_id = self.next_id;
+        let mut limits = self.limits;
+
+        match action {
+            QueueAction::Add(new_files) => {
+                for file in new_files {
+                    let state = limits.initial_state(&file);
+                    files.push(QueuedFile {
+                        id: next_id,
+                        file,
+                        state,
+                    });
+                    next_id += 1;
+                }
+            }
+            QueueAction::Remove(id) => files.retain(|f| f.id != id),
+            QueueAction::Clear => files.clear(),
+            QueueAction::SetState(id, state) => {
+                if let Some(entry) = files.iter_mut().find(|f| f.id == id) {
+                    entry.state = state;
+                }
+            }
+            QueueAction::SetLimits(new_limits) => {
+                limits = new_limits;
+                // Files added before the server's limits were known
+                for entry in files.iter_mut() {
+                    if matches!(entry.state, UploadState::Idle) {
+                        entry.state = limits.initial_state(&entry.file);
+                    }
+                }
+            }
+        }
+
+        Rc::new(UploadQueue {
+            files,
+            next_id,
+            limits,
+        })
+    }
+}
+
 #[derive(Properties, PartialEq)]
 pub struct UploadProps {
     pub on_upload_complete: Callback<(String, String, String, String)>, // file_id, download_url, encryption_key, curl_command
 }
 
+// Collect every file from a FileList
+fn files_from_list(files: Option<web_sys::FileList>) -> Vec<File> {
+    let Some(files) = files else {
+        return Vec::new();
+    };
+    (0..files.length())
+        .filter_map(|i| files.get(i))
+        .map(File::from)
+        .collect()
+}
+
 #[function_component(Upload)]
 pub fn upload(props: &UploadProps) -> Html {
     let file_input_ref = use_node_ref();
-    let selected_file = use_state(|| None::<File>);
-    let upload_state = use_state(|| UploadState::Idle);
+    let queue = use_reducer(UploadQueue::default);
     let expires_in_hours = use_state(|| 24i32);
     let max_downloads = use_state(|| None::<i32>);
     let enable_max_downloads = use_state(|| false);
+    let burn_after_minutes = use_state(|| None::<i32>);
+    let compress = use_state(|| false);
     let api_client = use_state(ApiClient::new);
     let drag_over = use_state(|| false);
+    let toaster = use_toaster();
+
+    // Adapt to the server's limits; the defaults stay if it doesn't advertise them
+    {
+        let queue = queue.clone();
+        let api_client = api_client.clone();
+        use_effect_with((), move |_| {
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Ok(config) = api_client.get_server_config().await {
+                    queue.dispatch(QueueAction::SetLimits(UploadLimits::from(&config)));
+                }
+            });
+            || ()
+        });
+    }
+    let limits = queue.limits;
 
     let on_file_select = {
-        let selected_file = selected_file.clone();
-        let upload_state = upload_state.clone();
+        let queue = queue.clone();
 
         Callback::from(move |e: Event| {
             let input: HtmlInputElement = e.target_unchecked_into();
-            if let Some(files) = input.files() {
-                if files.length() > 0 {
-                    if let Some(file) = files.get(0) {
-                        let file = File::from(file);
-
-                        // Check file size
-                        if file.size() > MAX_FILE_SIZE {
-                            upload_state.set(UploadState::Error(format!(
-                                "File too large. Maximum size is {} MB.",
-                                MAX_FILE_SIZE / (1024 * 1024)
-                            )));
-                            return;
-                        }
-
-                        selected_file.set(Some(file));
-                        upload_state.set(UploadState::Idle);
-                    }
-                }
+            let files = files_from_list(input.files());
+            if !files.is_empty() {
+                queue.dispatch(QueueAction::Add(files));
             }
+            // Reset so selecting the same file again still fires onchange
+            input.set_value("");
         })
     };
 
@@ -93,33 +277,23 @@ pub fn upload(props: &UploadProps) -> Html {
         })
     };
 
+    let is_uploading = queue.files.iter().any(|f| f.state.is_active());
+
     let on_drop = {
-        let selected_file = selected_file.clone();
-        let upload_state = upload_state.clone();
+        let queue = queue.clone();
         let drag_over = drag_over.clone();
 
         Callback::from(move |e: DragEvent| {
             e.prevent_default();
             drag_over.set(false);
 
-            if let Some(files) = e.data_transfer().and_then(|dt| dt.files()) {
-                if files.length() > 0 {
-                    if let Some(file) = files.get(0) {
-                        let file = File::from(file);
-
-                        // Check file size
-                        if file.size() > MAX_FILE_SIZE {
-                            upload_state.set(UploadState::Error(format!(
-                                "File too large. Maximum size is {} MB.",
-                                MAX_FILE_SIZE / (1024 * 1024)
-                            )));
-                            return;
-                        }
+            if is_uploading {
+                return;
+            }
 
-                        selected_file.set(Some(file));
-                        upload_state.set(UploadState::Idle);
-                    }
-                }
+            let files = files_from_list(e.data_transfer().and_then(|dt| dt.files()));
+            if !files.is_empty() {
+                queue.dispatch(QueueAction::Add(files));
             }
         })
     };
@@ -134,84 +308,90 @@ pub fn upload(props: &UploadProps) -> Html {
     };
 
     let start_upload = {
-        let selected_file = selected_file.clone();
-        let upload_state = upload_state.clone();
+        let queue = queue.clone();
         let expires_in_hours = expires_in_hours.clone();
         let max_downloads = max_downloads.clone();
         let enable_max_downloads = enable_max_downloads.clone();
+        let burn_after_minutes = burn_after_minutes.clone();
+        let compress = compress.clone();
         let api_client = api_client.clone();
         let on_upload_complete = props.on_upload_complete.clone();
+        let toaster = toaster.clone();
 
         Callback::from(move |_| {
-            if let Some(file) = (*selected_file).clone() {
-                let upload_state = upload_state.clone();
-                let api_client = (*api_client).clone();
-                let on_upload_complete = on_upload_complete.clone();
-                let expires_in_hours = *expires_in_hours;
-                let max_downloads = if *enable_max_downloads {
+            // Pending files, plus failed ones that can be retried
+            let pending: VecDeque<(usize, File)> = queue
+                .files
+                .iter()
+                .filter(|f| match &f.state {
+                    UploadState::Idle => true,
+                    UploadState::Error(_) => limits.accepts(&f.file),
+                    _ => false,
+                })
+                .map(|f| (f.id, f.file.clone()))
+                .collect();
+
+            if pending.is_empty() {
+                return;
+            }
+
+            for (id, _) in &pending {
+                queue.dispatch(QueueAction::SetState(*id, UploadState::Queued));
+            }
+
+            let pending = Rc::new(RefCell::new(pending));
+            let options = UploadOptions {
+                expires_in_hours: Some(*expires_in_hours),
+                max_downloads: if *enable_max_downloads {
                     *max_downloads
                 } else {
                     None
-                };
+                },
+                slug: None,
+                burn_after_first_download_minutes: *burn_after_minutes,
+                expire_after_inactivity_hours: None,
+                pow_challenge: None,
+                pow_nonce: None,
+            };
+            let compress = *compress;
+
+            // Each worker takes the next file until the queue is drained
+            for _ in 0..MAX_CONCURRENT_UPLOADS {
+                let pending = pending.clone();
+                let options = options.clone();
+                let queue = queue.clone();
+                let api_client = (*api_client).clone();
+                let on_upload_complete = on_upload_complete.clone();
+                let toaster = toaster.clone();
 
                 wasm_bindgen_futures::spawn_local(async move {
-                    upload_state.set(UploadState::Preparing);
-
-                    let file_size = file.size() as usize;
-                    let filename = file.name();
-                    let mime_type = if file.raw_mime_type().is_empty() {
-                        None
-                    } else {
-                        Some(file.raw_mime_type())
-                    };
-
-                    // Decide between chunked and single upload
-                    if file_size <= MAX_SINGLE_UPLOAD_SIZE {
-                        // Use single upload for smaller files
-                        match perform_single_upload(
-                            &api_client,
-                            file,
-                            filename,
-                            mime_type,
-                            Some(expires_in_hours),
-                            max_downloads,
-                            upload_state.clone(),
-                        )
-                        .await
-                        {
-                            Ok((file_id, download_url, encryption_key, curl_command)) => {
-                                upload_state.set(UploadState::Completed {
-                                    file_id: file_id.clone(),
-                                    download_url: download_url.clone(),
-                                    encryption_key: encryption_key.clone(),
-                                    curl_command: curl_command.clone(),
-                                });
-                                on_upload_complete.emit((
-                                    file_id,
-                                    download_url,
-                                    encryption_key,
-                                    curl_command,
-                                ));
-                            }
-                            Err(error) => {
-                                upload_state.set(UploadState::Error(error));
-                            }
-                        }
-                    } else {
-                        // Use chunked upload for larger files
-                        match perform_chunked_upload(
+                    loop {
+                        let next = pending.borrow_mut().pop_front();
+                        let Some((id, file)) = next else {
+                            break;
+                        };
+                        let file_name = file.name();
+
+                        let set_state = {
+                            let queue = queue.clone();
+                            Callback::from(move |state| {
+                                queue.dispatch(QueueAction::SetState(id, state))
+                            })
+                        };
+
+                        match upload_one(
                             &api_client,
                             file,
-                            filename,
-                            mime_type,
-                            Some(expires_in_hours),
-                            max_downloads,
-                            upload_state.clone(),
+                            limits,
+                            &options,
+                            compress,
+                            set_state.clone(),
                         )
                         .await
                         {
                             Ok((file_id, download_url, encryption_key, curl_command)) => {
-                                upload_state.set(UploadState::Completed {
+                                toaster.success(format!("{} uploaded", file_name));
+                                set_state.emit(UploadState::Completed {
                                     file_id: file_id.clone(),
                                     download_url: download_url.clone(),
                                     encryption_key: encryption_key.clone(),
@@ -225,7 +405,8 @@ pub fn upload(props: &UploadProps) -> Html {
                                 ));
                             }
                             Err(error) => {
-                                upload_state.set(UploadState::Error(error));
+                                toaster.error(format!("{} failed to upload", file_name));
+                                set_state.emit(UploadState::Error(error));
                             }
                         }
                     }
@@ -234,31 +415,10 @@ pub fn upload(props: &UploadProps) -> Html {
         })
     };
 
-    let clear_file = {
-        let selected_file = selected_file.clone();
-        let upload_state = upload_state.clone();
-        let file_input_ref = file_input_ref.clone();
-
+    let clear_queue = {
+        let queue = queue.clone();
         Callback::from(move |_| {
-            selected_file.set(None);
-            upload_state.set(UploadState::Idle);
-            if let Some(input) = file_input_ref.cast::<HtmlInputElement>() {
-                input.set_value("");
-            }
-        })
-    };
-
-    let cancel_upload = {
-        let upload_state = upload_state.clone();
-        let selected_file = selected_file.clone();
-        let file_input_ref = file_input_ref.clone();
-
-        Callback::from(move |_| {
-            upload_state.set(UploadState::Idle);
-            selected_file.set(None);
-            if let Some(input) = file_input_ref.cast::<HtmlInputElement>() {
-                input.set_value("");
-            }
+            queue.dispatch(QueueAction::Clear);
         })
     };
 
@@ -287,6 +447,32 @@ pub fn upload(props: &UploadProps) -> Html {
         })
     };
 
+    let on_burn_toggle = {
+        let burn_after_minutes = burn_after_minutes.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            burn_after_minutes.set(input.checked().then_some(60));
+        })
+    };
+
+    let on_burn_minutes_change = {
+        let burn_after_minutes = burn_after_minutes.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(value) = input.value().parse::<i32>() {
+                burn_after_minutes.set(Some(value));
+            }
+        })
+    };
+
+    let on_compress_toggle = {
+        let compress = compress.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            compress.set(input.checked());
+        })
+    };
+
     let on_max_downloads_change = {
         let max_downloads = max_downloads.clone();
         Callback::from(move |e: Event| {
@@ -297,22 +483,47 @@ pub fn upload(props: &UploadProps) -> Html {
         })
     };
 
-    let is_uploading = matches!(
-        *upload_state,
-        UploadState::Preparing
-            | UploadState::StartingUpload
-            | UploadState::UploadingChunks { .. }
-            | UploadState::Completing
-    );
+    // Aggregate summary across the queue
+    let total_files = queue.files.len();
+    let completed_files = queue
+        .files
+        .iter()
+        .filter(|f| matches!(f.state, UploadState::Completed { .. }))
+        .count();
+    let failed_files = queue
+        .files
+        .iter()
+        .filter(|f| matches!(f.state, UploadState::Error(_)))
+        .count();
+    let has_pending = queue
+        .files
+        .iter()
+        .any(|f| matches!(f.state, UploadState::Idle | UploadState::Error(_)));
+    let has_unencrypted_pending = queue.files.iter().any(|f| {
+        matches!(f.state, UploadState::Idle | UploadState::Error(_))
+            && limits.uses_chunked_upload(&f.file)
+    });
+    let total_bytes: f64 = queue.files.iter().map(|f| f.file.size() as f64).sum();
+    let sent_bytes: f64 = queue
+        .files
+        .iter()
+        .map(|f| f.file.size() as f64 * f.state.progress() as f64)
+        .sum();
+    let overall_progress = if total_bytes > 0.0 {
+        sent_bytes / total_bytes
+    } else {
+        0.0
+    };
 
     html! {
         <div class="max-w-2xl mx-auto bg-white dark:bg-gray-800 rounded-xl shadow-lg p-8">
-            <h2 class="text-2xl font-bold text-gray-900 dark:text-gray-100 mb-8 text-center">{"Upload and attach file"}</h2>
+            <h2 class="text-2xl font-bold text-gray-900 dark:text-gray-100 mb-8 text-center">{"Upload and attach files"}</h2>
 
             // Hidden file input
             <input
                 ref={file_input_ref}
                 type="file"
+                multiple=true
                 class="hidden"
                 onchange={on_file_select}
                 disabled={is_uploading}
@@ -351,181 +562,53 @@ pub fn upload(props: &UploadProps) -> Html {
                         }
                     </p>
                     <p class="text-sm text-gray-500 dark:text-gray-400">
-                        {format!("(Max. File size: {} MB)", MAX_FILE_SIZE / (1024 * 1024))}
+                        {format!("(Max. File size: {} MB)", limits.max_file_size / (1024 * 1024))}
                     </p>
                 </div>
             </div>
 
-            // File preview and progress section
-            if let Some(file) = (*selected_file).as_ref() {
-                <div class="mt-8 space-y-4">
-                    <div class="bg-gray-50 dark:bg-gray-700 rounded-lg p-4">
-                        <div class="flex items-center justify-between">
-                            <div class="flex items-center space-x-3 flex-1 min-w-0">
-                                <span class="text-2xl">{
-                                    {
-                                        let mime_type = file.raw_mime_type();
-                                        crate::utils::file_utils::get_file_icon(
-                                            &file.name(),
-                                            if mime_type.is_empty() { None } else { Some(&mime_type) }
-                                        )
-                                    }
-                                }</span>
-                                <div class="flex-1 min-w-0">
-                                    <p class="text-sm font-medium text-gray-900 dark:text-gray-100 truncate">{&file.name()}</p>
-                                    <p class="text-sm text-gray-500 dark:text-gray-400">
-                                        {format!("{} • {}", crate::utils::file_utils::format_file_size(file.size() as usize),
-                                            match &*upload_state {
-                                                UploadState::Completed { .. } => "Completed",
-                                                UploadState::Error(_) => "Error",
-                                                UploadState::Preparing => "Preparing...",
-                                                UploadState::StartingUpload => "Starting...",
-                                                UploadState::UploadingChunks { .. } => "Uploading...",
-                                                UploadState::Completing => "Finalizing...",
-                                                _ => "Ready"
-                                            }
-                                        )}
-                                    </p>
-                                </div>
-                            </div>
-
-                            // Action button
-                            <button
-                                class="ml-4 p-2 text-gray-400 dark:text-gray-500 hover:text-gray-600 dark:hover:text-gray-300 transition-colors"
-                                onclick={if is_uploading { cancel_upload } else { clear_file }}
-                                title={if is_uploading { "Cancel upload" } else { "Remove file" }}
-                            >
-                                <svg class="w-5 h-5" fill="none" stroke="currentColor" viewBox="0 0 24 24">
-                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M6 18L18 6M6 6l12 12" />
-                                </svg>
-                            </button>
-                        </div>
-
-                        // Progress bar
-                        {match &*upload_state {
-                            UploadState::UploadingChunks { progress, uploaded_chunks, total_chunks, .. } => html! {
-                                <div class="mt-3">
-                                    <div class="flex justify-between text-xs text-gray-600 dark:text-gray-400 mb-1">
-                                        <span>{format!("Chunk {} of {}", uploaded_chunks + 1, total_chunks)}</span>
-                                        <span>{format!("{:.0}%", progress * 100.0)}</span>
-                                    </div>
-                                    <div class="w-full bg-gray-200 dark:bg-gray-600 rounded-full h-2">
-                                        <div
-                                            class="bg-blue-600 h-2 rounded-full transition-all duration-300"
-                                            style={format!("width: {:.1}%", progress * 100.0)}
-                                        ></div>
-                                    </div>
-                                </div>
-                            },
-                            UploadState::Preparing | UploadState::StartingUpload | UploadState::Completing => html! {
-                                <div class="mt-3">
-                                    <div class="w-full bg-gray-200 dark:bg-gray-600 rounded-full h-2">
-                                        <div class="bg-blue-600 h-2 rounded-full animate-pulse w-1/3"></div>
-                                    </div>
-                                </div>
-                            },
-                            UploadState::Completed { .. } => html! {
-                                <div class="mt-3">
-                                    <div class="w-full bg-green-200 dark:bg-green-800 rounded-full h-2">
-                                        <div class="bg-green-600 h-2 rounded-full w-full"></div>
-                                    </div>
-                                </div>
-                            },
-                            _ => html! {}
-                        }}
+            // Aggregate progress summary
+            if total_files > 0 {
+                <div class="mt-8">
+                    <div class="flex justify-between text-sm text-gray-700 dark:text-gray-300 mb-1">
+                        <span>
+                            {format!("{} of {} files uploaded", completed_files, total_files)}
+                            if failed_files > 0 {
+                                <span class="text-red-600 dark:text-red-400">{format!(" • {} failed", failed_files)}</span>
+                            }
+                        </span>
+                        <span>{format!("{:.0}%", overall_progress * 100.0)}</span>
                     </div>
-                </div>
-            }
-
-            // Error display
-            if let UploadState::Error(error) = &*upload_state {
-                <div class="mt-6 p-4 bg-red-50 dark:bg-red-900/50 border border-red-200 dark:border-red-800 rounded-lg">
-                    <div class="flex items-center">
-                        <svg class="w-5 h-5 text-red-400 mr-3" fill="none" stroke="currentColor" viewBox="0 0 24 24">
-                            <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M12 8v4m0 4h.01M21 12a9 9 0 11-18 0 9 9 0 0118 0z" />
-                        </svg>
-                        <p class="text-red-800 dark:text-red-300 text-sm">{error}</p>
+                    <div class="w-full bg-gray-200 dark:bg-gray-600 rounded-full h-2">
+                        <div
+                            class="bg-blue-600 h-2 rounded-full transition-all duration-300"
+                            style={format!("width: {:.1}%", overall_progress * 100.0)}
+                        ></div>
                     </div>
                 </div>
             }
 
-            // Success display
-            if let UploadState::Completed { file_id: _, download_url, encryption_key: _, curl_command } = &*upload_state {
-                <div class="mt-6 p-4 bg-green-50 dark:bg-green-900/50 border border-green-200 dark:border-green-800 rounded-lg">
-                    <div class="flex items-center">
-                        <svg class="w-5 h-5 text-green-400 mr-3" fill="none" stroke="currentColor" viewBox="0 0 24 24">
-                            <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M9 12l2 2 4-4m6 2a9 9 0 11-18 0 9 9 0 0118 0z" />
-                        </svg>
-                        <p class="text-green-800 dark:text-green-300 text-sm">
-                            {"File uploaded successfully!"  }
-                        </p>
-                    </div>
-                </div>
-
-                <div class="mt-4">
-                    <label class="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
-                        {"Shareable URL:"}
-                    </label>
-                    <div class="flex">
-                        <input
-                            type="text"
-                            value={download_url.clone()}
-                            readonly=true
-                            class="flex-1 p-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 rounded-l text-sm font-mono"
-                        />
-                        <button
-                            class="px-4 py-2 bg-blue-600 text-white rounded-r hover:bg-blue-700 text-sm"
-                            onclick={
-                                let url = download_url.clone();
-                                Callback::from(move |_| {
-                                    let url = url.clone();
-                                    wasm_bindgen_futures::spawn_local(async move {
-                                        if let Err(e) = url_utils::copy_to_clipboard(&url).await {
-                                            web_sys::console::log_1(&format!("Failed to copy: {:?}", e).into());
-                                        }
-                                    });
-                                })
+            // File queue
+            if total_files > 0 {
+                <div class="mt-4 space-y-4">
+                    { for queue.files.iter().map(|entry| html! {
+                        <QueueItem
+                            key={entry.id}
+                            entry={entry.clone()}
+                            limits={limits}
+                            compress={*compress}
+                            on_remove={
+                                let queue = queue.clone();
+                                let id = entry.id;
+                                Callback::from(move |_| queue.dispatch(QueueAction::Remove(id)))
                             }
-                        >
-                            {"Copy"}
-                        </button>
-                    </div>
-                </div>
-
-                <div class="mt-4">
-                    <label class="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
-                        {"Download with curl:"}
-                    </label>
-                    <div class="flex">
-                        <input
-                            type="text"
-                            value={curl_command.clone()}
-                            readonly=true
-                            class="flex-1 p-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 rounded-l text-sm font-mono text-xs"
                         />
-                        <button
-                            class="px-4 py-2 bg-gray-600 text-white rounded-r hover:bg-gray-700 text-sm"
-                            onclick={
-                                let curl_command = curl_command.clone();
-
-                                Callback::from(move |_| {
-                                    let cmd = curl_command.clone();
-                                    wasm_bindgen_futures::spawn_local(async move {
-                                        if let Err(e) = url_utils::copy_to_clipboard(&cmd).await {
-                                            web_sys::console::log_1(&format!("Failed to copy: {:?}", e).into());
-                                        }
-                                    });
-                                })
-                            }
-                        >
-                            {"Copy"}
-                        </button>
-                    </div>
+                    }) }
                 </div>
             }
 
             // Upload options
-            if selected_file.is_some() && !matches!(*upload_state, UploadState::Completed { .. }) {
+            if has_pending || is_uploading {
                 <div class="mt-8 space-y-6">
                     <div class="grid grid-cols-1 md:grid-cols-2 gap-6">
                         <div>
@@ -570,60 +653,404 @@ pub fn upload(props: &UploadProps) -> Html {
                         </div>
                     </div>
 
+                    <div class="flex flex-col">
+                        <label class="flex items-center mb-2">
+                            <input
+                                type="checkbox"
+                                checked={burn_after_minutes.is_some()}
+                                class="mr-2 rounded"
+                                onchange={on_burn_toggle}
+                                disabled={is_uploading}
+                            />
+                            <span class="text-sm font-medium text-gray-700 dark:text-gray-300">{"Burn after first download"}</span>
+                        </label>
+
+                        if let Some(minutes) = *burn_after_minutes {
+                            <input
+                                type="number"
+                                min="1"
+                                value={minutes.to_string()}
+                                class="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent"
+                                placeholder="Minutes after the first download"
+                                onchange={on_burn_minutes_change}
+                                disabled={is_uploading}
+                            />
+                            <p class="mt-1 text-xs text-gray-500 dark:text-gray-400">
+                                {"Minutes the file stays available once someone first downloads it."}
+                            </p>
+                        }
+                    </div>
+
+                    <div>
+                        <label class="flex items-center">
+                            <input
+                                type="checkbox"
+                                checked={*compress}
+                                class="mr-2 rounded"
+                                onchange={on_compress_toggle}
+                                disabled={is_uploading}
+                            />
+                            <span class="text-sm font-medium text-gray-700 dark:text-gray-300">{"Compress before encrypting"}</span>
+                        </label>
+                        <p class="mt-1 ml-6 text-xs text-gray-500 dark:text-gray-400">
+                            {"Makes text-heavy files smaller. Images, video and archives are sent as they are."}
+                        </p>
+                    </div>
+
+                    // Chunked uploads skip encryption, so say so before anything is sent
+                    if has_unencrypted_pending {
+                        <div class="p-4 bg-yellow-50 dark:bg-yellow-900/30 border border-yellow-200 dark:border-yellow-700 rounded-lg text-sm text-yellow-800 dark:text-yellow-200">
+                            {format!(
+                                "Files over {} MB are currently stored unencrypted. Only share them if that's acceptable.",
+                                limits.single_upload_max / (1024 * 1024)
+                            )}
+                        </div>
+                    }
+
                     // Upload button
                     <button
                         class="w-full bg-blue-600 text-white py-3 px-6 rounded-lg hover:bg-blue-700 disabled:bg-gray-400 disabled:cursor-not-allowed transition-colors font-medium text-lg"
                         onclick={start_upload}
                         disabled={is_uploading}
                     >
-                        {match &*upload_state {
-                            UploadState::Idle => "Upload File",
-                            UploadState::Preparing => "Preparing upload...",
-                            UploadState::StartingUpload => "Starting upload...",
-                            UploadState::UploadingChunks { .. } => "Uploading...",
-                            UploadState::Completing => "Finalizing...",
-                            UploadState::Completed { .. } => "Upload Complete",
-                            UploadState::Error(_) => "Retry Upload",
-                        }}
+                        if is_uploading {
+                            {"Uploading..."}
+                        } else if failed_files > 0 && !queue.files.iter().any(|f| f.state == UploadState::Idle) {
+                            {"Retry Failed Uploads"}
+                        } else {
+                            {"Upload Files"}
+                        }
                     </button>
                 </div>
             }
+
+            if total_files > 0 && !is_uploading {
+                <button
+                    class="mt-4 w-full text-sm text-gray-500 dark:text-gray-400 hover:text-gray-700 dark:hover:text-gray-200"
+                    onclick={clear_queue}
+                >
+                    {"Clear list"}
+                </button>
+            }
         </div>
     }
 }
 
-// Single upload for smaller files
-async fn perform_single_upload(
+#[derive(Properties, PartialEq)]
+struct QueueItemProps {
+    entry: QueuedFile,
+    limits: UploadLimits,
+    compress: bool,
+    on_remove: Callback<MouseEvent>,
+}
+
+// One row of the upload queue: file details, status, progress and result
+#[function_component(QueueItem)]
+fn queue_item(props: &QueueItemProps) -> Html {
+    let file = &props.entry.file;
+    let state = &props.entry.state;
+    let chunked = props.limits.uses_chunked_upload(file);
+    let mime_type = file.raw_mime_type();
+    let toaster = use_toaster();
+
+    // What compression would do to a file about to be sent: why it's skipped, or
+    // the savings estimated from a sample
+    let skip_compression = props
+        .compress
+        .then(|| compression::skip_reason(file, chunked))
+        .flatten();
+    let savings = use_state(|| None::<f64>);
+    {
+        let savings = savings.clone();
+        let file = file.clone();
+        let estimate = props.compress && skip_compression.is_none();
+        use_effect_with(estimate, move |&estimate| {
+            if estimate && savings.is_none() {
+                wasm_bindgen_futures::spawn_local(async move {
+                    savings.set(compression::estimate_savings(&file).await);
+                });
+            }
+            || ()
+        });
+    }
+    let compression_note = match (props.compress, skip_compression, *savings) {
+        (false, _, _) => None,
+        (true, Some(reason), _) => Some(format!("Not compressed: {}", reason)),
+        (true, None, Some(savings)) => Some(format!(
+            "Compression saves about {:.0}% (≈ {})",
+            savings * 100.0,
+            crate::utils::file_utils::format_file_size(
+                (file.size() as f64 * (1.0 - savings)) as usize
+            )
+        )),
+        (true, None, None) => None,
+    };
+
+    html! {
+        <div class="bg-gray-50 dark:bg-gray-700 rounded-lg p-4">
+            <div class="flex items-center justify-between">
+                <div class="flex items-center space-x-3 flex-1 min-w-0">
+                    <span class="text-2xl">{
+                        crate::utils::file_utils::get_file_icon(
+                            &file.name(),
+                            if mime_type.is_empty() { None } else { Some(&mime_type) }
+                        )
+                    }</span>
+                    <div class="flex-1 min-w-0">
+                        <p class="text-sm font-medium text-gray-900 dark:text-gray-100 truncate">
+                            {file.name()}
+                            if chunked {
+                                <span class="ml-2 px-2 py-0.5 rounded text-xs font-medium bg-yellow-100 dark:bg-yellow-900/50 text-yellow-800 dark:text-yellow-200">
+                                    {"Unencrypted"}
+                                </span>
+                            }
+                        </p>
+                        <p class="text-sm text-gray-500 dark:text-gray-400">
+                            {format!("{} • {}", crate::utils::file_utils::format_file_size(file.size() as usize), state.label())}
+                        </p>
+                        if let (Some(note), UploadState::Idle) = (&compression_note, state) {
+                            <p class="text-xs text-gray-500 dark:text-gray-400">{note}</p>
+                        }
+                    </div>
+                </div>
+
+                // Files can only be removed while they aren't being uploaded
+                if !state.is_active() {
+                    <button
+                        class="ml-4 p-2 text-gray-400 dark:text-gray-500 hover:text-gray-600 dark:hover:text-gray-300 transition-colors"
+                        onclick={props.on_remove.clone()}
+                        title="Remove file"
+                    >
+                        <svg class="w-5 h-5" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+                            <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M6 18L18 6M6 6l12 12" />
+                        </svg>
+                    </button>
+                }
+            </div>
+
+            // Progress bar
+            {match state {
+                UploadState::UploadingChunks { progress, uploaded_chunks, total_chunks, .. } => html! {
+                    <div class="mt-3">
+                        <div class="flex justify-between text-xs text-gray-600 dark:text-gray-400 mb-1">
+                            <span>{format!("Chunk {} of {}", uploaded_chunks + 1, total_chunks)}</span>
+                            <span>{format!("{:.0}%", progress * 100.0)}</span>
+                        </div>
+                        <div class="w-full bg-gray-200 dark:bg-gray-600 rounded-full h-2">
+                            <div
+                                class="bg-blue-600 h-2 rounded-full transition-all duration-300"
+                                style={format!("width: {:.1}%", progress * 100.0)}
+                            ></div>
+                        </div>
+                    </div>
+                },
+                UploadState::Preparing | UploadState::StartingUpload | UploadState::Completing => html! {
+                    <div class="mt-3">
+                        <div class="w-full bg-gray-200 dark:bg-gray-600 rounded-full h-2">
+                            <div class="bg-blue-600 h-2 rounded-full animate-pulse w-1/3"></div>
+                        </div>
+                    </div>
+                },
+                UploadState::Error(error) => html! {
+                    <p class="mt-3 text-red-800 dark:text-red-300 text-sm">{error}</p>
+                },
+                UploadState::Completed { file_id, download_url, encryption_key, curl_command } => {
+                    let shareable_url = url_utils::shareable_url(download_url, encryption_key);
+                    html! {
+                        <div class="mt-3 space-y-2">
+                            if chunked {
+                                <p class="text-xs text-yellow-700 dark:text-yellow-300">
+                                    {"Unencrypted link: only the filename is encrypted, the contents are stored in plain form on the server"}
+                                </p>
+                            }
+                            <div class="flex">
+                                <input
+                                    type="text"
+                                    value={shareable_url.clone()}
+                                    readonly=true
+                                    class="flex-1 p-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 rounded-l text-sm font-mono"
+                                />
+                                <button
+                                    class="px-4 py-2 bg-blue-600 text-white hover:bg-blue-700 text-sm"
+                                    onclick={copy_callback(&toaster, shareable_url, "Link copied to clipboard")}
+                                    title="Copy the shareable URL, including the key"
+                                >
+                                    {"Copy"}
+                                </button>
+                                <button
+                                    class="px-4 py-2 bg-gray-600 text-white rounded-r hover:bg-gray-700 text-sm"
+                                    onclick={copy_callback(&toaster, curl_command.clone(), "curl command copied to clipboard")}
+                                    title="Copy curl download command"
+                                >
+                                    {"curl"}
+                                </button>
+                            </div>
+                            <div class="flex items-center justify-between text-xs text-gray-500 dark:text-gray-400">
+                                <span class="font-mono truncate">{format!("File ID: {}", file_id)}</span>
+                                if !encryption_key.is_empty() {
+                                    <button
+                                        class="ml-4 text-blue-600 dark:text-blue-400 hover:underline whitespace-nowrap"
+                                        onclick={copy_callback(&toaster, encryption_key.clone(), "Encryption key copied to clipboard")}
+                                        title="Copy only the encryption key, to share it separately"
+                                    >
+                                        {"Copy key"}
+                                    </button>
+                                }
+                            </div>
+                        </div>
+                    }
+                }
+                _ => html! {}
+            }}
+        </div>
+    }
+}
+
+fn copy_callback(toaster: &Toaster, text: String, copied: &'static str) -> Callback<MouseEvent> {
+    let toaster = toaster.clone();
+    Callback::from(move |_| {
+        let text = text.clone();
+        let toaster = toaster.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match url_utils::copy_to_clipboard(&text).await {
+                Ok(()) => toaster.success(copied),
+                Err(e) => {
+                    web_sys::console::log_1(&format!("Failed to copy: {:?}", e).into());
+                    toaster.error("Could not copy to clipboard");
+                }
+            }
+        });
+    })
+}
+
+// Upload a single file, picking single or chunked upload based on its size
+async fn upload_one(
     api_client: &ApiClient,
     file: File,
+    limits: UploadLimits,
+    options: &UploadOptions,
+    compress: bool,
+    set_state: Callback<UploadState>,
+) -> Result<(String, String, String, String), String> {
+    set_state.emit(UploadState::Preparing);
+
+    // Each upload redeems its own challenge
+    let options = if limits.pow_required {
+        solve_upload_challenge(api_client, options).await?
+    } else {
+        options.clone()
+    };
+
+    let filename = file.name();
+    let mime_type = if file.raw_mime_type().is_empty() {
+        None
+    } else {
+        Some(file.raw_mime_type())
+    };
+
+    let chunked = limits.uses_chunked_upload(&file);
+    if !chunked {
+        let file_data = gloo::file::futures::read_as_bytes(&file)
+            .await
+            .map_err(|e| format!("Failed to read file: {:?}", e))?;
+
+        if compress && compression::skip_reason(&file, chunked).is_none() {
+            perform_compressed_upload(api_client, file_data, filename, mime_type, &options).await
+        } else {
+            perform_single_upload(api_client, file_data, filename, mime_type, &options).await
+        }
+    } else {
+        perform_chunked_upload(api_client, file, filename, mime_type, &options, set_state).await
+    }
+}
+
+// Upload options carrying a solved challenge, for servers requiring proof of work
+async fn solve_upload_challenge(
+    api_client: &ApiClient,
+    options: &UploadOptions,
+) -> Result<UploadOptions, String> {
+    let challenge = api_client
+        .get_upload_challenge()
+        .await
+        .map_err(|e| format!("Failed to get an upload challenge: {:?}", e))?;
+    let nonce = pow::solve(&challenge.challenge, challenge.difficulty);
+
+    Ok(UploadOptions {
+        pow_challenge: Some(challenge.challenge),
+        pow_nonce: Some(nonce),
+        ..options.clone()
+    })
+}
+
+// Single upload for smaller files, encrypted by the server
+async fn perform_single_upload(
+    api_client: &ApiClient,
+    file_data: Vec<u8>,
     filename: String,
     mime_type: Option<String>,
-    expires_in_hours: Option<i32>,
-    max_downloads: Option<i32>,
-    _upload_state: UseStateHandle<UploadState>,
+    options: &UploadOptions,
 ) -> Result<(String, String, String, String), String> {
-    // Read file data
-    let file_data = gloo::file::futures::read_as_bytes(&file)
+    // Upload file
+    let response = api_client
+        .upload_file(file_data, filename, mime_type, options, None)
         .await
-        .map_err(|e| format!("Failed to read file: {:?}", e))?;
+        .map_err(|e| format!("Upload failed: {:?}", e))?;
+
+    Ok((
+        response.file_id.to_string(),
+        response.download_url,
+        response.encryption_key.unwrap_or_else(|| "".to_string()),
+        response.curl_command,
+    ))
+}
+
+// Single upload compressed then encrypted here, since the server only ever gets
+// ciphertext; it decompresses downloads after decrypting them
+async fn perform_compressed_upload(
+    api_client: &ApiClient,
+    file_data: Vec<u8>,
+    filename: String,
+    mime_type: Option<String>,
+    options: &UploadOptions,
+) -> Result<(String, String, String, String), String> {
+    let compressed = compression::compress(&file_data)
+        .await
+        .map_err(|e| format!("Failed to compress file: {:?}", e))?;
+
+    // The sample looked compressible but the whole file isn't: send it as it is
+    if compressed.len() >= file_data.len() {
+        return perform_single_upload(api_client, file_data, filename, mime_type, options).await;
+    }
+
+    let encryption_key = crypto::generate_key();
+    let contents = crypto::encrypt(&compressed, &encryption_key)
+        .map_err(|e| format!("Failed to encrypt file: {}", e))?;
+    let encrypted_filename = crypto::encrypt(filename.as_bytes(), &encryption_key)
+        .map_err(|e| format!("Failed to encrypt filename: {}", e))?;
 
-    // Upload file
     let response = api_client
         .upload_file(
-            file_data,
+            contents.ciphertext,
             filename,
             mime_type,
-            expires_in_hours,
-            max_downloads,
+            options,
+            Some(ClientEncryption {
+                nonce: contents.nonce,
+                filename: encrypted_filename,
+                compression: Some((compression::FORMAT, file_data.len() as u64)),
+            }),
         )
         .await
         .map_err(|e| format!("Upload failed: {:?}", e))?;
 
+    let encryption_key = encryption_key.to_base64();
     Ok((
         response.file_id.to_string(),
         response.download_url,
-        response.encryption_key.unwrap_or_else(|| "".to_string()),
-        response.curl_command,
+        encryption_key.clone(),
+        response
+            .curl_command
+            .replace(KEY_PLACEHOLDER, &encryption_key),
     ))
 }
 
@@ -633,20 +1060,26 @@ async fn perform_chunked_upload(
     file: File,
     filename: String,
     mime_type: Option<String>,
-    expires_in_hours: Option<i32>,
-    max_downloads: Option<i32>,
-    upload_state: UseStateHandle<UploadState>,
+    options: &UploadOptions,
+    set_state: Callback<UploadState>,
 ) -> Result<(String, String, String, String), String> {
     // Start upload session
-    upload_state.set(UploadState::StartingUpload);
+    set_state.emit(UploadState::StartingUpload);
 
+    // The filename is only sent encrypted, on completion
     let start_request = StartUploadRequest {
-        filename: filename.clone(),
+        filename: filename_placeholder(&filename),
         file_size: file.size() as i64,
         mime_type,
-        chunk_size: Some(CHUNK_SIZE),
-        expires_in_hours,
-        max_downloads,
+        chunk_size: Some(recommended_chunk_size(file.size() as i64)),
+        expires_in_hours: options.expires_in_hours,
+        max_downloads: options.max_downloads,
+        slug: None,
+        burn_after_first_download_minutes: options.burn_after_first_download_minutes,
+        expire_after_inactivity_hours: options.expire_after_inactivity_hours,
+        pow_challenge: options.pow_challenge.clone(),
+        pow_nonce: options.pow_nonce,
+        filename_encrypted: true,
     };
 
     let start_response = api_client
@@ -658,12 +1091,10 @@ async fn perform_chunked_upload(
     let total_chunks = start_response.total_chunks;
     let chunk_size = start_response.chunk_size as usize;
 
-    // Upload chunks by reading file in chunks (don't load entire file into memory)
+    // Upload chunks as they are read from the file (don't load entire file into memory)
+    let mut chunk_reader = ChunkReader::new(&file);
     for chunk_number in 0..total_chunks {
-        let start_offset = chunk_number as usize * chunk_size;
-        let end_offset = std::cmp::min(start_offset + chunk_size, file.size() as usize);
-
-        upload_state.set(UploadState::UploadingChunks {
+        set_state.emit(UploadState::UploadingChunks {
             upload_id: upload_id.to_string(),
             uploaded_chunks: chunk_number,
             total_chunks,
@@ -672,7 +1103,8 @@ async fn perform_chunked_upload(
         });
 
         // Read only the chunk we need (not the entire file)
-        let chunk_data = read_file_chunk(&file, start_offset, end_offset - start_offset)
+        let chunk_data = chunk_reader
+            .next_chunk(chunk_size)
             .await
             .map_err(|e| format!("Failed to read chunk {}: {:?}", chunk_number, e))?;
 
@@ -683,43 +1115,25 @@ async fn perform_chunked_upload(
     }
 
     // Complete upload
-    upload_state.set(UploadState::Completing);
+    set_state.emit(UploadState::Completing);
+
+    // The contents stay unencrypted, but the filename is encrypted with a key kept in the link
+    let encryption_key = crypto::generate_key();
+    let encrypted_filename = crypto::encrypt(filename.as_bytes(), &encryption_key)
+        .map_err(|e| format!("Failed to encrypt filename: {}", e))?;
+    let encryption_key = encryption_key.to_base64();
 
     let complete_response = api_client
-        .complete_chunked_upload(&upload_id.to_string())
+        .complete_chunked_upload(&upload_id.to_string(), Some(&encrypted_filename))
         .await
         .map_err(|e| format!("Failed to complete upload: {:?}", e))?;
 
     Ok((
         complete_response.file_id.to_string(),
         complete_response.download_url,
+        encryption_key.clone(),
         complete_response
-            .encryption_key
-            .unwrap_or_else(|| "".to_string()),
-        complete_response.curl_command,
+            .curl_command
+            .replace(KEY_PLACEHOLDER, &encryption_key),
     ))
 }
-
-// Helper function to read a specific chunk of a file without loading the entire file
-async fn read_file_chunk(file: &File, start: usize, length: usize) -> Result<Vec<u8>, String> {
-    use gloo::file::Blob;
-
-    // Create a file slice for the specific chunk
-    let end = start + length;
-    let web_file: &web_sys::File = file.as_ref();
-    let web_blob_slice = web_file
-        .slice_with_i32_and_i32(start as i32, end as i32)
-        .map_err(|e| format!("Failed to slice file: {:?}", e))?;
-
-    // Convert web_sys::Blob to gloo::file::Blob
-    let gloo_blob = Blob::from(web_blob_slice);
-
-    // Convert blob slice to ArrayBuffer using the existing gloo functionality
-    let array_buffer = gloo::file::futures::read_as_array_buffer(&gloo_blob)
-        .await
-        .map_err(|e| format!("Failed to read chunk: {:?}", e))?;
-
-    // Convert ArrayBuffer to Vec<u8>
-    let uint8_array = js_sys::Uint8Array::new(&array_buffer);
-    Ok(uint8_array.to_vec())
-}
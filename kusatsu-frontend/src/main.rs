@@ -5,7 +5,7 @@ mod components;
 mod services;
 mod utils;
 
-use components::{download::Download, header::Header, upload::Upload};
+use components::{download::Download, header::Header, toast::ToastProvider, upload::Upload};
 
 #[derive(Clone, Routable, PartialEq)]
 pub enum AppRoute {
@@ -72,13 +72,15 @@ pub fn app() -> Html {
 
     html! {
         <BrowserRouter>
-            <div class="app">
-                <Header current_route={(*current_route).clone()} {on_navigate} />
+            <ToastProvider>
+                <div class="app">
+                    <Header current_route={(*current_route).clone()} {on_navigate} />
 
-                <main class="main-content">
-                    <Switch<AppRoute> render={switch} />
-                </main>
-            </div>
+                    <main class="main-content">
+                        <Switch<AppRoute> render={switch} />
+                    </main>
+                </div>
+            </ToastProvider>
         </BrowserRouter>
     }
 }
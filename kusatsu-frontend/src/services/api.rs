@@ -1,4 +1,6 @@
-use gloo::net::http::Request;
+use base64::{engine::general_purpose, Engine as _};
+use gloo::net::http::{Request, Response};
+use kusatsu_encrypt::EncryptedData;
 use uuid::Uuid;
 use web_sys::FormData;
 
@@ -12,11 +14,56 @@ pub enum ApiError {
     #[error("Parse error: {0}")]
     Parse(String),
     #[error("Server error: {status} - {message}")]
-    Server { status: u16, message: String },
+    Server {
+        status: u16,
+        /// Stable error code (see `kusatsu_types::error_code`), when the body carried one
+        code: Option<String>,
+        message: String,
+    },
+}
+
+impl ApiError {
+    /// Error code sent by the server, if any
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ApiError::Server { code, .. } => code.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+// Turn a failed response into an error, keeping the server's error code
+async fn server_error(response: Response) -> ApiError {
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(error) => ApiError::Server {
+            status,
+            code: Some(error.code),
+            message: error.message,
+        },
+        Err(_) => ApiError::Server {
+            status,
+            code: None,
+            message: body,
+        },
+    }
 }
 
 // All API types are now defined in kusatsu-types and re-exported above
 
+// Contents encrypted in the browser; the server stores them as sent and never sees the key
+pub struct ClientEncryption {
+    pub nonce: Vec<u8>,
+    pub filename: EncryptedData,
+    /// Format the contents were compressed with before encryption, and their size before it
+    pub compression: Option<(&'static str, u64)>,
+}
+
 #[derive(Clone)]
 pub struct ApiClient {
     pub base_url: String,
@@ -31,6 +78,25 @@ impl ApiClient {
         Self { base_url }
     }
 
+    // Get the limits and optional features of the server
+    pub async fn get_server_config(&self) -> Result<ServerConfigResponse, ApiError> {
+        let url = format!("{}/api/config", self.base_url);
+
+        let response = Request::get(&url)
+            .send()
+            .await
+            .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
+
+        if !response.ok() {
+            return Err(server_error(response).await);
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ApiError::Parse(format!("Failed to parse response: {:?}", e)))
+    }
+
     // Get file info with optional encryption key (to handle both encrypted and unencrypted files)
     pub async fn get_file_info(
         &self,
@@ -57,12 +123,64 @@ impl ApiClient {
             .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
 
         if !response.ok() {
-            let status = response.status();
-            let message = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ApiError::Server { status, message });
+            return Err(server_error(response).await);
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ApiError::Parse(format!("Failed to parse response: {:?}", e)))
+    }
+
+    // Get the passphrase-wrapped key of a file shared without its key
+    pub async fn get_wrapped_key(&self, file_id: &str) -> Result<WrappedKeyResponse, ApiError> {
+        let url = format!("{}/api/files/{}/wrapped-key", self.base_url, file_id);
+
+        let response = Request::get(&url)
+            .send()
+            .await
+            .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
+
+        if !response.ok() {
+            return Err(server_error(response).await);
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ApiError::Parse(format!("Failed to parse response: {:?}", e)))
+    }
+
+    // Get the encrypted listing of a bundle's contents
+    pub async fn get_listing(&self, file_id: &str) -> Result<ListingResponse, ApiError> {
+        let url = format!("{}/api/files/{}/listing", self.base_url, file_id);
+
+        let response = Request::get(&url)
+            .send()
+            .await
+            .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
+
+        if !response.ok() {
+            return Err(server_error(response).await);
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ApiError::Parse(format!("Failed to parse response: {:?}", e)))
+    }
+
+    // Get the stored bytes of a small file for an inline preview
+    pub async fn get_file_preview(&self, file_id: &str) -> Result<FilePreviewResponse, ApiError> {
+        let url = format!("{}/api/files/{}/preview", self.base_url, file_id);
+
+        let response = Request::get(&url)
+            .send()
+            .await
+            .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
+
+        if !response.ok() {
+            return Err(server_error(response).await);
         }
 
         response
@@ -76,8 +194,8 @@ impl ApiClient {
         file_data: Vec<u8>,
         filename: String,
         mime_type: Option<String>,
-        expires_in_hours: Option<i32>,
-        max_downloads: Option<i32>,
+        options: &UploadOptions,
+        client_encryption: Option<ClientEncryption>,
     ) -> Result<UploadResponse, ApiError> {
         let form_data = FormData::new()
             .map_err(|e| ApiError::Network(format!("Failed to create form data: {:?}", e)))?;
@@ -94,10 +212,37 @@ impl ApiClient {
             .append_with_blob("file_data", &file_blob)
             .map_err(|e| ApiError::Network(format!("Failed to append file_data: {:?}", e)))?;
 
-        // Add filename
+        // Add the filename, which only goes out encrypted with content encrypted here
+        let mut fields = Vec::new();
+        match client_encryption {
+            Some(sealed) => {
+                fields.push(("encrypted", "true".to_string()));
+                fields.push(("nonce", general_purpose::STANDARD.encode(&sealed.nonce)));
+                fields.push((
+                    "encrypted_filename",
+                    general_purpose::STANDARD.encode(&sealed.filename.ciphertext),
+                ));
+                fields.push((
+                    "filename_nonce",
+                    general_purpose::STANDARD.encode(&sealed.filename.nonce),
+                ));
+                if let Some((format, original_size)) = sealed.compression {
+                    fields.push(("compression", format.to_string()));
+                    fields.push(("original_size", original_size.to_string()));
+                }
+            }
+            None => fields.push(("filename", filename)),
+        }
+        for (name, value) in fields {
+            form_data
+                .append_with_str(name, &value)
+                .map_err(|e| ApiError::Network(format!("Failed to append {}: {:?}", name, e)))?;
+        }
+
+        // Declare the size so the server can detect a truncated upload
         form_data
-            .append_with_str("filename", &filename)
-            .map_err(|e| ApiError::Network(format!("Failed to append filename: {:?}", e)))?;
+            .append_with_str("file_size", &file_data.len().to_string())
+            .map_err(|e| ApiError::Network(format!("Failed to append file_size: {:?}", e)))?;
 
         // Add mime type if provided
         if let Some(mime) = mime_type {
@@ -110,14 +255,26 @@ impl ApiClient {
         let mut url = format!("{}/api/upload", self.base_url);
         let mut params = Vec::new();
 
-        if let Some(hours) = expires_in_hours {
+        if let Some(hours) = options.expires_in_hours {
             params.push(format!("expires_in_hours={}", hours));
         }
 
-        if let Some(max_dl) = max_downloads {
+        if let Some(max_dl) = options.max_downloads {
             params.push(format!("max_downloads={}", max_dl));
         }
 
+        if let Some(minutes) = options.burn_after_first_download_minutes {
+            params.push(format!("burn_after_first_download_minutes={}", minutes));
+        }
+
+        if let Some(hours) = options.expire_after_inactivity_hours {
+            params.push(format!("expire_after_inactivity_hours={}", hours));
+        }
+
+        if let (Some(challenge), Some(nonce)) = (&options.pow_challenge, options.pow_nonce) {
+            params.push(format!("pow_challenge={}&pow_nonce={}", challenge, nonce));
+        }
+
         if !params.is_empty() {
             url.push('?');
             url.push_str(&params.join("&"));
@@ -131,12 +288,26 @@ impl ApiClient {
             .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
 
         if !response.ok() {
-            let status = response.status();
-            let message = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ApiError::Server { status, message });
+            return Err(server_error(response).await);
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ApiError::Parse(format!("Failed to parse response: {:?}", e)))
+    }
+
+    // Get a proof-of-work challenge, on servers that require one before uploads
+    pub async fn get_upload_challenge(&self) -> Result<UploadChallengeResponse, ApiError> {
+        let url = format!("{}/api/upload/challenge", self.base_url);
+
+        let response = Request::post(&url)
+            .send()
+            .await
+            .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
+
+        if !response.ok() {
+            return Err(server_error(response).await);
         }
 
         response
@@ -160,12 +331,7 @@ impl ApiClient {
             .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
 
         if !response.ok() {
-            let status = response.status();
-            let message = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ApiError::Server { status, message });
+            return Err(server_error(response).await);
         }
 
         response
@@ -185,34 +351,17 @@ impl ApiClient {
             self.base_url, upload_id, chunk_number
         );
 
-        let form_data = FormData::new()
-            .map_err(|e| ApiError::Network(format!("Failed to create form data: {:?}", e)))?;
-
-        // Add chunk data as blob
-        let chunk_data_array = js_sys::Uint8Array::from(chunk_data);
-        use web_sys::Blob;
-        let chunk_blob =
-            Blob::new_with_u8_array_sequence(&js_sys::Array::of1(&chunk_data_array))
-                .map_err(|e| ApiError::Network(format!("Failed to create chunk blob: {:?}", e)))?;
-
-        form_data
-            .append_with_blob("chunk", &chunk_blob)
-            .map_err(|e| ApiError::Network(format!("Failed to append chunk: {:?}", e)))?;
-
-        let response = Request::post(&url)
-            .body(form_data)
+        // Send the chunk as the raw body, no multipart encoding needed
+        let response = Request::put(&url)
+            .header("Content-Type", "application/octet-stream")
+            .body(js_sys::Uint8Array::from(chunk_data))
             .map_err(|e| ApiError::Network(format!("Failed to create request: {:?}", e)))?
             .send()
             .await
             .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
 
         if !response.ok() {
-            let status = response.status();
-            let message = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ApiError::Server { status, message });
+            return Err(server_error(response).await);
         }
 
         response
@@ -224,6 +373,7 @@ impl ApiClient {
     pub async fn complete_chunked_upload(
         &self,
         upload_id: &str,
+        encrypted_filename: Option<&EncryptedData>,
     ) -> Result<UploadResponse, ApiError> {
         let url = format!("{}/api/upload/complete", self.base_url);
 
@@ -232,6 +382,11 @@ impl ApiClient {
 
         let request = CompleteUploadRequest {
             upload_id: upload_uuid,
+            encrypted_filename: encrypted_filename
+                .map(|data| general_purpose::STANDARD.encode(&data.ciphertext)),
+            filename_nonce: encrypted_filename
+                .map(|data| general_purpose::STANDARD.encode(&data.nonce)),
+            signature: None,
         };
 
         let response = Request::post(&url)
@@ -242,12 +397,7 @@ impl ApiClient {
             .map_err(|e| ApiError::Network(format!("Request failed: {:?}", e)))?;
 
         if !response.ok() {
-            let status = response.status();
-            let message = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ApiError::Server { status, message });
+            return Err(server_error(response).await);
         }
 
         response
@@ -0,0 +1,142 @@
+use gloo::file::{Blob, File};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ReadableStreamDefaultReader, ReadableStreamReadResult};
+
+/// Reads a file front to back in chunks for the chunked uploader
+///
+/// Uses `file.stream()` where available: the stream is only pulled when the next
+/// chunk is requested, so reading never runs ahead of the upload. Browsers without
+/// `Blob.stream` fall back to reading a slice of the file per chunk.
+pub enum ChunkReader {
+    Stream {
+        reader: ReadableStreamDefaultReader,
+        // Bytes read from the stream beyond the last chunk returned
+        pending: Vec<u8>,
+        done: bool,
+    },
+    Slice {
+        file: File,
+        offset: usize,
+    },
+}
+
+impl ChunkReader {
+    pub fn new(file: &File) -> Self {
+        let web_file: &web_sys::File = file.as_ref();
+
+        if supports_streams(web_file) {
+            if let Ok(reader) = web_file
+                .stream()
+                .get_reader()
+                .dyn_into::<ReadableStreamDefaultReader>()
+            {
+                return ChunkReader::Stream {
+                    reader,
+                    pending: Vec::new(),
+                    done: false,
+                };
+            }
+        }
+
+        ChunkReader::Slice {
+            file: file.clone(),
+            offset: 0,
+        }
+    }
+
+    /// Read the next `length` bytes, fewer only at the end of the file
+    pub async fn next_chunk(&mut self, length: usize) -> Result<Vec<u8>, String> {
+        match self {
+            ChunkReader::Stream {
+                reader,
+                pending,
+                done,
+            } => {
+                pending.reserve(length.saturating_sub(pending.len()));
+                while pending.len() < length && !*done {
+                    match read_stream(reader).await? {
+                        // Copy straight from the JS buffer onto the end of the chunk
+                        Some(data) => {
+                            let start = pending.len();
+                            pending.resize(start + data.length() as usize, 0);
+                            data.copy_to(&mut pending[start..]);
+                        }
+                        None => *done = true,
+                    }
+                }
+
+                if pending.len() <= length {
+                    return Ok(std::mem::take(pending));
+                }
+                let rest = pending.split_off(length);
+                Ok(std::mem::replace(pending, rest))
+            }
+            ChunkReader::Slice { file, offset } => {
+                let start = *offset;
+                let end = (start + length).min(file.size() as usize);
+                let data = read_file_slice(file, start, end).await?;
+                *offset = end;
+                Ok(data)
+            }
+        }
+    }
+}
+
+impl Drop for ChunkReader {
+    // Stop the browser reading a file whose upload was abandoned
+    fn drop(&mut self) {
+        if let ChunkReader::Stream {
+            reader,
+            done: false,
+            ..
+        } = self
+        {
+            let _ = reader.cancel();
+        }
+    }
+}
+
+fn supports_streams(file: &web_sys::File) -> bool {
+    js_sys::Reflect::get(file, &JsValue::from_str("stream"))
+        .map(|stream| stream.is_function())
+        .unwrap_or(false)
+}
+
+// Next piece of the stream, or None once it is exhausted
+async fn read_stream(
+    reader: &ReadableStreamDefaultReader,
+) -> Result<Option<js_sys::Uint8Array>, String> {
+    let result: ReadableStreamReadResult = JsFuture::from(reader.read())
+        .await
+        .map_err(|e| format!("Failed to read file stream: {:?}", e))?
+        .unchecked_into();
+
+    if result.get_done().unwrap_or(false) {
+        return Ok(None);
+    }
+
+    result
+        .get_value()
+        .dyn_into::<js_sys::Uint8Array>()
+        .map(Some)
+        .map_err(|_| "File stream returned an unexpected value".to_string())
+}
+
+// Read the bytes from `start` to `end` without loading the entire file
+async fn read_file_slice(file: &File, start: usize, end: usize) -> Result<Vec<u8>, String> {
+    // Create a file slice for the specific chunk
+    let web_file: &web_sys::File = file.as_ref();
+    let web_blob_slice = web_file
+        .slice_with_f64_and_f64(start as f64, end as f64)
+        .map_err(|e| format!("Failed to slice file: {:?}", e))?;
+
+    // Convert blob slice to ArrayBuffer using the existing gloo functionality
+    let array_buffer = gloo::file::futures::read_as_array_buffer(&Blob::from(web_blob_slice))
+        .await
+        .map_err(|e| format!("Failed to read chunk: {:?}", e))?;
+
+    // Convert ArrayBuffer to Vec<u8>
+    let uint8_array = js_sys::Uint8Array::new(&array_buffer);
+    Ok(uint8_array.to_vec())
+}
@@ -0,0 +1,96 @@
+//! Compression of file contents before they are encrypted in the browser
+//!
+//! Uses the browser's `CompressionStream`, reached through `js_sys` since
+//! web-sys only binds it as an unstable API.
+
+use gloo::file::File;
+use js_sys::{Array, Function, Reflect, Uint8Array};
+use kusatsu_types::compression_format;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::Response;
+
+/// Format the upload form compresses with
+pub const FORMAT: &str = compression_format::GZIP;
+
+// Bytes from the start of a file compressed to estimate the savings
+const SAMPLE_SIZE: u64 = 256 * 1024;
+
+// Formats that are compressed already, so gzip wouldn't shrink them further
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "7z", "aac", "apk", "avi", "avif", "br", "bz2", "docx", "epub", "flac", "gif", "gz", "heic",
+    "jar", "jpeg", "jpg", "m4a", "mkv", "mov", "mp3", "mp4", "odp", "ods", "odt", "ogg", "opus",
+    "png", "pptx", "rar", "tgz", "webm", "webp", "xlsx", "xz", "zip", "zst",
+];
+const COMPRESSED_MIME_TYPES: &[&str] = &[
+    "application/gzip",
+    "application/x-7z-compressed",
+    "application/x-bzip2",
+    "application/x-rar-compressed",
+    "application/x-xz",
+    "application/zip",
+    "application/zstd",
+];
+
+/// Why a file is sent uncompressed even though compression was asked for, if it is
+pub fn skip_reason(file: &File, chunked: bool) -> Option<&'static str> {
+    if chunked {
+        Some("files sent in chunks aren't compressed")
+    } else if is_already_compressed(&file.name(), &file.raw_mime_type()) {
+        Some("this type of file is already compressed")
+    } else if !is_supported() {
+        Some("this browser can't compress files")
+    } else {
+        None
+    }
+}
+
+fn is_already_compressed(filename: &str, mime_type: &str) -> bool {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    let mime_type = mime_type.to_lowercase();
+
+    COMPRESSED_EXTENSIONS.contains(&extension.as_str())
+        || COMPRESSED_MIME_TYPES.contains(&mime_type.as_str())
+        // SVG and BMP are the images that still shrink
+        || (mime_type.starts_with("image/") && mime_type != "image/svg+xml" && mime_type != "image/bmp")
+        || mime_type.starts_with("video/")
+        || (mime_type.starts_with("audio/") && mime_type != "audio/wav")
+}
+
+fn is_supported() -> bool {
+    Reflect::has(&js_sys::global(), &"CompressionStream".into()).unwrap_or(false)
+}
+
+/// Compress data in [`FORMAT`]
+pub async fn compress(data: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let constructor: Function =
+        Reflect::get(&js_sys::global(), &"CompressionStream".into())?.dyn_into()?;
+    let compression_stream = Reflect::construct(&constructor, &Array::of1(&FORMAT.into()))?;
+
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&Array::of1(&Uint8Array::from(data)))?;
+    let compressed = blob
+        .stream()
+        .pipe_through(compression_stream.unchecked_ref());
+
+    // A Response collects the whole stream into one buffer
+    let buffer =
+        JsFuture::from(Response::new_with_opt_readable_stream(Some(&compressed))?.array_buffer()?)
+            .await?;
+
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+/// Estimated fraction of a file compression saves, from its first bytes
+pub async fn estimate_savings(file: &File) -> Option<f64> {
+    let sample = file.slice(0, SAMPLE_SIZE.min(file.size()));
+    let data = gloo::file::futures::read_as_bytes(&sample).await.ok()?;
+    if data.is_empty() {
+        return None;
+    }
+
+    let compressed = compress(&data).await.ok()?;
+    Some((1.0 - compressed.len() as f64 / data.len() as f64).max(0.0))
+}
@@ -0,0 +1,65 @@
+//! Client-side encryption for the upload and download components
+//!
+//! Keys are shared as the anchor of a download link (`…/download/<id>#<key>`),
+//! which browsers never send to the server.
+
+use kusatsu_encrypt::{EncryptedData, Encryption, EncryptionError, EncryptionKey};
+
+/// Generate a fresh key for a file
+pub fn generate_key() -> EncryptionKey {
+    EncryptionKey::generate()
+}
+
+/// The key text in a location hash, None when there is no anchor
+pub fn anchor_key(hash: &str) -> Option<&str> {
+    Some(hash.strip_prefix('#').unwrap_or(hash)).filter(|key| !key.is_empty())
+}
+
+/// Parse a key from a location hash, or from a key entered on its own
+pub fn key_from_anchor(hash: &str) -> Result<EncryptionKey, EncryptionError> {
+    EncryptionKey::from_base64(anchor_key(hash).unwrap_or_default())
+}
+
+/// Encrypt data under a random nonce
+pub fn encrypt(data: &[u8], key: &EncryptionKey) -> Result<EncryptedData, EncryptionError> {
+    Encryption::encrypt(data, key)
+}
+
+/// Decrypt data sealed with `key`, given the nonce stored alongside it
+pub fn decrypt(
+    ciphertext: Vec<u8>,
+    nonce: Vec<u8>,
+    key: &EncryptionKey,
+) -> Result<Vec<u8>, EncryptionError> {
+    Encryption::decrypt(
+        &EncryptedData {
+            ciphertext,
+            nonce,
+            key_size: key.size(),
+        },
+        key,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn test_round_trip_through_anchor() {
+        let key = generate_key();
+        let hash = format!("#{}", key.to_base64());
+
+        let encrypted = encrypt(b"kusatsu onsen", &key).unwrap();
+        let key = key_from_anchor(&hash).unwrap();
+        let decrypted = decrypt(encrypted.ciphertext, encrypted.nonce, &key).unwrap();
+        assert_eq!(decrypted, b"kusatsu onsen");
+
+        assert_eq!(anchor_key("#"), None);
+        assert!(key_from_anchor("").is_err());
+        assert!(key_from_anchor("#not-a-key").is_err());
+    }
+}
@@ -1 +1,4 @@
 pub mod api;
+pub mod chunk_reader;
+pub mod compression;
+pub mod crypto;
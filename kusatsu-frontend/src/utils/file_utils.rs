@@ -1,3 +1,5 @@
+use chrono::{DateTime, Utc};
+
 pub fn format_file_size(bytes: usize) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
     let mut size = bytes as f64;
@@ -15,6 +17,28 @@ pub fn format_file_size(bytes: usize) -> String {
     }
 }
 
+pub fn format_relative_time(target: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let delta = target - now;
+
+    if delta.num_seconds() <= 0 {
+        return "expired".to_string();
+    }
+
+    let days = delta.num_days();
+    let hours = delta.num_hours() % 24;
+    let minutes = delta.num_minutes() % 60;
+
+    if days > 0 {
+        format!("in {}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("in {}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("in {}m", minutes)
+    } else {
+        "in less than a minute".to_string()
+    }
+}
+
 pub fn get_file_icon(filename: &str, mime_type: Option<&str>) -> &'static str {
     let name = filename.to_lowercase();
     let mime = mime_type.unwrap_or("").to_lowercase();
@@ -2,6 +2,17 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{window, HtmlDocument, HtmlElement, HtmlInputElement};
 
+// Build the link to share: the download URL with the key as its anchor.
+// The server may already embed the key, so any existing anchor is replaced.
+pub fn shareable_url(download_url: &str, encryption_key: &str) -> String {
+    let bare_url = download_url.split('#').next().unwrap_or(download_url);
+    if encryption_key.is_empty() {
+        bare_url.to_string()
+    } else {
+        format!("{}#{}", bare_url, encryption_key)
+    }
+}
+
 pub async fn copy_to_clipboard(text: &str) -> Result<(), JsValue> {
     let window = window().ok_or("no global `window` exists")?;
 
@@ -2,6 +2,19 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20231101_000001_create_files_table;
 mod m20231102_000001_create_upload_sessions_table;
+mod m20231103_000001_add_owner_token_to_files;
+mod m20231104_000001_add_slug_to_files;
+mod m20231105_000001_add_signature_to_files;
+mod m20231106_000001_add_wrapped_key_to_files;
+mod m20231107_000001_add_content_sha256_to_files;
+mod m20231108_000001_add_inline_data_to_files;
+mod m20231109_000001_add_compression_to_files;
+mod m20231110_000001_add_burn_after_first_download;
+mod m20231111_000001_add_listing_to_files;
+mod m20231112_000001_add_is_encrypted_to_files;
+mod m20231113_000001_add_expire_after_inactivity;
+mod m20231114_000001_hash_owner_tokens;
+mod m20231115_000001_unique_file_slugs;
 
 pub struct Migrator;
 
@@ -11,6 +24,19 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20231101_000001_create_files_table::Migration),
             Box::new(m20231102_000001_create_upload_sessions_table::Migration),
+            Box::new(m20231103_000001_add_owner_token_to_files::Migration),
+            Box::new(m20231104_000001_add_slug_to_files::Migration),
+            Box::new(m20231105_000001_add_signature_to_files::Migration),
+            Box::new(m20231106_000001_add_wrapped_key_to_files::Migration),
+            Box::new(m20231107_000001_add_content_sha256_to_files::Migration),
+            Box::new(m20231108_000001_add_inline_data_to_files::Migration),
+            Box::new(m20231109_000001_add_compression_to_files::Migration),
+            Box::new(m20231110_000001_add_burn_after_first_download::Migration),
+            Box::new(m20231111_000001_add_listing_to_files::Migration),
+            Box::new(m20231112_000001_add_is_encrypted_to_files::Migration),
+            Box::new(m20231113_000001_add_expire_after_inactivity::Migration),
+            Box::new(m20231114_000001_hash_owner_tokens::Migration),
+            Box::new(m20231115_000001_unique_file_slugs::Migration),
         ]
     }
 }
@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Optional human-readable name a file can be downloaded by. Not unique at
+        // the database level: an expired file may still hold a slug until cleanup.
+        // (Made unique by m20231115_000001_unique_file_slugs.)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column(ColumnDef::new(Files::Slug).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_files_slug")
+                    .table(Files::Table)
+                    .col(Files::Slug)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Chunked uploads carry the slug until they complete
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadSessions::Table)
+                    .add_column(ColumnDef::new(UploadSessions::Slug).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadSessions::Table)
+                    .drop_column(UploadSessions::Slug)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_files_slug")
+                    .table(Files::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .drop_column(Files::Slug)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    Slug,
+}
+
+#[derive(DeriveIden)]
+enum UploadSessions {
+    Table,
+    Slug,
+}
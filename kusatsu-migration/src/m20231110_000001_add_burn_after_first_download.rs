@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Minutes a file stays available once first downloaded, and when that was
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column(ColumnDef::new(Files::BurnAfterFirstDownloadMinutes).integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column(ColumnDef::new(Files::FirstDownloadedAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Chunked uploads keep the setting until the file is created
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadSessions::Table)
+                    .add_column(
+                        ColumnDef::new(UploadSessions::BurnAfterFirstDownloadMinutes).integer(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadSessions::Table)
+                    .drop_column(UploadSessions::BurnAfterFirstDownloadMinutes)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .drop_column(Files::FirstDownloadedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .drop_column(Files::BurnAfterFirstDownloadMinutes)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    BurnAfterFirstDownloadMinutes,
+    FirstDownloadedAt,
+}
+
+#[derive(DeriveIden)]
+enum UploadSessions {
+    Table,
+    BurnAfterFirstDownloadMinutes,
+}
@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Whether the contents are encrypted, rather than inferring it from the nonce
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column(
+                        ColumnDef::new(Files::IsEncrypted)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Existing files were encrypted exactly when they have a nonce
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Files::Table)
+                    .value(Files::IsEncrypted, true)
+                    .and_where(Expr::expr(Func::cust(Length).arg(Expr::col(Files::Nonce))).gt(0))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .drop_column(Files::IsEncrypted)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    Nonce,
+    IsEncrypted,
+}
+
+// LENGTH() gives the size of a binary value in bytes on every supported database
+#[derive(Iden)]
+#[iden = "LENGTH"]
+struct Length;
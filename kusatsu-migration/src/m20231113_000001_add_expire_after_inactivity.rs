@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Hours without downloads after which a file expires, renewed by each download
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .add_column(ColumnDef::new(Files::ExpireAfterInactivityHours).integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Chunked uploads keep the setting until the file is created
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadSessions::Table)
+                    .add_column(
+                        ColumnDef::new(UploadSessions::ExpireAfterInactivityHours).integer(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadSessions::Table)
+                    .drop_column(UploadSessions::ExpireAfterInactivityHours)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Files::Table)
+                    .drop_column(Files::ExpireAfterInactivityHours)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    ExpireAfterInactivityHours,
+}
+
+#[derive(DeriveIden)]
+enum UploadSessions {
+    Table,
+    ExpireAfterInactivityHours,
+}
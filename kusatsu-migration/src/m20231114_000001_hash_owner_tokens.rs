@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::ConnectionTrait;
+use sha2::{Digest, Sha256};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Owner tokens are kept as their SHA-256 (lowercase hex), so a leaked
+        // record or log line can't be used to manage the file
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+        let rows = db
+            .query_all(
+                backend.build(
+                    Query::select()
+                        .columns([Files::Id, Files::OwnerToken])
+                        .from(Files::Table)
+                        .and_where(Expr::col(Files::OwnerToken).is_not_null()),
+                ),
+            )
+            .await?;
+
+        for row in rows {
+            let id: i32 = row.try_get("", "id")?;
+            let owner_token: String = row.try_get("", "owner_token")?;
+            db.execute(
+                backend.build(
+                    Query::update()
+                        .table(Files::Table)
+                        .value(
+                            Files::OwnerToken,
+                            format!("{:x}", Sha256::digest(owner_token.as_bytes())),
+                        )
+                        .and_where(Expr::col(Files::Id).eq(id)),
+                ),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // Tokens can't be recovered from their hashes; files migrated back
+        // can no longer be managed with their owner token
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    Id,
+    OwnerToken,
+}
@@ -0,0 +1,106 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, TransactionTrait};
+use std::collections::HashSet;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // On one connection: SQLite pool connections may not see the old index
+        // dropped yet when the new one is created
+        let transaction = manager.get_connection().begin().await?;
+        let manager = SchemaManager::new(&transaction);
+
+        // Two uploads checking the same slug at once could both take it. Files
+        // sharing one keep it on the newest, which is the one it resolved to.
+        let backend = manager.get_database_backend();
+        let rows = transaction
+            .query_all(
+                backend.build(
+                    Query::select()
+                        .columns([Files::Id, Files::Slug])
+                        .from(Files::Table)
+                        .and_where(Expr::col(Files::Slug).is_not_null())
+                        .order_by(Files::Id, Order::Desc),
+                ),
+            )
+            .await?;
+
+        let mut seen = HashSet::new();
+        for row in rows {
+            let id: i32 = row.try_get("", "id")?;
+            let slug: String = row.try_get("", "slug")?;
+            if seen.insert(slug) {
+                continue;
+            }
+            transaction
+                .execute(
+                    backend.build(
+                        Query::update()
+                            .table(Files::Table)
+                            .value(Files::Slug, Option::<String>::None)
+                            .and_where(Expr::col(Files::Id).eq(id)),
+                    ),
+                )
+                .await?;
+        }
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_files_slug")
+                    .table(Files::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Expired files give their slug up when a new upload claims it
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_files_slug")
+                    .table(Files::Table)
+                    .col(Files::Slug)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        transaction.commit().await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let transaction = manager.get_connection().begin().await?;
+        let manager = SchemaManager::new(&transaction);
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_files_slug")
+                    .table(Files::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_files_slug")
+                    .table(Files::Table)
+                    .col(Files::Slug)
+                    .to_owned(),
+            )
+            .await?;
+
+        transaction.commit().await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Files {
+    Table,
+    Id,
+    Slug,
+}
@@ -1,6 +1,67 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+const MB: i32 = 1024 * 1024;
+const GB: i64 = 1024 * 1024 * 1024;
+
+/// Largest chunk accepted by the chunk upload endpoint
+pub const MAX_CHUNK_SIZE: i32 = 32 * MB;
+
+/// Largest file sent in a single request; bigger files go through a chunked upload
+pub const MAX_SINGLE_UPLOAD_SIZE: i64 = 5 * MB as i64;
+
+/// Chunk size for a chunked upload of `file_size` bytes
+///
+/// Larger files get larger chunks to keep the number of requests down:
+/// 5 MB up to 1 GB, 16 MB up to 10 GB and [`MAX_CHUNK_SIZE`] beyond.
+pub fn recommended_chunk_size(file_size: i64) -> i32 {
+    if file_size <= GB {
+        5 * MB
+    } else if file_size <= 10 * GB {
+        16 * MB
+    } else {
+        MAX_CHUNK_SIZE
+    }
+}
+
+/// Header carrying the id that correlates client and server logs of one operation
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Header carrying a file's detached signature (base64) on downloads
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Stands in for the encryption key in a `curl_command` the server can't complete itself
+pub const KEY_PLACEHOLDER: &str = "<key>";
+
+/// Header naming the format a raw download's plaintext is compressed with, if any
+pub const COMPRESSION_HEADER: &str = "x-compression";
+
+/// Largest encrypted bundle listing kept for a file, in bytes
+pub const MAX_LISTING_SIZE: usize = 64 * 1024;
+
+/// Name to send instead of a filename the server shouldn't see
+///
+/// Keeps only the last two extensions (enough for `tar.gz`), which the server
+/// still checks against its denied extensions; the real name goes out encrypted.
+pub fn filename_placeholder(filename: &str) -> String {
+    let mut extensions: Vec<&str> = filename.rsplit('.').collect();
+    // The part before the first dot is the name itself
+    extensions.pop();
+    extensions.truncate(2);
+    extensions
+        .iter()
+        .rev()
+        .fold("file".to_string(), |placeholder, extension| {
+            format!("{}.{}", placeholder, extension)
+        })
+}
+
+/// Formats a client can compress contents with before encrypting them
+pub mod compression_format {
+    /// gzip, as produced by the browser's `CompressionStream`
+    pub const GZIP: &str = "gzip";
+}
+
 // Request types
 #[derive(Serialize, Deserialize, Clone)]
 pub struct StartUploadRequest {
@@ -10,11 +71,39 @@ pub struct StartUploadRequest {
     pub chunk_size: Option<i32>,
     pub expires_in_hours: Option<i32>,
     pub max_downloads: Option<i32>,
+    /// Human-readable name to download the file by (lowercase alphanumeric and hyphens)
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// Minutes the file stays available after its first download
+    #[serde(default)]
+    pub burn_after_first_download_minutes: Option<i32>,
+    /// Hours without downloads after which the file expires; each download renews them
+    #[serde(default)]
+    pub expire_after_inactivity_hours: Option<i32>,
+    /// Challenge from `POST /api/upload/challenge`, when the server requires proof of work
+    #[serde(default)]
+    pub pow_challenge: Option<String>,
+    /// Solution of `pow_challenge`
+    #[serde(default)]
+    pub pow_nonce: Option<u64>,
+    /// The filename will be sent encrypted on completion, so `filename` only
+    /// needs its extension (see [`filename_placeholder`]) and no more is kept
+    #[serde(default)]
+    pub filename_encrypted: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CompleteUploadRequest {
     pub upload_id: Uuid,
+    /// Filename encrypted client-side with the file's key (base64), stored instead of the plain name
+    #[serde(default)]
+    pub encrypted_filename: Option<String>,
+    /// Nonce of `encrypted_filename` (base64)
+    #[serde(default)]
+    pub filename_nonce: Option<String>,
+    /// Uploader's detached ed25519 signature of the file contents (base64)
+    #[serde(default)]
+    pub signature: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -22,19 +111,169 @@ pub struct DownloadRequest {
     pub encryption_key: Option<String>,
 }
 
-#[derive(Deserialize)]
+/// A small file and its metadata, from `POST /api/files/:id/download-json`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JsonDownloadResponse {
+    /// Decrypted original filename
+    pub filename: String,
+    pub mime_type: Option<String>,
+    /// Size of the decrypted file in bytes
+    pub size: i64,
+    /// File contents, standard base64
+    pub data_base64: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExtendExpiryRequest {
+    pub owner_token: String,
+    pub expires_in_hours: Option<i32>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub encryption_key: Option<String>,
+}
+
+/// Give a file a new ID, so links with the old one stop working
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RotateFileRequest {
+    pub owner_token: String,
+}
+
+/// Delete a file and its stored data before it expires
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DeleteFileRequest {
+    pub owner_token: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RotateFileResponse {
+    pub file_id: Uuid,
+    /// New download URL, without the key anchor: the key is unchanged, so the
+    /// client appends the one it already has
+    pub download_url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignDownloadRequest {
+    pub owner_token: String,
+    pub expires_in_seconds: i64,
+}
+
+/// Store a file's key wrapped with a passphrase, so the link can be shared without the key
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WrapKeyRequest {
+    pub owner_token: String,
+    /// Wrapped key as nonce then ciphertext (base64), see `kusatsu_encrypt::WrappedKey`
+    pub wrapped_key: String,
+    /// Salt the passphrase was derived with (base64)
+    pub key_salt: String,
+}
+
+/// Attach a listing of a bundle's contents to the file, see [`BundleListing`]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ListingRequest {
+    pub owner_token: String,
+    /// JSON [`BundleListing`] encrypted with the file key (base64)
+    pub ciphertext: String,
+    /// Nonce of the encryption (base64)
+    pub nonce: String,
+}
+
+#[derive(Deserialize, Clone, Default)]
 pub struct UploadOptions {
     pub expires_in_hours: Option<i32>,
     pub max_downloads: Option<i32>,
+    pub slug: Option<String>,
+    /// Minutes the file stays available after its first download
+    pub burn_after_first_download_minutes: Option<i32>,
+    /// Hours without downloads after which the file expires; each download renews them
+    pub expire_after_inactivity_hours: Option<i32>,
+    /// Challenge from `POST /api/upload/challenge`, when the server requires proof of work
+    pub pow_challenge: Option<String>,
+    /// Solution of `pow_challenge`
+    pub pow_nonce: Option<u64>,
+}
+
+/// Proof of work to do before an upload, see `kusatsu_encrypt::pow`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UploadChallengeResponse {
+    pub challenge: String,
+    /// Leading zero bits the hash of the solution must have
+    pub difficulty: u8,
+    /// When the challenge stops being accepted; each one is good for one upload
+    pub expires_at: chrono::DateTime<chrono::Utc>,
 }
 
 // Response types
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignDownloadResponse {
+    /// Signed download URL; encrypted files still need the key posted separately
+    pub url: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A file's passphrase-wrapped key, see [`WrapKeyRequest`]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct WrappedKeyResponse {
+    pub wrapped_key: String,
+    pub key_salt: String,
+}
+
+/// A file's encrypted bundle listing, see [`ListingRequest`]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ListingResponse {
+    pub ciphertext: String,
+    pub nonce: String,
+}
+
+/// Files in an archive bundling several files, listed so recipients can see what
+/// it holds before downloading it; only ever stored encrypted
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct BundleListing {
+    pub entries: Vec<BundleEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct BundleEntry {
+    /// Path of the file within the bundle
+    pub name: String,
+    pub size: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct UploadResponse {
     pub file_id: Uuid,
     pub download_url: String,
     pub encryption_key: Option<String>,
     pub curl_command: String,
+    pub owner_token: Option<String>,
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// When the file expires, including a server default applied when none was requested
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// SHA-256 of the stored contents (lowercase hex), for chunked uploads
+    #[serde(default)]
+    pub content_sha256: Option<String>,
+}
+
+/// How an upload is sent to the server
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadMethod {
+    Single,
+    Chunked,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UploadPlanResponse {
+    pub method: UploadMethod,
+    pub file_size: i64,
+    /// Chunk size and count, for chunked uploads
+    pub chunk_size: Option<i32>,
+    pub total_chunks: Option<i32>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub max_downloads: Option<i32>,
+    /// Largest file the server accepts, in bytes
+    pub max_file_size: i64,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -42,6 +281,9 @@ pub struct StartUploadResponse {
     pub upload_id: Uuid,
     pub chunk_size: i32,
     pub total_chunks: i32,
+    /// Lifetime of the file, counted from when the upload completes (`None` never expires)
+    #[serde(default)]
+    pub expires_in_hours: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -52,6 +294,67 @@ pub struct ChunkUploadResponse {
     pub progress: f32,
 }
 
+/// Progress of a chunked upload
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UploadStatusResponse {
+    pub upload_id: Uuid,
+    pub filename: String,
+    pub total_size: i64,
+    pub total_chunks: i32,
+    pub chunk_size: i32,
+    pub uploaded_chunks: i32,
+    /// Numbers of the chunks received so far, sorted
+    pub uploaded_chunk_numbers: Vec<i32>,
+    pub progress: f32,
+    /// Throughput over the latest chunks, unknown until a couple have arrived
+    /// and while the upload is paused
+    #[serde(default)]
+    pub bytes_per_second: Option<f64>,
+    /// Estimated seconds until the remaining chunks are uploaded, at that throughput
+    #[serde(default)]
+    pub eta_seconds: Option<u64>,
+    pub is_complete: bool,
+    pub is_expired: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// Stored size of each received chunk, only with `?verify=true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_chunks: Option<Vec<ChunkCheck>>,
+}
+
+/// A received chunk's stored size against the size expected at its position
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChunkCheck {
+    pub chunk_number: i32,
+    pub size: u64,
+    pub expected_size: u64,
+    /// False when the sizes differ; the chunk should be uploaded again
+    pub is_valid: bool,
+}
+
+impl UploadStatusResponse {
+    /// Numbers of the received chunks that don't need uploading again
+    ///
+    /// Chunks a verified status flagged as invalid are left out.
+    pub fn valid_chunk_numbers(&self) -> Vec<i32> {
+        match &self.verified_chunks {
+            Some(checks) => checks
+                .iter()
+                .filter(|check| check.is_valid)
+                .map(|check| check.chunk_number)
+                .collect(),
+            None => self.uploaded_chunk_numbers.clone(),
+        }
+    }
+}
+
+/// New deadline of an upload session after a keepalive
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UploadKeepaliveResponse {
+    pub upload_id: Uuid,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct FileInfo {
     pub file_id: Uuid,
@@ -64,4 +367,179 @@ pub struct FileInfo {
     pub max_downloads: Option<i32>,
     pub filename: String,
     pub is_encrypted: bool,
+    /// Uploader's detached ed25519 signature of the plaintext (base64), if any
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// SHA-256 of the stored contents (lowercase hex), if the server recorded one
+    #[serde(default)]
+    pub content_sha256: Option<String>,
+    /// Format the contents were compressed with before encryption; downloads undo it
+    #[serde(default)]
+    pub compression: Option<String>,
+    /// Minutes the file stays available after its first download, if set
+    #[serde(default)]
+    pub burn_after_first_download_minutes: Option<i32>,
+    /// When the file was first downloaded; with a burn time, `expires_at` follows from it
+    #[serde(default)]
+    pub first_downloaded_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Hours without downloads after which the file expires, if set; each
+    /// download pushes `expires_at` back to this far ahead
+    #[serde(default)]
+    pub expire_after_inactivity_hours: Option<i32>,
+    /// Whether the uploader attached a listing of the bundle's contents
+    #[serde(default)]
+    pub has_listing: bool,
+    /// Whether `original_size` and `encrypted_size` were rounded up to hide the
+    /// exact sizes, which are then only upper bounds
+    #[serde(default)]
+    pub sizes_padded: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FilePreviewResponse {
+    /// Stored file bytes (ciphertext for encrypted files), base64-encoded
+    pub data: String,
+    /// Nonce used for file encryption, base64-encoded (empty for unencrypted files)
+    pub nonce: String,
+    pub is_encrypted: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HealthResponse {
+    pub status: String,
+    pub service: String,
+    /// Whether uploads are currently refused for maintenance
+    pub maintenance: bool,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Use of the database connection pool
+    #[serde(default)]
+    pub database_pool: Option<DatabasePoolStatus>,
+    /// Error code of the latest storage write, while storage doesn't take writes
+    /// (`status` is then `degraded`)
+    #[serde(default)]
+    pub storage_error: Option<String>,
+}
+
+/// Connections of the database pool; all of them busy means requests are waiting
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DatabasePoolStatus {
+    /// Connections currently open, idle or in use
+    pub connections: u32,
+    pub idle: u32,
+    pub max_connections: u32,
+}
+
+/// Version of [`ServerConfigResponse`], bumped when a field changes meaning or is removed
+pub const SERVER_CONFIG_SCHEMA_VERSION: u32 = 1;
+
+/// Limits and features a server advertises to its clients
+///
+/// Clients should ignore fields they don't know, so adding one keeps the
+/// schema version.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ServerConfigResponse {
+    pub schema_version: u32,
+    /// Largest accepted file, in bytes
+    pub max_file_size: i64,
+    /// Files up to this size are sent in a single request, in bytes
+    pub single_upload_max: i64,
+    /// Chunk size of uploads up to 1 GB without a `chunk_size`, see [`recommended_chunk_size`]
+    pub default_chunk_size: i32,
+    pub max_chunk_size: i32,
+    /// Most chunks one upload session may have
+    pub max_chunks: i32,
+    /// Whether files can be decrypted client-side (`/raw`) and filenames encrypted client-side
+    pub client_encryption_supported: bool,
+    /// Whether `/api/upload` encrypts plaintext; when false it only accepts
+    /// content encrypted by the client
+    pub server_side_encryption: bool,
+    /// Whether file keys can be wrapped with a passphrase
+    pub password_protection_supported: bool,
+    /// Whether downloads can be signed, see `/api/files/{file_id}/sign`
+    pub signed_urls_supported: bool,
+    /// Expiry applied when an upload asks for none; None keeps such files
+    pub default_expiry_hours: Option<i32>,
+    pub max_expires_in_hours: i64,
+    pub max_downloads_limit: i32,
+    /// Difficulty of the proof of work uploads must include, None when not required
+    #[serde(default)]
+    pub upload_pow_difficulty: Option<u8>,
+}
+
+/// Result of an admin cleanup run
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CleanupResponse {
+    pub items_cleaned: u64,
+    pub cleanup_type: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Body of every error response
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ErrorResponse {
+    /// Short category, for display only
+    pub error: String,
+    /// Stable identifier to branch on, one of the [`error_code`] constants
+    pub code: String,
+    /// Human-readable detail
+    pub message: String,
+    /// Which request fields were rejected and why, for [`error_code::VALIDATION_FAILED`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+/// A request field that failed validation
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FieldError {
+    /// Name of the field as sent in the request
+    pub field: String,
+    /// What is wrong with it, including the allowed range where there is one
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Stable values of [`ErrorResponse::code`]
+pub mod error_code {
+    pub const BAD_REQUEST: &str = "BAD_REQUEST";
+    pub const CONFIGURATION_ERROR: &str = "CONFIGURATION_ERROR";
+    pub const CONFLICT: &str = "CONFLICT";
+    pub const DATABASE_ERROR: &str = "DATABASE_ERROR";
+    pub const DOWNLOAD_LIMIT_EXCEEDED: &str = "DOWNLOAD_LIMIT_EXCEEDED";
+    pub const ENCRYPTION_KEY_REQUIRED: &str = "ENCRYPTION_KEY_REQUIRED";
+    pub const FILE_EXPIRED: &str = "FILE_EXPIRED";
+    pub const FILE_NOT_FOUND: &str = "FILE_NOT_FOUND";
+    pub const FILE_TOO_LARGE: &str = "FILE_TOO_LARGE";
+    pub const FILE_TYPE_NOT_ALLOWED: &str = "FILE_TYPE_NOT_ALLOWED";
+    pub const FORBIDDEN: &str = "FORBIDDEN";
+    pub const HTTPS_REQUIRED: &str = "HTTPS_REQUIRED";
+    pub const INTERNAL_SERVER_ERROR: &str = "INTERNAL_SERVER_ERROR";
+    pub const INVALID_ENCRYPTION_KEY: &str = "INVALID_ENCRYPTION_KEY";
+    pub const INVALID_FILE_FORMAT: &str = "INVALID_FILE_FORMAT";
+    pub const INVALID_JSON: &str = "INVALID_JSON";
+    pub const INVALID_SIGNATURE: &str = "INVALID_SIGNATURE";
+    pub const IO_ERROR: &str = "IO_ERROR";
+    pub const MAINTENANCE_MODE: &str = "MAINTENANCE_MODE";
+    pub const MALWARE_DETECTED: &str = "MALWARE_DETECTED";
+    pub const PROOF_OF_WORK_REQUIRED: &str = "PROOF_OF_WORK_REQUIRED";
+    pub const SERVER_BUSY: &str = "SERVER_BUSY";
+    pub const SERVER_ERROR: &str = "SERVER_ERROR";
+    pub const STORAGE_FULL: &str = "STORAGE_FULL";
+    pub const STORAGE_UNAVAILABLE: &str = "STORAGE_UNAVAILABLE";
+    pub const TOO_MANY_CHUNK_WRITES: &str = "TOO_MANY_CHUNK_WRITES";
+    pub const UNAUTHORIZED: &str = "UNAUTHORIZED";
+    pub const VALIDATION_FAILED: &str = "VALIDATION_FAILED";
 }